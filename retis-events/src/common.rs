@@ -1,9 +1,14 @@
-use std::fmt;
+use std::{collections::BTreeMap, fmt};
 
 use chrono::{DateTime, Utc};
 
 use crate::*;
 
+/// Version of the events schema, reported in the startup section. It must be
+/// bumped on breaking changes of the events format (e.g. a field being removed
+/// or changing type) so readers can handle them.
+pub const SCHEMA_VERSION: u32 = 1;
+
 /// Startup event section. Contains global information about a collection as a
 /// whole, with data gathered at collection startup time.
 #[event_section(SectionId::Startup)]
@@ -12,11 +17,19 @@ pub struct StartupEvent {
     pub retis_version: String,
     /// CLOCK_MONOTONIC offset in regards to local machine time.
     pub clock_monotonic_offset: TimeSpec,
+    /// Version of the events schema (see `SCHEMA_VERSION`). Events collected
+    /// before the schema was versioned report 0.
+    #[serde(default)]
+    pub schema_version: u32,
 }
 
 impl EventFmt for StartupEvent {
     fn event_fmt(&self, f: &mut Formatter, _: &DisplayFormat) -> fmt::Result {
-        write!(f, "Retis version {}", self.retis_version)
+        write!(
+            f,
+            "Retis version {} (schema {})",
+            self.retis_version, self.schema_version
+        )
     }
 }
 
@@ -42,6 +55,18 @@ pub struct CommonEvent {
     pub smp_id: Option<u32>,
     /// Information about the task linked to the event.
     pub task: Option<TaskEvent>,
+    /// Names of event field values, translated using user-provided tables,
+    /// by field path (e.g. "skb.meta.mark"). Only set when asked for and for
+    /// the values found in the tables.
+    pub names: Option<BTreeMap<String, String>>,
+    /// Static tags describing the collection (e.g. host role, environment),
+    /// loaded from a user-provided file. Only set when asked for; not
+    /// displayed as they are the same for all events.
+    pub tags: Option<BTreeMap<String, String>>,
+    /// Time elapsed between the event being generated and it being
+    /// processed in userspace, in nanoseconds. Rising values indicate the
+    /// consumer falls behind. Only set when asked for; not displayed.
+    pub processing_latency_ns: Option<u64>,
 }
 
 impl EventFmt for CommonEvent {
@@ -70,6 +95,51 @@ impl EventFmt for CommonEvent {
             write!(f, "{}", current.tgid)?;
         }
 
+        if let Some(names) = &self.names {
+            write!(f, " {{")?;
+            names.iter().enumerate().try_for_each(|(i, (field, name))| {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{field}={name}")
+            })?;
+            write!(f, "}}")?;
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_version() {
+        let mut event = Event::new();
+        event
+            .insert_section(
+                SectionId::Startup,
+                Box::new(StartupEvent {
+                    retis_version: "1.0".to_string(),
+                    clock_monotonic_offset: TimeSpec::new(0, 0),
+                    schema_version: SCHEMA_VERSION,
+                }),
+            )
+            .unwrap();
+
+        let json = event.to_json();
+        assert_eq!(json["startup"]["schema_version"], SCHEMA_VERSION);
+
+        // Events collected before the schema was versioned.
+        let event = Event::from_json(
+            r#"{"startup":{"retis_version":"1.0","clock_monotonic_offset":{"sec":0,"nsec":0}}}"#
+                .to_string(),
+        )
+        .unwrap();
+        let startup = event
+            .get_section::<StartupEvent>(SectionId::Startup)
+            .unwrap();
+        assert_eq!(startup.schema_version, 0);
+    }
+}
@@ -24,6 +24,8 @@ pub struct DisplayFormat {
     pub time_format: TimeFormat,
     /// Offset of the monotonic clock to the wall-clock time.
     pub monotonic_offset: Option<TimeSpec>,
+    /// Colorize the output using ANSI escape sequences.
+    pub color: bool,
 }
 
 impl DisplayFormat {
@@ -48,6 +50,13 @@ impl DisplayFormat {
         self.monotonic_offset = Some(offset);
         self
     }
+
+    /// Configure colorized output, e.g. when writing to a terminal. Sections
+    /// are colorized by type and drops are highlighted.
+    pub fn color(mut self, enabled: bool) -> Self {
+        self.color = enabled;
+        self
+    }
 }
 
 /// `Formatter` implements `std::fmt::Write` and controls how events are being
@@ -72,7 +72,7 @@ impl Event {
     }
 
     /// Create an Event from a json string.
-    pub(crate) fn from_json(line: String) -> Result<Event> {
+    pub fn from_json(line: String) -> Result<Event> {
         let event_js: HashMap<String, serde_json::Value> = serde_json::from_str(line.as_str())
             .map_err(|e| anyhow!("Failed to parse json event at line {line}: {e}"))?;
 
@@ -133,6 +133,48 @@ impl Event {
     }
 }
 
+/// Reset sequence for colorized output, see `DisplayFormat::color`.
+const COLOR_RESET: &str = "\x1b[0m";
+
+/// Color of a section, as an ANSI escape sequence, when colorizing the output.
+/// Drops are highlighted.
+fn section_color(id: SectionId) -> Option<&'static str> {
+    use SectionId::*;
+    Some(match id {
+        Kernel | Userspace => "\x1b[36m",
+        Tracking | SkbTracking => "\x1b[33m",
+        SkbDrop => "\x1b[1;31m",
+        Skb => "\x1b[32m",
+        Ovs => "\x1b[34m",
+        Nft | Ct => "\x1b[35m",
+        Common | Startup | _MAX => return None,
+    })
+}
+
+impl Event {
+    /// Format a section, colorized if asked to.
+    fn section_fmt(
+        &self,
+        id: SectionId,
+        f: &mut Formatter,
+        format: &DisplayFormat,
+    ) -> std::fmt::Result {
+        let section = match self.0.get(&id) {
+            Some(section) => section,
+            None => return Ok(()),
+        };
+
+        match section_color(id).filter(|_| format.color) {
+            Some(color) => {
+                write!(f, "{color}")?;
+                section.event_fmt(f, format)?;
+                write!(f, "{COLOR_RESET}")
+            }
+            None => section.event_fmt(f, format),
+        }
+    }
+}
+
 impl EventFmt for Event {
     fn event_fmt(&self, f: &mut Formatter, format: &DisplayFormat) -> std::fmt::Result {
         // First format the first event line starting with the always-there
@@ -141,26 +183,26 @@ impl EventFmt for Event {
             .get(&SectionId::Common)
             .unwrap()
             .event_fmt(f, format)?;
-        if let Some(kernel) = self.0.get(&SectionId::Kernel) {
+        if self.0.contains_key(&SectionId::Kernel) {
             write!(f, " ")?;
-            kernel.event_fmt(f, format)?;
-        } else if let Some(user) = self.0.get(&SectionId::Userspace) {
+            self.section_fmt(SectionId::Kernel, f, format)?;
+        } else if self.0.contains_key(&SectionId::Userspace) {
             write!(f, " ")?;
-            user.event_fmt(f, format)?;
+            self.section_fmt(SectionId::Userspace, f, format)?;
         }
 
         // If we do have tracking and/or drop sections, put them there too.
         // Special case the global tracking information from here for now.
-        if let Some(tracking) = self.0.get(&SectionId::Tracking) {
+        if self.0.contains_key(&SectionId::Tracking) {
             write!(f, " ")?;
-            tracking.event_fmt(f, format)?;
-        } else if let Some(skb_tracking) = self.0.get(&SectionId::SkbTracking) {
+            self.section_fmt(SectionId::Tracking, f, format)?;
+        } else if self.0.contains_key(&SectionId::SkbTracking) {
             write!(f, " ")?;
-            skb_tracking.event_fmt(f, format)?;
+            self.section_fmt(SectionId::SkbTracking, f, format)?;
         }
-        if let Some(skb_drop) = self.0.get(&SectionId::SkbDrop) {
+        if self.0.contains_key(&SectionId::SkbDrop) {
             write!(f, " ")?;
-            skb_drop.event_fmt(f, format)?;
+            self.section_fmt(SectionId::SkbDrop, f, format)?;
         }
 
         // Separator between each following sections.
@@ -180,12 +222,11 @@ impl EventFmt for Event {
 
         // Finally show all sections.
         (SectionId::Skb as u8..SectionId::_MAX as u8)
-            .collect::<Vec<u8>>()
-            .iter()
-            .filter_map(|id| self.0.get(&SectionId::from_u8(*id).unwrap()))
-            .try_for_each(|section| {
+            .map(|id| SectionId::from_u8(id).unwrap())
+            .filter(|id| self.0.contains_key(id))
+            .try_for_each(|id| {
                 write!(f, "{sep}")?;
-                section.event_fmt(f, format)
+                self.section_fmt(id, f, format)
             })?;
 
         f.conf.reset_level();
@@ -421,3 +462,51 @@ pub mod test {
 
 #[cfg(feature = "test-events")]
 pub use test::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color() {
+        let mut event = Event::new();
+        event
+            .insert_section(SectionId::Common, Box::<CommonEvent>::default())
+            .unwrap();
+        event
+            .insert_section(
+                SectionId::Kernel,
+                Box::new(KernelEvent {
+                    symbol: "kfree_skb_reason".to_string(),
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        event
+            .insert_section(
+                SectionId::SkbDrop,
+                Box::new(SkbDropEvent {
+                    subsys: None,
+                    drop_reason: "NO_SOCKET".to_string(),
+                }),
+            )
+            .unwrap();
+
+        let conf = FormatterConf::new();
+        let plain = format!("{}", event.display(&DisplayFormat::new(), &conf));
+        assert!(!plain.contains('\x1b'));
+        assert!(plain.contains("drop (reason NO_SOCKET)"));
+
+        let format = DisplayFormat::new().color(true);
+        let color = format!("{}", event.display(&format, &conf));
+        assert!(color.contains("\x1b[1;31mdrop (reason NO_SOCKET)\x1b[0m"));
+        // Only escape sequences are added.
+        assert_eq!(
+            color
+                .replace("\x1b[0m", "")
+                .replace("\x1b[1;31m", "")
+                .replace("\x1b[36m", ""),
+            plain
+        );
+    }
+}
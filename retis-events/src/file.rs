@@ -1,6 +1,8 @@
-//! Handles the file (json) to Rust event retrieval and the unmarshaling process.
+//! Handles the file (json or MessagePack) to Rust event retrieval and the
+//! unmarshaling process.
 
 use std::{
+    collections::HashMap,
     fs::File,
     io::{BufRead, BufReader, Seek},
     path::Path,
@@ -8,7 +10,10 @@ use std::{
 
 use anyhow::{anyhow, bail, Result};
 
-use super::{Event, EventSeries};
+use super::{
+    msgpack::{self, MSGPACK_MAGIC},
+    Event, EventSeries,
+};
 
 // Type of file that is being processed.
 #[derive(Debug, Clone)]
@@ -19,11 +24,86 @@ pub enum FileType {
     Series,
 }
 
+/// Key holding the delta-encoded timestamp in the common section, see
+/// `TimestampDelta`.
+const TS_DELTA: &str = "ts_delta";
+
+/// Delta encoding of event timestamps. The first event keeps its absolute
+/// timestamp while the following ones only store the (signed, as events are
+/// not strictly ordered) difference with the previous event timestamp, in
+/// `common.ts_delta`. Events without a timestamp are left untouched.
+///
+/// The same object must be used for all events of a file, in order.
+#[derive(Default)]
+pub struct TimestampDelta {
+    prev: Option<u64>,
+}
+
+impl TimestampDelta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delta-encode the timestamp of a json event.
+    pub fn encode(&mut self, event: &mut serde_json::Value) {
+        let common = match event.get_mut("common").and_then(|c| c.as_object_mut()) {
+            Some(common) => common,
+            None => return,
+        };
+        let ts = match common.get("timestamp").and_then(|ts| ts.as_u64()) {
+            Some(ts) => ts,
+            None => return,
+        };
+
+        if let Some(prev) = self.prev {
+            common.remove("timestamp");
+            common.insert(TS_DELTA.to_string(), (ts.wrapping_sub(prev) as i64).into());
+        }
+        self.prev = Some(ts);
+    }
+
+    /// Reconstruct the absolute timestamp of a json event. Events not using
+    /// delta encoding are left untouched but are used as reference for the
+    /// following ones.
+    pub fn decode(&mut self, event: &mut serde_json::Value) -> Result<()> {
+        let common = match event.get_mut("common").and_then(|c| c.as_object_mut()) {
+            Some(common) => common,
+            None => return Ok(()),
+        };
+
+        let ts = match common.remove(TS_DELTA) {
+            Some(delta) => {
+                let delta = delta
+                    .as_i64()
+                    .ok_or_else(|| anyhow!("Invalid timestamp delta {delta}"))?;
+                let prev = self
+                    .prev
+                    .ok_or_else(|| anyhow!("Timestamp delta found without a reference"))?;
+
+                let ts = prev.wrapping_add(delta as u64);
+                common.insert("timestamp".to_string(), ts.into());
+                ts
+            }
+            None => match common.get("timestamp").and_then(|ts| ts.as_u64()) {
+                Some(ts) => ts,
+                None => return Ok(()),
+            },
+        };
+
+        self.prev = Some(ts);
+        Ok(())
+    }
+}
+
 /// File events factory retrieving and unmarshaling events
 /// parts.
 pub struct FileEventsFactory {
     reader: BufReader<File>,
     filetype: FileType,
+    /// Files can hold delta-encoded timestamps, see `TimestampDelta`.
+    deltas: TimestampDelta,
+    /// Events are stored in the MessagePack format, see `msgpack`.
+    msgpack: bool,
 }
 
 impl FileEventsFactory {
@@ -35,9 +115,22 @@ impl FileEventsFactory {
             File::open(&file)
                 .map_err(|e| anyhow!("Could not open {}: {e}", file.as_ref().display()))?,
         );
-        let filetype = Self::detect_type(&mut reader)?;
+        // MessagePack logs only hold events.
+        let msgpack = reader.fill_buf()?.starts_with(MSGPACK_MAGIC);
+        let filetype = match msgpack {
+            true => {
+                reader.consume(MSGPACK_MAGIC.len());
+                FileType::Event
+            }
+            false => Self::detect_type(&mut reader)?,
+        };
 
-        Ok(FileEventsFactory { reader, filetype })
+        Ok(FileEventsFactory {
+            reader,
+            filetype,
+            deltas: TimestampDelta::new(),
+            msgpack,
+        })
     }
 }
 
@@ -49,13 +142,25 @@ impl FileEventsFactory {
             FileType::Event => (),
             FileType::Series => bail!("Cannot read event from sorted file"),
         }
+        if self.msgpack {
+            return msgpack::read_event(&mut self.reader);
+        }
+
         let mut line = String::new();
 
         match self.reader.read_line(&mut line) {
-            Err(e) => Err(e.into()),
-            Ok(0) => Ok(None),
-            Ok(_) => Ok(Some(Event::from_json(line)?)),
+            Err(e) => return Err(e.into()),
+            Ok(0) => return Ok(None),
+            Ok(_) => (),
         }
+
+        let mut event: serde_json::Value = serde_json::from_str(line.as_str())
+            .map_err(|e| anyhow!("Failed to parse json event at line {line}: {e}"))?;
+        self.deltas.decode(&mut event)?;
+
+        Ok(Some(Event::from_json_obj(serde_json::from_value::<
+            HashMap<String, serde_json::Value>,
+        >(event)?)?))
     }
 
     /// Retrieve the next series or None if we've reached the end of the file.
@@ -116,4 +221,81 @@ mod tests {
         }
         assert!(events.len() == 4);
     }
+
+    #[test]
+    fn read_from_msgpack_file() {
+        let mut fact = FileEventsFactory::new("test_data/test_events.json").unwrap();
+        let file = std::env::temp_dir().join(format!("retis-msgpack-{}", std::process::id()));
+
+        let mut writer = msgpack::MsgpackWriter::new(File::create(&file).unwrap()).unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = fact.next_event().unwrap() {
+            writer.write_event(&event).unwrap();
+            events.push(event.to_json());
+        }
+        writer.into_inner().unwrap();
+
+        let mut fact = FileEventsFactory::new(&file).unwrap();
+        assert!(matches!(fact.file_type(), FileType::Event));
+        let mut read = Vec::new();
+        while let Some(event) = fact.next_event().unwrap() {
+            read.push(event.to_json());
+        }
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(read, events);
+    }
+
+    #[test]
+    fn timestamp_delta() {
+        let ts = [1000u64, 1500, 1200, 1200, u64::MAX, 3];
+        let events = ts
+            .iter()
+            .map(|ts| serde_json::json!({"common": {"timestamp": ts, "smp_id": 1}}))
+            .chain([
+                serde_json::json!({"kernel": {"symbol": "consume_skb", "probe_type": "kprobe"}}),
+            ]);
+
+        let mut encoder = TimestampDelta::new();
+        let encoded = events
+            .map(|mut event| {
+                encoder.encode(&mut event);
+                event
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(encoded[0]["common"]["timestamp"], 1000);
+        assert!(encoded[0]["common"].get(TS_DELTA).is_none());
+        assert_eq!(encoded[1]["common"][TS_DELTA], 500);
+        assert_eq!(encoded[2]["common"][TS_DELTA], -300);
+        assert!(encoded[1]["common"].get("timestamp").is_none());
+        assert!(encoded[6].get("common").is_none());
+
+        // Write and read back the delta-encoded events.
+        let file = std::env::temp_dir().join(format!("retis-ts-delta-{}", std::process::id()));
+        std::fs::write(
+            &file,
+            encoded.iter().map(|e| format!("{e}\n")).collect::<String>(),
+        )
+        .unwrap();
+
+        let mut fact = FileEventsFactory::new(&file).unwrap();
+        let mut decoded = Vec::new();
+        while let Some(event) = fact.next_event().unwrap() {
+            decoded.push(
+                event
+                    .get_section::<crate::CommonEvent>(crate::SectionId::Common)
+                    .map(|c| c.timestamp),
+            );
+        }
+        std::fs::remove_file(&file).unwrap();
+
+        let mut expected = ts.iter().map(|ts| Some(*ts)).collect::<Vec<_>>();
+        expected.push(None);
+        assert_eq!(decoded, expected);
+
+        // A delta without a reference cannot be decoded.
+        let mut event = encoded[1].clone();
+        assert!(TimestampDelta::new().decode(&mut event).is_err());
+    }
 }
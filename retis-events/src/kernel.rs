@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{collections::BTreeMap, fmt};
 
 use super::*;
 use crate::{event_section, event_type, Formatter};
@@ -9,29 +9,146 @@ pub struct KernelEvent {
     /// Kernel symbol name associated with the event (i.e. which probe generated
     /// the event).
     pub symbol: String,
-    /// Probe type: one of "kprobe", "kretprobe" or "raw_tracepoint".
-    pub probe_type: String,
+    /// Type of the probe which generated the event.
+    pub probe_type: ProbeType,
+    /// Return value of the probed function. Only set for kretprobes and fexit
+    /// probes.
+    pub ret: Option<i64>,
+    /// Time elapsed since the function entry, in nanoseconds. Only set for
+    /// kretprobes, when the function entry was seen.
+    pub latency_ns: Option<u64>,
+    /// Probe arguments, by name. Only set for the arguments explicitly asked
+    /// for.
+    pub args: Option<BTreeMap<String, KernelArg>>,
+    /// Kernel memory region the pointer arguments point into (e.g.
+    /// "vmalloc"), by argument name. Only set when asked for and for the
+    /// arguments whose region is known.
+    pub arg_regions: Option<BTreeMap<String, String>>,
+    /// Raw value of the first probe arguments, in order. Only set when asked
+    /// for; arguments the probe does not have are not reported.
+    pub args_raw: Option<Vec<u64>>,
+    /// Error reported by a hook while processing the event, if any.
+    pub error: Option<KernelHookError>,
     pub stack_trace: Option<StackTrace>,
+    /// Stack frames in a structured form, only reported when explicitly asked
+    /// for. The symbolized form is still reported in `stack_trace`.
+    pub stack_frames: Option<Vec<StackFrame>>,
 }
 
 impl EventFmt for KernelEvent {
     fn event_fmt(&self, f: &mut Formatter, _: &DisplayFormat) -> fmt::Result {
-        write!(
-            f,
-            "[{}] {}",
-            match self.probe_type.as_str() {
-                "raw_tracepoint" => "tp",
-                "kprobe" => "k",
-                "kretprobe" => "kr",
-                _ => "invalid",
-            },
-            self.symbol,
-        )?;
+        write!(f, "[{}] {}", self.probe_type.to_short_str(), self.symbol)?;
+
+        if let Some(ret) = self.ret {
+            write!(f, " ret {ret}")?;
+        }
+
+        if let Some(latency) = self.latency_ns {
+            write!(f, " lat {latency}ns")?;
+        }
+
+        if let Some(args) = &self.args {
+            write!(f, " (")?;
+            args.iter().enumerate().try_for_each(|(i, (name, value))| {
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{name}={value}")?;
+                match self.arg_regions.as_ref().and_then(|r| r.get(name)) {
+                    Some(region) => write!(f, "[{region}]"),
+                    None => Ok(()),
+                }
+            })?;
+            write!(f, ")")?;
+        }
+
+        if let Some(args_raw) = &self.args_raw {
+            write!(f, " args_raw [")?;
+            args_raw.iter().enumerate().try_for_each(|(i, arg)| {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{arg:#x}")
+            })?;
+            write!(f, "]")?;
+        }
+
+        if let Some(error) = &self.error {
+            write!(f, " error {} (hook {})", error.code, error.hook)?;
+        }
 
         Ok(())
     }
 }
 
+/// Kernel probe type. Its serialized form is one of "kprobe", "kretprobe",
+/// "raw_tracepoint", "fentry" or "fexit".
+#[event_type]
+#[serde(rename_all = "snake_case")]
+#[derive(Copy, Default)]
+pub enum ProbeType {
+    #[default]
+    Kprobe,
+    Kretprobe,
+    RawTracepoint,
+    Fentry,
+    Fexit,
+}
+
+impl ProbeType {
+    /// String representation, matching the serialized one.
+    pub fn to_str(&self) -> &'static str {
+        use ProbeType::*;
+        match self {
+            Kprobe => "kprobe",
+            Kretprobe => "kretprobe",
+            RawTracepoint => "raw_tracepoint",
+            Fentry => "fentry",
+            Fexit => "fexit",
+        }
+    }
+
+    /// Short string representation, as used in the probe cli arguments.
+    pub fn to_short_str(&self) -> &'static str {
+        use ProbeType::*;
+        match self {
+            Kprobe => "k",
+            Kretprobe => "kr",
+            RawTracepoint => "tp",
+            Fentry => "fe",
+            Fexit => "fx",
+        }
+    }
+}
+
+impl fmt::Display for ProbeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+/// Decoded value of a probe argument. Integers are reported as numbers, enums
+/// using the name of their value when known and other types (e.g. pointers) as
+/// raw hex values.
+#[event_type]
+#[serde(untagged)]
+#[derive(PartialEq)]
+pub enum KernelArg {
+    Unsigned(u64),
+    Signed(i64),
+    String(String),
+}
+
+impl fmt::Display for KernelArg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KernelArg::Unsigned(val) => write!(f, "{val}"),
+            KernelArg::Signed(val) => write!(f, "{val}"),
+            KernelArg::String(val) => write!(f, "{val}"),
+        }
+    }
+}
+
 #[event_type]
 #[derive(Default)]
 pub struct StackTrace(pub Vec<String>);
@@ -42,6 +159,31 @@ impl StackTrace {
     }
 }
 
+/// Error reported by a hook while processing an event. Only the first hook
+/// failing is reported.
+#[event_type]
+#[derive(Default, PartialEq)]
+pub struct KernelHookError {
+    /// Error code returned by the hook (e.g. -2 for -ENOENT).
+    pub code: i32,
+    /// Index of the hook in the probe hook list.
+    pub hook: u8,
+}
+
+/// Structured stack frame, e.g. for offline symbolization.
+#[event_type]
+#[derive(Default, PartialEq)]
+pub struct StackFrame {
+    /// Address of the frame.
+    pub addr: u64,
+    /// Symbol the address belongs to, if it was resolved.
+    pub symbol: Option<String>,
+    /// Offset of the address in the symbol, if it was resolved.
+    pub offset: Option<u64>,
+    /// Build id of the kernel or module image the symbol belongs to, if known.
+    pub build_id: Option<String>,
+}
+
 impl EventFmt for StackTrace {
     fn event_fmt(&self, f: &mut Formatter, format: &DisplayFormat) -> fmt::Result {
         let last = self.0.len() - 1;
@@ -14,10 +14,14 @@ pub use display::*;
 
 pub mod file;
 pub mod helpers;
+pub mod msgpack;
 #[cfg(feature = "python")]
 pub mod python;
 #[cfg(feature = "python-embed")]
 pub mod python_embed;
+pub mod rotate;
+pub mod segment;
+pub mod unix;
 
 pub mod common;
 pub use common::*;
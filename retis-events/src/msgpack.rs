@@ -0,0 +1,153 @@
+//! Handles the binary (MessagePack) event format, a compact alternative to
+//! json for high-rate collections.
+//!
+//! A MessagePack log starts with `MSGPACK_MAGIC`, followed by the events. Each
+//! event is stored as its length (u32, little endian) followed by its
+//! MessagePack representation. The latter is built from the same serde
+//! representation as the json one, so both formats hold the same data.
+
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read, Write},
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use super::Event;
+
+/// Magic identifying MessagePack event logs.
+pub const MSGPACK_MAGIC: &[u8] = b"RETISMP1";
+
+/// Writes events in the MessagePack format.
+pub struct MsgpackWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> MsgpackWriter<W> {
+    /// Create a new MessagePack writer, writing the log header.
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(MSGPACK_MAGIC)?;
+        Ok(Self { writer })
+    }
+
+    /// Write a single event.
+    pub fn write_event(&mut self, event: &Event) -> Result<()> {
+        let data = rmp_serde::to_vec_named(&event.to_json())?;
+        let len = u32::try_from(data.len())
+            .map_err(|_| anyhow!("Event is too large ({} bytes)", data.len()))?;
+
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(&data)?;
+        Ok(())
+    }
+
+    /// Flush the writer and give it back.
+    pub fn into_inner(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Read the next event of a MessagePack log, or None if we've reached its
+/// end. The header must have been consumed already.
+pub(crate) fn read_event<R: Read>(reader: &mut R) -> Result<Option<Event>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+        Ok(_) => (),
+    }
+
+    let mut data = vec![0; u32::from_le_bytes(len) as usize];
+    reader
+        .read_exact(&mut data)
+        .map_err(|e| anyhow!("Truncated MessagePack event: {e}"))?;
+
+    let obj: HashMap<String, serde_json::Value> = rmp_serde::from_slice(&data)
+        .map_err(|e| anyhow!("Failed to parse MessagePack event: {e}"))?;
+    Ok(Some(Event::from_json_obj(obj)?))
+}
+
+/// Reads events from a MessagePack log.
+pub struct MsgpackReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> MsgpackReader<R> {
+    /// Create a new MessagePack reader, checking the log header.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0; MSGPACK_MAGIC.len()];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|e| anyhow!("Could not read the MessagePack header: {e}"))?;
+        if magic != MSGPACK_MAGIC {
+            bail!("Not a MessagePack event log");
+        }
+
+        Ok(Self { reader })
+    }
+
+    /// Retrieve the next event or None if we've reached the end of the log.
+    pub fn next_event(&mut self) -> Result<Option<Event>> {
+        read_event(&mut self.reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{CommonEvent, OvsEvent, SectionId, UpcallEvent};
+
+    #[test]
+    fn round_trip() {
+        let ovs = OvsEvent::Upcall {
+            upcall: UpcallEvent {
+                cmd: 1,
+                port: 3366920467,
+                cpu: 7,
+            },
+        };
+
+        let mut event = Event::new();
+        event
+            .insert_section(
+                SectionId::Common,
+                Box::new(CommonEvent {
+                    timestamp: 23868955827403,
+                    smp_id: Some(7),
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        event
+            .insert_section(SectionId::Ovs, Box::new(ovs.clone()))
+            .unwrap();
+
+        let mut writer = MsgpackWriter::new(Vec::new()).unwrap();
+        writer.write_event(&event).unwrap();
+        writer.write_event(&Event::new()).unwrap();
+        let data = writer.into_inner().unwrap();
+        assert!(data.starts_with(MSGPACK_MAGIC));
+
+        let mut reader = MsgpackReader::new(Cursor::new(&data)).unwrap();
+        let read = reader.next_event().unwrap().unwrap();
+        assert_eq!(read.get_section::<OvsEvent>(SectionId::Ovs), Some(&ovs));
+        assert_eq!(
+            read.get_section::<CommonEvent>(SectionId::Common)
+                .map(|c| c.timestamp),
+            Some(23868955827403)
+        );
+        assert_eq!(read.to_json(), event.to_json());
+
+        assert!(reader.next_event().unwrap().unwrap().to_json() == serde_json::json!({}));
+        assert!(reader.next_event().unwrap().is_none());
+
+        // Truncated events and invalid headers are reported.
+        let mut reader = MsgpackReader::new(Cursor::new(&data[..data.len() - 1])).unwrap();
+        assert!(reader.next_event().is_ok());
+        assert!(reader.next_event().is_err());
+        assert!(MsgpackReader::new(Cursor::new(b"{\"common\":{}}\n")).is_err());
+    }
+}
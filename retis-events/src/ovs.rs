@@ -1,4 +1,7 @@
-use std::fmt;
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
 
 use anyhow::{bail, Result};
 use serde::{de::Error as Derror, ser::Error as Serror, Deserialize, Deserializer, Serializer};
@@ -57,6 +60,31 @@ pub enum OvsEvent {
     },
 }
 
+impl OvsEvent {
+    /// Is this an upcall event?
+    pub fn is_upcall(&self) -> bool {
+        matches!(self, OvsEvent::Upcall { .. })
+    }
+
+    /// Is this a flow operation event?
+    pub fn is_operation(&self) -> bool {
+        matches!(self, OvsEvent::Operation { .. })
+    }
+
+    /// Is this an action execution event?
+    pub fn is_action(&self) -> bool {
+        matches!(self, OvsEvent::Action { .. })
+    }
+
+    /// Get the action execution event, if this is one.
+    pub fn action(&self) -> Option<&ActionEvent> {
+        match self {
+            OvsEvent::Action { action_execute } => Some(action_execute),
+            _ => None,
+        }
+    }
+}
+
 impl EventFmt for OvsEvent {
     fn event_fmt(&self, f: &mut Formatter, format: &DisplayFormat) -> fmt::Result {
         use OvsEvent::*;
@@ -151,6 +179,9 @@ pub struct UpcallReturnEvent {
     pub upcall_ts: u64,
     pub upcall_cpu: u32,
     pub ret: i32,
+    /// Time spent processing the upcall, in nanoseconds. Only set when the
+    /// matching upcall event was seen.
+    pub upcall_latency_ns: Option<u64>,
 }
 
 impl EventFmt for UpcallReturnEvent {
@@ -159,7 +190,11 @@ impl EventFmt for UpcallReturnEvent {
             f,
             "upcall_ret ({}/{}) ret {}",
             self.upcall_cpu, self.upcall_ts, self.ret
-        )
+        )?;
+        if let Some(latency) = self.upcall_latency_ns {
+            write!(f, " latency {latency}ns")?;
+        }
+        Ok(())
     }
 }
 
@@ -228,9 +263,11 @@ impl EventFmt for OperationEvent {
 
 /// OVS Receive Event
 #[event_type]
-#[derive(Copy, Default, PartialEq)]
+#[derive(Default, PartialEq)]
 pub struct RecvUpcallEvent {
-    /// Type of upcall
+    /// Type of upcall. Holds dpif_upcall_type:
+    ///   DPIF_UC_MISS   = 0
+    ///   DPIF_UC_ACTION = 1
     pub r#type: u32,
     /// Packet size
     pub pkt_size: u32,
@@ -242,19 +279,274 @@ pub struct RecvUpcallEvent {
     pub batch_ts: u64,
     /// Index within the batch
     pub batch_idx: u8,
+    /// Flow key bytes, hex encoded. Might be truncated, see key_size for the
+    /// actual size.
+    pub key: Option<String>,
+    /// Decoded flow key, if the key bytes were captured.
+    pub flow_key: Option<OvsFlowKey>,
+}
+
+impl RecvUpcallEvent {
+    fn upcall_type_str(r#type: u32) -> &'static str {
+        match r#type {
+            0 => "miss",
+            1 => "action",
+            _ => "?",
+        }
+    }
 }
 
 impl EventFmt for RecvUpcallEvent {
     fn event_fmt(&self, f: &mut Formatter, _: &DisplayFormat) -> fmt::Result {
-        // FIXME: there are more fields.
         write!(
             f,
-            "upcall_recv q {} pkt_size {}",
-            self.queue_id, self.pkt_size
+            "upcall_recv ({}) q {} pkt_size {} key_size {} ts {} ({})",
+            RecvUpcallEvent::upcall_type_str(self.r#type),
+            self.queue_id,
+            self.pkt_size,
+            self.key_size,
+            self.batch_ts,
+            self.batch_idx
         )
     }
 }
 
+// OVS flow key attributes, see include/uapi/linux/openvswitch.h.
+const OVS_KEY_ATTR_PRIORITY: u16 = 2;
+const OVS_KEY_ATTR_IN_PORT: u16 = 3;
+const OVS_KEY_ATTR_ETHERNET: u16 = 4;
+const OVS_KEY_ATTR_ETHERTYPE: u16 = 6;
+const OVS_KEY_ATTR_IPV4: u16 = 7;
+const OVS_KEY_ATTR_IPV6: u16 = 8;
+const OVS_KEY_ATTR_TCP: u16 = 9;
+const OVS_KEY_ATTR_UDP: u16 = 10;
+const OVS_KEY_ATTR_ICMP: u16 = 11;
+const OVS_KEY_ATTR_ICMPV6: u16 = 12;
+const OVS_KEY_ATTR_SKB_MARK: u16 = 15;
+const OVS_KEY_ATTR_SCTP: u16 = 17;
+const OVS_KEY_ATTR_RECIRC_ID: u16 = 20;
+
+// Netlink attribute header size and flags.
+const NLA_HDRLEN: usize = 4;
+const NLA_TYPE_MASK: u16 = !((1 << 15) | (1 << 14));
+
+/// Decoded OVS flow key. Only the common attributes are decoded, others are
+/// ignored.
+#[event_type]
+#[derive(Default, PartialEq)]
+pub struct OvsFlowKey {
+    /// Datapath input port.
+    pub in_port: Option<u32>,
+    /// Packet priority.
+    pub priority: Option<u32>,
+    /// Packet mark.
+    pub skb_mark: Option<u32>,
+    /// Recirculation id.
+    pub recirc_id: Option<u32>,
+    /// Ethernet addresses.
+    pub eth: Option<OvsFlowKeyEth>,
+    /// Ethertype.
+    pub eth_type: Option<u16>,
+    /// IPv4 fields.
+    pub ipv4: Option<OvsFlowKeyIp>,
+    /// IPv6 fields.
+    pub ipv6: Option<OvsFlowKeyIp>,
+    /// TCP ports.
+    pub tcp: Option<OvsFlowKeyPorts>,
+    /// UDP ports.
+    pub udp: Option<OvsFlowKeyPorts>,
+    /// SCTP ports.
+    pub sctp: Option<OvsFlowKeyPorts>,
+    /// ICMP type and code.
+    pub icmp: Option<OvsFlowKeyIcmp>,
+    /// ICMPv6 type and code.
+    pub icmpv6: Option<OvsFlowKeyIcmp>,
+}
+
+/// Ethernet part of an OVS flow key.
+#[event_type]
+#[derive(Default, PartialEq)]
+pub struct OvsFlowKeyEth {
+    /// Source MAC address.
+    pub src: String,
+    /// Destination MAC address.
+    pub dst: String,
+}
+
+/// IPv4 or IPv6 part of an OVS flow key.
+#[event_type]
+#[derive(Default, PartialEq)]
+pub struct OvsFlowKeyIp {
+    /// Source address.
+    pub src: String,
+    /// Destination address.
+    pub dst: String,
+    /// L4 protocol.
+    pub proto: u8,
+    /// Type of service (IPv4) or traffic class (IPv6).
+    pub tos: u8,
+    /// Time to live (IPv4) or hop limit (IPv6).
+    pub ttl: u8,
+    /// Fragment type (OVS_FRAG_TYPE_*).
+    pub frag: u8,
+    /// Flow label, IPv6 only.
+    pub label: Option<u32>,
+}
+
+/// L4 ports part of an OVS flow key.
+#[event_type]
+#[derive(Default, PartialEq)]
+pub struct OvsFlowKeyPorts {
+    /// Source port.
+    pub src: u16,
+    /// Destination port.
+    pub dst: u16,
+}
+
+/// ICMP part of an OVS flow key.
+#[event_type]
+#[derive(Default, PartialEq)]
+pub struct OvsFlowKeyIcmp {
+    /// ICMP type.
+    pub r#type: u8,
+    /// ICMP code.
+    pub code: u8,
+}
+
+impl OvsFlowKey {
+    /// Decode a flow key from its netlink representation (a list of
+    /// OVS_KEY_ATTR_* attributes). As the key might have been truncated, a
+    /// trailing incomplete attribute is ignored.
+    pub fn from_bytes(key: &[u8]) -> Result<Self> {
+        let mut flow_key = OvsFlowKey::default();
+        let mut key = key;
+
+        while key.len() >= NLA_HDRLEN {
+            let len = u16::from_ne_bytes([key[0], key[1]]) as usize;
+            let r#type = u16::from_ne_bytes([key[2], key[3]]) & NLA_TYPE_MASK;
+
+            if len < NLA_HDRLEN {
+                bail!("Invalid flow key attribute length ({len})");
+            }
+            // Truncated attribute.
+            if len > key.len() {
+                break;
+            }
+
+            flow_key.decode_attr(r#type, &key[NLA_HDRLEN..len])?;
+
+            // Attributes are 4 bytes aligned.
+            key = &key[len.next_multiple_of(4).min(key.len())..];
+        }
+
+        Ok(flow_key)
+    }
+
+    fn decode_attr(&mut self, r#type: u16, data: &[u8]) -> Result<()> {
+        let check_len = |len: usize| -> Result<()> {
+            if data.len() < len {
+                bail!(
+                    "Flow key attribute {type} is too short ({} < {len})",
+                    data.len()
+                );
+            }
+            Ok(())
+        };
+        let u32_ne = |off: usize| u32::from_ne_bytes(data[off..off + 4].try_into().unwrap());
+        let u16_be = |off: usize| u16::from_be_bytes([data[off], data[off + 1]]);
+        let mac = |off: usize| {
+            data[off..off + 6]
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<Vec<_>>()
+                .join(":")
+        };
+        let ipv6 = |off: usize| {
+            Ipv6Addr::from(<[u8; 16]>::try_from(&data[off..off + 16]).unwrap()).to_string()
+        };
+
+        match r#type {
+            OVS_KEY_ATTR_IN_PORT => {
+                check_len(4)?;
+                self.in_port = Some(u32_ne(0));
+            }
+            OVS_KEY_ATTR_PRIORITY => {
+                check_len(4)?;
+                self.priority = Some(u32_ne(0));
+            }
+            OVS_KEY_ATTR_SKB_MARK => {
+                check_len(4)?;
+                self.skb_mark = Some(u32_ne(0));
+            }
+            OVS_KEY_ATTR_RECIRC_ID => {
+                check_len(4)?;
+                self.recirc_id = Some(u32_ne(0));
+            }
+            OVS_KEY_ATTR_ETHERNET => {
+                check_len(12)?;
+                self.eth = Some(OvsFlowKeyEth {
+                    src: mac(0),
+                    dst: mac(6),
+                });
+            }
+            OVS_KEY_ATTR_ETHERTYPE => {
+                check_len(2)?;
+                self.eth_type = Some(u16_be(0));
+            }
+            OVS_KEY_ATTR_IPV4 => {
+                check_len(12)?;
+                self.ipv4 = Some(OvsFlowKeyIp {
+                    src: Ipv4Addr::new(data[0], data[1], data[2], data[3]).to_string(),
+                    dst: Ipv4Addr::new(data[4], data[5], data[6], data[7]).to_string(),
+                    proto: data[8],
+                    tos: data[9],
+                    ttl: data[10],
+                    frag: data[11],
+                    label: None,
+                });
+            }
+            OVS_KEY_ATTR_IPV6 => {
+                check_len(40)?;
+                self.ipv6 = Some(OvsFlowKeyIp {
+                    src: ipv6(0),
+                    dst: ipv6(16),
+                    label: Some(u32::from_be_bytes(data[32..36].try_into().unwrap())),
+                    proto: data[36],
+                    tos: data[37],
+                    ttl: data[38],
+                    frag: data[39],
+                });
+            }
+            OVS_KEY_ATTR_TCP | OVS_KEY_ATTR_UDP | OVS_KEY_ATTR_SCTP => {
+                check_len(4)?;
+                let ports = Some(OvsFlowKeyPorts {
+                    src: u16_be(0),
+                    dst: u16_be(2),
+                });
+                match r#type {
+                    OVS_KEY_ATTR_TCP => self.tcp = ports,
+                    OVS_KEY_ATTR_UDP => self.udp = ports,
+                    _ => self.sctp = ports,
+                }
+            }
+            OVS_KEY_ATTR_ICMP | OVS_KEY_ATTR_ICMPV6 => {
+                check_len(2)?;
+                let icmp = Some(OvsFlowKeyIcmp {
+                    r#type: data[0],
+                    code: data[1],
+                });
+                match r#type {
+                    OVS_KEY_ATTR_ICMP => self.icmp = icmp,
+                    _ => self.icmpv6 = icmp,
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
 /// OVS output action data.
 #[event_type]
 #[derive(Default, PartialEq)]
@@ -271,7 +563,7 @@ pub struct ActionEvent {
 }
 
 impl EventFmt for ActionEvent {
-    fn event_fmt(&self, f: &mut Formatter, _: &DisplayFormat) -> fmt::Result {
+    fn event_fmt(&self, f: &mut Formatter, format: &DisplayFormat) -> fmt::Result {
         if self.recirc_id != 0 {
             write!(f, "[recirc_id {:#x}] ", self.recirc_id)?;
         }
@@ -279,91 +571,10 @@ impl EventFmt for ActionEvent {
         write!(f, "exec")?;
 
         match &self.action {
-            Some(OvsAction::Output { output }) => write!(f, " oport {}", output.port)?,
-            Some(OvsAction::Userspace(_)) => write!(f, " userspace")?,
-            Some(OvsAction::Set(_)) => write!(f, " tunnel_set")?,
-            Some(OvsAction::PushVlan(_)) => write!(f, " push_vlan")?,
-            Some(OvsAction::PopVlan(_)) => write!(f, " pop_vlan")?,
-            Some(OvsAction::Sample(_)) => write!(f, " sample")?,
-            Some(OvsAction::Recirc { recirc }) => write!(f, " recirc {:#x}", recirc.id)?,
-            Some(OvsAction::Hash(_)) => write!(f, " hash")?,
-            Some(OvsAction::PushMpls(_)) => write!(f, " push_mpls")?,
-            Some(OvsAction::PopMpls(_)) => write!(f, " pop_mpls")?,
-            Some(OvsAction::SetMasked(_)) => write!(f, " set_masked")?,
-            Some(OvsAction::Ct { ct }) => {
-                write!(f, " ct zone {}", ct.zone_id)?;
-
-                if let Some(nat) = &ct.nat {
-                    write!(f, " nat")?;
-                    if let Some(dir) = &nat.dir {
-                        match dir {
-                            NatDirection::Src => write!(f, "(src")?,
-                            NatDirection::Dst => write!(f, "(dst")?,
-                        }
-
-                        if ct.flags & R_OVS_CT_NAT_RANGE_MAP_IPS != 0 {
-                            if let (Some(min_addr), Some(max_addr)) =
-                                (nat.min_addr.as_ref(), nat.max_addr.as_ref())
-                            {
-                                if min_addr.eq(max_addr) {
-                                    write!(f, "={}", min_addr)?;
-                                } else {
-                                    write!(f, "={}-{}", min_addr, max_addr)?;
-                                }
-                            }
-                        }
-                        if ct.flags & R_OVS_CT_NAT_RANGE_PROTO_SPECIFIED != 0 {
-                            if let (Some(min_port), Some(max_port)) =
-                                (nat.min_port.as_ref(), nat.max_port.as_ref())
-                            {
-                                if min_port.eq(max_port) {
-                                    write!(f, ":{}", min_port)?;
-                                } else {
-                                    write!(f, ":{}-{}", min_port, max_port)?;
-                                }
-                            }
-                        }
-                        write!(f, ")")?;
-                    }
-                }
-
-                if ct.is_commit()
-                    || ct.is_force()
-                    || ct.is_persistent()
-                    || ct.is_hash()
-                    || ct.is_random()
-                {
-                    let mut flags = Vec::new();
-                    if ct.is_commit() {
-                        flags.push("commit");
-                    }
-                    if ct.is_force() {
-                        flags.push("force");
-                    }
-                    if ct.is_persistent() {
-                        flags.push("persistent");
-                    }
-                    if ct.is_hash() {
-                        flags.push("hash");
-                    }
-                    if ct.is_random() {
-                        flags.push("random");
-                    }
-                    write!(f, " {}", flags.join(","))?;
-                }
+            Some(action) => {
+                write!(f, " ")?;
+                action.event_fmt(f, format)?;
             }
-            Some(OvsAction::Trunc(_)) => write!(f, " trunc")?,
-            Some(OvsAction::PushEth(_)) => write!(f, " push_eth")?,
-            Some(OvsAction::PopEth(_)) => write!(f, " pop_eth")?,
-            Some(OvsAction::CtClear(_)) => write!(f, " ct_clear")?,
-            Some(OvsAction::PushNsh(_)) => write!(f, " push_nsh")?,
-            Some(OvsAction::PopNsh(_)) => write!(f, " pop_nsh")?,
-            Some(OvsAction::Meter(_)) => write!(f, " meter")?,
-            Some(OvsAction::Clone(_)) => write!(f, " clone")?,
-            Some(OvsAction::CheckPktLen(_)) => write!(f, " check_pkt_len")?,
-            Some(OvsAction::AddMpls(_)) => write!(f, " add_mpls")?,
-            Some(OvsAction::DecTtl(_)) => write!(f, " dec_ttl")?,
-            Some(OvsAction::Drop { reason }) => write!(f, " drop {}", reason)?,
             None => write!(f, " unspec")?,
         }
 
@@ -433,9 +644,15 @@ pub enum OvsAction {
     #[serde(rename = "meter")]
     Meter(OvsDummyAction),
     #[serde(rename = "clone")]
-    Clone(OvsDummyAction),
+    Clone {
+        #[serde(flatten)]
+        clone: OvsActionClone,
+    },
     #[serde(rename = "check_pkt_len")]
-    CheckPktLen(OvsDummyAction),
+    CheckPktLen {
+        #[serde(flatten)]
+        check_pkt_len: OvsActionCheckPktLen,
+    },
     #[serde(rename = "add_mpls")]
     AddMpls(OvsDummyAction),
     #[serde(rename = "dec_ttl")]
@@ -444,6 +661,146 @@ pub enum OvsAction {
     Drop { reason: u32 },
 }
 
+impl OvsAction {
+    /// Format a list of actions, e.g. the nested ones of a clone or
+    /// check_pkt_len action.
+    fn fmt_actions(
+        f: &mut Formatter,
+        format: &DisplayFormat,
+        actions: &[OvsAction],
+    ) -> fmt::Result {
+        actions.iter().enumerate().try_for_each(|(i, action)| {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            action.event_fmt(f, format)
+        })
+    }
+}
+
+impl EventFmt for OvsAction {
+    fn event_fmt(&self, f: &mut Formatter, format: &DisplayFormat) -> fmt::Result {
+        match self {
+            OvsAction::Output { output } => write!(f, "oport {}", output.port)?,
+            OvsAction::Userspace(_) => write!(f, "userspace")?,
+            OvsAction::Set(_) => write!(f, "tunnel_set")?,
+            OvsAction::PushVlan(_) => write!(f, "push_vlan")?,
+            OvsAction::PopVlan(_) => write!(f, "pop_vlan")?,
+            OvsAction::Sample(_) => write!(f, "sample")?,
+            OvsAction::Recirc { recirc } => write!(f, "recirc {:#x}", recirc.id)?,
+            OvsAction::Hash(_) => write!(f, "hash")?,
+            OvsAction::PushMpls(_) => write!(f, "push_mpls")?,
+            OvsAction::PopMpls(_) => write!(f, "pop_mpls")?,
+            OvsAction::SetMasked(_) => write!(f, "set_masked")?,
+            OvsAction::Ct { ct } => {
+                write!(f, "ct zone {}", ct.zone_id)?;
+
+                if let Some(nat) = &ct.nat {
+                    write!(f, " nat")?;
+                    if let Some(dir) = &nat.dir {
+                        write!(f, "({}", dir)?;
+
+                        if ct.flags & R_OVS_CT_NAT_RANGE_MAP_IPS != 0 {
+                            if let (Some(min_addr), Some(max_addr)) =
+                                (nat.min_addr.as_ref(), nat.max_addr.as_ref())
+                            {
+                                if min_addr.eq(max_addr) {
+                                    write!(f, "={}", min_addr)?;
+                                } else {
+                                    write!(f, "={}-{}", min_addr, max_addr)?;
+                                }
+                            }
+                        }
+                        if ct.flags & R_OVS_CT_NAT_RANGE_PROTO_SPECIFIED != 0 {
+                            if let (Some(min_port), Some(max_port)) =
+                                (nat.min_port.as_ref(), nat.max_port.as_ref())
+                            {
+                                if min_port.eq(max_port) {
+                                    write!(f, ":{}", min_port)?;
+                                } else {
+                                    write!(f, ":{}-{}", min_port, max_port)?;
+                                }
+                            }
+                        }
+                        write!(f, ")")?;
+                    }
+                }
+
+                if ct.is_commit()
+                    || ct.is_force()
+                    || ct.is_persistent()
+                    || ct.is_hash()
+                    || ct.is_random()
+                {
+                    let mut flags = Vec::new();
+                    if ct.is_commit() {
+                        flags.push("commit");
+                    }
+                    if ct.is_force() {
+                        flags.push("force");
+                    }
+                    if ct.is_persistent() {
+                        flags.push("persistent");
+                    }
+                    if ct.is_hash() {
+                        flags.push("hash");
+                    }
+                    if ct.is_random() {
+                        flags.push("random");
+                    }
+                    write!(f, " {}", flags.join(","))?;
+                }
+
+                if let Some(mark) = ct.mark {
+                    write!(f, " mark {:#x}", mark)?;
+                }
+                if let Some(labels) = &ct.labels {
+                    write!(f, " labels 0x")?;
+                    labels.iter().try_for_each(|b| write!(f, "{:02x}", b))?;
+                }
+            }
+            OvsAction::Trunc(_) => write!(f, "trunc")?,
+            OvsAction::PushEth(_) => write!(f, "push_eth")?,
+            OvsAction::PopEth(_) => write!(f, "pop_eth")?,
+            OvsAction::CtClear(_) => write!(f, "ct_clear")?,
+            OvsAction::PushNsh(_) => write!(f, "push_nsh")?,
+            OvsAction::PopNsh(_) => write!(f, "pop_nsh")?,
+            OvsAction::Meter(_) => write!(f, "meter")?,
+            OvsAction::Clone { clone } => {
+                write!(f, "clone")?;
+
+                if let Some(actions) = &clone.actions {
+                    write!(f, "(")?;
+                    Self::fmt_actions(f, format, actions)?;
+                    write!(f, ")")?;
+                }
+            }
+            OvsAction::CheckPktLen { check_pkt_len } => {
+                write!(f, "check_pkt_len")?;
+
+                if let Some(pkt_len) = check_pkt_len.pkt_len {
+                    write!(f, "({})", pkt_len)?;
+                }
+                if let Some(actions) = &check_pkt_len.actions_if_greater {
+                    write!(f, " gt(")?;
+                    Self::fmt_actions(f, format, actions)?;
+                    write!(f, ")")?;
+                }
+                if let Some(actions) = &check_pkt_len.actions_if_less_equal {
+                    write!(f, " le(")?;
+                    Self::fmt_actions(f, format, actions)?;
+                    write!(f, ")")?;
+                }
+            }
+            OvsAction::AddMpls(_) => write!(f, "add_mpls")?,
+            OvsAction::DecTtl(_) => write!(f, "dec_ttl")?,
+            OvsAction::Drop { reason } => write!(f, "drop {}", reason)?,
+        }
+
+        Ok(())
+    }
+}
+
 /// OVS output action data.
 #[event_type]
 #[derive(Copy, Default, PartialEq)]
@@ -460,6 +817,32 @@ pub struct OvsActionRecirc {
     pub id: u32,
 }
 
+/// OVS clone action data. Nested actions are not set if they were not
+/// captured.
+#[event_type]
+#[derive(Default, PartialEq)]
+pub struct OvsActionClone {
+    /// Actions executed on the cloned packet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actions: Option<Vec<OvsAction>>,
+}
+
+/// OVS check_pkt_len action data. Fields are not set if they were not
+/// captured.
+#[event_type]
+#[derive(Default, PartialEq)]
+pub struct OvsActionCheckPktLen {
+    /// Packet length to compare against.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pkt_len: Option<u16>,
+    /// Actions executed if the packet length is greater than pkt_len.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actions_if_greater: Option<Vec<OvsAction>>,
+    /// Actions executed if the packet length is lesser or equal to pkt_len.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actions_if_less_equal: Option<Vec<OvsAction>>,
+}
+
 /// OVS conntrack flags
 pub const R_OVS_CT_COMMIT: u32 = 1 << 0;
 pub const R_OVS_CT_FORCE: u32 = 1 << 1;
@@ -473,6 +856,8 @@ pub const R_OVS_CT_NAT_RANGE_PROTO_SPECIFIED: u32 = 1 << 8;
 pub const R_OVS_CT_NAT_RANGE_PROTO_RANDOM: u32 = 1 << 9;
 pub const R_OVS_CT_NAT_RANGE_PERSISTENT: u32 = 1 << 10;
 pub const R_OVS_CT_NAT_RANGE_PROTO_RANDOM_FULLY: u32 = 1 << 11;
+pub const R_OVS_CT_MARK: u32 = 1 << 12;
+pub const R_OVS_CT_LABELS: u32 = 1 << 13;
 
 /// OVS conntrack action data.
 #[event_type]
@@ -485,6 +870,12 @@ pub struct OvsActionCt {
     /// NAT
     #[serde(skip_serializing_if = "Option::is_none")]
     pub nat: Option<OvsActionCtNat>,
+    /// Conntrack mark set by the action, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mark: Option<u32>,
+    /// Conntrack labels set by the action, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<[u8; 16]>,
 }
 
 impl OvsActionCt {
@@ -522,6 +913,16 @@ pub enum NatDirection {
     #[serde(rename = "dst")]
     Dst,
 }
+
+impl fmt::Display for NatDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NatDirection::Src => write!(f, "src"),
+            NatDirection::Dst => write!(f, "dst"),
+        }
+    }
+}
+
 /// OVS NAT action data.
 #[event_type]
 #[derive(Default, PartialEq)]
@@ -546,7 +947,7 @@ mod tests {
 
     #[test]
     fn test_event_to_from_json() -> Result<()> {
-        let events: [(&'static str, OvsEvent); 8] = [
+        let events: [(&'static str, OvsEvent); 15] = [
             // Upcall event
             (
                 r#"{"cmd":1,"cpu":0,"event_type":"upcall","port":4195744766}"#,
@@ -593,6 +994,19 @@ mod tests {
                         ret: 0,
                         upcall_ts: 61096236973661,
                         upcall_cpu: 0,
+                        upcall_latency_ns: None,
+                    },
+                },
+            ),
+            // Upcall return event with latency
+            (
+                r#"{"event_type":"upcall_return","ret":0,"upcall_cpu":0,"upcall_latency_ns":42301,"upcall_ts":61096236973661}"#,
+                OvsEvent::UpcallReturn {
+                    upcall_return: UpcallReturnEvent {
+                        ret: 0,
+                        upcall_ts: 61096236973661,
+                        upcall_cpu: 0,
+                        upcall_latency_ns: Some(42301),
                     },
                 },
             ),
@@ -620,6 +1034,22 @@ mod tests {
                     },
                 },
             ),
+            // Recv upcall event
+            (
+                r#"{"batch_idx":1,"batch_ts":61096237019698,"event_type":"recv_upcall","key":"0400000000000000","key_size":200,"pkt_size":98,"queue_id":3316322986,"type":0}"#,
+                OvsEvent::RecvUpcall {
+                    recv_upcall: RecvUpcallEvent {
+                        r#type: 0,
+                        pkt_size: 98,
+                        key_size: 200,
+                        queue_id: 3316322986,
+                        batch_ts: 61096237019698,
+                        batch_idx: 1,
+                        key: Some(String::from("0400000000000000")),
+                        flow_key: None,
+                    },
+                },
+            ),
             // Conntrack action event
             (
                 r#"{"action":"ct","event_type":"action_execute","flags":485,"nat":{"dir":"dst","max_addr":"10.244.1.30","max_port":36900,"min_addr":"10.244.1.3","min_port":36895},"recirc_id":34,"zone_id":20}"#,
@@ -636,6 +1066,27 @@ mod tests {
                                     min_port: Some(36895),
                                     max_port: Some(36900),
                                 }),
+                                mark: None,
+                                labels: None,
+                            },
+                        }),
+                        recirc_id: 34,
+                        queue_id: None,
+                    },
+                },
+            ),
+            // Conntrack action event with mark and labels
+            (
+                r#"{"action":"ct","event_type":"action_execute","flags":12293,"labels":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,42],"mark":3735928559,"recirc_id":34,"zone_id":20}"#,
+                OvsEvent::Action {
+                    action_execute: ActionEvent {
+                        action: Some(OvsAction::Ct {
+                            ct: OvsActionCt {
+                                zone_id: 20,
+                                flags: 12293,
+                                nat: None,
+                                mark: Some(0xdeadbeef),
+                                labels: Some([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 42]),
                             },
                         }),
                         recirc_id: 34,
@@ -654,6 +1105,73 @@ mod tests {
                     },
                 },
             ),
+            // Clone action event with nested actions
+            (
+                r#"{"action":"clone","actions":[{"action":"output","port":2}],"event_type":"action_execute","recirc_id":0}"#,
+                OvsEvent::Action {
+                    action_execute: ActionEvent {
+                        action: Some(OvsAction::Clone {
+                            clone: OvsActionClone {
+                                actions: Some(vec![OvsAction::Output {
+                                    output: OvsActionOutput { port: 2 },
+                                }]),
+                            },
+                        }),
+                        recirc_id: 0,
+                        queue_id: None,
+                    },
+                },
+            ),
+            // Clone action event, without its nested actions
+            (
+                r#"{"action":"clone","event_type":"action_execute","recirc_id":0}"#,
+                OvsEvent::Action {
+                    action_execute: ActionEvent {
+                        action: Some(OvsAction::Clone {
+                            clone: OvsActionClone::default(),
+                        }),
+                        recirc_id: 0,
+                        queue_id: None,
+                    },
+                },
+            ),
+            // Check packet length action event with nested actions
+            (
+                r#"{"action":"check_pkt_len","actions_if_greater":[{"action":"output","port":3}],"actions_if_less_equal":[{"action":"recirc","id":5},{"action":"drop","reason":0}],"event_type":"action_execute","pkt_len":1500,"recirc_id":0}"#,
+                OvsEvent::Action {
+                    action_execute: ActionEvent {
+                        action: Some(OvsAction::CheckPktLen {
+                            check_pkt_len: OvsActionCheckPktLen {
+                                pkt_len: Some(1500),
+                                actions_if_greater: Some(vec![OvsAction::Output {
+                                    output: OvsActionOutput { port: 3 },
+                                }]),
+                                actions_if_less_equal: Some(vec![
+                                    OvsAction::Recirc {
+                                        recirc: OvsActionRecirc { id: 5 },
+                                    },
+                                    OvsAction::Drop { reason: 0 },
+                                ]),
+                            },
+                        }),
+                        recirc_id: 0,
+                        queue_id: None,
+                    },
+                },
+            ),
+            // Check packet length action event, without its arguments
+            (
+                r#"{"action":"check_pkt_len","event_type":"action_execute","recirc_id":0}"#,
+                OvsEvent::Action {
+                    action_execute: ActionEvent {
+                        action: Some(OvsAction::CheckPktLen {
+                            check_pkt_len: OvsActionCheckPktLen::default(),
+                        }),
+                        recirc_id: 0,
+                        queue_id: None,
+                    },
+                },
+            ),
         ];
 
         for (event_json, event) in events.iter() {
@@ -671,4 +1189,166 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_event_helpers() {
+        let upcall = OvsEvent::Upcall {
+            upcall: UpcallEvent::default(),
+        };
+        assert!(upcall.is_upcall());
+        assert!(!upcall.is_operation());
+        assert!(!upcall.is_action());
+        assert!(upcall.action().is_none());
+
+        let operation = OvsEvent::Operation {
+            flow_operation: OperationEvent::default(),
+        };
+        assert!(!operation.is_upcall());
+        assert!(operation.is_operation());
+        assert!(!operation.is_action());
+        assert!(operation.action().is_none());
+
+        let action = OvsEvent::Action {
+            action_execute: ActionEvent {
+                action: Some(OvsAction::Drop { reason: 0 }),
+                recirc_id: 3,
+                queue_id: None,
+            },
+        };
+        assert!(!action.is_upcall());
+        assert!(!action.is_operation());
+        assert!(action.is_action());
+        assert_eq!(action.action().map(|a| a.recirc_id), Some(3));
+    }
+
+    #[test]
+    fn test_nat_direction_display() {
+        assert_eq!(NatDirection::Src.to_string(), "src");
+        assert_eq!(NatDirection::Dst.to_string(), "dst");
+    }
+
+    #[test]
+    fn test_event_display() {
+        let event = RecvUpcallEvent {
+            r#type: 0,
+            pkt_size: 98,
+            key_size: 200,
+            queue_id: 3316322986,
+            batch_ts: 61096237019698,
+            batch_idx: 1,
+            key: None,
+            flow_key: None,
+        };
+        assert_eq!(
+            format!(
+                "{}",
+                event.display(&DisplayFormat::new(), &FormatterConf::new())
+            ),
+            "upcall_recv (miss) q 3316322986 pkt_size 98 key_size 200 ts 61096237019698 (1)"
+        );
+
+        let action = OvsAction::Ct {
+            ct: OvsActionCt {
+                zone_id: 20,
+                flags: R_OVS_CT_COMMIT | R_OVS_CT_MARK | R_OVS_CT_LABELS,
+                nat: None,
+                mark: Some(0xdeadbeef),
+                labels: Some([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 42]),
+            },
+        };
+        assert_eq!(
+            format!(
+                "{}",
+                action.display(&DisplayFormat::new(), &FormatterConf::new())
+            ),
+            "ct zone 20 commit mark 0xdeadbeef labels 0x0000000000000000000000000000002a"
+        );
+    }
+
+    #[test]
+    fn flow_key_decode() -> Result<()> {
+        fn attr(key: &mut Vec<u8>, r#type: u16, data: &[u8]) {
+            key.extend_from_slice(&((4 + data.len()) as u16).to_ne_bytes());
+            key.extend_from_slice(&r#type.to_ne_bytes());
+            key.extend_from_slice(data);
+            key.resize(key.len().next_multiple_of(4), 0);
+        }
+
+        let mut key = Vec::new();
+        attr(&mut key, OVS_KEY_ATTR_RECIRC_ID, &0u32.to_ne_bytes());
+        attr(&mut key, OVS_KEY_ATTR_IN_PORT, &2u32.to_ne_bytes());
+        attr(
+            &mut key,
+            OVS_KEY_ATTR_ETHERNET,
+            &[0x0a, 0x58, 0, 0, 0, 1, 0x0a, 0x58, 0, 0, 0, 2],
+        );
+        attr(&mut key, OVS_KEY_ATTR_ETHERTYPE, &0x0800u16.to_be_bytes());
+        attr(
+            &mut key,
+            OVS_KEY_ATTR_IPV4,
+            &[10, 0, 0, 1, 10, 0, 0, 2, 6, 0, 64, 0],
+        );
+        attr(&mut key, OVS_KEY_ATTR_TCP, &[0xa4, 0x10, 0x01, 0xbb]);
+        // Unknown attributes are skipped.
+        attr(&mut key, 25, &[0; 16]);
+        // Truncated attribute.
+        key.extend_from_slice(&[24, 0, OVS_KEY_ATTR_UDP as u8, 0]);
+
+        let flow_key = OvsFlowKey::from_bytes(&key)?;
+        assert_eq!(
+            flow_key,
+            OvsFlowKey {
+                in_port: Some(2),
+                recirc_id: Some(0),
+                eth: Some(OvsFlowKeyEth {
+                    src: "0a:58:00:00:00:01".to_string(),
+                    dst: "0a:58:00:00:00:02".to_string(),
+                }),
+                eth_type: Some(0x0800),
+                ipv4: Some(OvsFlowKeyIp {
+                    src: "10.0.0.1".to_string(),
+                    dst: "10.0.0.2".to_string(),
+                    proto: 6,
+                    tos: 0,
+                    ttl: 64,
+                    frag: 0,
+                    label: None,
+                }),
+                tcp: Some(OvsFlowKeyPorts {
+                    src: 42000,
+                    dst: 443,
+                }),
+                ..Default::default()
+            }
+        );
+
+        let mut key = Vec::new();
+        let mut ipv6 = Vec::new();
+        ipv6.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>()?.octets());
+        ipv6.extend_from_slice(&"2001:db8::2".parse::<Ipv6Addr>()?.octets());
+        ipv6.extend_from_slice(&0x12345u32.to_be_bytes());
+        ipv6.extend_from_slice(&[58, 0, 255, 0]);
+        attr(&mut key, OVS_KEY_ATTR_IPV6, &ipv6);
+        attr(&mut key, OVS_KEY_ATTR_ICMPV6, &[128, 0]);
+
+        let flow_key = OvsFlowKey::from_bytes(&key)?;
+        let ip = flow_key.ipv6.unwrap();
+        assert_eq!(ip.src, "2001:db8::1");
+        assert_eq!(ip.dst, "2001:db8::2");
+        assert_eq!(ip.label, Some(0x12345));
+        assert_eq!(ip.proto, 58);
+        assert_eq!(ip.ttl, 255);
+        assert_eq!(
+            flow_key.icmpv6,
+            Some(OvsFlowKeyIcmp {
+                r#type: 128,
+                code: 0
+            })
+        );
+
+        // Malformed attributes.
+        assert!(OvsFlowKey::from_bytes(&[2, 0, 3, 0]).is_err());
+        assert!(OvsFlowKey::from_bytes(&[6, 0, 3, 0, 0, 0, 0, 0]).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,170 @@
+//! Handles time-rotated event logs: events are written (in json, one per
+//! line) to a new file every fixed time interval, so downstream systems can
+//! ingest complete time buckets.
+//!
+//! A rotated log is a directory holding `events-<time>.json` files, where
+//! `<time>` is the UTC start time of the bucket the file covers (e.g.
+//! `events-20240102T030400Z.json`). Buckets are aligned on the interval and
+//! files are only created for buckets holding at least one event.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::DateTime;
+
+use super::Event;
+
+fn bucket_path(dir: &Path, bucket: u64) -> Result<PathBuf> {
+    let time = DateTime::from_timestamp(i64::try_from(bucket)?, 0)
+        .ok_or_else(|| anyhow!("Invalid bucket start time ({bucket})"))?;
+    Ok(dir.join(format!("events-{}.json", time.format("%Y%m%dT%H%M%SZ"))))
+}
+
+/// Writes events to a time-rotated log.
+pub struct RotatingWriter {
+    dir: PathBuf,
+    /// Rotation interval, in seconds.
+    interval: u64,
+    /// Writer of the current bucket, if any event was written to it.
+    writer: Option<BufWriter<File>>,
+    /// Start time of the current bucket, in seconds since the epoch.
+    bucket: u64,
+}
+
+impl RotatingWriter {
+    /// Create a new time-rotated log in `dir`, starting a new file every
+    /// `interval` seconds.
+    pub fn new<P>(dir: P, interval: u64) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref().to_path_buf();
+        if interval == 0 {
+            bail!("Rotation interval cannot be 0");
+        }
+
+        fs::create_dir_all(&dir).map_err(|e| anyhow!("Could not create {}: {e}", dir.display()))?;
+
+        Ok(Self {
+            dir,
+            interval,
+            writer: None,
+            bucket: 0,
+        })
+    }
+
+    /// Close the current file, if any, and start the one of `bucket`. Files
+    /// are appended to, so restarting a collection does not lose the events
+    /// already written in the current bucket.
+    fn rotate(&mut self, bucket: u64) -> Result<()> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush()?;
+        }
+
+        let path = bucket_path(&self.dir, bucket)?;
+        self.writer = Some(BufWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(|e| anyhow!("Could not create or open '{}': {e}", path.display()))?,
+        ));
+        self.bucket = bucket;
+        Ok(())
+    }
+
+    /// Write a single event to the log.
+    pub fn write_event(&mut self, event: &Event) -> Result<()> {
+        self.write_event_at(event, SystemTime::now())
+    }
+
+    /// Write a single event to the log, `now` being the current time.
+    fn write_event_at(&mut self, event: &Event, now: SystemTime) -> Result<()> {
+        let secs = now.duration_since(UNIX_EPOCH)?.as_secs();
+        let bucket = secs - secs % self.interval;
+
+        if self.writer.is_none() || bucket != self.bucket {
+            self.rotate(bucket)?;
+        }
+
+        let mut line = serde_json::to_vec(&event.to_json())?;
+        line.push(b'\n');
+
+        // Unwrap as the writer was set above.
+        self.writer.as_mut().unwrap().write_all(&line)?;
+        Ok(())
+    }
+
+    /// Flush the current file. No event can be written afterwards.
+    pub fn finish(mut self) -> Result<()> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::file::FileEventsFactory;
+
+    #[test]
+    fn rotate() {
+        let dir = std::env::temp_dir().join(format!("retis-rotate-{}", std::process::id()));
+
+        let mut fact = FileEventsFactory::new("test_data/test_events.json").unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = fact.next_event().unwrap() {
+            events.push(event);
+        }
+
+        assert!(RotatingWriter::new(&dir, 0).is_err());
+        let mut writer = RotatingWriter::new(&dir, 60).unwrap();
+
+        // 2024-01-02T03:04:30Z.
+        let start = UNIX_EPOCH + Duration::from_secs(1704164670);
+        writer.write_event_at(&events[0], start).unwrap();
+        writer
+            .write_event_at(&events[1], start + Duration::from_secs(29))
+            .unwrap();
+        // Past the interval, a new file is created.
+        writer
+            .write_event_at(&events[2], start + Duration::from_secs(30))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut files = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                "events-20240102T030400Z.json",
+                "events-20240102T030500Z.json"
+            ]
+        );
+
+        let count = |file: &str| {
+            let mut fact = FileEventsFactory::new(dir.join(file)).unwrap();
+            let mut count = 0;
+            while fact.next_event().unwrap().is_some() {
+                count += 1;
+            }
+            count
+        };
+        assert_eq!(count(&files[0]), 2);
+        assert_eq!(count(&files[1]), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+//! Handles segmented event logs: events are written (in json) to a set of
+//! fixed-size segment files, alongside an index mapping timestamps to
+//! segments. This allows to later seek to a given point in time without
+//! scanning the whole capture.
+//!
+//! A segmented log is a directory with the following layout:
+//! - `index.json`: one json entry per segment, see `SegmentIndexEntry`.
+//! - `segment-<id>.json`: events, one per line.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{CommonEvent, Event, SectionId};
+
+const INDEX_FILE: &str = "index.json";
+
+/// Index entry describing a single segment.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SegmentIndexEntry {
+    /// Segment id, used to build the segment file name.
+    pub id: u32,
+    /// Number of events in the segment.
+    pub events: u64,
+    /// Lowest event timestamp found in the segment, if any.
+    pub min_ts: Option<u64>,
+    /// Highest event timestamp found in the segment, if any.
+    pub max_ts: Option<u64>,
+}
+
+impl SegmentIndexEntry {
+    fn new(id: u32) -> Self {
+        Self {
+            id,
+            ..Default::default()
+        }
+    }
+
+    fn account(&mut self, ts: Option<u64>) {
+        self.events += 1;
+
+        if let Some(ts) = ts {
+            self.min_ts = Some(self.min_ts.map_or(ts, |min| min.min(ts)));
+            self.max_ts = Some(self.max_ts.map_or(ts, |max| max.max(ts)));
+        }
+    }
+}
+
+fn segment_path(dir: &Path, id: u32) -> PathBuf {
+    dir.join(format!("segment-{id:06}.json"))
+}
+
+fn event_timestamp(event: &Event) -> Option<u64> {
+    event
+        .get_section::<CommonEvent>(SectionId::Common)
+        .map(|common| common.timestamp)
+}
+
+/// Writes events to a segmented log.
+pub struct SegmentWriter {
+    dir: PathBuf,
+    segment_size: u64,
+    index: BufWriter<File>,
+    writer: BufWriter<File>,
+    /// Size of the current segment, in bytes.
+    size: u64,
+    /// Index entry of the current segment.
+    entry: SegmentIndexEntry,
+}
+
+impl SegmentWriter {
+    /// Create a new segmented log in `dir`, with segments of at most
+    /// `segment_size` bytes (a segment holds at least one event). Existing
+    /// segments in `dir` are removed.
+    pub fn new<P>(dir: P, segment_size: u64) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref().to_path_buf();
+        if segment_size == 0 {
+            bail!("Segment size cannot be 0");
+        }
+
+        fs::create_dir_all(&dir).map_err(|e| anyhow!("Could not create {}: {e}", dir.display()))?;
+
+        // Remove stale segments, if any, so they are not mistaken for
+        // unindexed ones when reading the log back.
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("segment-") && n.ends_with(".json"))
+            {
+                fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(Self {
+            index: BufWriter::new(Self::create(&dir.join(INDEX_FILE))?),
+            writer: BufWriter::new(Self::create(&segment_path(&dir, 0))?),
+            dir,
+            segment_size,
+            size: 0,
+            entry: SegmentIndexEntry::new(0),
+        })
+    }
+
+    fn create(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|e| anyhow!("Could not create or open '{}': {e}", path.display()))
+    }
+
+    /// Write the index entry of the current segment.
+    fn write_index(&mut self) -> Result<()> {
+        let mut entry = serde_json::to_vec(&self.entry)?;
+        entry.push(b'\n');
+        self.index.write_all(&entry)?;
+        Ok(())
+    }
+
+    /// Close the current segment and start a new one.
+    fn rotate(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.write_index()?;
+
+        let id = self.entry.id + 1;
+        self.writer = BufWriter::new(Self::create(&segment_path(&self.dir, id))?);
+        self.entry = SegmentIndexEntry::new(id);
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Write a single event to the log.
+    pub fn write_event(&mut self, event: &Event) -> Result<()> {
+        let mut line = serde_json::to_vec(&event.to_json())?;
+        line.push(b'\n');
+
+        if self.size > 0 && self.size + line.len() as u64 > self.segment_size {
+            self.rotate()?;
+        }
+
+        self.writer.write_all(&line)?;
+        self.size += line.len() as u64;
+        self.entry.account(event_timestamp(event));
+        Ok(())
+    }
+
+    /// Flush and index the current segment. No event can be written
+    /// afterwards.
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.write_index()?;
+        Ok(self.index.flush()?)
+    }
+}
+
+/// Segmented log events factory retrieving and unmarshaling events.
+pub struct SegmentEventsFactory {
+    dir: PathBuf,
+    segments: Vec<SegmentIndexEntry>,
+    /// Position of the next segment to read in `segments`.
+    next: usize,
+    reader: Option<BufReader<File>>,
+    /// Events older than this timestamp are skipped.
+    seek: Option<u64>,
+}
+
+impl SegmentEventsFactory {
+    pub fn new<P>(dir: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref().to_path_buf();
+        let index = dir.join(INDEX_FILE);
+        let index = BufReader::new(
+            File::open(&index).map_err(|e| anyhow!("Could not open {}: {e}", index.display()))?,
+        );
+
+        let mut segments = index
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<SegmentIndexEntry>(&line?)
+                    .map_err(|e| anyhow!("Failed to parse segment index: {e}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // The log might not have been properly finished (e.g. collection
+        // was interrupted). Segments not in the index are still read, but
+        // are never skipped when seeking.
+        let mut id = segments.last().map_or(0, |s| s.id + 1);
+        while segment_path(&dir, id).exists() {
+            segments.push(SegmentIndexEntry::new(id));
+            id += 1;
+        }
+
+        Ok(Self {
+            dir,
+            segments,
+            next: 0,
+            reader: None,
+            seek: None,
+        })
+    }
+
+    /// Seek to the first event at or after `ts`. Segments only holding older
+    /// events are skipped entirely, using the index.
+    pub fn seek(&mut self, ts: u64) {
+        self.seek = Some(ts);
+        self.reader = None;
+        self.next = self
+            .segments
+            .iter()
+            .position(|s| s.events == 0 || !matches!(s.max_ts, Some(max) if max < ts))
+            .unwrap_or(self.segments.len());
+    }
+
+    /// Retrieve the next event or None if we've reached the end of the log.
+    pub fn next_event(&mut self) -> Result<Option<Event>> {
+        loop {
+            let reader = match self.reader.as_mut() {
+                Some(reader) => reader,
+                None => {
+                    let segment = match self.segments.get(self.next) {
+                        Some(segment) => segment,
+                        None => return Ok(None),
+                    };
+                    let path = segment_path(&self.dir, segment.id);
+
+                    self.next += 1;
+                    self.reader
+                        .insert(BufReader::new(File::open(&path).map_err(|e| {
+                            anyhow!("Could not open {}: {e}", path.display())
+                        })?))
+                }
+            };
+
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                self.reader = None;
+                continue;
+            }
+
+            let event = Event::from_json(line)?;
+            match (self.seek, event_timestamp(&event)) {
+                (Some(seek), Some(ts)) if ts < seek => continue,
+                _ => return Ok(Some(event)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::FileEventsFactory;
+
+    #[test]
+    fn write_and_seek() {
+        let dir = std::env::temp_dir().join(format!("retis-segment-{}", std::process::id()));
+
+        let mut fact = FileEventsFactory::new("test_data/test_events.json").unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = fact.next_event().unwrap() {
+            events.push(event);
+        }
+        assert_eq!(events.len(), 4);
+
+        // Small segments so each one holds a single event.
+        let mut writer = SegmentWriter::new(&dir, 1).unwrap();
+        events.iter().for_each(|e| writer.write_event(e).unwrap());
+        writer.finish().unwrap();
+
+        let mut reader = SegmentEventsFactory::new(&dir).unwrap();
+        assert_eq!(reader.segments.len(), 4);
+        let mut count = 0;
+        while reader.next_event().unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 4);
+
+        // Seek to the third event, the first two segments must be skipped.
+        let ts = event_timestamp(&events[2]).unwrap();
+        let mut reader = SegmentEventsFactory::new(&dir).unwrap();
+        reader.seek(ts);
+        assert_eq!(reader.next, 2);
+
+        let mut read = Vec::new();
+        while let Some(event) = reader.next_event().unwrap() {
+            read.push(event_timestamp(&event).unwrap());
+        }
+        assert_eq!(read, vec![ts, event_timestamp(&events[3]).unwrap()]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+//! Streams events to a Unix domain socket (in json, one per line), e.g. to
+//! feed a local analysis daemon.
+//!
+//! When the peer disconnects the stream either ends or, if asked to, the
+//! writer periodically tries to reconnect; events written in the meantime
+//! are dropped.
+
+use std::{
+    io::{ErrorKind, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+
+use super::Event;
+
+/// Minimum delay between two reconnection attempts.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Writes events to a Unix domain socket.
+pub struct UnixSocketWriter {
+    path: PathBuf,
+    /// Try to reconnect when the peer disconnects, instead of ending the
+    /// stream.
+    reconnect: bool,
+    /// Connected stream, if any.
+    stream: Option<UnixStream>,
+    /// Time of the last reconnection attempt, if any.
+    last_attempt: Option<Instant>,
+    /// Number of events dropped while disconnected.
+    dropped: u64,
+}
+
+impl UnixSocketWriter {
+    /// Connect to the socket at `path`. A peer must already be listening.
+    pub fn connect<P>(path: P, reconnect: bool) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let stream = UnixStream::connect(&path)
+            .map_err(|e| anyhow!("Could not connect to '{}': {e}", path.display()))?;
+
+        Ok(Self {
+            path,
+            reconnect,
+            stream: Some(stream),
+            last_attempt: None,
+            dropped: 0,
+        })
+    }
+
+    /// Try to reconnect to the socket, if the last attempt is old enough.
+    fn try_reconnect(&mut self) -> bool {
+        if self
+            .last_attempt
+            .is_some_and(|last| last.elapsed() < RECONNECT_DELAY)
+        {
+            return false;
+        }
+        self.last_attempt = Some(Instant::now());
+
+        match UnixStream::connect(&self.path) {
+            Ok(stream) => {
+                info!(
+                    "Reconnected to '{}' ({} event(s) dropped)",
+                    self.path.display(),
+                    self.dropped
+                );
+                self.stream = Some(stream);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Write a single event to the socket. Returns false once the peer
+    /// disconnected and events can't be written anymore, which only happens
+    /// when not reconnecting.
+    pub fn write_event(&mut self, event: &Event) -> Result<bool> {
+        if self.stream.is_none() && !(self.reconnect && self.try_reconnect()) {
+            self.dropped += 1;
+            return Ok(self.reconnect);
+        }
+
+        let mut line = serde_json::to_vec(&event.to_json())?;
+        line.push(b'\n');
+
+        // Unwrap as the stream was set above.
+        match self.stream.as_mut().unwrap().write_all(&line) {
+            Ok(()) => Ok(true),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::NotConnected
+                ) =>
+            {
+                warn!("Peer of '{}' disconnected", self.path.display());
+                self.stream = None;
+                self.dropped += 1;
+                Ok(self.reconnect)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Number of events which could not be written as the peer was
+    /// disconnected.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{BufRead, BufReader},
+        os::unix::net::UnixListener,
+    };
+
+    use super::*;
+    use crate::file::FileEventsFactory;
+
+    #[test]
+    fn unix_socket() {
+        let path = std::env::temp_dir().join(format!("retis-unix-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut fact = FileEventsFactory::new("test_data/test_events.json").unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = fact.next_event().unwrap() {
+            events.push(event);
+        }
+
+        // Nothing is listening.
+        assert!(UnixSocketWriter::connect(&path, false).is_err());
+
+        let listener = UnixListener::bind(&path).unwrap();
+        let mut writer = UnixSocketWriter::connect(&path, false).unwrap();
+        let (peer, _) = listener.accept().unwrap();
+
+        assert!(writer.write_event(&events[0]).unwrap());
+        assert!(writer.write_event(&events[1]).unwrap());
+
+        let mut lines = BufReader::new(&peer).lines();
+        for event in events[..2].iter() {
+            let line = lines.next().unwrap().unwrap();
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&line).unwrap(),
+                event.to_json()
+            );
+        }
+
+        // The peer disconnecting ends the stream.
+        drop(lines);
+        drop(peer);
+        while writer.write_event(&events[2]).unwrap() {}
+        assert!(!writer.write_event(&events[2]).unwrap());
+        assert!(writer.dropped() > 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
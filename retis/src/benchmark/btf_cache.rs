@@ -0,0 +1,35 @@
+use std::{collections::BTreeSet, fs, time::Instant};
+
+use anyhow::Result;
+
+use crate::core::{inspect::btf_benchmark::resolve_prototypes, kernel::Symbol};
+
+/// Benchmark time to resolve the prototypes of functions at startup, with a
+/// cold and a warm BTF cache.
+pub(super) fn bench(ci: bool) -> Result<()> {
+    let count = match ci {
+        false => 5000,
+        true => 10,
+    };
+
+    let symbols = fs::read_to_string("retis/test_data/available_filter_functions")?
+        .lines()
+        .filter_map(|l| l.split(' ').next())
+        .map(String::from)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .take(count)
+        .map(Symbol::Func)
+        .collect::<Vec<_>>();
+
+    let dir = std::env::temp_dir().join(format!("retis-bench-btf-{}", std::process::id()));
+
+    for name in ["cold", "warm"] {
+        let now = Instant::now();
+        resolve_prototypes(&dir, &symbols)?;
+        println!("btf_{name}_cache_us {}", now.elapsed().as_micros());
+    }
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
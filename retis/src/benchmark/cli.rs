@@ -8,7 +8,7 @@ use crate::{benchmark::*, cli::*, module::Modules};
 #[command(name = "benchmark")]
 pub(crate) struct Benchmark {
     #[arg(
-        value_parser=PossibleValuesParser::new(["events_parsing", "events_output"]),
+        value_parser=PossibleValuesParser::new(["events_parsing", "events_output", "stack_symbolization", "btf_cache"]),
         help = "Benchmark to run",
     )]
     pub(super) r#type: String,
@@ -25,6 +25,8 @@ impl SubCommandParserRunner for Benchmark {
         match self.r#type.as_str() {
             "events_parsing" => events_parsing::bench(self.ci)?,
             "events_output" => events_output::bench(self.ci)?,
+            "stack_symbolization" => stack_symbolization::bench(self.ci)?,
+            "btf_cache" => btf_cache::bench(self.ci)?,
             x => bail!("Unknown benchmark '{x}'"),
         }
 
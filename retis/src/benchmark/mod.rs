@@ -1,5 +1,7 @@
 pub(crate) mod cli;
 pub(crate) mod helpers;
 
+mod btf_cache;
 mod events_output;
 mod events_parsing;
+mod stack_symbolization;
@@ -0,0 +1,62 @@
+use std::time::Instant;
+
+use anyhow::Result;
+
+use crate::core::inspect::inspector;
+
+/// Benchmark time to symbolize stack traces, resolving frames one by one or
+/// in a single batch.
+pub(super) fn bench(ci: bool) -> Result<()> {
+    let iters = match ci {
+        false => 100000,
+        true => 1,
+    };
+
+    let kernel = &inspector()?.kernel;
+
+    // Typical receive path stack trace, up to kfree_skb_reason.
+    let stack = [
+        ("kfree_skb_reason", 0x1),
+        ("tcp_v4_rcv", 0x35a),
+        ("ip_protocol_deliver_rcu", 0x36),
+        ("ip_local_deliver_finish", 0x78),
+        ("ip_local_deliver", 0x6e),
+        ("ip_sublist_rcv_finish", 0x6b),
+        ("ip_sublist_rcv", 0x17f),
+        ("ip_list_rcv", 0xfe),
+        ("__netif_receive_skb_list_core", 0x229),
+        ("netif_receive_skb_list_internal", 0x1a0),
+        ("napi_complete_done", 0x6f),
+        ("__napi_poll", 0x29),
+        ("net_rx_action", 0x29e),
+        ("__do_softirq", 0xc6),
+        ("irq_exit_rcu", 0x92),
+        ("common_interrupt", 0x82),
+        ("asm_common_interrupt", 0x26),
+    ]
+    .iter()
+    .map(|(name, offset)| Ok(kernel.get_symbol_addr(name)? + offset))
+    .collect::<Result<Vec<_>>>()?;
+
+    let now = Instant::now();
+    for _ in 0..iters {
+        for addr in stack.iter() {
+            kernel.get_name_offt_from_addr_near(*addr)?;
+        }
+    }
+    println!(
+        "100k_stacks_per_address_symbolization_us {}",
+        now.elapsed().as_micros()
+    );
+
+    let now = Instant::now();
+    for _ in 0..iters {
+        kernel.get_names_offt_from_addrs_near(&stack)?;
+    }
+    println!(
+        "100k_stacks_batched_symbolization_us {}",
+        now.elapsed().as_micros()
+    );
+
+    Ok(())
+}
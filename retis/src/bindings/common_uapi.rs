@@ -1,11 +1,15 @@
 /* automatically generated by rust-bindgen 0.70.1 */
 
 pub type __u8 = ::std::os::raw::c_uchar;
+pub type __u16 = ::std::os::raw::c_ushort;
 pub type __u64 = ::std::os::raw::c_ulonglong;
 pub type u8_ = __u8;
+pub type u16_ = __u16;
 pub type u64_ = __u64;
 pub type __s8 = ::std::os::raw::c_schar;
 pub type s8 = __s8;
+pub type __s32 = ::std::os::raw::c_int;
+pub type s32 = __s32;
 #[doc = " Per-probe parameter offsets. A value of -1 means the argument isn't\n available. Please try to reuse the targeted object names.\n\n Skip Default trait implementation:\n\n <div rustbindgen nodefault></div>"]
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -17,16 +21,38 @@ pub struct retis_probe_offsets {
     pub nft_pktinfo: s8,
     pub nft_traceinfo: s8,
 }
+pub const KERNEL_SECTION_CORE: kernel_sections = 0;
+pub const KERNEL_SECTION_ARGS: kernel_sections = 1;
+pub const KERNEL_SECTION_ERROR: kernel_sections = 2;
+pub type kernel_sections = ::std::os::raw::c_uint;
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct kernel_event {
     pub symbol: u64_,
     pub stack_id: ::std::os::raw::c_long,
+    pub ret: ::std::os::raw::c_long,
     pub type_: u8_,
+    pub latency: u64_,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct kernel_args_event {
+    pub args: [u64_; 12usize],
+    pub nargs: u8_,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct kernel_error_event {
+    pub code: s32,
+    pub hook: u8_,
 }
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct retis_probe_config {
     pub offsets: retis_probe_offsets,
     pub stack_trace: u8_,
+    pub pid_filter: u8_,
+    pub args: u16_,
+    pub cpu_filter: u8_,
+    pub cpus: [u64_; 4usize],
 }
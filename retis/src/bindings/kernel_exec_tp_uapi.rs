@@ -56,6 +56,8 @@ pub struct exec_ct {
     pub zone_id: u16_,
     pub min_port: u16_,
     pub max_port: u16_,
+    pub mark: u32_,
+    pub labels: [u8_; 16usize],
 }
 impl Default for exec_ct {
     fn default() -> Self {
@@ -6,8 +6,10 @@ pub type __u64 = ::std::os::raw::c_ulonglong;
 pub type u8_ = __u8;
 pub type u32_ = __u32;
 pub type u64_ = __u64;
+pub const RECV_UPCALL_KEY_MAX: enum_RECV_UPCALL_KEY_MAX = 128;
+pub type enum_RECV_UPCALL_KEY_MAX = ::std::os::raw::c_uint;
 #[repr(C)]
-#[derive(Debug, Default, Copy, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub struct recv_upcall_event {
     pub key_size: u64_,
     pub batch_ts: u64_,
@@ -15,4 +17,15 @@ pub struct recv_upcall_event {
     pub queue_id: u32_,
     pub type_: u32_,
     pub batch_idx: u8_,
+    pub key_len: u8_,
+    pub key: [u8_; 128usize],
+}
+impl Default for recv_upcall_event {
+    fn default() -> Self {
+        let mut s = ::std::mem::MaybeUninit::<Self>::uninit();
+        unsafe {
+            ::std::ptr::write_bytes(s.as_mut_ptr(), 0, 1);
+            s.assume_init()
+        }
+    }
 }
@@ -3,7 +3,15 @@
 //! Cli module, providing tools for registering and accessing command line interface arguments
 //! as well as defining the subcommands that the tool supports.
 #![allow(dead_code)] // FIXME
-use std::{any::Any, convert::From, env, ffi::OsString, fmt::Debug, path::PathBuf};
+use std::{
+    any::Any,
+    convert::From,
+    env,
+    ffi::OsString,
+    fmt::Debug,
+    io::{self, IsTerminal},
+    path::PathBuf,
+};
 
 use anyhow::{anyhow, bail, Result};
 use clap::{
@@ -201,6 +209,13 @@ pub(crate) struct MainConfig {
         help = "Path to kernel configuration (e.g. /boot/config-6.3.8-200.fc38.x86_64; default: auto-detect)"
     )]
     pub(crate) kconf: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Do not use the on-disk cache of the data derived from the kernel BTF (stored in
+/var/cache/retis), e.g. to troubleshoot type resolution issues. The cache is automatically
+invalidated when the kernel or its BTF files change."
+    )]
+    pub(crate) no_btf_cache: bool,
 }
 
 /// ThinCli handles the first (a.k.a "thin") round of Command Line Interface parsing.
@@ -421,12 +436,36 @@ pub(crate) enum CliDisplayFormat {
     MultiLine,
 }
 
+/// Type of the "color" argument.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub(crate) enum CliColor {
+    /// Colorize the output when writing to a terminal.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl CliColor {
+    /// Should the events printed to stdout be colorized?
+    pub(crate) fn stdout(self) -> bool {
+        match self {
+            CliColor::Auto => {
+                io::stdout().is_terminal() && matches!(env::var("TERM"), Ok(x) if x != "dumb")
+            }
+            CliColor::Always => true,
+            CliColor::Never => false,
+        }
+    }
+}
+
 /// Create and register a ThinCli
 pub(crate) fn get_cli() -> Result<ThinCli> {
     let mut cli = ThinCli::new()?;
     cli.add_subcommand(Box::new(Collect::new()?))?;
     cli.add_subcommand(Box::new(Print::new()?))?;
     cli.add_subcommand(Box::new(Sort::new()?))?;
+    cli.add_subcommand(Box::new(Replay::new()?))?;
     #[cfg(feature = "python")]
     cli.add_subcommand(Box::new(PythonCli::new()?))?;
     cli.add_subcommand(Box::new(Pcap::new()?))?;
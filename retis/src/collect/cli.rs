@@ -2,16 +2,57 @@
 //!
 //! Collect is a dynamic CLI subcommand that allows collectors to register their arguments.
 
-use std::{any::Any, collections::HashSet, path::PathBuf};
+use std::{any::Any, collections::HashSet, path::PathBuf, time::Duration};
 
 use anyhow::Result;
 use clap::{
     error::Error as ClapError,
-    {builder::PossibleValuesParser, error::ErrorKind, Arg, ArgAction, ArgMatches, Args, Command},
+    {
+        builder::PossibleValuesParser, error::ErrorKind, Arg, ArgAction, ArgMatches, Args, Command,
+        ValueEnum,
+    },
 };
 
 use super::CollectRunner;
-use crate::cli::{dynamic::DynamicCommand, SubCommand, *};
+use crate::{
+    cli::{dynamic::DynamicCommand, SubCommand, *},
+    core::events::BPF_EVENTS_BUFFER_MAX,
+    events::SectionId,
+    helpers::{compress::Compression, time::parse_duration},
+    process::{
+        cidr_filter::CidrSpec, meta_map::MetaMapSpec, moving_avg::MovingAvgSpec,
+        outlier::OutlierSpec, regex_filter::RegexSpec, sections::parse_section,
+    },
+};
+
+/// Format of the events written with --out.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub(crate) enum OutFormat {
+    /// Newline-delimited json events.
+    #[default]
+    Json,
+    /// Packets of the events holding one, in the pcapng format.
+    Pcapng,
+    /// Length-prefixed MessagePack events, a compact binary format.
+    Msgpack,
+    /// Columnar Parquet file, for analytics.
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+/// Format of the events written to stdout.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub(crate) enum PrintFormat {
+    /// Text representation, one line per event.
+    SingleLine,
+    /// Text representation, using multiple lines per event if needed.
+    #[default]
+    MultiLine,
+    /// Newline-delimited json events.
+    Json,
+    /// Pretty-printed json events.
+    JsonlPretty,
+}
 
 #[derive(Args, Debug, Default)]
 pub(crate) struct CollectArgs {
@@ -22,11 +63,137 @@ pub(crate) struct CollectArgs {
 not released. If exhausted, no stack trace will be included."
     )]
     pub(super) stack: bool,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..=127),
+        help = "Maximum number of frames reported in stack traces. Full stack traces are still captured,
+only the reported frames are limited. Defaults to reporting all of them."
+    )]
+    pub(super) stack_depth: Option<u32>,
+    #[arg(
+        long,
+        help = "Report the raw address of stack frames next to their symbolized form, e.g. to check
+the symbolization is correct."
+    )]
+    pub(super) stack_raw: bool,
+    #[arg(
+        long,
+        help = "Also report stack frames in a structured form (address, symbol, offset and build id
+of the kernel or module image), e.g. for offline symbolization."
+    )]
+    pub(super) stack_frames: bool,
+    #[arg(
+        long,
+        help = "Collapse consecutive identical frames of stack traces into a single one, suffixed
+with the number of repetitions, e.g. 'func+0x10 (x42)'. Useful for deep recursions."
+    )]
+    pub(super) stack_dedup: bool,
+    #[arg(
+        long,
+        help = "Write the kernel symbols table (address, symbol and module) used for stack
+symbolization, as found when the collection starts, to a sidecar file in a kallsyms-like format.
+This allows raw addresses (e.g. reported with --stack-raw) to be symbolized consistently later on,
+even after the kernel changed."
+    )]
+    pub(super) export_symbols: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "stack",
+        help = "Report a summary of the unique stack traces seen during the collection, with their
+number of occurrences, on stderr when it ends."
+    )]
+    pub(super) unique_stacks: bool,
+    #[arg(
+        id = "pid",
+        long,
+        help = "Only report events from the given process or thread id. Can be used multiple times.
+The filtering is done in the probes and is best effort: in some contexts, e.g. softirqs, the
+current task is not related to the event and matching events can be dropped (or unrelated ones
+reported)."
+    )]
+    pub(super) pids: Vec<u32>,
+    #[arg(
+        id = "cpu",
+        long,
+        help = "Only report events happening on the given cpu. Can be used multiple times. The
+filtering is done in the probes, before any processing, which reduces the overhead when
+investigating a subset of the cpus."
+    )]
+    pub(super) cpus: Vec<u32>,
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Number of times attaching a probe is retried when failing transiently (e.g. the kernel
+being temporarily short of resources), with an exponential backoff starting at 10ms. Other failures
+(invalid program, missing symbol, etc) are not retried."
+    )]
+    pub(super) attach_retries: u32,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Stop the collection once the given number of events were emitted. Only events
+passing all filters and post-processing steps are counted. Events generated by Retis itself
+(e.g. the startup one) are not counted."
+    )]
+    pub(super) count: Option<u64>,
+    #[arg(
+        long,
+        value_parser = parse_duration,
+        help = "Stop the collection after the given duration, e.g. '30s', '5m' or '1h' (seconds if no
+unit is given)."
+    )]
+    pub(super) duration: Option<Duration>,
     #[arg(
         long,
         help = "Execute a command and terminate the collection once done."
     )]
     pub(super) cmd: Option<String>,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u64).range(1..=BPF_EVENTS_BUFFER_MAX),
+        help = "Size of the buffer used to retrieve events from the kernel, in bytes. It is rounded up
+to a power-of-2 number of pages. Defaults to 8MiB. A larger buffer absorbs larger bursts of events
+(fewer events are lost) at the cost of memory, which is allocated in the kernel and locked for the
+whole collection."
+    )]
+    pub(super) buffer_size: Option<u64>,
+    #[arg(
+        long,
+        conflicts_with = "buffer_size",
+        help = "Directory of the BPF filesystem holding maps pinned by a privileged helper, to be used
+instead of creating them (e.g. /sys/fs/bpf/retis). The events ring buffers (events_map and log_map)
+must be pinned there. Other maps found there (e.g. stack_map) are reused by all probes. This allows
+to run the collection with reduced privileges."
+    )]
+    pub(super) pinned_maps: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Dump the structure of the events retrieved from the kernel to stderr, as they are
+unmarshaled: raw sections (owner, data type and size) and decoded sections. This is a debugging
+aid for event decoding issues and is not meant to be parsed."
+    )]
+    pub(super) debug_events: bool,
+    #[arg(
+        long,
+        help = "Report in each event the time elapsed between the event being generated in the kernel
+and it being processed in userspace (processing_latency_ns, in the common section). Rising values
+indicate the collection is falling behind."
+    )]
+    pub(super) processing_latency: bool,
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Number of threads unmarshaling the events retrieved from the kernel. By default (0)
+this is done by the thread retrieving them, which can become a bottleneck at high event rates."
+    )]
+    pub(super) workers: usize,
+    #[arg(
+        long,
+        help = "When using --workers, output events as soon as they are unmarshaled instead of in
+the order they were retrieved from the kernel. This lowers the latency and memory usage of the
+collection, but events are no longer ordered."
+    )]
+    pub(super) unordered: bool,
     // Some of the options that we want for this arg are not available in clap's derive interface
     // so both the argument definition and the field population will be done manually.
     #[arg(skip)]
@@ -47,6 +214,8 @@ Valid TYPEs:
 - kprobe | k: kernel probes.
 - kretprobe | kr: kernel return probes.
 - raw_tracepoint | tp: kernel tracepoints.
+- fentry | fe: kernel functions entry, using BPF trampolines (requires Linux >= 5.5).
+- fexit | fx: kernel functions exit, using BPF trampolines (requires Linux >= 5.5).
 
 Wildcards (*) can be used, eg. \"kprobe:tcp_*\" or \"tp:skb:*\".
 
@@ -55,26 +224,255 @@ Examples:
   --probe skb:kfree_skb --probe consume_skb"
     )]
     pub(super) probes: Vec<String>,
+    #[arg(
+        long,
+        help = "Report arguments of a probed tracepoint in its events, decoded using their BTF
+type. Can be used multiple times. Arguments are given by name and follow the
+TRACEPOINT=ARG[,ARG...] pattern. The tracepoint must be probed using --probe.
+
+Example:
+  --probe tp:skb:kfree_skb --probe-args skb:kfree_skb=skb,reason"
+    )]
+    pub(super) probe_args: Vec<String>,
+    #[arg(
+        long,
+        requires = "probe_args",
+        help = "Annotate the pointer arguments reported using --probe-args with the kernel memory
+region they point into (e.g. 'text', 'vmalloc' or 'direct-map'). Region boundaries are retrieved
+from the running kernel when possible; regions randomized at boot can't be reported."
+    )]
+    pub(super) classify_pointers: bool,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..=12),
+        help = "Report the raw value of the first N arguments of all probes (as u64, in order), e.g.
+to correlate with pahole or gdb. Unlike --probe-args, arguments are neither named nor decoded."
+    )]
+    pub(super) probe_args_raw: Option<u32>,
     #[arg(
         short,
         long,
         num_args = 0..=1,
         default_missing_value = "retis.data",
         help = "Write the events to a file rather than to sdout. If the flag is used without a file name,
-defaults to \"retis.data\"."
+defaults to \"retis.data\". Use 'unix:<path>' to stream the events (as json) to a Unix domain
+socket instead, e.g. to feed a local analysis daemon; a peer must already be listening on it."
     )]
     pub(super) out: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "out",
+        help = "When streaming events to a Unix domain socket, try to reconnect (every second) when the
+peer disconnects instead of terminating the collection. Events are dropped while disconnected."
+    )]
+    pub(super) out_reconnect: bool,
+    #[arg(
+        long,
+        requires = "out",
+        conflicts_with_all = ["segment_size", "rotate_interval", "delta_timestamps"],
+        help = "Format of the events written with --out. With 'pcapng', only the packets of the events
+holding one are written (one Enhanced Packet Block per packet and one Interface Description Block
+per interface seen), so the file can be opened with packet analysis tools such as Wireshark.
+With 'msgpack', events are written in a compact binary format holding the same data as json;
+such files can be read back by the post-processing commands (e.g. 'print'). With 'parquet' (only
+available when built with the 'parquet' feature), events are written in a columnar file for
+analytics (e.g. using SQL engines or dataframes): common fields and the main section fields are
+stored in typed columns and each section in its own column, as json."
+    )]
+    #[clap(value_enum, default_value_t=OutFormat::Json)]
+    pub(super) out_format: OutFormat,
+    #[arg(
+        long,
+        requires = "out",
+        help = "Split the events written with --out into segments of at most the given size (in bytes).
+The output is then a directory holding the segments and an index, allowing to efficiently seek
+to a given timestamp later on (see 'print --seek')."
+    )]
+    pub(super) segment_size: Option<u64>,
+    #[arg(
+        long,
+        requires = "out",
+        conflicts_with = "segment_size",
+        help = "Rotate the file written with --out every given number of seconds. The output is then a
+directory holding one file per time bucket, named after the bucket start time (in UTC, e.g.
+'events-20240102T030400Z.json'), so downstream systems can ingest complete time buckets."
+    )]
+    pub(super) rotate_interval: Option<u64>,
+    #[arg(
+        long,
+        requires = "out",
+        conflicts_with_all = ["segment_size", "rotate_interval"],
+        help = "Delta-encode the event timestamps in the file written with --out: only the first event
+holds an absolute timestamp, the following ones hold the difference with the previous event. This
+reduces the size of dense captures. Files are transparently decoded when read back."
+    )]
+    pub(super) delta_timestamps: bool,
+    #[arg(
+        long,
+        value_enum,
+        requires = "out",
+        conflicts_with_all = ["segment_size", "rotate_interval"],
+        help = "Compress the json events written with --out. By default the compression is chosen from the
+file extension ('.gz' for gzip, '.zst' for zstd), if any."
+    )]
+    pub(super) compress: Option<Compression>,
+    #[arg(
+        long,
+        help = "Emit the events following the order of the given reference capture (a file generated
+with --out). Events are matched using stable fields only (probe, sections and packet data) and are
+buffered until all the ones preceding them in the reference were emitted. Events not found in the
+reference are emitted last, in their arrival order. At most 65536 events are buffered for each of
+those: past that, the missing reference events are skipped and the oldest unmatched events are
+emitted. Useful to get diff-stable outputs across runs."
+    )]
+    pub(super) reference: Option<PathBuf>,
+    #[arg(
+        long,
+        value_parser = parse_duration,
+        help = "Buffer the events for the given time window, e.g. '100ms', and emit them ordered by
+timestamp. Events are retrieved from multiple cpus and are otherwise not strictly ordered. A larger
+window orders events more reliably at the cost of a higher output latency."
+    )]
+    pub(super) sort_window: Option<Duration>,
+    #[arg(
+        long,
+        help = "Watch for kernel modules being reloaded and re-attach the probes targeting their
+symbols."
+    )]
+    pub(super) follow: bool,
+    #[arg(
+        long,
+        help = "Only emit events whose numeric field exceeds a baseline learned during a warm-up window
+(see --outlier-warmup). The field and the percentile used as a baseline are given as
+section.field[:pNN], e.g. 'skb.len:p99' (defaults to p99). Events not having the field are always
+emitted."
+    )]
+    pub(super) outlier: Option<OutlierSpec>,
+    #[arg(
+        long,
+        default_value = "1000",
+        requires = "outlier",
+        help = "Number of events used to learn the --outlier baseline. Those are not emitted."
+    )]
+    pub(super) outlier_warmup: usize,
+    #[arg(
+        long,
+        help = "Only emit events whose numeric field deviates from its moving average, computed over the
+previous values (see --moving-avg-window). The field and the deviation factor are given as
+section.field[:FACTOR], e.g. 'skb.len:2' (defaults to 2): values above average*FACTOR or below
+average/FACTOR are emitted. Events not having the field are always emitted."
+    )]
+    pub(super) moving_avg: Option<MovingAvgSpec>,
+    #[arg(
+        long,
+        default_value = "100",
+        requires = "moving_avg",
+        help = "Number of values the --moving-avg average is computed over. No event is emitted until
+the window is full."
+    )]
+    pub(super) moving_avg_window: usize,
+    #[arg(
+        long,
+        help = "Only emit events whose string field matches a regular expression, given as
+section.field=pattern, e.g. 'skb.dev.name=^eth\\d+$'. This is evaluated in userspace on the fields
+captured in the events. Can be used multiple times, events must then match all of them."
+    )]
+    pub(super) meta_regex: Vec<RegexSpec>,
+    #[arg(
+        long,
+        help = "Discard events until one matches a trigger, then emit it and all the following events.
+The trigger is given as section.field=pattern, as for --meta-regex, e.g.
+'kernel.symbol=^kfree_skb_reason$'. Can be used multiple times, the trigger event must then match
+all of them. Combined with --count or --duration, this records events from an interesting moment
+on."
+    )]
+    pub(super) trigger: Vec<RegexSpec>,
+    #[arg(
+        long,
+        help = "Only emit events whose IP address field is part of a list of CIDRs loaded from a file,
+given as section.field=/path/to/cidrs, e.g. 'skb.ip.saddr=/etc/retis/cidrs'. The file contains one
+CIDR (or address) per line, empty lines and lines starting with '#' are ignored. This is evaluated
+in userspace on the fields captured in the events. Can be used multiple times, events must then
+match all of them."
+    )]
+    pub(super) meta_cidr: Vec<CidrSpec>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_section,
+        help = "Only emit events holding at least one of the given sections, e.g. 'ovs' or
+'skb-drop,nft'. Events are emitted as a whole, including their other sections (e.g. tracking
+ids). This is evaluated in userspace, all the collectors still run."
+    )]
+    pub(super) only: Vec<SectionId>,
+    #[arg(
+        long,
+        help = "Only emit events holding a packet with an invalid IPv4 header, TCP or UDP checksum.
+Checksums are verified in userspace on the captured packets; L4 checksums are only verified when the
+whole segment was captured. When the skb 'meta' section is collected, L4 checksums still to be
+computed (CHECKSUM_PARTIAL) are not verified."
+    )]
+    pub(super) checksum_bad_only: bool,
+    #[arg(
+        long,
+        help = "Translate the value of a field to a name using a table loaded from a csv file, given
+as section.field=/path/to/table.csv, e.g. 'skb.meta.priority=/etc/retis/priorities.csv'. The file
+contains one 'value,name' entry per line, integer values can be given in hexadecimal (0x prefix).
+Empty lines and lines starting with '#' are ignored. Names are reported in the common section of
+the events. Can be used multiple times."
+    )]
+    pub(super) meta_map: Vec<MetaMapSpec>,
+    #[arg(
+        long,
+        help = "Stamp all the events with static tags loaded from the given file, e.g. to describe the
+host role or environment when aggregating captures from many hosts. The file contains one
+'key=value' entry per line, empty lines and lines starting with '#' are ignored. Tags are reported
+in the common section of the events."
+    )]
+    pub(super) tags: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Post-process the events using a plugin loaded from the given shared library. The
+library must expose 'long retis_plugin_process(const char *event, size_t len, char *out, size_t
+out_size)', called for each event given in its json representation. It returns 0 to keep the event
+as-is, the size of the modified json event written in 'out' to replace it or a negative value to
+drop it. The plugin runs before the other userspace filters."
+    )]
+    pub(super) plugin: Option<PathBuf>,
     #[arg(
         long,
         help = "Write the events to stdout even if --out is used.",
         default_value = "false"
     )]
     pub(super) print: bool,
+    #[arg(
+        short,
+        long,
+        conflicts_with = "print",
+        help = "Do not write the events to stdout, only report the number of events matching the
+filters when the collection ends. Useful to quickly check if a path is hit at all. Events are still
+written to a file if --out is used."
+    )]
+    pub(super) quiet: bool,
+    #[arg(
+        long,
+        help = "Periodically warn when events are lost in the kernel while writing events (to stdout
+or to a file) takes most of the time, reporting both figures. This usually means the output is
+too slow to keep up, e.g. a slow pipe or terminal."
+    )]
+    pub(super) backpressure: bool,
     #[arg(long, help = "Format used when printing an event.")]
-    #[clap(value_enum, default_value_t=CliDisplayFormat::MultiLine)]
-    pub(super) format: CliDisplayFormat,
+    #[clap(value_enum, default_value_t=PrintFormat::MultiLine)]
+    pub(super) format: PrintFormat,
     #[arg(long, help = "Print the time as UTC")]
     pub(super) utc: bool,
+    #[arg(
+        long,
+        help = "Colorize the events printed to stdout: sections are colorized by type and drops are
+highlighted. 'auto' only does so when writing to a terminal."
+    )]
+    #[clap(value_enum, default_value_t=CliColor::Auto)]
+    pub(super) color: CliColor,
     #[arg(
         id = "filter-packet",
         short,
@@ -92,11 +490,18 @@ Example: --filter-packet "ip dst host 10.0.0.1""#
 The syntax follows:
 sk_buff.member1.[...].memberN.member_leaf [==|<=|>=|!=] value
 With value ::= "string" | number.
+Multiple fields can be matched against the same value using any(field1, field2, ...).
+Multiple expressions can be combined using '||', the filter matching if any of them does. When used
+alongside --filter-packet, both filters must match.
+A sample(1/N) gate can be added using '&&' to only let pass roughly 1 out of N matching packets.
 "==" is the only operator valid for "string" assuming member_leaf type is a pointer to a char or array of chars.
 
 Examples of meta filters:
 --filter-meta 'sk_buff.dev.name == "eth0"'
---filter-meta 'sk_buff.dev.nd_net.net.ns.inum == 4026531840'"#
+--filter-meta 'sk_buff.dev.nd_net.net.ns.inum == 4026531840'
+--filter-meta 'any(sk_buff.mark, sk_buff.priority) == 0x1'
+--filter-meta 'sk_buff.mark == 1 || sk_buff.mark == 2'
+--filter-meta 'sk_buff.mark == 1 && sample(1/100)'"#
     )]
     pub(super) meta_filter: Option<String>,
     #[arg(
@@ -159,14 +564,23 @@ impl SubCommand for Collect {
         Ok(Collect {
             args: CollectArgs::default(),
             collectors: DynamicCommand::new(
-                CollectArgs::augment_args(Command::new("collect")).arg(
-                    Arg::new("collectors")
-                        .long("collectors")
-                        .short('c')
-                        .value_delimiter(',')
-                        .action(ArgAction::Append)
-                        .help("Comma-separated list of collectors to enable. When not specified default to auto-mode (all collectors are enabled unless a prerequisite is missing)."),
-                ),
+                CollectArgs::augment_args(Command::new("collect"))
+                    .arg(
+                        Arg::new("collectors")
+                            .long("collectors")
+                            .short('c')
+                            .value_delimiter(',')
+                            .action(ArgAction::Append)
+                            .help("Comma-separated list of collectors to enable. When not specified default to auto-mode (all collectors are enabled unless a prerequisite is missing)."),
+                    )
+                    .arg(
+                        Arg::new("no_collectors")
+                            .long("no-collectors")
+                            .visible_alias("exclude")
+                            .value_delimiter(',')
+                            .action(ArgAction::Append)
+                            .help("Comma-separated list of collectors to disable. Disabled collectors are not initialized nor started. Can be combined with --collectors, in which case collectors are removed from its list."),
+                    ),
                 "collector",
             )?,
             default_collectors_list: true,
@@ -211,6 +625,9 @@ impl SubCommand for Collect {
             .mut_arg("collectors", |a| {
                 a.value_parser(PossibleValuesParser::new(possible_collectors.clone()))
                     .default_value(possible_collectors.join(","))
+            })
+            .mut_arg("no_collectors", |a| {
+                a.value_parser(PossibleValuesParser::new(possible_collectors.clone()))
             });
 
         Ok(full_command)
@@ -235,6 +652,11 @@ impl SubCommand for Collect {
             .ok_or_else(|| ClapError::new(ErrorKind::MissingRequiredArgument))?
             .map(|x: &String| x.to_owned())
             .collect();
+        if let Some(disabled) = args.get_many::<String>("no_collectors") {
+            disabled.for_each(|x| {
+                self.args.collectors.remove(x);
+            });
+        }
         Ok(())
     }
 
@@ -1,42 +1,58 @@
 #[cfg(not(test))]
 use std::os::fd::{AsFd, AsRawFd};
 use std::{
-    collections::HashSet,
-    fs::OpenOptions,
-    io::{self, BufWriter},
+    cell::{Cell, OnceCell},
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
     process::{Command, Stdio},
     str::FromStr,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, bail, Result};
 use log::{debug, info, warn};
 use nix::{errno::Errno, mount::*, unistd::Uid};
+use pcap_file::pcapng::PcapNgWriter;
 
-use super::cli::Collect;
+use super::cli::{Collect, CollectArgs, OutFormat, PrintFormat};
 use crate::{
-    bindings::packet_filter_uapi,
-    cli::{dynamic::DynamicCommand, CliConfig, CliDisplayFormat, FullCli, SubCommandRunner},
+    cli::{dynamic::DynamicCommand, CliConfig, FullCli, SubCommandRunner},
     core::{
         events::{BpfEventsFactory, EventResult, RetisEventsFactory},
-        filters::{
-            filters::{BpfFilter, Filter},
-            meta::filter::FilterMeta,
-            packets::filter::FilterPacket,
-        },
-        inspect::check::collection_prerequisites,
-        kernel::Symbol,
+        filters::filters::CombinedFilter,
+        inspect::{check::collection_prerequisites, inspector},
+        kernel::{modules::ModuleWatcher, Symbol},
         probe::{
-            kernel::{probe_stack::ProbeStack, utils::probe_from_cli},
+            kernel::{
+                config::{CPU_FILTER_MAX, PID_FILTER_MAX},
+                kernel::ProbeArg,
+                probe_stack::ProbeStack,
+                utils::{probe_args_from_cli, probe_from_cli},
+            },
             *,
         },
         tracking::{gc::TrackingGC, skb_tracking::init_tracking},
     },
-    events::*,
-    helpers::{signals::Running, time::*},
+    events::{
+        msgpack::MsgpackWriter, rotate::RotatingWriter, segment::SegmentWriter,
+        unix::UnixSocketWriter, *,
+    },
+    helpers::{
+        compress::{CompressedWriter, Compression},
+        signals::Running,
+        time::*,
+    },
     module::{ModuleId, Modules},
-    process::display::*,
+    process::{
+        checksum::ChecksumFilter, cidr_filter::CidrFilter, display::*, meta_map::MetaMap,
+        moving_avg::MovingAvgFilter, outlier::OutlierFilter, pcap::EventParser, plugin::Plugin,
+        regex_filter::RegexFilter, region::RegionClassifier, reorder::ReferenceOrder,
+        sections::SectionFilter, sort::SortWindow, stacks::UniqueStacks, tags::EventTags,
+        trigger::Trigger, upcall::UpcallLatency,
+    },
 };
 
 #[cfg(not(test))]
@@ -44,6 +60,8 @@ use crate::core::{
     events::FactoryId,
     probe::kernel::{config::init_stack_map, kernel::KernelEventFactory},
 };
+#[cfg(feature = "parquet")]
+use crate::process::parquet::ParquetWriter;
 
 /// Generic trait representing a collector. All collectors are required to
 /// implement this, as they'll be manipulated through this trait.
@@ -111,6 +129,33 @@ pub(crate) struct Collectors {
     events_factory: Arc<RetisEventsFactory>,
     // Did we mount debugfs ourselves?
     mounted_debugfs: bool,
+    // Output file (--out), opened early so errors are reported before the
+    // collection starts.
+    out_file: Option<File>,
+    // Output Unix socket (--out unix:<path>), connected early for the same
+    // reason.
+    out_socket: Option<UnixSocketWriter>,
+    // Directory holding maps pinned by a privileged helper (--pinned-maps).
+    #[cfg_attr(test, allow(dead_code))]
+    pinned_maps: Option<PathBuf>,
+    // Report raw addresses in stack traces.
+    #[cfg_attr(test, allow(dead_code))]
+    raw_stack: bool,
+    // Report structured stack frames.
+    #[cfg_attr(test, allow(dead_code))]
+    stack_frames: bool,
+    // Collapse consecutive identical stack frames.
+    #[cfg_attr(test, allow(dead_code))]
+    dedup_stack: bool,
+    // Maximum number of stack frames to report, 0 means unlimited.
+    #[cfg_attr(test, allow(dead_code))]
+    stack_depth: usize,
+    // Probe arguments to report, by probed symbol address.
+    #[cfg_attr(test, allow(dead_code))]
+    probe_args: HashMap<u64, Vec<ProbeArg>>,
+    // Number of probe arguments to report the raw value of.
+    #[cfg_attr(test, allow(dead_code))]
+    probe_args_raw: usize,
 }
 
 impl Collectors {
@@ -129,53 +174,73 @@ impl Collectors {
             loaded: Vec::new(),
             events_factory: Arc::new(RetisEventsFactory::default()),
             mounted_debugfs: false,
+            out_file: None,
+            out_socket: None,
+            pinned_maps: None,
+            raw_stack: false,
+            stack_frames: false,
+            dedup_stack: false,
+            stack_depth: 0,
+            probe_args: HashMap::new(),
+            probe_args_raw: 0,
         })
     }
 
     /// Setup user defined input filter.
     fn setup_filters(probes: &mut ProbeBuilderManager, collect: &Collect) -> Result<()> {
-        if let Some(f) = &collect.args()?.packet_filter {
-            // L2 filter MUST always succeed. Any failure means we need to bail.
-            let fb = FilterPacket::from_string_opt(f.to_string(), packet_filter_uapi::FILTER_L2)?;
-
-            probes.register_filter(Filter::Packet(
-                packet_filter_uapi::FILTER_L2,
-                BpfFilter(fb.to_bytes()?),
-            ))?;
-
-            let mut loaded_info = "L2";
-            // L3 filter is non mandatory.
-            let fb = if f.contains("ether[") {
-                debug!("Skipping L3 filter generation (ether[n:m] not allowed)");
-                FilterPacket::reject_filter()
-            } else {
-                match FilterPacket::from_string_opt(f.to_string(), packet_filter_uapi::FILTER_L3) {
-                    Err(e) => {
-                        debug!("Skipping L3 filter generation ({e}).");
-                        FilterPacket::reject_filter()
-                    }
-                    Ok(f) => {
-                        loaded_info = "L2+L3";
-                        f
-                    }
-                }
-            };
+        let args = collect.args()?;
+        let filter =
+            CombinedFilter::new(args.packet_filter.as_deref(), args.meta_filter.as_deref())?;
 
-            probes.register_filter(Filter::Packet(
-                packet_filter_uapi::FILTER_L3,
-                BpfFilter(fb.to_bytes()?),
-            ))?;
+        filter
+            .filters()?
+            .into_iter()
+            .try_for_each(|f| probes.register_filter(f))?;
 
-            info!("{} packet filter(s) loaded", loaded_info);
-        }
+        Ok(())
+    }
 
-        if let Some(f) = &collect.args()?.meta_filter {
-            let fb =
-                FilterMeta::from_string(f.to_string()).map_err(|e| anyhow!("meta filter: {e}"))?;
-            probes.register_filter(Filter::Meta(fb))?;
-        }
+    /// Path of the Unix socket events are streamed to, if any (--out
+    /// unix:<path>).
+    fn out_socket(collect: &CollectArgs) -> Option<PathBuf> {
+        collect
+            .out
+            .as_ref()
+            .and_then(|out| out.to_str())
+            .and_then(|out| out.strip_prefix("unix:"))
+            .map(PathBuf::from)
+    }
 
-        Ok(())
+    /// Compression of the file events are written to, if any: given by
+    /// --compress or guessed from the file extension.
+    fn out_compression(collect: &CollectArgs) -> Option<Compression> {
+        collect
+            .compress
+            .or_else(|| collect.out.as_deref().and_then(Compression::from_path))
+    }
+
+    /// Create (or truncate) the file events are written to, if any. Segmented
+    /// and time-rotated logs, as well as Unix sockets, are handled separately.
+    fn open_out(collect: &CollectArgs) -> Result<Option<File>> {
+        let out = match collect.out.as_ref() {
+            Some(out)
+                if collect.segment_size.is_none()
+                    && collect.rotate_interval.is_none()
+                    && Self::out_socket(collect).is_none() =>
+            {
+                out
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(out)
+                .map_err(|e| anyhow!("Could not create or open '{}': {e}", out.display()))?,
+        ))
     }
 
     /// Check prerequisites and cli arguments to ensure we can run.
@@ -191,6 +256,27 @@ impl Collectors {
             bail!("Probe-stack mode requires filtering (--filter-packet and/or --filter-meta)");
         }
 
+        self.out_file = Self::open_out(collect)?;
+
+        if let Some(path) = Self::out_socket(collect) {
+            if collect.segment_size.is_some()
+                || collect.rotate_interval.is_some()
+                || collect.delta_timestamps
+                || collect.out_format != OutFormat::Json
+            {
+                bail!("Only json events can be streamed to a Unix socket");
+            }
+            self.out_socket = Some(UnixSocketWriter::connect(path, collect.out_reconnect)?);
+        } else if collect.out_reconnect {
+            bail!("--out-reconnect requires streaming events to a Unix socket");
+        }
+
+        if collect.compress.is_some()
+            && (Self::out_socket(collect).is_some() || collect.out_format != OutFormat::Json)
+        {
+            bail!("Only json events written to a file can be compressed");
+        }
+
         // --allow-system-changes requires root.
         if collect.allow_system_changes && !Uid::effective().is_root() {
             bail!("Retis needs to be run as root when --allow-system-changes is used");
@@ -235,13 +321,80 @@ impl Collectors {
             .downcast_ref::<Collect>()
             .ok_or_else(|| anyhow!("wrong subcommand"))?;
 
+        // Use the pinned events maps, or resize the events buffer, if asked
+        // to.
+        if let Some(dir) = collect.args()?.pinned_maps.as_ref() {
+            self.factory = BpfEventsFactory::from_pinned(dir)?;
+            self.pinned_maps = Some(dir.clone());
+        }
+        if let Some(size) = collect.args()?.buffer_size {
+            self.factory.set_buffer_size(size)?;
+        }
+        self.factory.set_debug(collect.args()?.debug_events);
+        self.factory
+            .set_workers(collect.args()?.workers, !collect.args()?.unordered);
+
         // Check if we need to report stack traces in the events.
         if collect.args()?.stack || collect.args()?.probe_stack {
             self.probes
                 .builder_mut()?
                 .set_probe_opt(probe::ProbeOption::StackTrace)?;
+
+            self.raw_stack = collect.args()?.stack_raw;
+            self.stack_frames = collect.args()?.stack_frames;
+            self.dedup_stack = collect.args()?.stack_dedup;
+            self.stack_depth = collect.args()?.stack_depth.unwrap_or(0) as usize;
+        }
+
+        // Export the symbols snapshot used for symbolization, if asked to.
+        if let Some(path) = collect.args()?.export_symbols.as_ref() {
+            let mut writer = BufWriter::new(
+                File::create(path)
+                    .map_err(|e| anyhow!("Could not create {}: {e}", path.display()))?,
+            );
+            inspector()?.kernel.export_symbols(&mut writer)?;
+            writer.flush()?;
+            info!("Symbols table written to {}", path.display());
+        }
+
+        // Check if we need to report the raw value of the probe arguments.
+        if let Some(nargs) = collect.args()?.probe_args_raw {
+            self.probes
+                .builder_mut()?
+                .set_probe_opt(probe::ProbeOption::Args((1 << nargs) - 1))?;
+            self.probe_args_raw = nargs as usize;
+        }
+
+        // Check if we need to filter events by pid.
+        let pids = &collect.args()?.pids;
+        if !pids.is_empty() {
+            if pids.len() > PID_FILTER_MAX {
+                bail!("Too many pids to filter on (max {PID_FILTER_MAX})");
+            }
+
+            self.probes
+                .builder_mut()?
+                .set_probe_opt(probe::ProbeOption::PidFilter(pids.clone()))?;
+        }
+
+        // Check if we need to filter events by cpu.
+        let cpus = &collect.args()?.cpus;
+        if !cpus.is_empty() {
+            if let Some(cpu) = cpus.iter().find(|cpu| **cpu >= CPU_FILTER_MAX) {
+                bail!("Cannot filter on cpu {cpu} (max {})", CPU_FILTER_MAX - 1);
+            }
+
+            self.probes
+                .builder_mut()?
+                .set_probe_opt(probe::ProbeOption::CpuFilter(cpus.clone()))?;
         }
 
+        // Retry attaching the probes on transient failures, if asked to.
+        self.probes.builder_mut()?.set_attach_retry(RetryPolicy {
+            retries: collect.args()?.attach_retries,
+            ..Default::default()
+        });
+
         // Generate an initial event with the startup section.
         self.events_factory.add_event(|event| {
             event.insert_section(
@@ -251,10 +404,15 @@ impl Collectors {
                         .unwrap_or("unspec")
                         .to_string(),
                     clock_monotonic_offset: monotonic_clock_offset()?,
+                    schema_version: SCHEMA_VERSION,
                 }),
             )
         })?;
 
+        if collect.args()?.collectors.is_empty() {
+            bail!("No collector enabled");
+        }
+
         // Try initializing all collectors.
         for name in &collect.args()?.collectors {
             let id = ModuleId::from_str(name)?;
@@ -343,30 +501,79 @@ impl Collectors {
             }
             ok
         };
+        // Arguments to report, by tracepoint name.
+        let mut probe_args: HashMap<String, Vec<ProbeArg>> = HashMap::new();
+        for spec in collect.args()?.probe_args.iter() {
+            let (symbol, mut args) = probe_args_from_cli(spec)?;
+            probe_args
+                .entry(symbol.name())
+                .or_default()
+                .append(&mut args);
+        }
+
         collect
             .args()?
             .probes
             .iter()
             .try_for_each(|p| -> Result<()> {
-                probe_from_cli(p, filter)?
-                    .drain(..)
-                    .try_for_each(|p| self.probes.builder_mut()?.register_probe(p))?;
+                probe_from_cli(p, filter)?.drain(..).try_for_each(|mut p| {
+                    if let probe::ProbeType::RawTracepoint(tp) = p.r#type() {
+                        // Probes on the same target are merged later on, the
+                        // option only has to be set once.
+                        if let Some(args) = probe_args.remove(&tp.symbol.name()) {
+                            let mask = args.iter().fold(0, |mask, arg| mask | (1 << arg.index));
+                            self.probe_args.insert(tp.symbol.addr()?, args);
+                            p.set_option(probe::ProbeOption::Args(mask))?;
+                        }
+                    }
+                    self.probes.builder_mut()?.register_probe(p)
+                })?;
                 Ok(())
             })?;
 
+        if let Some(tp) = probe_args.keys().next() {
+            bail!("Arguments of {tp} were requested but no probe was attached to it");
+        }
+
         Ok(())
     }
 
     /// Start the event retrieval for all collectors by calling
     /// their `start()` function.
     pub(crate) fn start(&mut self) -> Result<()> {
-        // Create factories.
+        // Create factories, one set per worker thread (or a single one when
+        // not using workers).
         #[cfg_attr(test, allow(unused_mut))]
-        let mut section_factories = self.modules.section_factories()?;
+        let mut section_factories = (0..self.factory.workers().max(1))
+            .map(|_| self.modules.section_factories())
+            .collect::<Result<Vec<_>>>()?;
 
         #[cfg(not(test))]
         {
-            let sm = init_stack_map()?;
+            // Reuse the maps pinned by a privileged helper, if any. The
+            // events maps are handled by the events factory.
+            let mut sm = None;
+            if let Some(dir) = self.pinned_maps.as_ref() {
+                for entry in std::fs::read_dir(dir)? {
+                    let entry = entry?;
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    match name.as_str() {
+                        "events_map" | "log_map" => (),
+                        "stack_map" => {
+                            sm = Some(libbpf_rs::MapHandle::from_pinned_path(entry.path())?)
+                        }
+                        _ => self
+                            .probes
+                            .builder_mut()?
+                            .reuse_pinned_map(&name, &entry.path())?,
+                    }
+                }
+            }
+
+            let sm = match sm {
+                Some(sm) => sm,
+                None => init_stack_map()?,
+            };
             self.probes
                 .builder_mut()?
                 .reuse_map("stack_map", sm.as_fd().as_raw_fd())?;
@@ -376,16 +583,33 @@ impl Collectors {
             self.probes
                 .builder_mut()?
                 .reuse_map("log_map", self.factory.log_map_fd())?;
-            match section_factories.get_mut(&FactoryId::Kernel) {
-                Some(kernel_factory) => {
-                    kernel_factory
-                        .as_any_mut()
-                        .downcast_mut::<KernelEventFactory>()
-                        .ok_or_else(|| anyhow!("Failed to downcast KernelEventFactory"))?
-                        .stack_map = Some(sm)
-                }
 
-                None => bail!("Can't get kernel section factory"),
+            // Each set of factories gets its own handle to the stack map. The
+            // original one is given last, as its fd must stay valid until the
+            // probes are loaded.
+            let mut stack_maps = (1..section_factories.len())
+                .map(|_| libbpf_rs::MapHandle::try_from(&sm))
+                .collect::<Result<Vec<_>, _>>()?;
+            stack_maps.push(sm);
+
+            for (factories, sm) in section_factories.iter_mut().zip(stack_maps) {
+                match factories.get_mut(&FactoryId::Kernel) {
+                    Some(kernel_factory) => {
+                        let kernel_factory = kernel_factory
+                            .as_any_mut()
+                            .downcast_mut::<KernelEventFactory>()
+                            .ok_or_else(|| anyhow!("Failed to downcast KernelEventFactory"))?;
+                        kernel_factory.stack_map = Some(sm);
+                        kernel_factory.raw_stack = self.raw_stack;
+                        kernel_factory.stack_frames = self.stack_frames;
+                        kernel_factory.dedup_stack = self.dedup_stack;
+                        kernel_factory.stack_depth = self.stack_depth;
+                        kernel_factory.args = self.probe_args.clone();
+                        kernel_factory.args_raw = self.probe_args_raw;
+                    }
+
+                    None => bail!("Can't get kernel section factory"),
+                }
             }
         }
 
@@ -403,6 +627,12 @@ impl Collectors {
         let probes = std::mem::take(&mut self.probes);
         let _ = std::mem::replace(&mut self.probes, probes.into_runtime()?);
 
+        // Probes are now set up, save what was resolved from BTF for the next
+        // runs.
+        if let Err(e) = inspector()?.kernel.save_btf_cache() {
+            warn!("Could not save the BTF cache: {e}");
+        }
+
         for id in &self.loaded {
             let c = self
                 .modules
@@ -457,10 +687,32 @@ impl Collectors {
         Ok(())
     }
 
+    /// Re-attach the probes targeting kernel modules which were (re)loaded
+    /// since the last poll.
+    fn follow_modules(&mut self, watcher: &mut ModuleWatcher) -> Result<()> {
+        let loaded = watcher.poll()?;
+        if loaded.is_empty() {
+            return Ok(());
+        }
+
+        // Symbol addresses changed if a module was reloaded.
+        inspector()?.kernel.reload_symbols()?;
+
+        for module in loaded.iter() {
+            match self.probes.runtime_mut()?.reattach_module_probes(module) {
+                Ok(0) => (),
+                Ok(count) => info!("Re-attached {count} probe(s) to module {module}"),
+                Err(e) => warn!("Could not re-attach probes to module {module}: {e}"),
+            }
+        }
+
+        Ok(())
+    }
+
     /// Starts the processing loop and block until we get a single SIGINT
-    /// (e.g. ctrl+c), then return after properly cleaning up. This is the main
-    /// collector cmd loop.
-    pub(crate) fn process(&mut self, cli: &CliConfig) -> Result<()> {
+    /// (e.g. ctrl+c), then return the summary of the collection after properly
+    /// cleaning up. This is the main collector cmd loop.
+    pub(crate) fn process(&mut self, cli: &CliConfig) -> Result<CollectSummary> {
         let collect = cli
             .subcommand
             .as_any()
@@ -469,38 +721,52 @@ impl Collectors {
             .args()?;
 
         let mut printers = Vec::new();
-
-        // Write events to stdout if we don't write to a file (--out) or if
-        // explicitly asked to (--print).
-        if collect.out.is_none() || collect.print {
-            let format = DisplayFormat::new()
-                .multiline(collect.format == CliDisplayFormat::MultiLine)
-                .time_format(if collect.utc {
-                    TimeFormat::UtcDate
-                } else {
-                    TimeFormat::MonotonicTimestamp
-                })
-                .monotonic_offset(monotonic_clock_offset()?);
-
-            printers.push(PrintEvent::new(
-                Box::new(io::stdout()),
-                PrintEventFormat::Text(format),
-            ));
-        }
-
-        // Write the events to a file if asked to.
-        if let Some(out) = collect.out.as_ref() {
-            printers.push(PrintEvent::new(
-                Box::new(BufWriter::new(
-                    OpenOptions::new()
-                        .create(true)
-                        .write(true)
-                        .truncate(true)
-                        .open(out)
-                        .or_else(|_| bail!("Could not create or open '{}'", out.display()))?,
-                )),
-                PrintEventFormat::Json,
-            ));
+        printers.extend(stdout_printer(collect, Box::new(io::stdout()))?);
+
+        // Write the events to a file (as json, pcapng, msgpack or parquet), or
+        // to a segmented or time-rotated log, if asked to.
+        let mut segments = None;
+        let mut rotating = None;
+        let mut socket = self.out_socket.take();
+        let mut pcap_writer = None;
+        let mut msgpack = None;
+        #[cfg(feature = "parquet")]
+        let mut parquet = None;
+        if let (Some(out), Some(size)) = (collect.out.as_ref(), collect.segment_size) {
+            segments = Some(SegmentWriter::new(out, size)?);
+        } else if let (Some(out), Some(interval)) = (collect.out.as_ref(), collect.rotate_interval)
+        {
+            rotating = Some(RotatingWriter::new(out, interval)?);
+        } else if let Some(file) = self.out_file.take() {
+            match collect.out_format {
+                OutFormat::Pcapng => {
+                    pcap_writer = Some(PcapNgWriter::new(BufWriter::new(file))?);
+                }
+                OutFormat::Msgpack => {
+                    msgpack = Some(MsgpackWriter::new(BufWriter::new(file))?);
+                }
+                #[cfg(feature = "parquet")]
+                OutFormat::Parquet => {
+                    parquet = Some(ParquetWriter::new(BufWriter::new(file))?);
+                }
+                OutFormat::Json => {
+                    let writer: Box<dyn Write> = match Self::out_compression(collect) {
+                        Some(compression) => {
+                            Box::new(BufWriter::new(CompressedWriter::new(file, compression)?))
+                        }
+                        None => Box::new(BufWriter::new(file)),
+                    };
+                    let formatter = match collect.delta_timestamps {
+                        true => JsonFormatter::default().delta_timestamps(),
+                        false => JsonFormatter::default(),
+                    };
+
+                    printers.push(PrintEvent::new(
+                        writer,
+                        PrintEventFormat::Custom(Box::new(formatter)),
+                    ));
+                }
+            }
         }
 
         if let Some(cmd) = collect.cmd.to_owned() {
@@ -523,6 +789,212 @@ impl Collectors {
             });
         }
 
+        // Post-process the events using a plugin, if asked to.
+        let mut plugin = match collect.plugin.as_ref() {
+            Some(path) => Some(Plugin::load(path)?),
+            None => None,
+        };
+
+        // Annotate pointer arguments with their kernel region, if asked to.
+        let regions = match collect.classify_pointers {
+            true => Some(RegionClassifier::from_inspector()?),
+            false => None,
+        };
+
+        // Translate field values to names, if asked to.
+        let names = match collect.meta_map.is_empty() {
+            true => None,
+            false => Some(MetaMap::new(collect.meta_map.clone())),
+        };
+
+        // Stamp the events with static tags, if asked to.
+        let tags = match collect.tags.as_ref() {
+            Some(file) => Some(EventTags::from_file(file)?),
+            None => None,
+        };
+
+        // Only emit outliers, if asked to.
+        let mut outliers = match collect.outlier.as_ref() {
+            Some(spec) => Some(OutlierFilter::new(spec.clone(), collect.outlier_warmup)?),
+            None => None,
+        };
+
+        // Only emit events deviating from the moving average, if asked to.
+        let mut moving_avg = match collect.moving_avg.as_ref() {
+            Some(spec) => Some(MovingAvgFilter::new(
+                spec.clone(),
+                collect.moving_avg_window,
+            )?),
+            None => None,
+        };
+
+        // Only emit events matching the regex filters, if any.
+        let regexes = match collect.meta_regex.is_empty() {
+            true => None,
+            false => Some(RegexFilter::new(collect.meta_regex.clone())),
+        };
+
+        // Only emit events holding given sections, if any.
+        let sections = match collect.only.is_empty() {
+            true => None,
+            false => Some(SectionFilter::new(&collect.only)),
+        };
+
+        // Only emit events holding a packet with an invalid checksum, if asked
+        // to.
+        let checksum = collect.checksum_bad_only.then(ChecksumFilter::new);
+
+        // Only emit events matching the CIDR filters, if any.
+        let cidrs = match collect.meta_cidr.is_empty() {
+            true => None,
+            false => Some(CidrFilter::new(collect.meta_cidr.clone())),
+        };
+
+        // Discard events until the trigger is seen, if asked to.
+        let mut trigger = match collect.trigger.is_empty() {
+            true => None,
+            false => Some(Trigger::new(collect.trigger.clone())),
+        };
+
+        // Emit events following the order of a reference capture, if asked
+        // to.
+        let mut reorder = match collect.reference.as_ref() {
+            Some(reference) => Some(ReferenceOrder::from_file(reference)?),
+            None => None,
+        };
+
+        // Emit events ordered by timestamp, if asked to.
+        let mut sort = collect.sort_window.map(SortWindow::new);
+
+        // Only events holding a packet can be written as pcapng. Retis events
+        // (e.g. the startup one) are skipped.
+        let pcap = pcap_writer.as_mut().map(EventParser::from);
+
+        // Summarize the unique stack traces at the end, if asked to.
+        let unique_stacks = match collect.unique_stacks {
+            true => Some(UniqueStacks::new()),
+            false => None,
+        };
+
+        // Warn about the output falling behind, if asked to. The time spent
+        // writing events is shared with the output.
+        let mut backpressure = match collect.backpressure {
+            true => Some(Backpressure::new(BACKPRESSURE_INTERVAL)),
+            false => None,
+        };
+        let write_time = Cell::new(Duration::ZERO);
+
+        let mut out = EventOutput {
+            printers,
+            pcap,
+            msgpack,
+            #[cfg(feature = "parquet")]
+            parquet,
+            segments,
+            rotating,
+            socket,
+            unique_stacks,
+            limit: EventLimit::new(collect.count, self.run.clone()),
+            summary: CollectSummary::new(),
+            write_time: &write_time,
+            run: self.run.clone(),
+        };
+        let mut output = |event: &crate::events::Event| out.output(event);
+
+        // Report the upcall latency when OvS events are collected.
+        let mut upcalls = match self.loaded.contains(&ModuleId::Ovs) {
+            true => Some(UpcallLatency::new()),
+            false => None,
+        };
+
+        let mut emit = |mut event| -> Result<()> {
+            if let Some(upcalls) = upcalls.as_mut() {
+                upcalls.process_one(&mut event);
+            }
+
+            if let Some(regions) = regions.as_ref() {
+                regions.process_one(&mut event);
+            }
+
+            if let Some(names) = names.as_ref() {
+                let json = event.to_json();
+                names.process_one(&mut event, &json);
+            }
+
+            if let Some(tags) = tags.as_ref() {
+                tags.process_one(&mut event);
+            }
+
+            if let Some(plugin) = plugin.as_mut() {
+                event = match plugin.process_one(event)? {
+                    Some(event) => event,
+                    None => return Ok(()),
+                };
+            }
+
+            if let Some(sections) = sections.as_ref() {
+                if !sections.process_one(&event) {
+                    return Ok(());
+                }
+            }
+
+            // The event is serialized at most once for all the filters working
+            // on its json representation.
+            let serialized = OnceCell::new();
+            let json = || serialized.get_or_init(|| event.to_json());
+
+            if let Some(regexes) = regexes.as_ref() {
+                if !regexes.process_one(json()) {
+                    return Ok(());
+                }
+            }
+
+            if let Some(cidrs) = cidrs.as_ref() {
+                if !cidrs.process_one(json()) {
+                    return Ok(());
+                }
+            }
+
+            if let Some(checksum) = checksum.as_ref() {
+                if !checksum.process_one(&event) {
+                    return Ok(());
+                }
+            }
+
+            if let Some(outliers) = outliers.as_mut() {
+                if !outliers.process_one(json()) {
+                    return Ok(());
+                }
+            }
+
+            if let Some(moving_avg) = moving_avg.as_mut() {
+                if !moving_avg.process_one(json()) {
+                    return Ok(());
+                }
+            }
+
+            if let Some(trigger) = trigger.as_mut() {
+                if !trigger.process_one(json()) {
+                    return Ok(());
+                }
+            }
+
+            match reorder.as_mut() {
+                Some(reorder) => reorder.process_one(event).iter().try_for_each(&mut output),
+                None => output(&event),
+            }
+        };
+
+        // Watch for kernel modules being reloaded, if asked to.
+        let mut modules = match collect.follow {
+            true => Some(ModuleWatcher::new()?),
+            false => None,
+        };
+        let mut last_poll = Instant::now();
+
+        // Stop the collection after a given duration, if asked to.
+        let deadline = collect.duration.map(|d| Instant::now() + d);
+
         let (mut iccount, mut eccount) = (0, 0);
         let mut probe_stack = ProbeStack::new(
             collect.stack,
@@ -532,36 +1004,394 @@ impl Collectors {
 
         use EventResult::*;
         while self.run.running() {
+            let timeout = match poll_timeout(deadline) {
+                Some(timeout) => timeout,
+                None => {
+                    info!("Collection duration reached, terminating...");
+                    break;
+                }
+            };
+
             // First always try to dequeue all Retis events. This is not a
             // blocking call.
             while let Some(event) = self.events_factory.next_event() {
-                printers
-                    .iter_mut()
-                    .try_for_each(|p| p.process_one(&event))?;
+                match sort.as_mut() {
+                    Some(sort) => sort
+                        .process_one(event)
+                        .into_iter()
+                        .try_for_each(&mut emit)?,
+                    None => emit(event)?,
+                }
                 iccount += 1;
             }
 
+            if let Some(backpressure) = backpressure.as_mut() {
+                if backpressure.due() {
+                    let dropped = self.probes.runtime_mut()?.dropped_events()?;
+                    if let Some(warning) = backpressure.check(write_time.take(), dropped) {
+                        warn!("{warning}");
+                    }
+                }
+            }
+
+            if let Some(modules) = modules.as_mut() {
+                if last_poll.elapsed() >= Duration::from_secs(1) {
+                    self.follow_modules(modules)?;
+                    last_poll = Instant::now();
+                }
+            }
+
             // Then get raw events, if any.
-            match self.factory.next_event(Some(Duration::from_secs(1)))? {
+            match self.factory.next_event(Some(timeout))? {
                 Event(mut event) => {
+                    if collect.processing_latency {
+                        set_processing_latency(&mut event, monotonic_timestamp()?);
+                    }
+
                     if collect.probe_stack {
                         probe_stack.process_event(self.probes.runtime_mut()?, &mut event)?;
                     }
 
-                    printers
-                        .iter_mut()
-                        .try_for_each(|p| p.process_one(&event))?;
+                    match sort.as_mut() {
+                        Some(sort) => sort
+                            .process_one(event)
+                            .into_iter()
+                            .try_for_each(&mut emit)?,
+                        None => emit(event)?,
+                    }
                     eccount += 1;
                 }
-                Timeout => continue,
+                Timeout => {
+                    // Emit the sorted events when no new event is coming.
+                    if let Some(sort) = sort.as_mut() {
+                        sort.expire(monotonic_timestamp()?)
+                            .into_iter()
+                            .try_for_each(&mut emit)?;
+                    }
+                    continue;
+                }
             }
         }
 
-        printers.iter_mut().try_for_each(|p| p.flush())?;
+        if let Some(sort) = sort.as_mut() {
+            sort.flush().into_iter().try_for_each(&mut emit)?;
+        }
+
+        if let Some(reorder) = reorder.as_mut() {
+            reorder.flush().iter().try_for_each(&mut output)?;
+        }
+
+        let summary = out.finish()?;
+        if let Some(writer) = pcap_writer {
+            writer.into_inner().flush()?;
+        }
         info!("{} event(s) processed", eccount);
         debug!("{} internal event(s) processed", iccount);
+        // Not written to stdout as it can hold the events.
+        summary.write(&mut io::stderr())?;
+        let lost = self.probes.runtime_mut()?.dropped_events()?;
+        self.factory.stats().write(&mut io::stderr(), lost)?;
+
+        self.stop()?;
+        Ok(summary)
+    }
+}
+
+/// Whether events should be written to stdout, see --out, --print and
+/// --quiet.
+fn print_to_stdout(collect: &CollectArgs) -> bool {
+    !collect.quiet && (collect.out.is_none() || collect.print)
+}
+
+/// Get the printer of the events written to stdout, if any. Events are written
+/// to stdout if we don't write to a file (--out) or if explicitly asked to
+/// (--print), unless asked not to (--quiet).
+fn stdout_printer(collect: &CollectArgs, stdout: Box<dyn Write>) -> Result<Option<PrintEvent>> {
+    Ok(match print_to_stdout(collect) {
+        true => Some(PrintEvent::new(
+            stdout,
+            PrintEventFormat::Custom(output_formatter(collect)?),
+        )),
+        false => None,
+    })
+}
+
+/// Get the formatter of the events written to stdout, see --format.
+fn output_formatter(collect: &CollectArgs) -> Result<Box<dyn OutputFormatter>> {
+    Ok(match collect.format {
+        PrintFormat::SingleLine | PrintFormat::MultiLine => Box::new(TextFormatter::new(
+            DisplayFormat::new()
+                .multiline(collect.format == PrintFormat::MultiLine)
+                .color(collect.color.stdout())
+                .time_format(if collect.utc {
+                    TimeFormat::UtcDate
+                } else {
+                    TimeFormat::MonotonicTimestamp
+                })
+                .monotonic_offset(monotonic_clock_offset()?),
+        )),
+        PrintFormat::Json => Box::<JsonFormatter>::default(),
+        PrintFormat::JsonlPretty => Box::new(JsonPrettyFormatter),
+    })
+}
+
+/// Get the timeout to use when polling for events, so the collection can stop
+/// in time if a deadline is set (see --duration). Returns None once the
+/// deadline is reached.
+fn poll_timeout(deadline: Option<Instant>) -> Option<Duration> {
+    const POLL_TIMEOUT: Duration = Duration::from_secs(1);
+
+    match deadline {
+        Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => Some(remaining.min(POLL_TIMEOUT)),
+            _ => None,
+        },
+        None => Some(POLL_TIMEOUT),
+    }
+}
 
-        self.stop()
+/// Report the time elapsed between an event being generated and `now` (a
+/// monotonic timestamp), see --processing-latency.
+fn set_processing_latency(event: &mut crate::events::Event, now: u64) {
+    if let Some(common) = event.get_section_mut::<CommonEvent>(SectionId::Common) {
+        common.processing_latency_ns = Some(now.saturating_sub(common.timestamp));
+    }
+}
+
+/// Writes the events reaching the end of the processing pipeline to all the
+/// outputs of a collection (stdout, files and Unix socket).
+struct EventOutput<'a> {
+    printers: Vec<PrintEvent>,
+    pcap: Option<EventParser<'a, BufWriter<File>>>,
+    msgpack: Option<MsgpackWriter<BufWriter<File>>>,
+    #[cfg(feature = "parquet")]
+    parquet: Option<ParquetWriter<BufWriter<File>>>,
+    segments: Option<SegmentWriter>,
+    rotating: Option<RotatingWriter>,
+    socket: Option<UnixSocketWriter>,
+    unique_stacks: Option<UniqueStacks>,
+    /// Stop the collection after a given number of events, if asked to.
+    limit: EventLimit,
+    /// Summarize the collection at the end.
+    summary: CollectSummary,
+    /// Time spent writing events, see --backpressure.
+    write_time: &'a Cell<Duration>,
+    /// Stop the collection when the Unix socket peer disconnects, unless
+    /// asked to reconnect.
+    run: Running,
+}
+
+impl EventOutput<'_> {
+    /// Write an event to all outputs.
+    fn output(&mut self, event: &crate::events::Event) -> Result<()> {
+        // Only events coming from the probes count toward the limit, Retis
+        // ones (e.g. the startup event) are always written.
+        let probed = event
+            .sections()
+            .any(|id| id == SectionId::Kernel || id == SectionId::Userspace);
+        if probed && !self.limit.account() {
+            return Ok(());
+        }
+
+        self.summary.account(event);
+        let start = Instant::now();
+        self.printers
+            .iter_mut()
+            .try_for_each(|p| p.process_one(event))?;
+        if let Some(pcap) = self.pcap.as_mut() {
+            if event
+                .get_section::<KernelEvent>(SectionId::Kernel)
+                .is_some()
+            {
+                pcap.parse(event)?;
+            }
+        }
+        if let Some(unique_stacks) = self.unique_stacks.as_mut() {
+            unique_stacks.process_one(event);
+        }
+        if let Some(msgpack) = self.msgpack.as_mut() {
+            msgpack.write_event(event)?;
+        }
+        #[cfg(feature = "parquet")]
+        if let Some(parquet) = self.parquet.as_mut() {
+            parquet.write_event(event)?;
+        }
+        if let Some(segments) = self.segments.as_mut() {
+            segments.write_event(event)?;
+        }
+        if let Some(rotating) = self.rotating.as_mut() {
+            rotating.write_event(event)?;
+        }
+        if let Some(writer) = self.socket.as_mut() {
+            if !writer.write_event(event)? && self.run.running() {
+                info!("Unix socket peer disconnected, terminating ...");
+                self.run.terminate();
+            }
+        }
+        self.write_time.set(self.write_time.get() + start.elapsed());
+        Ok(())
+    }
+
+    /// Flush and finalize all outputs, reporting their statistics if any.
+    /// Returns the summary of the collection.
+    fn finish(mut self) -> Result<CollectSummary> {
+        self.printers.iter_mut().try_for_each(|p| p.flush())?;
+        // Finalize compressed outputs now, before reporting anything.
+        drop(self.printers);
+        // Not written to stdout as it can hold the events.
+        if let Some(unique_stacks) = self.unique_stacks {
+            unique_stacks.write_summary(&mut io::stderr())?;
+        }
+        if let Some(pcap) = self.pcap {
+            pcap.report_stats();
+        }
+        if let Some(msgpack) = self.msgpack {
+            msgpack.into_inner()?;
+        }
+        #[cfg(feature = "parquet")]
+        if let Some(parquet) = self.parquet {
+            parquet.finish()?.flush()?;
+        }
+        if let Some(segments) = self.segments {
+            segments.finish()?;
+        }
+        if let Some(rotating) = self.rotating {
+            rotating.finish()?;
+        }
+        if let Some(writer) = self.socket {
+            if writer.dropped() > 0 {
+                warn!(
+                    "{} event(s) dropped while the Unix socket peer was disconnected",
+                    writer.dropped()
+                );
+            }
+        }
+        Ok(self.summary)
+    }
+}
+
+/// Limits the number of events emitted by a collection, see --count.
+struct EventLimit {
+    remaining: Option<u64>,
+    run: Running,
+}
+
+impl EventLimit {
+    fn new(count: Option<u64>, run: Running) -> Self {
+        Self {
+            remaining: count,
+            run,
+        }
+    }
+
+    /// Account for an event about to be emitted. Returns false if the limit
+    /// was already reached, in which case the event must be dropped. The
+    /// collection is stopped once the last allowed event is accounted for.
+    fn account(&mut self) -> bool {
+        let remaining = match self.remaining.as_mut() {
+            Some(remaining) => remaining,
+            None => return true,
+        };
+
+        if *remaining == 0 {
+            return false;
+        }
+
+        *remaining -= 1;
+        if *remaining == 0 {
+            self.run.terminate();
+        }
+        true
+    }
+}
+
+/// Summary of a collection, reported when it ends: number of events emitted,
+/// in total and by section, and elapsed time.
+pub(crate) struct CollectSummary {
+    start: Instant,
+    total: u64,
+    sections: BTreeMap<&'static str, u64>,
+}
+
+impl CollectSummary {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            total: 0,
+            sections: BTreeMap::new(),
+        }
+    }
+
+    /// Account for an emitted event.
+    fn account(&mut self, event: &crate::events::Event) {
+        self.total += 1;
+        event
+            .sections()
+            .for_each(|id| *self.sections.entry(id.to_str()).or_default() += 1);
+    }
+
+    /// Write the summary.
+    fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writeln!(
+            writer,
+            "{} event(s) collected in {:.3}s",
+            self.total,
+            self.start.elapsed().as_secs_f64()
+        )?;
+        for (section, count) in self.sections.iter() {
+            writeln!(writer, "  {section}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Interval at which backpressure is checked, see --backpressure.
+const BACKPRESSURE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Detects the output falling behind, see --backpressure: events being lost
+/// in the kernel while writing events took most of the time.
+struct Backpressure {
+    interval: Duration,
+    last: Instant,
+    /// Events lost at the time of the last check.
+    dropped: u64,
+}
+
+impl Backpressure {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Instant::now(),
+            dropped: 0,
+        }
+    }
+
+    /// Whether a new check is due.
+    fn due(&self) -> bool {
+        self.last.elapsed() >= self.interval
+    }
+
+    /// Check for backpressure given the time spent writing events since the
+    /// last check and the total number of events lost so far. Returns a
+    /// warning if events were lost while writing took at least half of the
+    /// time.
+    fn check(&mut self, write_time: Duration, dropped: u64) -> Option<String> {
+        let elapsed = self.last.elapsed();
+        let lost = dropped.saturating_sub(self.dropped);
+
+        self.last = Instant::now();
+        self.dropped = dropped;
+
+        if lost == 0 || elapsed.is_zero() || write_time < elapsed / 2 {
+            return None;
+        }
+
+        Some(format!(
+            "Output backpressure: {lost} event(s) lost in the last {:.1}s while writing events took {}ms ({:.0}% of the time)",
+            elapsed.as_secs_f64(),
+            write_time.as_millis(),
+            (write_time.as_secs_f64() / elapsed.as_secs_f64() * 100.0).min(100.0),
+        ))
     }
 }
 
@@ -586,6 +1416,8 @@ impl SubCommandRunner for CollectRunner {
 
 #[cfg(test)]
 mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
     use super::*;
     use crate::{
         core::{events::bpf::*, probe::ProbeBuilderManager},
@@ -595,6 +1427,7 @@ mod tests {
 
     struct DummyCollectorA;
     struct DummyCollectorB;
+    struct DummyCollectorC;
 
     impl Collector for DummyCollectorA {
         fn new() -> Result<DummyCollectorA> {
@@ -661,6 +1494,38 @@ mod tests {
         }
     }
 
+    impl Collector for DummyCollectorC {
+        fn new() -> Result<DummyCollectorC> {
+            Ok(DummyCollectorC)
+        }
+        fn known_kernel_types(&self) -> Option<Vec<&'static str>> {
+            None
+        }
+        fn register_cli(&self, cli: &mut DynamicCommand) -> Result<()> {
+            cli.register_module_noargs(SectionId::Nft)
+        }
+        fn init(
+            &mut self,
+            _: &CliConfig,
+            probes: &mut ProbeBuilderManager,
+            _: Arc<RetisEventsFactory>,
+        ) -> Result<()> {
+            probes.register_probe(Probe::raw_tracepoint(Symbol::from_name("skb:kfree_skb")?)?)
+        }
+        fn start(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Module for DummyCollectorC {
+        fn collector(&mut self) -> &mut dyn Collector {
+            self
+        }
+        fn section_factory(&self) -> Result<Option<Box<dyn EventSectionFactory>>> {
+            Ok(None)
+        }
+    }
+
     #[event_section_factory(FactoryId::Common)]
     #[derive(Default)]
     struct TestEventFactory {}
@@ -724,6 +1589,469 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn select_collectors() -> Result<()> {
+        let collectors = |args: &[&str]| -> Result<Collectors> {
+            let mut group = Modules::new()?;
+            group.register(ModuleId::Skb, Box::new(DummyCollectorA::new()?))?;
+            group.register(ModuleId::Ovs, Box::new(DummyCollectorB::new()?))?;
+
+            let mut cmd = vec!["retis", "collect"];
+            cmd.extend_from_slice(args);
+            let config = crate::cli::get_cli()?.build_from(cmd)?.run()?;
+
+            // The ovs dummy collector fails to initialize, so the following
+            // only succeeds if it was not selected.
+            let mut collectors = Collectors::new(group)?;
+            collectors.init(&config)?;
+            Ok(collectors)
+        };
+
+        assert_eq!(collectors(&["-c", "skb"])?.loaded, vec![ModuleId::Skb]);
+        assert_eq!(
+            collectors(&["-c", "skb,ovs", "--no-collectors", "ovs"])?.loaded,
+            vec![ModuleId::Skb]
+        );
+        assert!(collectors(&["-c", "skb,ovs", "--no-collectors", "skb"]).is_err());
+        assert!(collectors(&["-c", "skb", "--no-collectors", "skb"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn exclude_collectors() -> Result<()> {
+        let probes = |args: &[&str]| -> Result<Vec<String>> {
+            let mut group = Modules::new()?;
+            group.register(ModuleId::Skb, Box::new(DummyCollectorA::new()?))?;
+            group.register(ModuleId::Nft, Box::new(DummyCollectorC::new()?))?;
+
+            let mut cmd = vec!["retis", "collect"];
+            cmd.extend_from_slice(args);
+            let config = crate::cli::get_cli()?.build_from(cmd)?.run()?;
+
+            let mut collectors = Collectors::new(group)?;
+            collectors.init(&config)?;
+            collectors.probes.builder().map(|b| b.registered_probes())
+        };
+
+        assert_eq!(probes(&["-c", "skb,nft"])?, vec!["tp:skb:kfree_skb"]);
+        // Disabled collectors do not register probes.
+        assert!(probes(&["-c", "skb,nft", "--exclude", "nft"])?.is_empty());
+        assert!(probes(&["-c", "skb,nft", "--no-collectors", "nft"])?.is_empty());
+        // Unknown collectors are reported.
+        assert!(probes(&["-c", "skb", "--exclude", "foo"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn out_file() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("retis-out-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("events.json");
+        std::fs::write(&path, "garbage\n")?;
+
+        let config = crate::cli::get_cli()?
+            .build_from(vec!["retis", "collect", "--out", path.to_str().unwrap()])?
+            .run()?;
+        let collect = config
+            .subcommand
+            .as_any()
+            .downcast_ref::<Collect>()
+            .unwrap()
+            .args()?;
+
+        // The file is truncated up front.
+        let file = Collectors::open_out(collect)?.unwrap();
+        assert_eq!(std::fs::read_to_string(&path)?, "");
+
+        let mut factory = file::FileEventsFactory::new("test_data/test_events_bench.json")?;
+        let mut events = Vec::new();
+        while let Some(event) = factory.next_event()? {
+            events.push(event);
+        }
+
+        let mut printer = PrintEvent::new(Box::new(BufWriter::new(file)), PrintEventFormat::Json);
+        events.iter().try_for_each(|e| printer.process_one(e))?;
+        printer.flush()?;
+
+        // One valid json event per line.
+        let content = std::fs::read_to_string(&path)?;
+        let lines = content.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), events.len());
+        lines
+            .iter()
+            .try_for_each(|l| Event::from_json(l.to_string()).map(|_| ()))?;
+
+        std::fs::remove_dir_all(&dir)?;
+
+        // Errors are reported.
+        let config = crate::cli::get_cli()?
+            .build_from(vec![
+                "retis",
+                "collect",
+                "--out",
+                "/nonexistent/events.json",
+            ])?
+            .run()?;
+        let collect = config
+            .subcommand
+            .as_any()
+            .downcast_ref::<Collect>()
+            .unwrap()
+            .args()?;
+        assert!(Collectors::open_out(collect).is_err());
+        Ok(())
+    }
+
+    // Build an output writing events to the given printers only.
+    fn event_output<'a>(
+        printers: Vec<PrintEvent>,
+        count: Option<u64>,
+        run: &Running,
+        write_time: &'a Cell<Duration>,
+    ) -> EventOutput<'a> {
+        EventOutput {
+            printers,
+            pcap: None,
+            msgpack: None,
+            #[cfg(feature = "parquet")]
+            parquet: None,
+            segments: None,
+            rotating: None,
+            socket: None,
+            unique_stacks: None,
+            limit: EventLimit::new(count, run.clone()),
+            summary: CollectSummary::new(),
+            write_time,
+            run: run.clone(),
+        }
+    }
+
+    // Sink keeping what is written to it, shared with the test.
+    #[derive(Clone, Default)]
+    struct SharedSink(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn event_limit() -> Result<()> {
+        let mut factory = file::FileEventsFactory::new("test_data/test_events_bench.json")?;
+        let mut events = Vec::new();
+        while let Some(event) = factory.next_event()? {
+            events.push(event);
+        }
+        assert!(events.len() > 4);
+
+        // Like collections, start with a startup event.
+        let mut startup = Event::new();
+        startup.insert_section(SectionId::Common, Box::<CommonEvent>::default())?;
+        startup.insert_section(
+            SectionId::Startup,
+            Box::new(StartupEvent {
+                retis_version: "unspec".to_string(),
+                clock_monotonic_offset: TimeSpec::new(0, 0),
+                schema_version: SCHEMA_VERSION,
+            }),
+        )?;
+
+        let dir = std::env::temp_dir().join(format!("retis-count-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("events.json");
+
+        // Write the startup event followed by N+1 events, using a limit of N.
+        let run = Running::new();
+        let write_time = Cell::new(Duration::ZERO);
+        let mut out = event_output(
+            vec![PrintEvent::new(
+                Box::new(BufWriter::new(File::create(&path)?)),
+                PrintEventFormat::Json,
+            )],
+            Some(3),
+            &run,
+            &write_time,
+        );
+        out.output(&startup)?;
+        events[..4].iter().try_for_each(|e| out.output(e))?;
+        out.finish()?;
+
+        // The startup event and exactly N other events were written and the
+        // collection was stopped.
+        let written = std::fs::read_to_string(&path)?
+            .lines()
+            .map(|l| Event::from_json(l.to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        assert!(!run.running());
+        assert_eq!(written.len(), 4);
+        assert!(written[0].sections().any(|id| id == SectionId::Startup));
+        assert_eq!(
+            written[1..].iter().map(|e| e.to_json()).collect::<Vec<_>>(),
+            events[..3].iter().map(|e| e.to_json()).collect::<Vec<_>>(),
+        );
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn summary() -> Result<()> {
+        let process = |extra: &[&str], terminated: bool| -> Result<CollectSummary> {
+            let mut args = vec!["retis", "collect", "--quiet"];
+            args.extend_from_slice(extra);
+            let config = crate::cli::get_cli()?.build_from(args)?.run()?;
+
+            let mut collectors = Collectors::new(Modules::new()?)?;
+            collectors.start()?;
+            // Simulate a collection being interrupted (e.g. SIGINT).
+            if terminated {
+                collectors.run.terminate();
+            }
+            collectors.process(&config)
+        };
+
+        // The collection loop exits right away when the shutdown flag is set.
+        let summary = process(&[], true)?;
+        assert_eq!(summary.total, 0);
+
+        // It also exits when the flag is set during the collection, here
+        // after 3 events, and reports their counts.
+        let summary = process(&["--count", "3"], false)?;
+        assert_eq!(summary.total, 3);
+        assert_eq!(summary.sections.get("kernel").copied(), Some(3));
+
+        let mut out = Vec::new();
+        summary.write(&mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(out.starts_with("3 event(s) collected in "));
+        assert!(out.lines().skip(1).all(|l| l.starts_with("  ")));
+        Ok(())
+    }
+
+    #[test]
+    fn output_formatter() -> Result<()> {
+        let formatter = |extra: &[&str]| -> Result<Box<dyn OutputFormatter>> {
+            let mut args = vec!["retis", "collect"];
+            args.extend_from_slice(extra);
+            let config = crate::cli::get_cli()?.build_from(args)?.run()?;
+            super::output_formatter(
+                config
+                    .subcommand
+                    .as_any()
+                    .downcast_ref::<Collect>()
+                    .unwrap()
+                    .args()?,
+            )
+        };
+
+        let mut factory = file::FileEventsFactory::new("test_data/test_events_bench.json")?;
+        let event = factory.next_event()?.unwrap();
+
+        // Text, on a single line.
+        let out = String::from_utf8(formatter(&["--format", "single-line"])?.format(&event)?)?;
+        assert!(!out.starts_with('{'));
+        assert!(out.contains("tcp_v4_rcv"));
+        assert_eq!(out.lines().count(), 1);
+
+        // Json, as written to files.
+        let out = formatter(&["--format", "json"])?.format(&event)?;
+        assert_eq!(out, JsonFormatter::default().format(&event)?);
+        let out = String::from_utf8(out)?;
+        assert_eq!(out.lines().count(), 1);
+        assert!(Event::from_json(out.trim_end().to_string()).is_ok());
+
+        // Pretty-printed json.
+        let out = String::from_utf8(formatter(&["--format", "jsonl-pretty"])?.format(&event)?)?;
+        assert!(out.lines().count() > 1);
+        Ok(())
+    }
+
+    #[test]
+    fn quiet() -> Result<()> {
+        let print = |extra: &[&str]| -> Result<bool> {
+            let mut args = vec!["retis", "collect"];
+            args.extend_from_slice(extra);
+            let config = crate::cli::get_cli()?.build_from(args)?.run()?;
+            Ok(print_to_stdout(
+                config
+                    .subcommand
+                    .as_any()
+                    .downcast_ref::<Collect>()
+                    .unwrap()
+                    .args()?,
+            ))
+        };
+
+        assert!(print(&[])?);
+        assert!(!print(&["--quiet"])?);
+        assert!(!print(&["-q", "-o", "/tmp/events.json"])?);
+        assert!(print(&["-q", "-o", "/tmp/events.json", "--print"]).is_err());
+
+        // Output the events as a collection would, capturing what is written
+        // to stdout.
+        let mut factory = file::FileEventsFactory::new("test_data/test_events_bench.json")?;
+        let mut events = Vec::new();
+        while let Some(event) = factory.next_event()? {
+            events.push(event);
+        }
+        let output = |extra: &[&str]| -> Result<(Vec<u8>, CollectSummary)> {
+            let mut args = vec!["retis", "collect"];
+            args.extend_from_slice(extra);
+            let config = crate::cli::get_cli()?.build_from(args)?.run()?;
+            let collect = config
+                .subcommand
+                .as_any()
+                .downcast_ref::<Collect>()
+                .unwrap()
+                .args()?;
+
+            let stdout = SharedSink::default();
+            let printers = stdout_printer(collect, Box::new(stdout.clone()))?
+                .into_iter()
+                .collect();
+            let write_time = Cell::new(Duration::ZERO);
+            let mut out = event_output(printers, None, &Running::new(), &write_time);
+            events.iter().try_for_each(|e| out.output(e))?;
+            let summary = out.finish()?;
+
+            let written = stdout.0.borrow().clone();
+            Ok((written, summary))
+        };
+
+        // Events are printed by default.
+        let (written, summary) = output(&[])?;
+        assert!(!written.is_empty());
+        assert_eq!(summary.total, events.len() as u64);
+
+        // Nothing is printed in quiet mode, but events are still accounted
+        // for in the summary.
+        let (written, summary) = output(&["--quiet"])?;
+        assert!(written.is_empty());
+        assert_eq!(summary.total, events.len() as u64);
+
+        let mut out = Vec::new();
+        summary.write(&mut out)?;
+        assert!(String::from_utf8(out)?
+            .starts_with(&format!("{} event(s) collected in ", events.len())));
+        Ok(())
+    }
+
+    // Sink taking a while to write, e.g. a slow pipe.
+    struct SlowSink;
+
+    impl Write for SlowSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            std::thread::sleep(Duration::from_millis(2));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn backpressure() -> Result<()> {
+        let mut factory = file::FileEventsFactory::new("test_data/test_events_bench.json")?;
+        let event = factory.next_event()?.unwrap();
+        let mut printer = PrintEvent::new(Box::new(SlowSink), PrintEventFormat::Json);
+
+        // Write events to the sink until a check is due, returning the time
+        // spent writing.
+        let mut write_until_due = |backpressure: &Backpressure| -> Result<Duration> {
+            let mut write_time = Duration::ZERO;
+            while !backpressure.due() {
+                let start = Instant::now();
+                printer.process_one(&event)?;
+                write_time += start.elapsed();
+            }
+            Ok(write_time)
+        };
+
+        let mut backpressure = Backpressure::new(Duration::from_millis(50));
+        assert!(!backpressure.due());
+
+        // Events are lost while the sink is slow.
+        let write_time = write_until_due(&backpressure)?;
+        let warning = backpressure.check(write_time, 10).unwrap();
+        assert!(warning.contains("10 event(s) lost"));
+
+        // The sink is still slow but no more event is lost.
+        let write_time = write_until_due(&backpressure)?;
+        assert!(backpressure.check(write_time, 10).is_none());
+
+        // Events are lost but the sink is not the bottleneck.
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(backpressure.check(Duration::ZERO, 20).is_none());
+
+        // Drops are reported relative to the previous check.
+        let write_time = write_until_due(&backpressure)?;
+        let warning = backpressure.check(write_time, 25).unwrap();
+        assert!(warning.contains("5 event(s) lost"));
+        Ok(())
+    }
+
+    #[test]
+    fn processing_latency() -> Result<()> {
+        let mut event = crate::events::Event::new();
+        event.insert_section(
+            SectionId::Common,
+            Box::new(CommonEvent {
+                timestamp: monotonic_timestamp()?,
+                ..Default::default()
+            }),
+        )?;
+
+        std::thread::sleep(Duration::from_millis(20));
+        set_processing_latency(&mut event, monotonic_timestamp()?);
+
+        let latency = event
+            .get_section::<CommonEvent>(SectionId::Common)
+            .unwrap()
+            .processing_latency_ns
+            .unwrap();
+        assert!(latency >= 20_000_000);
+        assert!(latency < 10_000_000_000);
+        Ok(())
+    }
+
+    #[test]
+    fn duration() -> Result<()> {
+        assert_eq!(poll_timeout(None), Some(Duration::from_secs(1)));
+        assert_eq!(poll_timeout(Some(Instant::now())), None);
+        assert!(poll_timeout(Some(Instant::now() + Duration::from_secs(30)))
+            .is_some_and(|t| t <= Duration::from_secs(1)));
+
+        let config = crate::cli::get_cli()?
+            .build_from(vec!["retis", "collect", "--duration", "1s"])?
+            .run()?;
+        let collect = config
+            .subcommand
+            .as_any()
+            .downcast_ref::<Collect>()
+            .unwrap()
+            .args()?;
+        assert_eq!(collect.duration, Some(Duration::from_secs(1)));
+
+        // Simulate a collection without events, it must stop promptly once
+        // the deadline is reached.
+        let start = Instant::now();
+        let deadline = Some(start + Duration::from_millis(200));
+        while let Some(timeout) = poll_timeout(deadline) {
+            std::thread::sleep(timeout);
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(200));
+        assert!(elapsed < Duration::from_secs(1));
+        Ok(())
+    }
+
     #[test]
     fn start_collectors() -> Result<()> {
         let mut group = Modules::new()?;
@@ -5,17 +5,20 @@
 
 use std::{
     any,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
+    io::Write,
     mem,
     os::fd::{AsFd, AsRawFd, RawFd},
-    sync::mpsc,
+    path::Path,
+    sync::{mpsc, Arc, Mutex},
     thread,
     time::Duration,
 };
 
 use anyhow::{anyhow, bail, Result};
-use btf_rs::Type;
+use libbpf_rs::MapCore;
 use log::{error, log, Level};
+use nix::unistd::{sysconf, SysconfVar};
 use plain::Plain;
 
 use crate::{
@@ -30,6 +33,37 @@ pub(super) const COMMON_SECTION_TASK: u64 = 1;
 /// Timeout when polling for new events from BPF.
 const BPF_EVENTS_POLL_TIMEOUT_MS: u64 = 200;
 
+/// Number of raw events a worker thread can have pending before the polling
+/// thread blocks on it.
+const BPF_EVENTS_WORKER_QUEUE: usize = 1024;
+
+/// Default size of the events ring buffer, in bytes.
+pub(crate) const BPF_EVENTS_BUFFER_SIZE: u64 =
+    BPF_EVENTS_MAX as u64 * mem::size_of::<RawEvent>() as u64;
+/// Maximum size of the events ring buffer, in bytes.
+pub(crate) const BPF_EVENTS_BUFFER_MAX: u64 = 1 << 30;
+
+/// Compute the size of the events ring buffer given the requested one, if
+/// any (see --buffer-size). Ring buffers must hold a power-of-2 number of
+/// pages, so the requested size is rounded up accordingly.
+pub(crate) fn events_buffer_size(requested: Option<u64>, page_size: u64) -> Result<u32> {
+    let size = requested.unwrap_or(BPF_EVENTS_BUFFER_SIZE);
+    if size == 0 || size > BPF_EVENTS_BUFFER_MAX {
+        bail!("Invalid events buffer size ({size}), must be in [1, {BPF_EVENTS_BUFFER_MAX}]");
+    }
+
+    let pages = size.div_ceil(page_size).next_power_of_two();
+    u32::try_from(pages * page_size).map_err(|_| anyhow!("Events buffer size is too large"))
+}
+
+/// Size of a memory page, in bytes.
+fn page_size() -> Result<u64> {
+    match sysconf(SysconfVar::PAGE_SIZE)? {
+        Some(size) if size > 0 => Ok(size as u64),
+        _ => bail!("Could not retrieve the page size"),
+    }
+}
+
 /// Macro used to convert c_char into String.
 /// The macro returns error if the conversion fails.
 #[macro_export]
@@ -83,6 +117,48 @@ pub(crate) enum EventResult {
     Timeout,
 }
 
+/// Statistics of the events retrieved from the BPF parts, see
+/// `BpfEventsFactory::stats`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BpfEventsStats {
+    /// Events successfully unmarshaled and queued for processing.
+    pub(crate) processed: u64,
+    /// Events dropped as they could not be unmarshaled.
+    pub(crate) dropped: u64,
+    /// Number of processed events holding a given section.
+    pub(crate) sections: BTreeMap<&'static str, u64>,
+}
+
+impl BpfEventsStats {
+    /// Account for a raw event, once unmarshaled.
+    fn account(&mut self, event: &Result<Event>) {
+        match event {
+            Ok(event) => {
+                self.processed += 1;
+                event
+                    .sections()
+                    .for_each(|id| *self.sections.entry(id.to_str()).or_default() += 1);
+            }
+            Err(_) => self.dropped += 1,
+        }
+    }
+
+    /// Write the statistics. Events lost in the kernel, because the ring
+    /// buffer was full, are not seen here and are accounted for by the probes;
+    /// their number is given by the caller.
+    pub(crate) fn write<W: Write>(&self, writer: &mut W, lost: u64) -> Result<()> {
+        writeln!(
+            writer,
+            "{} event(s) retrieved from the kernel, {} dropped (invalid), {lost} lost (buffer full)",
+            self.processed, self.dropped,
+        )?;
+        for (section, count) in self.sections.iter() {
+            writeln!(writer, "  {section}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
 /// BPF events factory retrieving and unmarshaling events coming from the BPF
 /// parts.
 #[cfg(not(test))]
@@ -95,24 +171,26 @@ pub(crate) struct BpfEventsFactory {
     handle: Option<thread::JoinHandle<()>>,
     log_handle: Option<thread::JoinHandle<()>>,
     run_state: Running,
+    /// Statistics of the events retrieved so far, updated by the polling
+    /// thread.
+    stats: Arc<Mutex<BpfEventsStats>>,
+    /// Dump the structure of the events to stderr, see `debug_raw_event`.
+    debug: bool,
+    /// Number of worker threads unmarshaling the events, see `set_workers`.
+    workers: usize,
+    /// Output the events in the order they were retrieved when using worker
+    /// threads.
+    ordered: bool,
+    /// Worker threads handles, followed by the one merging their output.
+    worker_handles: Vec<thread::JoinHandle<()>>,
+    /// The events maps were pinned by another process, see `from_pinned`.
+    pinned: bool,
 }
 
 #[cfg(not(test))]
 impl BpfEventsFactory {
     pub(crate) fn new() -> Result<BpfEventsFactory> {
-        let opts = libbpf_sys::bpf_map_create_opts {
-            sz: mem::size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
-            ..Default::default()
-        };
-        let map = libbpf_rs::MapHandle::create(
-            libbpf_rs::MapType::RingBuf,
-            Some("events_map"),
-            0,
-            0,
-            mem::size_of::<RawEvent>() as u32 * BPF_EVENTS_MAX,
-            &opts,
-        )
-        .or_else(|e| bail!("Failed to create events map: {}", e))?;
+        let map = Self::events_map(events_buffer_size(None, page_size()?)?)?;
 
         let opts = libbpf_sys::bpf_map_create_opts {
             sz: mem::size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
@@ -128,14 +206,90 @@ impl BpfEventsFactory {
         )
         .or_else(|e| bail!("Failed to create log map: {}", e))?;
 
-        Ok(BpfEventsFactory {
+        Ok(Self::with_maps(map, log_map))
+    }
+
+    /// Use the events maps pinned in a directory of the BPF filesystem
+    /// (`events_map` and `log_map`), e.g. by a privileged helper, instead of
+    /// creating them. This allows to stream events with reduced privileges.
+    pub(crate) fn from_pinned(dir: &Path) -> Result<BpfEventsFactory> {
+        let open = |name: &str| -> Result<libbpf_rs::MapHandle> {
+            let path = dir.join(name);
+            let map = libbpf_rs::MapHandle::from_pinned_path(&path)
+                .map_err(|e| anyhow!("Failed to open pinned map {}: {e}", path.display()))?;
+            if map.map_type() != libbpf_rs::MapType::RingBuf {
+                bail!("Pinned map {} is not a ring buffer", path.display());
+            }
+            Ok(map)
+        };
+
+        let mut factory = Self::with_maps(open("events_map")?, open("log_map")?);
+        factory.pinned = true;
+        Ok(factory)
+    }
+
+    fn with_maps(map: libbpf_rs::MapHandle, log_map: libbpf_rs::MapHandle) -> BpfEventsFactory {
+        BpfEventsFactory {
             map,
             log_map,
             rxc: None,
             handle: None,
             log_handle: None,
             run_state: Running::new(),
-        })
+            stats: Arc::new(Mutex::new(BpfEventsStats::default())),
+            debug: false,
+            workers: 0,
+            ordered: true,
+            worker_handles: Vec::new(),
+            pinned: false,
+        }
+    }
+
+    fn events_map(size: u32) -> Result<libbpf_rs::MapHandle> {
+        let opts = libbpf_sys::bpf_map_create_opts {
+            sz: mem::size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+            ..Default::default()
+        };
+        libbpf_rs::MapHandle::create(
+            libbpf_rs::MapType::RingBuf,
+            Some("events_map"),
+            0,
+            0,
+            size,
+            &opts,
+        )
+        .or_else(|e| bail!("Failed to create events map: {}", e))
+    }
+
+    /// Resize the events ring buffer, see `events_buffer_size`. This must be
+    /// done before the events map is reused.
+    pub(crate) fn set_buffer_size(&mut self, size: u64) -> Result<()> {
+        if self.pinned {
+            bail!("Cannot resize a pinned events map");
+        }
+        self.map = Self::events_map(events_buffer_size(Some(size), page_size()?)?)?;
+        Ok(())
+    }
+
+    /// Dump the structure of the events to stderr as they are retrieved. Must
+    /// be set before starting the factory.
+    pub(crate) fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    /// Unmarshal the events on dedicated worker threads instead of the polling
+    /// one; 0 (the default) means not using workers. As events are unmarshaled
+    /// concurrently they can be output in the order they were retrieved
+    /// (`ordered`) or as soon as they are ready. Must be set before starting
+    /// the factory.
+    pub(crate) fn set_workers(&mut self, workers: usize, ordered: bool) {
+        self.workers = workers;
+        self.ordered = ordered;
+    }
+
+    /// Number of worker threads, see `set_workers`.
+    pub(crate) fn workers(&self) -> usize {
+        self.workers
     }
 
     /// Get the events map fd for reuse.
@@ -183,8 +337,20 @@ impl BpfEventsFactory {
 impl BpfEventsFactory {
     /// This starts the event polling mechanism. A dedicated thread is started
     /// for events to be retrieved and processed.
-    pub(crate) fn start(&mut self, mut section_factories: SectionFactories) -> Result<()> {
-        if section_factories.is_empty() {
+    pub(crate) fn start(&mut self, mut section_factories: Vec<SectionFactories>) -> Result<()> {
+        // One set of section factories is needed per worker, or a single one
+        // when unmarshaling events in the polling thread.
+        if section_factories.len() != self.workers.max(1) {
+            bail!(
+                "Expected {} set(s) of section factories, got {}",
+                self.workers.max(1),
+                section_factories.len()
+            );
+        }
+        if section_factories
+            .iter()
+            .any(|factories| factories.is_empty())
+        {
             bail!("No section factory, can't parse events, aborting");
         }
 
@@ -192,6 +358,25 @@ impl BpfEventsFactory {
         let (txc, rxc) = mpsc::channel();
         self.rxc = Some(rxc);
 
+        let mut handle_event: Box<dyn FnMut(&[u8])> = match self.workers {
+            0 => {
+                // Unwrap as we checked above a single set is available.
+                let mut factories = section_factories.pop().unwrap();
+                let stats = self.stats.clone();
+                let debug = self.debug;
+                Box::new(move |data: &[u8]| {
+                    if let Some(event) = unmarshal_raw_event(data, &mut factories, &stats, debug) {
+                        // Send the event into the events channel for future
+                        // retrieval.
+                        if let Err(e) = txc.send(event) {
+                            error!("Could not send event: {}", e);
+                        }
+                    }
+                })
+            }
+            _ => self.start_workers(section_factories, txc)?,
+        };
+
         let run_state = self.run_state.clone();
         // Closure to handle the raw events coming from the BPF part.
         let process_event = move |data: &[u8]| -> i32 {
@@ -203,20 +388,8 @@ impl BpfEventsFactory {
             if !run_state.running() {
                 return -4;
             }
-            // Parse the raw event.
-            let event = match parse_raw_event(data, &mut section_factories) {
-                Ok(event) => event,
-                Err(e) => {
-                    error!("Could not parse raw event: {}", e);
-                    return 0;
-                }
-            };
-
-            // Send the event into the events channel for future retrieval.
-            if let Err(e) = txc.send(event) {
-                error!("Could not send event: {}", e);
-            }
 
+            handle_event(data);
             0
         };
 
@@ -270,8 +443,90 @@ impl BpfEventsFactory {
         Ok(())
     }
 
+    /// Start the worker threads unmarshaling the events, one per set of
+    /// section factories, and the thread merging their output into the events
+    /// channel. Returns the closure dispatching raw events to the workers, to
+    /// be run by the polling thread.
+    fn start_workers(
+        &mut self,
+        section_factories: Vec<SectionFactories>,
+        txc: mpsc::Sender<Event>,
+    ) -> Result<Box<dyn FnMut(&[u8])>> {
+        // Raw events are tagged with a sequence number, in the order they
+        // were retrieved, for the merging thread to restore it if needed.
+        // Events which could not be unmarshaled are still sent so no hole is
+        // left in the sequence.
+        let (merge_txc, merge_rxc) = mpsc::channel::<(u64, Option<Event>)>();
+
+        let mut workers = Vec::new();
+        for (i, mut factories) in section_factories.into_iter().enumerate() {
+            let (worker_txc, worker_rxc) =
+                mpsc::sync_channel::<(u64, Vec<u8>)>(BPF_EVENTS_WORKER_QUEUE);
+            let merge_txc = merge_txc.clone();
+            let stats = self.stats.clone();
+            let debug = self.debug;
+
+            self.worker_handles.push(
+                thread::Builder::new()
+                    .name(format!("events-worker-{i}"))
+                    .spawn(move || {
+                        // Stops once the polling thread is gone.
+                        while let Ok((seq, data)) = worker_rxc.recv() {
+                            let event = unmarshal_raw_event(&data, &mut factories, &stats, debug);
+                            if merge_txc.send((seq, event)).is_err() {
+                                break;
+                            }
+                        }
+                    })?,
+            );
+            workers.push(worker_txc);
+        }
+        // Only the workers should keep the merging thread alive.
+        drop(merge_txc);
+
+        let ordered = self.ordered;
+        self.worker_handles.push(
+            thread::Builder::new()
+                .name("events-merge".to_string())
+                .spawn(move || {
+                    let send = |event: Option<Event>| {
+                        if let Some(event) = event {
+                            // Send the event into the events channel for
+                            // future retrieval.
+                            if let Err(e) = txc.send(event) {
+                                error!("Could not send event: {}", e);
+                            }
+                        }
+                    };
+
+                    let mut reorder = ReorderBuffer::default();
+                    // Stops once all the workers are gone.
+                    while let Ok((seq, event)) = merge_rxc.recv() {
+                        if !ordered {
+                            send(event);
+                            continue;
+                        }
+
+                        reorder.insert(seq, event);
+                        while let Some(event) = reorder.pop() {
+                            send(event);
+                        }
+                    }
+                })?,
+        );
+
+        let mut seq = 0;
+        Ok(Box::new(move |data: &[u8]| {
+            let worker = &workers[(seq % workers.len() as u64) as usize];
+            if let Err(e) = worker.send((seq, data.to_vec())) {
+                error!("Could not send raw event to worker: {e}");
+            }
+            seq += 1;
+        }))
+    }
+
     /// Stops the event polling mechanism. The dedicated thread is stopped
-    /// joining the execution
+    /// joining the execution, followed by the worker threads, if any.
     pub(crate) fn stop(&mut self) -> Result<()> {
         self.handle.take().map_or(Ok(()), |th| {
             self.run_state.terminate();
@@ -279,6 +534,13 @@ impl BpfEventsFactory {
                 .map_err(|_| anyhow!("while joining bpf event thread"))
         })?;
 
+        // Workers stop once the polling thread is gone, and so does the
+        // merging thread once the workers are.
+        self.worker_handles.drain(..).try_for_each(|th| {
+            th.join()
+                .map_err(|_| anyhow!("while joining bpf event worker thread"))
+        })?;
+
         self.log_handle.take().map_or(Ok(()), |th| {
             th.join()
                 .map_err(|_| anyhow!("while joining bpf log event thread"))
@@ -301,12 +563,68 @@ impl BpfEventsFactory {
             None => EventResult::Event(rxc.recv()?),
         })
     }
+
+    /// Statistics of the events retrieved so far.
+    pub(crate) fn stats(&self) -> BpfEventsStats {
+        match self.stats.lock() {
+            Ok(stats) => stats.clone(),
+            Err(_) => BpfEventsStats::default(),
+        }
+    }
 }
 
-pub(crate) fn parse_raw_event<'a>(
-    data: &'a [u8],
-    factories: &'a mut SectionFactories,
-) -> Result<Event> {
+/// Buffer restoring the order of items tagged with a sequence number, when
+/// they are processed out of order.
+#[derive(Default)]
+struct ReorderBuffer<T> {
+    /// Sequence number of the next item to return.
+    next: u64,
+    /// Items waiting for the ones preceding them.
+    pending: BTreeMap<u64, T>,
+}
+
+impl<T> ReorderBuffer<T> {
+    fn insert(&mut self, seq: u64, item: T) {
+        self.pending.insert(seq, item);
+    }
+
+    /// Return the next item in sequence, if it is available.
+    fn pop(&mut self) -> Option<T> {
+        let item = self.pending.remove(&self.next)?;
+        self.next += 1;
+        Some(item)
+    }
+}
+
+/// Unmarshal a raw event, accounting for it in the statistics and dumping its
+/// structure if asked to. Events which can't be unmarshaled are reported and
+/// discarded.
+fn unmarshal_raw_event(
+    data: &[u8],
+    factories: &mut SectionFactories,
+    stats: &Mutex<BpfEventsStats>,
+    debug: bool,
+) -> Option<Event> {
+    let event = parse_raw_event(data, factories);
+    if let Ok(mut stats) = stats.lock() {
+        stats.account(&event);
+    }
+    if debug {
+        eprint!("{}", debug_raw_event(data, &event));
+    }
+
+    match event {
+        Ok(event) => Some(event),
+        Err(e) => {
+            error!("Could not parse raw event: {}", e);
+            None
+        }
+    }
+}
+
+/// Split a raw event into its raw sections, grouped by owner. Invalid
+/// sections are skipped.
+fn parse_raw_sections(data: &[u8]) -> Result<HashMap<FactoryId, Vec<BpfRawSection>>> {
     // First retrieve the buffer length.
     let data_size = data.len();
     if data_size < 2 {
@@ -378,6 +696,15 @@ pub(crate) fn parse_raw_event<'a>(
             .push(raw_section);
     }
 
+    Ok(raw_sections)
+}
+
+pub(crate) fn parse_raw_event<'a>(
+    data: &'a [u8],
+    factories: &'a mut SectionFactories,
+) -> Result<Event> {
+    let mut raw_sections = parse_raw_sections(data)?;
+
     let mut event = Event::new();
     raw_sections.drain().try_for_each(|(owner, sections)| {
         let factory = factories
@@ -393,6 +720,51 @@ pub(crate) fn parse_raw_event<'a>(
     Ok(event)
 }
 
+/// Dump the structure of a raw event and of the event unmarshaled from it, if
+/// any, for debugging purposes (see --debug-events): raw sections (owner, data
+/// type and size) and decoded sections.
+pub(crate) fn debug_raw_event(data: &[u8], event: &Result<Event>) -> String {
+    let mut dump = format!("raw event: {} byte(s)\n", data.len());
+
+    match parse_raw_sections(data) {
+        Ok(raw_sections) => {
+            let mut raw_sections = raw_sections.into_iter().collect::<Vec<_>>();
+            raw_sections.sort_by_key(|(owner, _)| *owner as u8);
+
+            raw_sections.iter().for_each(|(owner, sections)| {
+                sections.iter().for_each(|section| {
+                    dump.push_str(&format!(
+                        "  raw section: owner {owner:?} ({}), data type {}, {} byte(s)\n",
+                        *owner as u8,
+                        section.header.data_type,
+                        section.data.len(),
+                    ))
+                })
+            });
+        }
+        Err(e) => dump.push_str(&format!("  invalid raw event: {e}\n")),
+    }
+
+    match event {
+        Ok(event) => {
+            let json = event.to_json();
+            let mut sections = event.sections().collect::<Vec<_>>();
+            sections.sort_by_key(|id| *id as u8);
+
+            sections.iter().for_each(|id| {
+                dump.push_str(&format!(
+                    "  section {}: {}\n",
+                    id.to_str(),
+                    json.get(id.to_str()).unwrap_or(&serde_json::Value::Null),
+                ))
+            });
+        }
+        Err(e) => dump.push_str(&format!("  could not unmarshal event: {e}\n")),
+    }
+
+    dump
+}
+
 /// Helper to check a raw section validity and parse it into a structured type.
 pub(crate) fn parse_raw_section<'a, T>(raw_section: &'a BpfRawSection) -> Result<&'a T> {
     if raw_section.data.len() != mem::size_of::<T>() {
@@ -422,18 +794,16 @@ pub(crate) fn parse_single_raw_section<'a, T>(raw_sections: &'a [BpfRawSection])
 pub(crate) fn parse_enum(r#enum: &str, trim_start: &[&str]) -> Result<HashMap<u32, String>> {
     let mut values = HashMap::new();
 
-    if let Ok(types) = inspector()?.kernel.btf.resolve_types_by_name(r#enum) {
-        if let Some((btf, Type::Enum(r#enum))) =
-            types.iter().find(|(_, t)| matches!(t, Type::Enum(_)))
-        {
-            for member in r#enum.members.iter() {
-                let mut val = btf.resolve_name(member)?;
-                trim_start
-                    .iter()
-                    .for_each(|p| val = val.trim_start_matches(p).to_string());
-                values.insert(member.val(), val.to_string());
-            }
-        }
+    for (val, mut name) in inspector()?
+        .kernel
+        .btf
+        .enum_values(r#enum)?
+        .unwrap_or_default()
+    {
+        trim_start
+            .iter()
+            .for_each(|p| name = name.trim_start_matches(p).to_string());
+        values.insert(val, name);
     }
 
     Ok(values)
@@ -483,21 +853,39 @@ impl BpfEventsFactory {
     pub(crate) fn new() -> Result<BpfEventsFactory> {
         Ok(BpfEventsFactory {})
     }
+    pub(crate) fn from_pinned(_: &Path) -> Result<BpfEventsFactory> {
+        Ok(BpfEventsFactory {})
+    }
     pub(crate) fn map_fd(&self) -> i32 {
         0
     }
+    pub(crate) fn set_buffer_size(&mut self, size: u64) -> Result<()> {
+        events_buffer_size(Some(size), page_size()?)?;
+        Ok(())
+    }
+    pub(crate) fn set_debug(&mut self, _: bool) {}
+    pub(crate) fn set_workers(&mut self, _: usize, _: bool) {}
+    pub(crate) fn workers(&self) -> usize {
+        0
+    }
 }
 #[cfg(test)]
 impl BpfEventsFactory {
-    pub(crate) fn start(&mut self, _: SectionFactories) -> Result<()> {
+    pub(crate) fn start(&mut self, _: Vec<SectionFactories>) -> Result<()> {
         Ok(())
     }
+    // Always return an event, as if coming from a probe.
     pub(crate) fn next_event(&mut self, _: Option<Duration>) -> Result<EventResult> {
-        Ok(EventResult::Event(Event::new()))
+        let mut event = Event::new();
+        event.insert_section(SectionId::Kernel, Box::<KernelEvent>::default())?;
+        Ok(EventResult::Event(event))
     }
     pub(crate) fn stop(&mut self) -> Result<()> {
         Ok(())
     }
+    pub(crate) fn stats(&self) -> BpfEventsStats {
+        BpfEventsStats::default()
+    }
 }
 
 /// Max number of events we can store at once in the shared map. Please keep in
@@ -542,8 +930,9 @@ unsafe impl Plain for BpfRawSectionHeader {}
 /// ebpf.
 ///
 /// Please use `#[retis_derive::event_section_factory]` to implement the common
-/// traits.
-pub(crate) trait EventSectionFactory: RawEventSectionFactory {
+/// traits. Factories must be `Send` as events can be unmarshaled on worker
+/// threads.
+pub(crate) trait EventSectionFactory: RawEventSectionFactory + Send {
     fn id(&self) -> u8;
     fn as_any_mut(&mut self) -> &mut dyn any::Any;
 }
@@ -807,5 +1196,170 @@ mod tests {
         let section = event.get_section::<TestEvent>(SectionId::Common).unwrap();
         assert!(section.field1 == Some(42));
         assert!(section.field2 == Some(1337));
+
+        // The debug dump lists all raw sections and the decoded ones.
+        let dump = debug_raw_event(&data, &Ok(event));
+        let mut lines = dump.lines();
+        assert_eq!(lines.next(), Some("raw event: 46 byte(s)"));
+        assert_eq!(
+            lines.next(),
+            Some("  raw section: owner Common (1), data type 1, 8 byte(s)")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("  raw section: owner Common (1), data type 1, 8 byte(s)")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("  raw section: owner Common (1), data type 2, 16 byte(s)")
+        );
+        assert!(lines.next().unwrap().starts_with("  section common: {"));
+        assert_eq!(lines.next(), None);
+
+        let dump = debug_raw_event(&data[..10], &Err(anyhow!("too short")));
+        assert!(dump.contains("invalid raw event"));
+        assert!(dump.contains("could not unmarshal event: too short"));
+    }
+
+    fn as_bytes<T>(raw: &T) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(raw as *const T as *const u8, std::mem::size_of::<T>())
+        }
+    }
+
+    #[test]
+    fn common_section() {
+        let raw = common_event {
+            timestamp: 123456789,
+            smp_id: 3,
+        };
+
+        let section = CommonEventFactory::default()
+            .create(vec![BpfRawSection {
+                header: BpfRawSectionHeader {
+                    data_type: COMMON_SECTION_CORE as u8,
+                    ..Default::default()
+                },
+                data: as_bytes(&raw),
+            }])
+            .unwrap();
+        let common = section.as_any().downcast_ref::<CommonEvent>().unwrap();
+
+        assert_eq!(common.timestamp, 123456789);
+        assert_eq!(common.smp_id, Some(3));
+    }
+
+    #[test]
+    fn buffer_size() {
+        let default = events_buffer_size(None, 4096).unwrap() as u64;
+        assert_eq!(default, BPF_EVENTS_BUFFER_SIZE);
+
+        // Sizes are rounded up to a power-of-2 number of pages.
+        assert_eq!(events_buffer_size(Some(1), 4096).unwrap(), 4096);
+        assert_eq!(events_buffer_size(Some(5000), 4096).unwrap(), 8192);
+        assert_eq!(events_buffer_size(Some(3 * 4096), 4096).unwrap(), 4 * 4096);
+        assert_eq!(events_buffer_size(Some(1 << 20), 65536).unwrap(), 1 << 20);
+        assert_eq!(events_buffer_size(Some(1), 65536).unwrap(), 65536);
+
+        // Requesting a larger buffer results in a larger map.
+        assert_eq!(
+            events_buffer_size(Some(4 * BPF_EVENTS_BUFFER_SIZE), 4096).unwrap() as u64,
+            4 * default
+        );
+        assert_eq!(
+            events_buffer_size(Some(BPF_EVENTS_BUFFER_MAX), 4096).unwrap() as u64,
+            BPF_EVENTS_BUFFER_MAX
+        );
+
+        assert!(events_buffer_size(Some(0), 4096).is_err());
+        assert!(events_buffer_size(Some(BPF_EVENTS_BUFFER_MAX + 1), 4096).is_err());
+    }
+
+    #[test]
+    fn stats() {
+        let mut factories: SectionFactories = HashMap::new();
+        factories.insert(FactoryId::Common, Box::<TestEventFactory>::default());
+        let mut stats = BpfEventsStats::default();
+
+        let valid = [
+            12,
+            0,
+            SectionId::Common as u8,
+            DATA_TYPE_U64,
+            8,
+            0,
+            42,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        ];
+        let invalid = [5, 0, SectionId::Common as u8, DATA_TYPE_U64, 1, 0, 42];
+
+        // Simulate events being polled.
+        for data in [&valid[..], &valid[..], &invalid[..], &valid[..]] {
+            stats.account(&super::parse_raw_event(data, &mut factories));
+        }
+
+        assert_eq!(stats.processed, 3);
+        assert_eq!(stats.dropped, 1);
+        assert_eq!(stats.sections.get("common").copied(), Some(3));
+
+        let mut out = Vec::new();
+        stats.write(&mut out, 7).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with(
+            "3 event(s) retrieved from the kernel, 1 dropped (invalid), 7 lost (buffer full)"
+        ));
+        assert!(out.contains("  common: 3"));
+    }
+
+    #[test]
+    fn reorder_buffer() {
+        let mut reorder = ReorderBuffer::default();
+        assert!(reorder.pop().is_none());
+
+        // Items processed out of order are held until the ones preceding
+        // them are available.
+        reorder.insert(2, "c");
+        reorder.insert(1, "b");
+        assert!(reorder.pop().is_none());
+
+        reorder.insert(0, "a");
+        assert_eq!(reorder.pop(), Some("a"));
+        assert_eq!(reorder.pop(), Some("b"));
+        assert_eq!(reorder.pop(), Some("c"));
+        assert!(reorder.pop().is_none());
+
+        reorder.insert(4, "e");
+        assert!(reorder.pop().is_none());
+        reorder.insert(3, "d");
+        assert_eq!(reorder.pop(), Some("d"));
+        assert_eq!(reorder.pop(), Some("e"));
+    }
+
+    #[test]
+    fn unmarshal_task() {
+        let mut raw = common_task_event {
+            pid: (1337 << 32) | 42,
+            ..Default::default()
+        };
+        raw.comm
+            .iter_mut()
+            .zip(b"ovs-vswitchd")
+            .for_each(|(c, b)| *c = *b as _);
+
+        let task = super::unmarshal_task(&BpfRawSection {
+            header: BpfRawSectionHeader::default(),
+            data: as_bytes(&raw),
+        })
+        .unwrap();
+
+        assert_eq!(task.pid, 1337);
+        assert_eq!(task.tgid, 42);
+        assert_eq!(task.comm, "ovs-vswitchd");
     }
 }
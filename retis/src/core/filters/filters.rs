@@ -1,8 +1,8 @@
 /// eBPF filter wrapper containing the sequence of bytes composing the eBPF program
 use std::{collections::HashMap, sync::Mutex};
 
-use anyhow::{bail, Result};
-use log::{debug, error};
+use anyhow::{anyhow, bail, Result};
+use log::{debug, error, info};
 use once_cell::sync::Lazy;
 
 use crate::{
@@ -12,6 +12,7 @@ use crate::{
         filters::packets::{
             ebpf::{eBpfProg, BpfReg},
             ebpfinsn::{eBpfInsn, MovInfo},
+            filter::{FilterLayer, FilterPacket},
         },
         workaround,
     },
@@ -28,6 +29,96 @@ pub(crate) enum Filter {
     Meta(FilterMeta),
 }
 
+/// Filters applied to all probes, evaluated as:
+///
+///   packet AND (meta_1 OR meta_2 OR ...)
+///
+/// The packet filter is a pcap-filter expression and the meta filter an
+/// or-group of meta expressions separated by '||'. A missing side always
+/// matches. Both sides are evaluated in the probes, in sequence (see
+/// `matches`), and an event is only generated if the whole condition holds.
+pub(crate) struct CombinedFilter {
+    packet: Option<String>,
+    meta: Option<FilterMeta>,
+}
+
+impl CombinedFilter {
+    /// Build a combined filter. The meta filter is parsed right away, while
+    /// the packet filter is compiled when registered (see `Filter::Packet`)
+    /// as it depends on the layer it applies to.
+    pub(crate) fn new(packet: Option<&str>, meta: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            packet: packet.map(String::from),
+            meta: match meta {
+                Some(meta) => Some(
+                    FilterMeta::from_string(meta.to_string())
+                        .map_err(|e| anyhow!("meta filter: {e}"))?,
+                ),
+                None => None,
+            },
+        })
+    }
+
+    /// Packet side of the filter, if any.
+    pub(crate) fn packet(&self) -> Option<&str> {
+        self.packet.as_deref()
+    }
+
+    /// Meta side of the filter (or-group), if any.
+    pub(crate) fn meta(&self) -> Option<&FilterMeta> {
+        self.meta.as_ref()
+    }
+
+    /// Generate the filters to register for the probes to evaluate the
+    /// combined filter: the packet filter at L2 and L3 (if it can be
+    /// expressed at this layer, otherwise nothing matches at L3), then the
+    /// meta filter ops.
+    pub(crate) fn filters(&self) -> Result<Vec<Filter>> {
+        let mut filters = Vec::new();
+
+        if let Some(f) = self.packet() {
+            // L2 filter MUST always succeed. Any failure means we need to bail.
+            let fb = FilterPacket::from_string(f, FilterLayer::L2)?;
+            debug!("L2 packet filter:\n{}", fb.disasm());
+            filters.push(Filter::Packet(
+                FilterLayer::L2.filter_type(),
+                BpfFilter(fb.to_bytes()?),
+            ));
+
+            let mut loaded_info = "L2";
+            // L3 filter is non mandatory.
+            let fb = if f.contains("ether[") {
+                debug!("Skipping L3 filter generation (ether[n:m] not allowed)");
+                FilterPacket::reject_filter()
+            } else {
+                match FilterPacket::from_string(f, FilterLayer::L3) {
+                    Err(e) => {
+                        debug!("Skipping L3 filter generation ({e:#}).");
+                        FilterPacket::reject_filter()
+                    }
+                    Ok(f) => {
+                        debug!("L3 packet filter:\n{}", f.disasm());
+                        loaded_info = "L2+L3";
+                        f
+                    }
+                }
+            };
+            filters.push(Filter::Packet(
+                FilterLayer::L3.filter_type(),
+                BpfFilter(fb.to_bytes()?),
+            ));
+
+            info!("{} packet filter(s) loaded", loaded_info);
+        }
+
+        if let Some(fb) = self.meta() {
+            filters.push(Filter::Meta(fb.clone()));
+        }
+
+        Ok(filters)
+    }
+}
+
 static FM: Lazy<Mutex<HashMap<u32, Filter>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
 pub(crate) fn register_filter(r#type: u32, filter: &Filter) -> Result<()> {
@@ -139,3 +230,78 @@ pub(crate) unsafe extern "C" fn fixup_filter_load_fn(
 
     0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::probe::ProbeBuilderManager;
+
+    #[test]
+    fn combined_filter() {
+        let filter =
+            CombinedFilter::new(Some("tcp"), Some("sk_buff.mark == 1 || sk_buff.mark == 2"))
+                .unwrap();
+        assert_eq!(filter.packet(), Some("tcp"));
+        // Two groups of (target, load), separated by an empty op.
+        assert_eq!(filter.meta().unwrap().0.len(), 5);
+
+        let filter = CombinedFilter::new(None, Some("sk_buff.mark == 1")).unwrap();
+        assert!(filter.packet().is_none());
+        assert_eq!(filter.meta().unwrap().0.len(), 2);
+
+        let filter = CombinedFilter::new(Some("tcp"), None).unwrap();
+        assert!(filter.meta().is_none());
+
+        assert!(CombinedFilter::new(Some("tcp"), Some("sk_buff.mark == 1 ||")).is_err());
+    }
+
+    #[test]
+    fn combined_filter_registration() {
+        let filter = CombinedFilter::new(Some("tcp"), Some("sk_buff.mark == 1")).unwrap();
+        let filters = filter.filters().unwrap();
+        assert_eq!(filters.len(), 3);
+
+        // The packet filter is compiled for both layers.
+        for (f, layer) in filters.iter().zip([FilterLayer::L2, FilterLayer::L3]) {
+            match f {
+                Filter::Packet(r#type, BpfFilter(prog)) => {
+                    assert_eq!(*r#type, layer.filter_type());
+                    assert_eq!(
+                        *prog,
+                        FilterPacket::from_string("tcp", layer)
+                            .unwrap()
+                            .to_bytes()
+                            .unwrap()
+                    );
+                }
+                _ => panic!("Expected a packet filter"),
+            }
+        }
+
+        // Followed by the meta filter ops.
+        match &filters[2] {
+            Filter::Meta(ops) => assert_eq!(ops.0.len(), filter.meta().unwrap().0.len()),
+            _ => panic!("Expected a meta filter"),
+        }
+
+        // All of them are registered to the probes.
+        let mut mgr = ProbeBuilderManager::new().unwrap();
+        filters
+            .into_iter()
+            .try_for_each(|f| mgr.register_filter(f))
+            .unwrap();
+
+        let filter = CombinedFilter::new(None, Some("sk_buff.mark == 1")).unwrap();
+        assert!(matches!(filter.filters().unwrap()[..], [Filter::Meta(_)]));
+        assert!(CombinedFilter::new(None, None)
+            .unwrap()
+            .filters()
+            .unwrap()
+            .is_empty());
+
+        assert!(CombinedFilter::new(Some("tcp port"), None)
+            .unwrap()
+            .filters()
+            .is_err());
+    }
+}
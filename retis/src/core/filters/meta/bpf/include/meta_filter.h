@@ -6,6 +6,7 @@
 /* Please keep in sync with its Rust counterpart. */
 #define META_OPS_MAX	32
 #define META_TARGET_MAX	32
+#define META_HASH_MAX	128
 
 enum retis_meta_cmp {
 	RETIS_EQ = 0,
@@ -29,6 +30,10 @@ union retis_meta_op {
 		u8 nmemb;
 		u16 offt;
 		u8 bf_size;
+		u8 shift;
+		u8 hash;
+		u8 sub;
+		u8 bitrev;
 		u64 mask;
 	} l;
 	struct {
@@ -64,10 +69,24 @@ struct retis_meta_ctx {
 	u8 sz;
 	/* mask for unsigned num comparison. */
 	u64 mask;
+	/* right shift applied after the mask. */
+	u8 shift;
+	/* compare the string hash instead of its bytes. */
+	u8 hash;
+	/* reverse the bits of the value before the comparison. */
+	u8 bitrev;
 	/* operation. */
 	u8 cmp;
+	/* a difference is pending, its first operand being minuend. */
+	bool sub;
+	/* the first operand of the pending difference was read. */
+	bool sub_ok;
+	u64 minuend;
 };
 
+#define SAMPLE_BIT 1 << 3
+#define ABS_BIT 1 << 4
+#define CPU_BIT 1 << 5
 #define PTR_BIT 1 << 6
 #define SIGN_BIT 1 << 7
 
@@ -76,58 +95,14 @@ struct retis_meta_ctx {
  */
 const volatile u32 nmeta = 0;
 
-static __always_inline long meta_process_ops(struct retis_meta_ctx *ctx)
-{
-	union retis_meta_op *val;
-	u32 k = 0;
-	u64 ptr;
-	u32 i;
-
-	val = bpf_map_lookup_elem(&filter_meta_map, &k);
-	if (!val) {
-		log_error("Failed to lookup meta-filter target");
-		return -1;
-	}
-
-	/* process target */
-	ctx->data = &val->t.md;
-	ctx->cmp = val->t.cmp;
-	ctx->sz = val->t.sz;
-
-	for (i = 1, k = 1; i < nmeta; k++, i++) {
-		val = bpf_map_lookup_elem(&filter_meta_map, &k);
-		if (!val) {
-			log_error("Failed to lookup meta-filter member at index %u", i);
-			return -1;
-		}
-
-		/* Load Pointer */
-		if (val->l.type == PTR_BIT) {
-			if (bpf_probe_read_kernel(&ptr, sizeof(void *),
-						  (char *)ctx->base + (val->l.offt)))
-				return -1;
-
-			ctx->base = val->l.mask ? (void *)(ptr & val->l.mask)
-				                : (void *)ptr;
-			continue;
-		}
-
-		/* Non intermediate */
-		ctx->offset = val->l.offt;
-		ctx->type = val->l.type;
-		ctx->mask = val->l.mask;
-		ctx->nmemb = val->l.nmemb;
-		ctx->bfs = val->l.bf_size;
-	}
-
-	return 0;
-}
-
 static __always_inline
-bool cmp_num(u64 operand1, u64 mmask, u64 operand2, bool sign_bit, u8 cmp_type)
+bool cmp_num(u64 operand1, u64 mmask, u8 shift, u64 operand2, bool sign_bit,
+	     u8 cmp_type)
 {
-	if (!sign_bit && mmask)
+	if (!sign_bit && mmask) {
 		operand1 &= mmask;
+		operand1 >>= (shift & 63);
+	}
 
 	switch (cmp_type) {
 	case RETIS_EQ:
@@ -196,10 +171,42 @@ bool cmp_bytes(struct retis_meta_ctx *ctx)
 	return !ret;
 }
 
+/* 64-bit FNV-1a hash of the string, up to its first NUL byte, compared
+ * against the precomputed hash of the target. This allows matching strings
+ * larger than the target with a bounded number of instructions, at the cost
+ * of a (tiny) risk of collision. Please keep in sync with its Rust
+ * counterpart.
+ */
+static __always_inline
+bool cmp_hash(struct retis_meta_ctx *ctx)
+{
+	u64 hash = 0xcbf29ce484222325ULL;
+	char val[META_HASH_MAX];
+	long sz, i;
+
+	sz = ctx->nmemb ? : META_HASH_MAX;
+	sz = MIN(sz, sizeof(val));
+
+	/* Returns the length of the string, including the NUL byte. */
+	sz = bpf_probe_read_kernel_str(val, sz, (char *)ctx->base + ctx->offset);
+	if (sz <= 0)
+		return 0;
+
+	for (i = 0; i < sizeof(val) - 1; i++) {
+		if (i >= sz - 1)
+			break;
+
+		hash ^= (u8)val[i];
+		hash *= 0x100000001b3ULL;
+	}
+
+	return hash == *((u64 *)ctx->data);
+}
+
 static __always_inline
 bool filter_bytes(struct retis_meta_ctx *ctx)
 {
-	bool ret = cmp_bytes(ctx);
+	bool ret = ctx->hash ? cmp_hash(ctx) : cmp_bytes(ctx);
 
 	switch (ctx->cmp) {
 	case RETIS_EQ:
@@ -229,6 +236,23 @@ u64 extract_bf(u64 val, bool has_sign, u16 bit_off, u16 bit_sz)
 	return has_sign ? (s64)val >> (64 - bit_sz) : val >> (64 - bit_sz);
 }
 
+/* Reverses the bits of a value, over its size (in bytes). Please keep in sync
+ * with its Rust counterpart.
+ */
+static __always_inline
+u64 bitrev(u64 val, u32 sz)
+{
+	val = ((val >> 1) & 0x5555555555555555ULL) |
+	      ((val & 0x5555555555555555ULL) << 1);
+	val = ((val >> 2) & 0x3333333333333333ULL) |
+	      ((val & 0x3333333333333333ULL) << 2);
+	val = ((val >> 4) & 0x0f0f0f0f0f0f0f0fULL) |
+	      ((val & 0x0f0f0f0f0f0f0f0fULL) << 4);
+	val = __builtin_bswap64(val);
+
+	return val >> ((64 - sz * 8) & 63);
+}
+
 static __always_inline
 u64 fixup_signed(u64 val, u32 sz)
 {
@@ -252,11 +276,14 @@ u64 fixup_signed(u64 val, u32 sz)
 	return ret;
 }
 
+/* Reads the numeric member described by ctx into val. Returns false if it
+ * could not be read.
+ */
 static __always_inline
-unsigned int filter_num(struct retis_meta_ctx *ctx)
+bool load_num(struct retis_meta_ctx *ctx, u64 *val)
 {
 	bool sign_bit = ctx->type & SIGN_BIT;
-	u64 tval, mval = 0;
+	u64 mval = 0;
 	u16 offset;
 	u32 sz;
 
@@ -269,7 +296,7 @@ unsigned int filter_num(struct retis_meta_ctx *ctx)
 		 */
 		sz = DIV_CEIL((ctx->offset - offset * 8U) + ctx->bfs, 8);
 		if (!sz)
-			return 0;
+			return false;
 	} else {
 		sz = ctx->sz;
 		offset = ctx->offset;
@@ -278,11 +305,11 @@ unsigned int filter_num(struct retis_meta_ctx *ctx)
 	sz = MIN(sz, sizeof(mval));
 	if (!sz) {
 		log_error("error while calculating bytes to read (zero not allowed)");
-		return 0;
+		return false;
 	}
 
 	if (bpf_probe_read_kernel(&mval, sz, (char *)ctx->base + offset))
-		return 0;
+		return false;
 
 	/* Bitfields are handled separately as, considering they could
 	 * start at any offset (can be "packed into adjacent bits of
@@ -294,9 +321,171 @@ unsigned int filter_num(struct retis_meta_ctx *ctx)
 	else if (sign_bit)
 		mval = fixup_signed(mval, sz);
 
+	/* Compare the bit-reversed value, only set for unsigned members. The
+	 * mask (if any) applies to the reversed value.
+	 */
+	if (ctx->bitrev)
+		mval = bitrev(mval, sz);
+
+	/* Compare the absolute value, only set for signed members. */
+	if (sign_bit && ctx->type & ABS_BIT && (s64)mval < 0)
+		mval = -mval;
+
+	*val = mval;
+	return true;
+}
+
+static __always_inline
+unsigned int filter_num(struct retis_meta_ctx *ctx)
+{
+	bool sign_bit = ctx->type & SIGN_BIT;
+	bool sub = ctx->sub;
+	u64 tval, mval;
+
+	/* The member is the second operand of the pending difference, if
+	 * any.
+	 */
+	ctx->sub = false;
+
+	if (!load_num(ctx, &mval))
+		return 0;
+
+	if (sub) {
+		if (!ctx->sub_ok)
+			return 0;
+
+		mval = ctx->minuend - mval;
+	}
+
 	tval = *((u64 *)ctx->data);
 
-	return cmp_num(mval, ctx->mask, tval, sign_bit, ctx->cmp);
+	return cmp_num(mval, ctx->mask, ctx->shift, tval, sign_bit, ctx->cmp);
+}
+
+static __always_inline
+unsigned int meta_cmp(struct retis_meta_ctx *ctx)
+{
+	if (ctx->type & PTR_BIT || ctx->nmemb > 0)
+		return filter_bytes(ctx);
+
+	return filter_num(ctx);
+}
+
+/* Walks the ops. Every leaf (non pointer) load terminates a chain of
+ * loads and triggers the comparison against the target. Multiple
+ * chains are ORed together and all of them start from the skb.
+ * An empty op (no type) starts a new or-group and is followed by the
+ * target of the group, used by the next chains.
+ * A leaf load flagged as the first operand of a difference does not
+ * trigger a comparison: its value is kept and the value of the next leaf
+ * load is subtracted from it, the result being compared instead.
+ * A sample gate, always placed right after the first target, lets pass
+ * 1 out of N packets (N being stored in the mask) and matches on its own
+ * if no chain follows.
+ * Returns 1 on match, 0 otherwise and -1 on error.
+ */
+static __always_inline
+long meta_process_ops(struct retis_meta_ctx *ctx, struct sk_buff *skb)
+{
+	union retis_meta_op *val;
+	u32 k = 0;
+	u64 ptr;
+	u32 i;
+
+	val = bpf_map_lookup_elem(&filter_meta_map, &k);
+	if (!val) {
+		log_error("Failed to lookup meta-filter target");
+		return -1;
+	}
+
+	/* process target */
+	ctx->data = &val->t.md;
+	ctx->cmp = val->t.cmp;
+	ctx->sz = val->t.sz;
+	ctx->base = skb;
+
+	for (i = 1, k = 1; i < nmeta; k++, i++) {
+		val = bpf_map_lookup_elem(&filter_meta_map, &k);
+		if (!val) {
+			log_error("Failed to lookup meta-filter member at index %u", i);
+			return -1;
+		}
+
+		/* Start of an or-group, the next op is its target. */
+		if (!val->l.type) {
+			k++;
+			i++;
+			val = bpf_map_lookup_elem(&filter_meta_map, &k);
+			if (!val) {
+				log_error("Failed to lookup meta-filter target at index %u", i);
+				return -1;
+			}
+
+			ctx->data = &val->t.md;
+			ctx->cmp = val->t.cmp;
+			ctx->sz = val->t.sz;
+			ctx->base = skb;
+			continue;
+		}
+
+		/* Sample gate, ANDed with the rest of the filter. */
+		if (val->l.type & SAMPLE_BIT) {
+			if (bpf_get_prandom_u32() % (u32)val->l.mask)
+				return 0;
+			if (i == nmeta - 1)
+				return 1;
+
+			continue;
+		}
+
+		/* Current cpu pseudo-load, not reading from the skb. */
+		if (val->l.type & CPU_BIT) {
+			if (cmp_num(bpf_get_smp_processor_id(), 0, 0,
+				    *((u64 *)ctx->data), false, ctx->cmp))
+				return 1;
+
+			continue;
+		}
+
+		/* Load Pointer */
+		if (val->l.type == PTR_BIT) {
+			if (bpf_probe_read_kernel(&ptr, sizeof(void *),
+						  (char *)ctx->base + (val->l.offt)))
+				return -1;
+
+			ctx->base = val->l.mask ? (void *)(ptr & val->l.mask)
+				                : (void *)ptr;
+			continue;
+		}
+
+		/* Non intermediate */
+		ctx->offset = val->l.offt;
+		ctx->type = val->l.type;
+		ctx->mask = val->l.mask;
+		ctx->shift = val->l.shift;
+		ctx->hash = val->l.hash;
+		ctx->bitrev = val->l.bitrev;
+		ctx->nmemb = val->l.nmemb;
+		ctx->bfs = val->l.bf_size;
+
+		/* First operand of a difference, kept until the second one
+		 * is loaded by the next chain.
+		 */
+		if (val->l.sub) {
+			ctx->sub = true;
+			ctx->sub_ok = load_num(ctx, &ctx->minuend);
+			ctx->base = skb;
+			continue;
+		}
+
+		if (meta_cmp(ctx))
+			return 1;
+
+		/* Next chain (if any) starts over from the skb. */
+		ctx->base = skb;
+	}
+
+	return 0;
 }
 
 static __always_inline
@@ -310,15 +499,7 @@ unsigned int meta_filter(struct sk_buff *skb)
 	if (!nmeta || nmeta > META_OPS_MAX)
 		return 1;
 
-	ctx.base = skb;
-
-	if (meta_process_ops(&ctx) < 0 || !ctx.data)
-		return 0;
-
-	if (ctx.type & PTR_BIT || ctx.nmemb > 0)
-		return filter_bytes(&ctx);
-
-	return filter_num(&ctx);
+	return meta_process_ops(&ctx, skb) > 0;
 }
 
 #endif
@@ -10,11 +10,45 @@ use anyhow::{anyhow, bail, Result};
 use btf_rs::*;
 use plain::Plain;
 
-use crate::core::inspect::inspector;
+use crate::core::inspect::{inspector, kernel_version::KernelVersionReq};
 
 const META_OPS_MAX: u32 = 32;
 const META_TARGET_MAX: usize = 32;
-
+// Maximum size (including the NUL byte) of the strings hashed in the kernel.
+const META_HASH_MAX: usize = 128;
+// Maximum number of fields an any() expression can hold.
+const META_ANY_MAX: usize = 4;
+
+// Pseudo-member matching the cpu the probe runs on.
+const META_CPU: &str = "cpu()";
+// Transform taking the absolute value of a signed member.
+const META_ABS: &str = "abs(";
+// Transform comparing the FNV-1a hash of a string member, see `fnv1a`.
+const META_HASH: &str = "hash(";
+// Transform reversing the bits of an unsigned member, over its width.
+const META_BITREV: &str = "bitrev(";
+// Separator of the expressions of an or-group.
+const META_OR: &str = "||";
+// Separator of the sample gate, the kernel version condition and the rest of
+// the filter.
+const META_AND: &str = "&&";
+// Gate letting pass 1 out of N packets, e.g. "sample(1/100)".
+const META_SAMPLE: &str = "sample(";
+// Condition on the running kernel version, evaluated when building the
+// filter, e.g. "kversion() >= 6.1".
+const META_KVERSION: &str = "kversion()";
+// Expression never matching, used when the kernel version condition is not
+// met (the cpu id is unsigned).
+const META_REJECT: &str = "cpu() < 0";
+// Separator of the offset adjustment of a leaf member, e.g. "mark@-4:u32".
+const META_ADJUST: char = '@';
+// Separator of the BTF source (vmlinux or a module) a struct type is taken
+// from, e.g. "sk_buff@vmlinux".
+const META_SOURCE: char = '@';
+
+const SAMPLE_BIT: u8 = 1 << 3;
+const ABS_BIT: u8 = 1 << 4;
+const CPU_BIT: u8 = 1 << 5;
 const PTR_BIT: u8 = 1 << 6;
 const SIGN_BIT: u8 = 1 << 7;
 
@@ -22,7 +56,39 @@ const SIGN_BIT: u8 = 1 << 7;
 struct LhsNode<'a> {
     member: &'a str,
     mask: u64,
+    // Right shift applied after the mask, only valid for the leaf member.
+    shift: u8,
     tgt_type: Option<&'a str>,
+    // BTF source of the starting struct, only valid for the first member.
+    source: Option<&'a str>,
+    // Signedness override, only valid for the leaf member.
+    signed: Option<bool>,
+    // Compare the absolute value, only valid for the leaf member.
+    abs: bool,
+    // Compare the string hash, only valid for the leaf member.
+    hash: bool,
+    // Compare the bit-reversed value, only valid for the leaf member.
+    bitrev: bool,
+    // Offset adjustment, only valid for the leaf member.
+    adjust: Option<LhsAdjust>,
+}
+
+// Load of an explicit width at a signed byte offset from a leaf member, e.g.
+// "mark@-4:u32". The load must stay within the struct holding the member.
+#[derive(Clone, Copy)]
+struct LhsAdjust {
+    offset: i32,
+    size: u8,
+    signed: bool,
+}
+
+// Left-hand side of an expression.
+enum Lhs<'a> {
+    // One or more members, matching if at least one of them satisfies the
+    // comparison (see any()).
+    Any(Vec<Vec<LhsNode<'a>>>),
+    // Difference of two members, (minuend - subtrahend).
+    Diff(Vec<LhsNode<'a>>, Vec<LhsNode<'a>>),
 }
 
 #[derive(Eq, PartialEq)]
@@ -88,7 +154,8 @@ struct MetaTarget {
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 struct MetaLoad {
     // Type of data we're going to load
-    // bit 0-4: [char|short|int|long], bit5: reserved, bit6: is_ptr, bit7: sign
+    // bit 0-2: [char|short|int|long], bit3: is_sample, bit4: is_abs, bit5: is_cpu, bit6: is_ptr,
+    // bit7: sign
     r#type: u8,
     // Usually zero.
     // nmemb > 0 is valid iff MetaOp::r#type == MetaType::Char
@@ -97,7 +164,19 @@ struct MetaLoad {
     offt: u16,
     // Zero for no bitfield.
     bf_size: u8,
-    // Mask to apply. Only numbers are supported.
+    // Right shift to apply after the mask. Only numbers are supported.
+    shift: u8,
+    // Non-zero to compare the hash of the string instead of its bytes.
+    // Only strings are supported.
+    hash: u8,
+    // Non-zero if the leaf member is the first operand of a difference. Its
+    // value is kept and the value of the next leaf member is subtracted from
+    // it before the comparison. Only numbers are supported.
+    sub: u8,
+    // Non-zero to reverse the bits of the value, over its width, before the
+    // comparison. Only unsigned numbers are supported.
+    bitrev: u8,
+    // Mask to apply. Only numbers are supported. Modulus for sample gates.
     mask: u64,
 }
 
@@ -107,32 +186,66 @@ impl MetaLoad {
     }
 
     fn is_byte(&self) -> bool {
-        self.r#type & 0x1f == MetaType::Char as u8
+        self.r#type & 0x7 == MetaType::Char as u8
     }
 
     fn is_short(&self) -> bool {
-        self.r#type & 0x1f == MetaType::Short as u8
+        self.r#type & 0x7 == MetaType::Short as u8
     }
 
     fn is_int(&self) -> bool {
-        self.r#type & 0x1f == MetaType::Int as u8
+        self.r#type & 0x7 == MetaType::Int as u8
     }
 
     fn is_long(&self) -> bool {
-        self.r#type & 0x1f == MetaType::Long as u8
+        self.r#type & 0x7 == MetaType::Long as u8
     }
 
     fn is_ptr(&self) -> bool {
         self.r#type & PTR_BIT > 0
     }
 
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn is_sample(&self) -> bool {
+        self.r#type & SAMPLE_BIT > 0
+    }
+
+    fn is_cpu(&self) -> bool {
+        self.r#type & CPU_BIT > 0
+    }
+
     fn is_signed(&self) -> bool {
         self.r#type & SIGN_BIT > 0
     }
 
+    fn is_abs(&self) -> bool {
+        self.r#type & ABS_BIT > 0
+    }
+
     fn is_arr(&self) -> bool {
         self.nmemb > 0
     }
+
+    fn is_hash(&self) -> bool {
+        self.hash > 0
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn is_sub(&self) -> bool {
+        self.sub > 0
+    }
+
+    fn is_bitrev(&self) -> bool {
+        self.bitrev > 0
+    }
+}
+
+// 64-bit FNV-1a hash, computed in the kernel over the bytes of strings up to
+// their first NUL byte. Please keep in sync with its eBPF counterpart.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
 }
 
 #[repr(C)]
@@ -181,16 +294,105 @@ impl MetaOp {
         Ok(())
     }
 
-    fn emit_load_ptr(offt: u32, mask: u64) -> Result<MetaOp> {
+    // Loads can only reach offsets up to u16::MAX (in bytes for regular
+    // members, in bits for bitfields).
+    fn check_offt(field: &str, offt: u32) -> Result<u16> {
+        u16::try_from(offt).map_err(|_| {
+            anyhow!(
+                "offset too large for field {field} ({offt}, max {}).",
+                u16::MAX
+            )
+        })
+    }
+
+    fn emit_load_ptr(field: &str, offt: u32, mask: u64) -> Result<MetaOp> {
         let mut op: MetaOp = MetaOp::new();
-        op.l.offt = u16::try_from(offt / 8)?;
+        op.l.offt = Self::check_offt(field, offt / 8)?;
         op.l.r#type = PTR_BIT;
         op.l.mask = mask;
 
         Ok(op)
     }
 
-    fn emit_load(btf: &Btf, r#type: &Type, offt: u32, bfs: u32, mask: u64) -> Result<MetaOp> {
+    // Pseudo-load of the current cpu id (u32). It does not read from the
+    // skb and always terminates a chain.
+    fn emit_load_cpu() -> MetaOp {
+        let mut op: MetaOp = MetaOp::new();
+        op.l.r#type = CPU_BIT | MetaType::Int as u8;
+
+        op
+    }
+
+    // Pseudo-load gating the whole filter, letting pass 1 out of modulus
+    // packets. It is not followed by a target.
+    fn emit_sample(modulus: u32) -> MetaOp {
+        let mut op: MetaOp = MetaOp::new();
+        op.l.r#type = SAMPLE_BIT;
+        op.l.mask = modulus as u64;
+
+        op
+    }
+
+    // Empty op (no type) starting a new or-group. It is followed by the
+    // target of the group.
+    fn emit_group() -> MetaOp {
+        MetaOp::new()
+    }
+
+    // Load of an integer of an explicit width, not relying on the BTF type of
+    // the member (see offset adjustments).
+    fn emit_load_int(
+        field: &str,
+        offt: u32,
+        size: u8,
+        signed: bool,
+        abs: bool,
+        bitrev: bool,
+    ) -> Result<MetaOp> {
+        let mut op: MetaOp = MetaOp::new();
+        let lop = op.load_ref_mut();
+
+        lop.r#type = match size {
+            8 => MetaType::Long,
+            4 => MetaType::Int,
+            2 => MetaType::Short,
+            1 => MetaType::Char,
+            _ => bail!("unsupported width ({size})."),
+        } as u8;
+        if signed {
+            lop.r#type |= SIGN_BIT;
+        }
+        if abs {
+            if !signed {
+                bail!("abs() is only supported for signed numeric members.");
+            }
+            lop.r#type |= ABS_BIT;
+        }
+        if bitrev {
+            if signed {
+                bail!("bitrev() is only supported for unsigned numeric members.");
+            }
+            lop.bitrev = 1;
+        }
+        lop.offt = Self::check_offt(field, offt / 8)?;
+
+        Ok(op)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn emit_load(
+        btf: &Btf,
+        r#type: &Type,
+        field: &str,
+        offt: u32,
+        bfs: u32,
+        mask: u64,
+        shift: u8,
+        signed: Option<bool>,
+        abs: bool,
+        hash: bool,
+        bitrev: bool,
+    ) -> Result<MetaOp> {
         let mut op: MetaOp = MetaOp::new();
         let lop = op.load_ref_mut();
         let mut t = r#type.clone();
@@ -268,6 +470,19 @@ impl MetaOp {
             };
         }
 
+        // Override the BTF-derived signedness, if asked to.
+        if let Some(signed) = signed {
+            if lop.is_ptr() || lop.is_arr() || !lop.is_num() {
+                bail!("signedness override is only supported for numeric members.");
+            }
+
+            if signed {
+                lop.r#type |= SIGN_BIT;
+            } else {
+                lop.r#type &= !SIGN_BIT;
+            }
+        }
+
         if mask > 0 {
             if lop.is_ptr() || (lop.is_num() && !lop.is_signed()) {
                 lop.mask = mask;
@@ -276,13 +491,45 @@ impl MetaOp {
             }
         }
 
-        lop.bf_size = u8::try_from(bfs)?;
-        lop.offt = u16::try_from(offt)?;
+        if shift > 0 {
+            if lop.is_ptr() || lop.mask == 0 {
+                bail!("shift is only supported for masked unsigned numeric members.");
+            }
+            lop.shift = shift;
+        }
+
+        if abs {
+            if lop.is_ptr() || lop.is_arr() || !lop.is_num() || !lop.is_signed() {
+                bail!("abs() is only supported for signed numeric members.");
+            }
+            lop.r#type |= ABS_BIT;
+        }
+
+        if hash {
+            if !lop.is_byte() || !(lop.is_ptr() || lop.is_arr()) {
+                bail!("hash() is only supported for string members.");
+            }
+            lop.hash = 1;
+        }
 
-        if bfs == 0 {
-            lop.offt /= 8;
+        // The bits are reversed over the width of the member, which bitfields
+        // do not have.
+        if bitrev {
+            if lop.is_ptr() || lop.is_arr() || !lop.is_num() || lop.is_signed() || bfs > 0 {
+                bail!("bitrev() is only supported for unsigned numeric members (not bitfields).");
+            }
+            lop.bitrev = 1;
         }
 
+        lop.bf_size = u8::try_from(bfs)?;
+        lop.offt = Self::check_offt(
+            field,
+            match bfs {
+                0 => offt / 8,
+                _ => offt,
+            },
+        )?;
+
         Ok(op)
     }
 
@@ -299,7 +546,26 @@ impl MetaOp {
                 );
             }
 
-            if let Rval::Str(val) = rval {
+            if lmo.is_hash() {
+                let val = match rval {
+                    Rval::Str(val) => val,
+                    _ => bail!("invalid target value for hash(). Only strings are supported."),
+                };
+
+                // Longer strings are truncated when read in the kernel and
+                // would never match.
+                let max = match lmo.is_arr() {
+                    true => (lmo.nmemb as usize).min(META_HASH_MAX),
+                    false => META_HASH_MAX,
+                };
+                if val.len() >= max {
+                    bail!("invalid rval size (max {} for hash()).", max - 1);
+                }
+
+                let hash = fnv1a(val.as_bytes());
+                top.md[..std::mem::size_of_val(&hash)].copy_from_slice(&hash.to_ne_bytes());
+                top.sz = std::mem::size_of_val(&hash) as u8;
+            } else if let Rval::Str(val) = rval {
                 let rval_len = val.len();
                 let md = &mut top.md;
                 if rval_len >= md.len() {
@@ -325,7 +591,9 @@ impl MetaOp {
                     }
                 }
                 Rval::Hex(val) => u64::from_str_radix(&val, 16)?,
-                _ => bail!("invalid target value (neither decimal nor hex)."),
+                Rval::Bin(val) => u64::from_str_radix(&val, 2)?,
+                Rval::Oct(val) => u64::from_str_radix(&val, 8)?,
+                _ => bail!("invalid target value (neither decimal, hex, binary nor octal)."),
             };
 
             top.md[..std::mem::size_of_val(&long)].copy_from_slice(&long.to_ne_bytes());
@@ -394,21 +662,65 @@ fn walk_btf_node(
 enum Rval {
     Dec(String),
     Hex(String),
+    Bin(String),
+    Oct(String),
     Str(String),
     // Btf,
 }
 
 impl Rval {
+    // Unit suffixes of decimal values. Decimal units (k, m, g) use powers of
+    // 1000, binary ones (ki, mi, gi) powers of 1024.
+    const UNITS: [(&'static str, u64); 6] = [
+        ("ki", 1 << 10),
+        ("mi", 1 << 20),
+        ("gi", 1 << 30),
+        ("k", 1_000),
+        ("m", 1_000_000),
+        ("g", 1_000_000_000),
+    ];
+
+    // Apply a unit suffix, if any, to a decimal value.
+    fn apply_unit(rval: &str) -> Result<String> {
+        let (val, unit) = match Self::UNITS
+            .iter()
+            .find_map(|(suffix, unit)| rval.strip_suffix(suffix).map(|val| (val, *unit)))
+        {
+            Some(x) => x,
+            None => return Ok(rval.to_string()),
+        };
+
+        let overflow = || anyhow!("value too large ({rval}).");
+        Ok(match val.strip_prefix('-') {
+            Some(_) => val
+                .parse::<i64>()
+                .map_err(|_| anyhow!("invalid value ({rval})."))?
+                .checked_mul(i64::try_from(unit)?)
+                .ok_or_else(overflow)?
+                .to_string(),
+            None => val
+                .parse::<u64>()
+                .map_err(|_| anyhow!("invalid value ({rval})."))?
+                .checked_mul(unit)
+                .ok_or_else(overflow)?
+                .to_string(),
+        })
+    }
+
     fn from_str(rval: &str) -> Result<Rval> {
         let detected = if (rval.starts_with('"') && rval.ends_with('"'))
             || (rval.starts_with('\'') && rval.ends_with('\''))
         {
             Rval::Str(rval[1..rval.len() - 1].to_string())
         } else {
-            let base = if rval.starts_with("0x") {
-                Rval::Hex(rval.trim_start_matches("0x").to_string())
+            let base = if let Some(hex) = rval.strip_prefix("0x") {
+                Rval::Hex(hex.to_string())
+            } else if let Some(bin) = rval.strip_prefix("0b") {
+                Rval::Bin(bin.to_string())
+            } else if let Some(oct) = rval.strip_prefix("0o") {
+                Rval::Oct(oct.to_string())
             } else {
-                Rval::Dec(rval.to_string())
+                Rval::Dec(Self::apply_unit(rval)?)
             };
 
             base
@@ -484,13 +796,15 @@ impl FilterMeta {
             (16, hex)
         } else if let Some(bin) = el.strip_prefix("0b") {
             (2, bin)
+        } else if let Some(oct) = el.strip_prefix("0o") {
+            (8, oct)
         } else {
             (10, el)
         };
 
         let mut mask = u64::from_str_radix(mask_str, base).map_err(|_| {
             anyhow!(
-                "invalid mask. Use an hex, binary or decimal mask (0x<hex>, 0b<bin>, <decimal>)"
+                "invalid mask. Use an hex, binary, octal or decimal mask (0x<hex>, 0b<bin>, 0o<oct>, <decimal>)"
             )
         })?;
 
@@ -502,26 +816,178 @@ impl FilterMeta {
         Ok(mask)
     }
 
-    // Parse (in a very simple way) the filter string splitting it
-    // into rhs op and lhs.
-    // Requires spaces as separator among elements.
-    fn parse_filter(filter: &str) -> Result<(Vec<LhsNode>, MetaCmp, &str)> {
-        let expr = filter.split(' ').collect::<Vec<_>>();
+    fn parse_shift(el: &str) -> Result<u8> {
+        let shift = el
+            .parse::<u8>()
+            .map_err(|_| anyhow!("invalid shift. Use a decimal number"))?;
 
-        let [lhs, op, rhs]: [&str; 3] = match expr.len() {
-            3 => expr
-                .try_into()
-                .map_err(|_| anyhow!("cannot split filter ({filter})"))?,
-            1 => [expr[0], "!=", "0"],
-            _ => bail!("invalid filter ({filter})"),
+        if shift == 0 || shift >= 64 {
+            bail!("shift must be in the [1, 63] range");
+        }
+
+        Ok(shift)
+    }
+
+    // Parse the offset adjustment of a leaf member, under the form
+    // {-,+}offset:{s,u}{8,16,32,64}.
+    fn parse_adjust(el: &str) -> Result<LhsAdjust> {
+        let (offset, width) = el.split_once(':').ok_or_else(|| {
+            anyhow!("offset adjustments require an explicit width (e.g. member@-4:u32)")
+        })?;
+
+        if !offset.starts_with(['-', '+']) {
+            bail!("invalid offset adjustment ({offset}), must be signed (e.g. -4)");
+        }
+        let offset = offset
+            .parse::<i32>()
+            .map_err(|_| anyhow!("invalid offset adjustment ({offset})"))?;
+
+        let (signed, bits) = if let Some(bits) = width.strip_prefix('s') {
+            (true, bits)
+        } else if let Some(bits) = width.strip_prefix('u') {
+            (false, bits)
+        } else {
+            bail!("invalid width ({width}), must be one of {{s,u}}{{8,16,32,64}}");
+        };
+        let size = match bits {
+            "8" => 1,
+            "16" => 2,
+            "32" => 4,
+            "64" => 8,
+            _ => bail!("invalid width ({width}), must be one of {{s,u}}{{8,16,32,64}}"),
         };
 
+        Ok(LhsAdjust {
+            offset,
+            size,
+            signed,
+        })
+    }
+
+    // Parse a single lhs member expression under the form
+    // sk_buff.member1[:mask[:type]].[...].leafmember[:mask[>>shift]][:{s,u}]
+    // or sk_buff.member1[:mask[:type]].[...].leafmember@{-,+}offset:width,
+    // optionally wrapped in abs(), hash() or bitrev().
+    fn parse_lhs(lhs: &str) -> Result<Vec<LhsNode>> {
+        // cpu() is standalone and can't be part of a member expression.
+        if lhs == META_CPU {
+            return Ok(vec![LhsNode {
+                member: META_CPU,
+                ..Default::default()
+            }]);
+        }
+
+        // abs() applies to the leaf member of the expression it wraps.
+        if let Some(inner) = lhs.strip_prefix(META_ABS) {
+            let inner = inner
+                .strip_suffix(')')
+                .ok_or_else(|| anyhow!("unterminated abs() expression ({lhs})"))?;
+            if inner == META_CPU {
+                bail!("abs() is only supported for signed numeric members.");
+            }
+
+            let mut lhs = Self::parse_lhs(inner)?;
+            if let Some(leaf) = lhs.last_mut() {
+                if leaf.abs {
+                    bail!("abs() can't be nested");
+                }
+                leaf.abs = true;
+            }
+            return Ok(lhs);
+        }
+
+        // hash() applies to the leaf member of the expression it wraps.
+        if let Some(inner) = lhs.strip_prefix(META_HASH) {
+            let inner = inner
+                .strip_suffix(')')
+                .ok_or_else(|| anyhow!("unterminated hash() expression ({lhs})"))?;
+            if inner == META_CPU {
+                bail!("hash() is only supported for string members.");
+            }
+
+            let mut lhs = Self::parse_lhs(inner)?;
+            if let Some(leaf) = lhs.last_mut() {
+                if leaf.hash || leaf.abs || leaf.bitrev {
+                    bail!("hash() can't be nested");
+                }
+                leaf.hash = true;
+            }
+            return Ok(lhs);
+        }
+
+        // bitrev() applies to the leaf member of the expression it wraps.
+        if let Some(inner) = lhs.strip_prefix(META_BITREV) {
+            let inner = inner
+                .strip_suffix(')')
+                .ok_or_else(|| anyhow!("unterminated bitrev() expression ({lhs})"))?;
+            if inner == META_CPU {
+                bail!("bitrev() is only supported for unsigned numeric members.");
+            }
+
+            let mut lhs = Self::parse_lhs(inner)?;
+            if let Some(leaf) = lhs.last_mut() {
+                if leaf.bitrev || leaf.hash || leaf.abs {
+                    bail!("bitrev() can't be nested");
+                }
+                leaf.bitrev = true;
+            }
+            return Ok(lhs);
+        }
+
+        let nodes = lhs.split('.').count();
         let lhs: Vec<_> = lhs
             .split('.')
             .enumerate()
             .map(|x| {
                 let first = x.0 == 0;
-                let mut elem = x.1.split(':');
+                let leaf = !first && x.0 == nodes - 1;
+                let mut node = x.1;
+
+                // The starting struct can be taken from a given BTF source.
+                let mut source = None;
+                if first {
+                    if let Some((member, src)) = node.split_once(META_SOURCE) {
+                        node = member;
+                        source = Some(src);
+                    }
+                }
+
+                // The leaf member can be loaded at an offset from it, using
+                // an explicit width. It can't be combined with other
+                // modifiers. A separator found after the member name is
+                // part of a qualified cast type instead.
+                if let Some((member, adjust)) = node
+                    .split_once(META_ADJUST)
+                    .filter(|(member, _)| !member.contains(':'))
+                {
+                    if !leaf {
+                        bail!("offset adjustments are only supported for the leaf member");
+                    }
+
+                    return Ok(LhsNode {
+                        member,
+                        adjust: Some(Self::parse_adjust(adjust)?),
+                        ..Default::default()
+                    });
+                }
+
+                // The leaf member can force the signedness of the
+                // comparison.
+                let mut signed = None;
+                if leaf {
+                    if let Some((rest, sign)) = node.rsplit_once(':') {
+                        signed = match sign {
+                            "s" => Some(true),
+                            "u" => Some(false),
+                            _ => None,
+                        };
+                        if signed.is_some() {
+                            node = rest;
+                        }
+                    }
+                }
+
+                let mut elem = node.split(':');
                 // member is mandatory.
                 let member = elem.next().ok_or_else(|| anyhow!("member is mandatory"))?;
 
@@ -530,13 +996,24 @@ impl FilterMeta {
                 }
                 // mask is optional and must be a number.
                 // Can be under the form [~]{hex, bin, dec}
-                let mask = if let Some(el) = elem.next() {
+                // For the leaf member, it can be followed by a right
+                // shift (>>dec).
+                let (mask, shift) = if let Some(el) = elem.next() {
                     if first {
                         bail!("initial type must be a base type only");
                     }
-                    Self::parse_mask(el)?
+
+                    match el.split_once(">>") {
+                        Some((mask, shift)) => {
+                            if !leaf {
+                                bail!("shift is only supported for the leaf member");
+                            }
+                            (Self::parse_mask(mask)?, Self::parse_shift(shift)?)
+                        }
+                        None => (Self::parse_mask(el)?, 0),
+                    }
                 } else {
-                    0x0
+                    (0x0, 0)
                 };
 
                 // tgt_type is optional.
@@ -551,7 +1028,14 @@ impl FilterMeta {
                 Ok(LhsNode {
                     member,
                     mask,
+                    shift,
                     tgt_type,
+                    source,
+                    signed,
+                    abs: false,
+                    hash: false,
+                    bitrev: false,
+                    adjust: None,
                 })
             })
             .collect::<Result<Vec<LhsNode<'_>>>>()?;
@@ -560,31 +1044,104 @@ impl FilterMeta {
             bail!("expression does not point to a member");
         }
 
+        Ok(lhs)
+    }
+
+    // Split what follows a parenthesized lhs into op and rhs.
+    fn split_rest(rest: &str) -> Vec<&str> {
+        match rest.trim_start() {
+            "" => Vec::new(),
+            rest => rest.split(' ').collect(),
+        }
+    }
+
+    // Parse (in a very simple way) the filter string splitting it
+    // into rhs op and lhs. The lhs can either be a single member, an
+    // any(member1, member2, ...) expression matching if at least one
+    // of the members satisfies the comparison or the difference of two
+    // members, (member1 - member2).
+    // Requires spaces as separator among elements.
+    fn parse_filter(filter: &str) -> Result<(Lhs, MetaCmp, &str)> {
+        let (lhs, expr) = if let Some(any) = filter.strip_prefix("any(") {
+            let (fields, rest) = any
+                .split_once(')')
+                .ok_or_else(|| anyhow!("unterminated any() expression ({filter})"))?;
+            let fields: Vec<_> = fields.split(',').map(|f| f.trim()).collect();
+
+            if fields.len() > META_ANY_MAX {
+                bail!("too many fields in any() expression (max {META_ANY_MAX})");
+            }
+
+            let fields = fields
+                .into_iter()
+                .map(Self::parse_lhs)
+                .collect::<Result<Vec<_>>>()?;
+
+            (Lhs::Any(fields), Self::split_rest(rest))
+        } else if let Some(diff) = filter.strip_prefix('(') {
+            let (diff, rest) = diff
+                .split_once(')')
+                .ok_or_else(|| anyhow!("unterminated difference expression ({filter})"))?;
+            let (minuend, subtrahend) = diff.split_once(" - ").ok_or_else(|| {
+                anyhow!("invalid difference expression ({filter}), must be (member1 - member2)")
+            })?;
+
+            (
+                Lhs::Diff(
+                    Self::parse_lhs(minuend.trim())?,
+                    Self::parse_lhs(subtrahend.trim())?,
+                ),
+                Self::split_rest(rest),
+            )
+        } else {
+            let mut expr = filter.split(' ').collect::<Vec<_>>();
+            let field = Self::parse_lhs(expr.remove(0))?;
+
+            (Lhs::Any(vec![field]), expr)
+        };
+
+        let [op, rhs]: [&str; 2] = match expr.len() {
+            2 => expr
+                .try_into()
+                .map_err(|_| anyhow!("cannot split filter ({filter})"))?,
+            0 => ["!=", "0"],
+            _ => bail!("invalid filter ({filter})"),
+        };
+
         Ok((lhs, MetaCmp::from_str(op)?, rhs))
     }
 
-    pub(crate) fn from_string(fstring: String) -> Result<Self> {
+    // Emit the loads needed to reach the leaf member described by
+    // fields, appending them to ops. The leaf load is returned.
+    fn emit_lhs(mut fields: Vec<LhsNode>, ops: &mut Vec<MetaOp>) -> Result<MetaOp> {
+        if fields.len() == 1 && fields[0].member == META_CPU {
+            let lmo = MetaOp::emit_load_cpu();
+            ops.push(lmo);
+            return Ok(lmo);
+        }
+
         let btf_info = &inspector()?.kernel.btf;
-        let mut ops: Vec<_> = Vec::new();
         let mut offt: u32 = 0;
         let mut stored_offset: u32 = 0;
         let mut stored_bf_size: u32 = 0;
         let mut mask = 0;
-
-        let (mut fields, op, rval) = Self::parse_filter(&fstring)?;
+        let mut shift = 0;
+        let mut signed = None;
+        let mut abs = false;
+        let mut hash = false;
+        let mut bitrev = false;
+        let mut adjust = None;
+        // Bounds (in bits) of the struct holding the leaf member.
+        let mut bounds = (0, 0);
 
         // At least two elements are present
-        let init_sym = fields.remove(0).member;
-
-        let mut types = btf_info
-            .resolve_types_by_name(init_sym)
-            .map_err(|e| anyhow!("unable to resolve sk_buff data type {e}"))?;
+        let init = fields.remove(0);
+        let init_sym = init.member;
 
-        let (mut btf, ref mut r#type) =
-            match types.iter_mut().find(|(_, t)| matches!(t, Type::Struct(_))) {
-                Some(r#struct) => r#struct,
-                None => bail!("Could not resolve {init_sym} to a struct"),
-            };
+        let (mut btf, mut init_type) = btf_info
+            .resolve_unique_type(init_sym, init.source, |t| matches!(t, Type::Struct(_)))
+            .map_err(|e| anyhow!("unable to resolve {init_sym} data type: {e}"))?;
+        let r#type = &mut init_type;
 
         for (pos, field) in fields.iter().enumerate() {
             let sub_node = walk_btf_node(btf, r#type, field.member, offt);
@@ -603,7 +1160,7 @@ impl FilterMeta {
                             std::cmp::Ordering::Equal => {
                                 offt = 0;
                                 // Emit load Ptr
-                                ops.push(MetaOp::emit_load_ptr(offset, field.mask)?);
+                                ops.push(MetaOp::emit_load_ptr(field.member, offset, field.mask)?);
                             }
                             std::cmp::Ordering::Greater => {
                                 bail!("pointers of pointers are not supported")
@@ -617,27 +1174,24 @@ impl FilterMeta {
                         }
 
                         if let Some(tgt) = field.tgt_type {
-                            let mut types = btf_info
-                                .resolve_types_by_name(tgt)
+                            let (name, source) = match tgt.split_once(META_SOURCE) {
+                                Some((name, source)) => (name, Some(source)),
+                                None => (tgt, None),
+                            };
+                            let (tgt_btf, tgt_type) = btf_info
+                                .resolve_unique_type(name, source, |t| {
+                                    matches!(t, Type::Union(_) | Type::Struct(_) | Type::Typedef(_))
+                                })
                                 .map_err(|e| anyhow!("unable to resolve data type: {e}"))?;
 
-                            (btf, *r#type) = match types.iter_mut().find(|(_, t)| {
-                                matches!(t, Type::Union(_))
-                                    || matches!(t, Type::Struct(_))
-                                    || matches!(t, Type::Typedef(_))
-                            }) {
-                                Some((ref btf, r#type)) => {
-                                    let nw = Self::next_walkable(btf, r#type.clone(), false)?;
-                                    if nw.0 > 0 {
-                                        bail!(
-                                            "cast type ({tgt}: {}) cannot be an alias to a pointer",
-                                            r#type.name()
-                                        );
-                                    }
-                                    (btf, nw.1)
-                                }
-                                None => bail!("Could not resolve {tgt} to a struct or typedef"),
-                            };
+                            let nw = Self::next_walkable(tgt_btf, tgt_type.clone(), false)?;
+                            if nw.0 > 0 {
+                                bail!(
+                                    "cast type ({tgt}: {}) cannot be an alias to a pointer",
+                                    tgt_type.name()
+                                );
+                            }
+                            (btf, *r#type) = (tgt_btf, nw.1);
                         } else {
                             *r#type = x.clone();
                         }
@@ -646,8 +1200,18 @@ impl FilterMeta {
                             bail!("trying to cast a leaf member into {tgt}");
                         }
 
+                        if let Type::Struct(s) | Type::Union(s) = r#type {
+                            bounds = (offt, offt + s.size() as u32 * 8);
+                        }
+
                         *r#type = snode;
                         mask = field.mask;
+                        shift = field.shift;
+                        signed = field.signed;
+                        abs = field.abs;
+                        hash = field.hash;
+                        bitrev = field.bitrev;
+                        adjust = field.adjust;
                     }
 
                     stored_offset = offset;
@@ -659,12 +1223,231 @@ impl FilterMeta {
             }
         }
 
-        let lmo = MetaOp::emit_load(btf, r#type, stored_offset, stored_bf_size, mask)?;
+        let leaf = fields.last().map_or(init_sym, |f| f.member);
+
+        if let Some(adjust) = adjust {
+            if stored_bf_size != 0 {
+                bail!("offset adjustments are not supported for bitfields.");
+            }
+            if hash {
+                bail!("hash() is only supported for string members.");
+            }
+
+            // Stay within the struct holding the member.
+            let start = stored_offset as i64 + adjust.offset as i64 * 8;
+            let end = start + adjust.size as i64 * 8;
+            if start < bounds.0 as i64 || end > bounds.1 as i64 {
+                bail!(
+                    "offset adjustment of {leaf} ({:+}) is out of the bounds of its struct.",
+                    adjust.offset
+                );
+            }
+
+            let lmo =
+                MetaOp::emit_load_int(leaf, start as u32, adjust.size, adjust.signed, abs, bitrev)?;
+            ops.push(lmo);
+            return Ok(lmo);
+        }
+
+        let lmo = MetaOp::emit_load(
+            btf,
+            r#type,
+            leaf,
+            stored_offset,
+            stored_bf_size,
+            mask,
+            shift,
+            signed,
+            abs,
+            hash,
+            bitrev,
+        )?;
         ops.push(lmo);
 
+        Ok(lmo)
+    }
+
+    // Emit the loads of both members of a difference, appending them to
+    // ops. The leaf load of the first member is flagged so its value is kept
+    // rather than compared; the leaf load of the second member is returned.
+    fn emit_diff(
+        minuend: Vec<LhsNode>,
+        subtrahend: Vec<LhsNode>,
+        ops: &mut Vec<MetaOp>,
+    ) -> Result<MetaLoad> {
+        let first = *Self::emit_lhs(minuend, ops)?.load_ref();
+        let pos = ops.len() - 1;
+        let second = *Self::emit_lhs(subtrahend, ops)?.load_ref();
+
+        for lmo in [&first, &second] {
+            if lmo.is_ptr()
+                || lmo.is_arr()
+                || !lmo.is_num()
+                || lmo.is_cpu()
+                || lmo.is_abs()
+                || lmo.is_bitrev()
+                || lmo.mask != 0
+            {
+                bail!("differences are only supported for unmasked numeric members.");
+            }
+        }
+
+        // The difference is compared using the type of its members.
+        if first.r#type != second.r#type {
+            bail!("both members of a difference must share the same type and width");
+        }
+
+        ops[pos].load_ref_mut().sub = 1;
+
+        Ok(second)
+    }
+
+    // Emit the ops of a single expression: its target followed by the loads
+    // of its member(s).
+    fn emit_expr(expr: &str) -> Result<Vec<MetaOp>> {
+        let mut ops: Vec<_> = Vec::new();
+
+        let (lhs, op, rval) = Self::parse_filter(expr)?;
+
+        let leaf = match lhs {
+            Lhs::Any(lhs) => {
+                let mut leaf: Option<MetaLoad> = None;
+
+                for fields in lhs {
+                    let lmo = *Self::emit_lhs(fields, &mut ops)?.load_ref();
+
+                    match leaf {
+                        // The target is shared among all the members, so
+                        // they must all be of the same kind.
+                        Some(first) => {
+                            if lmo.r#type != first.r#type
+                                || lmo.nmemb != first.nmemb
+                                || lmo.hash != first.hash
+                                || lmo.bitrev != first.bitrev
+                            {
+                                bail!("all fields of an any() expression must share the same type and width");
+                            }
+                        }
+                        None => leaf = Some(lmo),
+                    }
+                }
+
+                leaf.ok_or_else(|| anyhow!("expression does not point to a member"))?
+            }
+            Lhs::Diff(minuend, subtrahend) => Self::emit_diff(minuend, subtrahend, &mut ops)?,
+        };
+
         let rval = Rval::from_str(rval)?;
 
-        ops.insert(0, MetaOp::emit_target(lmo.load_ref(), rval, op)?);
+        ops.insert(0, MetaOp::emit_target(&leaf, rval, op)?);
+
+        Ok(ops)
+    }
+
+    // Parse a sample(1/N) gate, returning N.
+    fn parse_sample(gate: &str) -> Result<u32> {
+        let ratio = gate
+            .strip_prefix(META_SAMPLE)
+            .and_then(|g| g.strip_suffix(')'))
+            .ok_or_else(|| anyhow!("invalid sample gate ({gate})"))?;
+
+        match ratio.split_once('/') {
+            Some(("1", n)) => match Rval::apply_unit(n.trim())?.parse::<u32>() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => bail!(
+                    "invalid sample rate ({gate}), N must be in [1, {}]",
+                    u32::MAX
+                ),
+            },
+            _ => bail!("invalid sample rate ({gate}), must be of the form 1/N"),
+        }
+    }
+
+    // Parse a kversion() condition, returning the kernel version requirement.
+    fn parse_kversion(cond: &str) -> Result<KernelVersionReq> {
+        match cond.strip_prefix(META_KVERSION).map(str::trim) {
+            Some(req) if !req.is_empty() => KernelVersionReq::parse(req)
+                .map_err(|e| anyhow!("invalid kernel version condition ({cond}): {e}")),
+            _ => bail!("invalid kernel version condition ({cond})"),
+        }
+    }
+
+    // Parse a filter made of one or more expressions separated by '||'
+    // (or-group), matching if any of them matches. Expressions after the
+    // first one are introduced by an empty op (see `MetaOp::emit_group`).
+    //
+    // A sample(1/N) gate can be combined with the above using '&&', in which
+    // case it is emitted right after the first target so it is evaluated
+    // before any expression. A gate alone matches 1 out of N packets.
+    //
+    // A kversion() condition can also be combined using '&&'. It is evaluated
+    // against the running kernel when building the filter: if it is not met
+    // the rest of the filter is not built (fields might not exist) and the
+    // filter never matches.
+    pub(crate) fn from_string(fstring: String) -> Result<Self> {
+        let mut ops: Vec<_> = Vec::new();
+        let mut sample = None;
+        let mut kversion = None;
+        let mut filter = None;
+
+        for part in fstring.split(META_AND).map(|p| p.trim()) {
+            if part.starts_with(META_SAMPLE) {
+                if sample.is_some() {
+                    bail!("only one sample gate can be used ({fstring})");
+                }
+                sample = Some(Self::parse_sample(part)?);
+            } else if part.starts_with(META_KVERSION) {
+                if kversion.is_some() {
+                    bail!("only one kversion() condition can be used ({fstring})");
+                }
+                kversion = Some(Self::parse_kversion(part)?);
+            } else if filter.is_some() {
+                bail!("'{META_AND}' can only be used with a sample gate or a kversion() condition ({fstring})");
+            } else {
+                filter = Some(part);
+            }
+        }
+
+        if let Some(req) = kversion {
+            if filter.is_none() && sample.is_none() {
+                bail!("a kversion() condition can't be used alone ({fstring})");
+            }
+            if !req.matches(inspector()?.kernel.version()) {
+                return Ok(FilterMeta(Self::emit_expr(META_REJECT)?));
+            }
+        }
+
+        let filter = match (filter, sample) {
+            (Some(filter), _) => filter,
+            (None, Some(modulus)) => {
+                // Dummy target, as ops must start with one.
+                ops.push(MetaOp::new());
+                ops.push(MetaOp::emit_sample(modulus));
+                return Ok(FilterMeta(ops));
+            }
+            (None, None) => bail!("empty filter"),
+        };
+
+        for (i, expr) in filter.split(META_OR).enumerate() {
+            let expr = expr.trim();
+            if expr.is_empty() {
+                bail!("empty expression in or-group ({fstring})");
+            }
+
+            if i > 0 {
+                ops.push(MetaOp::emit_group());
+            }
+            ops.extend(Self::emit_expr(expr)?);
+        }
+
+        if let Some(modulus) = sample {
+            ops.insert(1, MetaOp::emit_sample(modulus));
+        }
+
+        if ops.len() > META_OPS_MAX as usize {
+            bail!("filter is too complex (max {META_OPS_MAX} operations)");
+        }
+
         Ok(FilterMeta(ops))
     }
 }
@@ -702,6 +1485,20 @@ mod tests {
         assert!(FilterMeta::from_string("sk_buff.dev.pcpu_refcnt == 0xbad".to_string()).is_err());
     }
 
+    #[test]
+    fn meta_offset_bounds() {
+        // Largest reachable offset.
+        let op = MetaOp::emit_load_ptr("sk", u16::MAX as u32 * 8, 0).unwrap();
+        assert_eq!(op.load_ref().offt, u16::MAX);
+
+        // Synthetic deep offset.
+        let err = MetaOp::emit_load_ptr("sk", (u16::MAX as u32 + 1) * 8, 0).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "offset too large for field sk (65536, max 65535)."
+        );
+    }
+
     #[test_case("==" ; "op is eq")]
     #[test_case("!=" ; "op is ne")]
     #[test_case("<" ; "op is lt")]
@@ -835,22 +1632,629 @@ mod tests {
     }
 
     #[test_case("dev.name:~0x00" => matches Err(_); "string failure")]
-    #[test_case("dev:~0x00.mtu" => matches Ok(l) if l == MetaLoad { r#type: PTR_BIT, nmemb: 0, offt: 16, bf_size: 0, mask: !0x00 }; "pointer")]
-    #[test_case("mark:0xff" => matches Ok(l) if l == MetaLoad { r#type: MetaType::Int as u8, nmemb: 0, offt: 168, bf_size: 0, mask: 0xff }; "u32")]
+    #[test_case("dev:~0x00.mtu" => matches Ok(l) if l == MetaLoad { r#type: PTR_BIT, nmemb: 0, offt: 16, bf_size: 0, shift: 0, hash: 0, sub: 0, bitrev: 0, mask: !0x00 }; "pointer")]
+    #[test_case("mark:0xff" => matches Ok(l) if l == MetaLoad { r#type: MetaType::Int as u8, nmemb: 0, offt: 168, bf_size: 0, shift: 0, hash: 0, sub: 0, bitrev: 0, mask: 0xff }; "u32")]
     #[test_case("mark:0x0" => matches Err(_); "zero hex mask failure")]
     #[test_case("mark:~0xffffffffffffffff" => matches Err(_); "bitwise not u64 hex mask failure")]
     #[test_case("mark:0b00" => matches Err(_); "zero bin mask failure")]
     #[test_case("mark:0" => matches Err(_); "mask format failure")]
     #[test_case("headers.skb_iif:0xbad" => matches Err(_); "signed int failure")]
-    #[test_case("pkt_type:0x2" => matches Ok(l) if l == MetaLoad { r#type: MetaType::Char as u8, nmemb: 0, offt: 1024, bf_size: 3, mask: 0x2 }; "unsigned bitfield")]
-    #[test_case("pkt_type:0b10" => matches Ok(l) if l == MetaLoad { r#type: MetaType::Char as u8, nmemb: 0, offt: 1024, bf_size: 3, mask: 0x2 }; "binary unsigned bitfield")]
-    #[test_case("pkt_type:~0b10" => matches Ok(l) if l == MetaLoad { r#type: MetaType::Char as u8, nmemb: 0, offt: 1024, bf_size: 3, mask: !0x2 }; "bitwise not binary unsigned bitfield")]
+    #[test_case("pkt_type:0x2" => matches Ok(l) if l == MetaLoad { r#type: MetaType::Char as u8, nmemb: 0, offt: 1024, bf_size: 3, shift: 0, hash: 0, sub: 0, bitrev: 0, mask: 0x2 }; "unsigned bitfield")]
+    #[test_case("pkt_type:0b10" => matches Ok(l) if l == MetaLoad { r#type: MetaType::Char as u8, nmemb: 0, offt: 1024, bf_size: 3, shift: 0, hash: 0, sub: 0, bitrev: 0, mask: 0x2 }; "binary unsigned bitfield")]
+    #[test_case("pkt_type:~0b10" => matches Ok(l) if l == MetaLoad { r#type: MetaType::Char as u8, nmemb: 0, offt: 1024, bf_size: 3, shift: 0, hash: 0, sub: 0, bitrev: 0, mask: !0x2 }; "bitwise not binary unsigned bitfield")]
+    #[test_case("mark:0o17" => matches Ok(l) if l == MetaLoad { r#type: MetaType::Int as u8, nmemb: 0, offt: 168, bf_size: 0, shift: 0, hash: 0, sub: 0, bitrev: 0, mask: 0xf }; "octal u32")]
+    #[test_case("mark:0o00" => matches Err(_); "zero oct mask failure")]
+    #[test_case("mark:0o8" => matches Err(_); "oct mask format failure")]
+    #[test_case("mark:0xf0>>4" => matches Ok(l) if l == MetaLoad { r#type: MetaType::Int as u8, nmemb: 0, offt: 168, bf_size: 0, shift: 4, hash: 0, sub: 0, bitrev: 0, mask: 0xf0 }; "masked and shifted u32")]
+    #[test_case("mark:0xf0>>0" => matches Err(_); "zero shift failure")]
+    #[test_case("mark:0xf0>>64" => matches Err(_); "too large shift failure")]
+    #[test_case("mark:0xf0>>0x4" => matches Err(_); "shift format failure")]
+    #[test_case("dev:~0x00>>4.mtu" => matches Err(_); "intermediate shift failure")]
     fn meta_filter_masks(expr: &'static str) -> Result<MetaLoad> {
         let filter = FilterMeta::from_string(format!("sk_buff.{expr}").to_string())?;
 
         Ok(filter.0[1].load_ref().clone())
     }
 
+    #[test]
+    fn meta_filter_sign_override() {
+        // Strings can't be interpreted as numbers.
+        assert!(FilterMeta::from_string("sk_buff.dev.name:s == 'dummy0'".to_string()).is_err());
+        // Unsigned members only accept positive values.
+        assert!(FilterMeta::from_string("sk_buff.mark < -1".to_string()).is_err());
+        // Signed members can't be masked.
+        assert!(FilterMeta::from_string("sk_buff.mark:0xff:s < -1".to_string()).is_err());
+
+        let filter = FilterMeta::from_string("sk_buff.mark:s < -1".to_string()).unwrap();
+        assert_eq!(filter.0.len(), 2);
+        let meta_load = filter.0[1].load_ref();
+        assert!(meta_load.is_int());
+        assert!(meta_load.is_signed());
+        assert_eq!(meta_load.offt, 168);
+
+        let meta_target = filter.0[0].target_ref();
+        assert_eq!(meta_target.cmp, MetaCmp::Lt as u8);
+        assert_eq!(meta_target.sz, 4);
+        let target = u64::from_ne_bytes(
+            meta_target.md[..std::mem::size_of::<u64>()]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(target as i64, -1);
+
+        // Signed members can be forced to unsigned, and then masked.
+        let filter =
+            FilterMeta::from_string("sk_buff.headers.skb_iif:0xff:u > 1".to_string()).unwrap();
+        let meta_load = filter.0[1].load_ref();
+        assert!(!meta_load.is_signed());
+        assert_eq!(meta_load.mask, 0xff);
+    }
+
+    #[test]
+    fn meta_filter_mask_shift() {
+        let filter = FilterMeta::from_string("sk_buff.mark:0xf0>>4 == 0x3".to_string()).unwrap();
+        assert_eq!(filter.0.len(), 2);
+
+        let meta_load = filter.0[1].load_ref();
+        assert!(meta_load.is_int());
+        assert_eq!(meta_load.mask, 0xf0);
+        assert_eq!(meta_load.shift, 4);
+
+        let meta_target = filter.0[0].target_ref();
+        assert_eq!(meta_target.cmp, MetaCmp::Eq as u8);
+        let target = u64::from_ne_bytes(
+            meta_target.md[..std::mem::size_of::<u64>()]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(target, 0x3);
+
+        // Shifting can be combined with the signedness override.
+        let filter =
+            FilterMeta::from_string("sk_buff.headers.skb_iif:0xff00>>8:u == 1".to_string())
+                .unwrap();
+        let meta_load = filter.0[1].load_ref();
+        assert!(!meta_load.is_signed());
+        assert_eq!(meta_load.shift, 8);
+    }
+
+    #[test]
+    fn meta_filter_any() {
+        // Fields must share the same width.
+        assert!(
+            FilterMeta::from_string("any(sk_buff.mark, sk_buff.mac_len) == 0x1".to_string())
+                .is_err()
+        );
+        // Number of fields is bounded.
+        assert!(FilterMeta::from_string(
+            "any(sk_buff.mark, sk_buff.priority, sk_buff.hash, sk_buff.len, sk_buff.data_len) == 0x1"
+                .to_string()
+        )
+        .is_err());
+        // Unterminated expression.
+        assert!(
+            FilterMeta::from_string("any(sk_buff.mark, sk_buff.priority == 0x1".to_string())
+                .is_err()
+        );
+
+        let filter =
+            FilterMeta::from_string("any(sk_buff.mark, sk_buff.priority) == 0x1".to_string())
+                .unwrap();
+        // One load per field and one shared target
+        assert_eq!(filter.0.len(), 3);
+        let meta_target = filter.0[0].target_ref();
+        assert_eq!(meta_target.cmp, MetaCmp::Eq as u8);
+        assert_eq!(meta_target.sz, 4);
+        let target = u64::from_ne_bytes(
+            meta_target.md[..std::mem::size_of::<u64>()]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(target, 0x1);
+
+        // 'mark' bits_offset=1344
+        let load = filter.0[1].load_ref();
+        assert!(load.is_int());
+        assert_eq!(load.offt, 168);
+        // 'priority' bits_offset=1120
+        let load = filter.0[2].load_ref();
+        assert!(load.is_int());
+        assert_eq!(load.offt, 140);
+    }
+
+    #[test]
+    fn meta_rval_units() {
+        let dec = |val: &str| Rval::Dec(val.to_string());
+
+        assert!(Rval::from_str("1k").unwrap() == dec("1000"));
+        assert!(Rval::from_str("1ki").unwrap() == dec("1024"));
+        assert!(Rval::from_str("2m").unwrap() == dec("2000000"));
+        assert!(Rval::from_str("2mi").unwrap() == dec("2097152"));
+        assert!(Rval::from_str("1g").unwrap() == dec("1000000000"));
+        assert!(Rval::from_str("1gi").unwrap() == dec("1073741824"));
+        assert!(Rval::from_str("-3k").unwrap() == dec("-3000"));
+        assert!(Rval::from_str("42").unwrap() == dec("42"));
+        // Units only apply to decimal values.
+        assert!(Rval::from_str("0x1k").unwrap() == Rval::Hex("1k".to_string()));
+        assert!(Rval::from_str("'1k'").unwrap() == Rval::Str("1k".to_string()));
+
+        assert!(Rval::from_str("k").is_err());
+        assert!(Rval::from_str("1.5k").is_err());
+        assert!(Rval::from_str("18446744073709551615k").is_err());
+
+        let filter = FilterMeta::from_string("sk_buff.len > 1k".to_string()).unwrap();
+        let meta_target = filter.0[0].target_ref();
+        assert_eq!(meta_target.cmp, MetaCmp::Gt as u8);
+        let target = u64::from_ne_bytes(
+            meta_target.md[..std::mem::size_of::<u64>()]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(target, 1000);
+
+        let filter = FilterMeta::from_string("sk_buff.len > 1ki".to_string()).unwrap();
+        let meta_target = filter.0[0].target_ref();
+        let target = u64::from_ne_bytes(
+            meta_target.md[..std::mem::size_of::<u64>()]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(target, 1024);
+    }
+
+    #[test]
+    fn meta_rval_bases() {
+        assert!(Rval::from_str("0b1010").unwrap() == Rval::Bin("1010".to_string()));
+        assert!(Rval::from_str("0o12").unwrap() == Rval::Oct("12".to_string()));
+
+        // Digits must be valid in the base.
+        assert!(FilterMeta::from_string("sk_buff.mark == 0b102".to_string()).is_err());
+        assert!(FilterMeta::from_string("sk_buff.mark == 0o18".to_string()).is_err());
+        // Only numbers can be expressed in a base.
+        assert!(FilterMeta::from_string("sk_buff.dev.name == 0b1010".to_string()).is_err());
+
+        let filter =
+            FilterMeta::from_string("sk_buff.dev.flags:0b1111 == 0b1010".to_string()).unwrap();
+        assert_eq!(filter.0.len(), 3);
+        assert_eq!(filter.0[2].load_ref().mask, 0xf);
+        let meta_target = filter.0[0].target_ref();
+        let target = u64::from_ne_bytes(
+            meta_target.md[..std::mem::size_of::<u64>()]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(target, 0b1010);
+
+        let filter = FilterMeta::from_string("sk_buff.mark:0o17 == 0o12".to_string()).unwrap();
+        assert_eq!(filter.0[1].load_ref().mask, 0xf);
+        let meta_target = filter.0[0].target_ref();
+        let target = u64::from_ne_bytes(
+            meta_target.md[..std::mem::size_of::<u64>()]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(target, 0o12);
+    }
+
+    #[test]
+    fn meta_filter_cpu() {
+        // cpu() can't be part of a member expression.
+        assert!(FilterMeta::from_string("sk_buff.cpu() == 2".to_string()).is_err());
+        assert!(FilterMeta::from_string("cpu().mark == 2".to_string()).is_err());
+        // cpu() is a number.
+        assert!(FilterMeta::from_string("cpu() == 'eth0'".to_string()).is_err());
+
+        let filter = FilterMeta::from_string("cpu() == 2".to_string()).unwrap();
+        assert_eq!(filter.0.len(), 2);
+        let load = filter.0[1].load_ref();
+        assert_eq!(
+            *load,
+            MetaLoad {
+                r#type: CPU_BIT | MetaType::Int as u8,
+                nmemb: 0,
+                offt: 0,
+                bf_size: 0,
+                shift: 0,
+                hash: 0,
+                sub: 0,
+                bitrev: 0,
+                mask: 0
+            }
+        );
+        assert!(load.is_cpu());
+        assert!(load.is_num());
+
+        let meta_target = filter.0[0].target_ref();
+        assert_eq!(meta_target.cmp, MetaCmp::Eq as u8);
+        assert_eq!(meta_target.sz, 4);
+        let target = u64::from_ne_bytes(
+            meta_target.md[..std::mem::size_of::<u64>()]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(target, 2);
+    }
+
+    #[test]
+    fn meta_filter_or_group() {
+        assert!(FilterMeta::from_string("sk_buff.mark == 1 ||".to_string()).is_err());
+        assert!(FilterMeta::from_string("|| sk_buff.mark == 1".to_string()).is_err());
+
+        let filter = FilterMeta::from_string(
+            "sk_buff.mark == 1 || any(sk_buff.mark, sk_buff.priority) == 0x2".to_string(),
+        )
+        .unwrap();
+        assert_eq!(filter.0.len(), 6);
+
+        let target = |op: &MetaOp| {
+            u64::from_ne_bytes(
+                op.target_ref().md[..std::mem::size_of::<u64>()]
+                    .try_into()
+                    .unwrap(),
+            )
+        };
+        assert_eq!(target(&filter.0[0]), 1);
+        assert!(filter.0[1].load_ref().is_int());
+        // Empty op starting the second group, followed by its target.
+        assert_eq!(filter.0[2].load_ref().r#type, 0);
+        assert_eq!(target(&filter.0[3]), 2);
+        assert!(filter.0[4].load_ref().is_int());
+        assert!(filter.0[5].load_ref().is_int());
+
+        // Groups don't have to share the same type.
+        assert!(FilterMeta::from_string(
+            "sk_buff.mark == 1 || sk_buff.dev.name == 'eth0'".to_string()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn meta_filter_sample() {
+        assert!(FilterMeta::from_string("sample(2/100)".to_string()).is_err());
+        assert!(FilterMeta::from_string("sample(1/0)".to_string()).is_err());
+        assert!(FilterMeta::from_string("sample(1/-1)".to_string()).is_err());
+        assert!(FilterMeta::from_string("sample(1/100".to_string()).is_err());
+        assert!(FilterMeta::from_string("sample(1/10) && sample(1/10)".to_string()).is_err());
+        // && can only be used with a sample gate.
+        assert!(
+            FilterMeta::from_string("sk_buff.mark == 1 && sk_buff.mark == 2".to_string()).is_err()
+        );
+        assert!(FilterMeta::from_string("sk_buff.mark == 1 &&".to_string()).is_err());
+
+        // Gate alone.
+        let filter = FilterMeta::from_string("sample(1/100)".to_string()).unwrap();
+        assert_eq!(filter.0.len(), 2);
+        let load = filter.0[1].load_ref();
+        assert!(load.is_sample());
+        assert!(!load.is_num());
+        assert_eq!(load.mask, 100);
+
+        // Gate combined with an or-group, always emitted after the first
+        // target.
+        for fstring in [
+            "sk_buff.mark == 1 || sk_buff.mark == 2 && sample(1/1k)",
+            "sample(1/1000) && sk_buff.mark == 1 || sk_buff.mark == 2",
+        ] {
+            let filter = FilterMeta::from_string(fstring.to_string()).unwrap();
+            assert_eq!(filter.0.len(), 6);
+            let load = filter.0[1].load_ref();
+            assert!(load.is_sample());
+            assert_eq!(load.mask, 1000);
+            assert!(filter.0[2].load_ref().is_int());
+            assert!(!filter.0[2].load_ref().is_sample());
+            assert_eq!(filter.0[3].load_ref().r#type, 0);
+        }
+    }
+
+    #[test]
+    fn meta_filter_kversion() {
+        assert!(FilterMeta::from_string("kversion() >= 6.1".to_string()).is_err());
+        assert!(FilterMeta::from_string("kversion() && sk_buff.mark == 1".to_string()).is_err());
+        assert!(
+            FilterMeta::from_string("kversion() 6.1 && sk_buff.mark == 1".to_string()).is_err()
+        );
+        assert!(FilterMeta::from_string(
+            "kversion() >= 6.1 && kversion() < 7 && sk_buff.mark == 1".to_string()
+        )
+        .is_err());
+
+        // Unit tests run against a fake 6.2.14 kernel. A satisfied condition
+        // does not change the filter.
+        let plain = FilterMeta::from_string("sk_buff.mark == 1".to_string()).unwrap();
+        for fstring in [
+            "kversion() >= 6.1 && sk_buff.mark == 1",
+            "sk_buff.mark == 1 && kversion() >= 6, < 6.3",
+        ] {
+            let filter = FilterMeta::from_string(fstring.to_string()).unwrap();
+            assert_eq!(filter.0.len(), plain.0.len());
+            assert_eq!(filter.0[1].load_ref(), plain.0[1].load_ref());
+            assert_eq!(filter.0[0].target_ref().md, plain.0[0].target_ref().md);
+        }
+
+        // Otherwise the filter never matches, and the rest of it is not even
+        // built.
+        let reject = FilterMeta::from_string(META_REJECT.to_string()).unwrap();
+        for fstring in [
+            "kversion() >= 6.3 && sk_buff.mark == 1",
+            "kversion() < 6 && sk_buff.non_existent == 1",
+        ] {
+            let filter = FilterMeta::from_string(fstring.to_string()).unwrap();
+            assert_eq!(filter.0.len(), 2);
+            assert_eq!(filter.0[1].load_ref(), reject.0[1].load_ref());
+            assert!(filter.0[1].load_ref().is_cpu());
+            assert_eq!(filter.0[0].target_ref().cmp, MetaCmp::Lt as u8);
+        }
+    }
+
+    #[test]
+    fn meta_filter_abs() {
+        // abs() only applies to signed numeric members.
+        assert!(FilterMeta::from_string("abs(sk_buff.mark) > 5".to_string()).is_err());
+        assert!(FilterMeta::from_string("abs(sk_buff.dev.name) == 'eth0'".to_string()).is_err());
+        assert!(FilterMeta::from_string("abs(cpu()) > 5".to_string()).is_err());
+        assert!(FilterMeta::from_string("abs(sk_buff.headers.skb_iif > 5".to_string()).is_err());
+        assert!(
+            FilterMeta::from_string("abs(abs(sk_buff.headers.skb_iif)) > 5".to_string()).is_err()
+        );
+
+        let filter =
+            FilterMeta::from_string("abs(sk_buff.headers.skb_iif) > 5".to_string()).unwrap();
+        assert_eq!(filter.0.len(), 2);
+        let load = filter.0[1].load_ref();
+        assert!(load.is_abs());
+        assert!(load.is_signed());
+        assert!(load.is_int());
+
+        let meta_target = filter.0[0].target_ref();
+        assert_eq!(meta_target.cmp, MetaCmp::Gt as u8);
+        assert_eq!(meta_target.sz, 4);
+        let target = u64::from_ne_bytes(
+            meta_target.md[..std::mem::size_of::<u64>()]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(target, 5);
+
+        // Unsigned members can be forced to signed.
+        let filter = FilterMeta::from_string("abs(sk_buff.mark:s) > 5".to_string()).unwrap();
+        assert!(filter.0[1].load_ref().is_abs());
+        let filter = FilterMeta::from_string("sk_buff.headers.skb_iif > 5".to_string()).unwrap();
+        assert!(!filter.0[1].load_ref().is_abs());
+    }
+
+    // Reverse the bits of a value over a width, in bytes. Please keep in sync
+    // with its eBPF counterpart.
+    fn bitrev(val: u64, size: u8) -> u64 {
+        val.reverse_bits() >> (64 - size as u32 * 8)
+    }
+
+    #[test]
+    fn meta_filter_bitrev() {
+        // bitrev() only applies to unsigned numeric members with a width.
+        assert!(FilterMeta::from_string("bitrev(sk_buff.dev.name) == 'eth0'".to_string()).is_err());
+        assert!(
+            FilterMeta::from_string("bitrev(sk_buff.headers.skb_iif) == 1".to_string()).is_err()
+        );
+        assert!(FilterMeta::from_string("bitrev(sk_buff.pkt_type) == 1".to_string()).is_err());
+        assert!(FilterMeta::from_string("bitrev(cpu()) == 1".to_string()).is_err());
+        assert!(FilterMeta::from_string("bitrev(sk_buff.mark == 1".to_string()).is_err());
+        assert!(FilterMeta::from_string("bitrev(bitrev(sk_buff.mark)) == 1".to_string()).is_err());
+
+        // The value is reversed over the width of the member, before being
+        // compared to the target as-is.
+        let filter =
+            FilterMeta::from_string("bitrev(sk_buff.mark) == 0x80000000".to_string()).unwrap();
+        assert_eq!(filter.0.len(), 2);
+        let load = filter.0[1].load_ref();
+        assert!(load.is_bitrev());
+        assert!(load.is_int());
+        assert!(!load.is_signed());
+        assert_eq!(load.offt, 168);
+
+        let meta_target = filter.0[0].target_ref();
+        assert_eq!(meta_target.cmp, MetaCmp::Eq as u8);
+        assert_eq!(meta_target.sz, 4);
+        let target = u64::from_ne_bytes(
+            meta_target.md[..std::mem::size_of::<u64>()]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(target, 0x80000000);
+        // A mark of 1 matches.
+        assert_eq!(bitrev(1, meta_target.sz), target);
+        assert_eq!(bitrev(0x1234, 2), 0x2c48);
+        assert_eq!(bitrev(0x80, 1), 0x1);
+
+        // Explicit widths and signedness overrides are honoured.
+        let filter =
+            FilterMeta::from_string("bitrev(sk_buff.mark@+2:u16) == 0x8000".to_string()).unwrap();
+        let load = filter.0[1].load_ref();
+        assert!(load.is_bitrev());
+        assert!(load.is_short());
+        assert!(
+            FilterMeta::from_string("bitrev(sk_buff.mark@+2:s16) == 0x8000".to_string()).is_err()
+        );
+        assert!(
+            FilterMeta::from_string("bitrev(sk_buff.headers.skb_iif:u) == 1".to_string())
+                .unwrap()
+                .0[1]
+                .load_ref()
+                .is_bitrev()
+        );
+
+        let filter = FilterMeta::from_string("sk_buff.mark == 1".to_string()).unwrap();
+        assert!(!filter.0[1].load_ref().is_bitrev());
+    }
+
+    #[test]
+    fn meta_filter_hash() {
+        // Reference FNV-1a values.
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+
+        // hash() only applies to string members and targets.
+        assert!(FilterMeta::from_string("hash(sk_buff.mark) == 'eth0'".to_string()).is_err());
+        assert!(FilterMeta::from_string("hash(cpu()) == 'eth0'".to_string()).is_err());
+        assert!(FilterMeta::from_string("hash(sk_buff.dev.name) == 0x1".to_string()).is_err());
+        assert!(FilterMeta::from_string("hash(sk_buff.dev.name) > 'eth0'".to_string()).is_err());
+        assert!(FilterMeta::from_string("hash(sk_buff.dev.name == 'eth0'".to_string()).is_err());
+        assert!(
+            FilterMeta::from_string("hash(hash(sk_buff.dev.name)) == 'eth0'".to_string()).is_err()
+        );
+        // The target can't be larger than the array.
+        assert!(
+            FilterMeta::from_string(format!("hash(sk_buff.dev.name) == '{}'", "a".repeat(16)))
+                .is_err()
+        );
+        assert!(
+            FilterMeta::from_string(format!("hash(sk_buff.dev.name) == '{}'", "a".repeat(15)))
+                .is_ok()
+        );
+
+        let filter =
+            FilterMeta::from_string("hash(sk_buff.dev.name) == 'foobar'".to_string()).unwrap();
+        assert_eq!(filter.0.len(), 3);
+        assert!(!filter.0[1].load_ref().is_hash());
+        let load = filter.0[2].load_ref();
+        assert!(load.is_hash());
+        assert!(load.is_byte());
+        assert_eq!(load.nmemb, 16);
+
+        let meta_target = filter.0[0].target_ref();
+        assert_eq!(meta_target.cmp, MetaCmp::Eq as u8);
+        assert_eq!(meta_target.sz, 8);
+        let target = u64::from_ne_bytes(
+            meta_target.md[..std::mem::size_of::<u64>()]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(target, 0x85944171f73967e8);
+
+        let filter = FilterMeta::from_string("sk_buff.dev.name == 'foobar'".to_string()).unwrap();
+        assert!(!filter.0[2].load_ref().is_hash());
+    }
+
+    #[test]
+    fn meta_filter_diff() {
+        // Both members must be numbers of the same type and width.
+        assert!(
+            FilterMeta::from_string("(sk_buff.len - sk_buff.mac_len) > 1000".to_string()).is_err()
+        );
+        assert!(
+            FilterMeta::from_string("(sk_buff.len - sk_buff.dev.name) > 1000".to_string()).is_err()
+        );
+        assert!(FilterMeta::from_string("(sk_buff.len - cpu()) > 1000".to_string()).is_err());
+        assert!(
+            FilterMeta::from_string("(sk_buff.len:0xff - sk_buff.data_len) > 10".to_string())
+                .is_err()
+        );
+        assert!(
+            FilterMeta::from_string("(sk_buff.len:s - sk_buff.data_len) > 10".to_string()).is_err()
+        );
+        // Malformed expressions.
+        assert!(
+            FilterMeta::from_string("(sk_buff.len - sk_buff.data_len > 10".to_string()).is_err()
+        );
+        assert!(
+            FilterMeta::from_string("(sk_buff.len + sk_buff.data_len) > 10".to_string()).is_err()
+        );
+        assert!(FilterMeta::from_string("(sk_buff.len) > 10".to_string()).is_err());
+
+        let filter =
+            FilterMeta::from_string("(sk_buff.len - sk_buff.data_len) > 1000".to_string()).unwrap();
+        // Two loads and the target the difference is compared to.
+        assert_eq!(filter.0.len(), 3);
+        let minuend = filter.0[1].load_ref();
+        assert!(minuend.is_sub());
+        assert!(minuend.is_int());
+        let subtrahend = filter.0[2].load_ref();
+        assert!(!subtrahend.is_sub());
+        assert_eq!(subtrahend.r#type, minuend.r#type);
+
+        let meta_target = filter.0[0].target_ref();
+        assert_eq!(meta_target.cmp, MetaCmp::Gt as u8);
+        assert_eq!(meta_target.sz, 4);
+        let target = u64::from_ne_bytes(
+            meta_target.md[..std::mem::size_of::<u64>()]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(target, 1000);
+
+        // Signed differences can be compared to negative values.
+        let filter =
+            FilterMeta::from_string("(sk_buff.len:s - sk_buff.data_len:s) < -1".to_string())
+                .unwrap();
+        assert!(filter.0[1].load_ref().is_sub());
+        assert!(filter.0[2].load_ref().is_signed());
+    }
+
+    #[test]
+    fn meta_filter_adjust() {
+        // 'mark' bits_offset=1344
+        let filter = FilterMeta::from_string("sk_buff.mark@-4:u32 == 1".to_string()).unwrap();
+        assert_eq!(filter.0.len(), 2);
+        let load = filter.0[1].load_ref();
+        assert!(load.is_int());
+        assert!(!load.is_signed());
+        assert_eq!(load.offt, 164);
+        assert_eq!(filter.0[0].target_ref().sz, 4);
+
+        let filter = FilterMeta::from_string("sk_buff.mark@+2:s16 < 0".to_string()).unwrap();
+        let load = filter.0[1].load_ref();
+        assert!(load.is_short());
+        assert!(load.is_signed());
+        assert_eq!(load.offt, 170);
+
+        // An explicit, valid width is required.
+        assert!(FilterMeta::from_string("sk_buff.mark@-4 == 1".to_string()).is_err());
+        assert!(FilterMeta::from_string("sk_buff.mark@-4:u24 == 1".to_string()).is_err());
+        // The offset must be signed.
+        assert!(FilterMeta::from_string("sk_buff.mark@4:u32 == 1".to_string()).is_err());
+        // Only the leaf member can be adjusted.
+        assert!(FilterMeta::from_string("sk_buff.dev@-8:u64.mtu == 1".to_string()).is_err());
+        // Loads out of the struct are rejected.
+        let err = FilterMeta::from_string("sk_buff.next@-4:u32 == 1".to_string()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "offset adjustment of next (-4) is out of the bounds of its struct."
+        );
+        assert!(FilterMeta::from_string("sk_buff.mark@+4096:u32 == 1".to_string()).is_err());
+    }
+
+    #[test]
+    fn meta_filter_btf_source() {
+        assert!(FilterMeta::from_string("sk_buff@vmlinux.mark == 1".to_string()).is_ok());
+        // sk_buff is not defined in the openvswitch module.
+        assert!(FilterMeta::from_string("sk_buff@openvswitch.mark == 1".to_string()).is_err());
+
+        // struct module is defined in both vmlinux and the openvswitch
+        // module: casts to it must be qualified.
+        let err = FilterMeta::from_string("sk_buff._nfct:~0x0:module.state == 1".to_string())
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("module@vmlinux, module@openvswitch"));
+
+        for source in ["vmlinux", "openvswitch"] {
+            let filter =
+                FilterMeta::from_string(format!("sk_buff._nfct:~0x0:module@{source}.state == 1"))
+                    .unwrap();
+            assert_eq!(filter.0.len(), 3);
+            // STRUCT 'module' size=960 vlen=?
+            //   'state' type_id=? bits_offset=0
+            assert_eq!(filter.0[2].load_ref().offt, 0);
+        }
+        assert!(
+            FilterMeta::from_string("sk_buff._nfct:~0x0:module@zram.state == 1".to_string())
+                .is_err()
+        );
+
+        // Adjustments are still parsed on the leaf member.
+        assert!(FilterMeta::from_string("sk_buff@vmlinux.mark@-4:u32 == 1".to_string()).is_ok());
+    }
+
     #[test]
     fn meta_filter_cast() {
         // Casting a field smaller than a pointer is not allowed
@@ -887,6 +2291,10 @@ mod tests {
                 nmemb: 0,
                 offt: 104,
                 bf_size: 0,
+                shift: 0,
+                hash: 0,
+                sub: 0,
+                bitrev: 0,
                 mask: !0
             }
         );
@@ -900,6 +2308,10 @@ mod tests {
                 nmemb: 0,
                 offt: 168,
                 bf_size: 0,
+                shift: 0,
+                hash: 0,
+                sub: 0,
+                bitrev: 0,
                 mask: 0
             }
         );
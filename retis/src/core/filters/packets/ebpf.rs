@@ -91,8 +91,19 @@ impl eBpfProg {
         self.0.iter().flat_map(|insn| insn.to_vec()).collect()
     }
 
+    /// Render the program in a human readable form, one instruction per line
+    /// prefixed by its index.
+    pub(crate) fn disasm(&self) -> String {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, insn)| format!("{i:4}: {insn}\n"))
+            .collect()
+    }
+
+    /// Print the program as disassembled by rbpf, to cross-check `disasm()`.
     #[cfg(feature = "debug")]
-    pub(crate) fn disasm(&self) {
+    pub(crate) fn rbpf_disasm(&self) {
         disassemble(&self.to_bytes());
     }
 
@@ -1,5 +1,5 @@
 #![allow(dead_code, non_camel_case_types)]
-use std::vec;
+use std::{fmt, vec};
 
 use crate::core::{
     bpf_sys,
@@ -310,3 +310,114 @@ impl eBpfInsn {
         byte_insn
     }
 }
+
+// Render an offset, e.g. "+0x10" or "-0x8".
+fn fmt_off(off: i16) -> String {
+    match off < 0 {
+        true => format!("-{:#x}", off.unsigned_abs()),
+        false => format!("+{off:#x}"),
+    }
+}
+
+// Render instructions using the usual eBPF assembly mnemonics, e.g.
+// "ldxh r0, [r1+0xc]" or "jeq r0, 0x800, +3".
+impl fmt::Display for eBpfInsn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let class = self.code & 0x07;
+        let size = match self.code & 0x18 {
+            bpf_sys::BPF_W => "w",
+            bpf_sys::BPF_H => "h",
+            bpf_sys::BPF_B => "b",
+            _ => "dw",
+        };
+        let src = match self.code & bpf_sys::BPF_X {
+            0 => format!("{:#x}", self.imm),
+            _ => format!("r{}", self.src),
+        };
+
+        match class {
+            bpf_sys::BPF_ALU | bpf_sys::BPF_ALU64 => {
+                let width = match class {
+                    bpf_sys::BPF_ALU => "32",
+                    _ => "64",
+                };
+                let op = match self.code & 0xf0 {
+                    bpf_sys::BPF_ADD => "add",
+                    bpf_sys::BPF_SUB => "sub",
+                    bpf_sys::BPF_MUL => "mul",
+                    bpf_sys::BPF_DIV => "div",
+                    bpf_sys::BPF_OR => "or",
+                    bpf_sys::BPF_AND => "and",
+                    bpf_sys::BPF_LSH => "lsh",
+                    bpf_sys::BPF_RSH => "rsh",
+                    bpf_sys::BPF_MOD => "mod",
+                    bpf_sys::BPF_XOR => "xor",
+                    bpf_sys::BPF_MOV => "mov",
+                    bpf_sys::BPF_ARSH => "arsh",
+                    bpf_sys::BPF_NEG => return write!(f, "neg{width} r{}", self.dst),
+                    bpf_sys::BPF_END => {
+                        let order = match self.code & bpf_sys::BPF_TO_BE {
+                            0 => "le",
+                            _ => "be",
+                        };
+                        return write!(f, "{order}{} r{}", self.imm, self.dst);
+                    }
+                    _ => return write!(f, "unknown {:#04x}", self.code),
+                };
+                write!(f, "{op}{width} r{}, {src}", self.dst)
+            }
+            bpf_sys::BPF_JMP | bpf_sys::BPF_JMP32 => {
+                let width = match class {
+                    bpf_sys::BPF_JMP32 => "32",
+                    _ => "",
+                };
+                let op = match self.code & 0xf0 {
+                    bpf_sys::BPF_JA => return write!(f, "ja {:+}", self.off),
+                    bpf_sys::BPF_CALL => return write!(f, "call {:#x}", self.imm),
+                    bpf_sys::BPF_EXIT => return write!(f, "exit"),
+                    bpf_sys::BPF_JEQ => "jeq",
+                    bpf_sys::BPF_JGT => "jgt",
+                    bpf_sys::BPF_JGE => "jge",
+                    bpf_sys::BPF_JSET => "jset",
+                    bpf_sys::BPF_JNE => "jne",
+                    bpf_sys::BPF_JSGT => "jsgt",
+                    bpf_sys::BPF_JSGE => "jsge",
+                    bpf_sys::BPF_JLT => "jlt",
+                    bpf_sys::BPF_JLE => "jle",
+                    bpf_sys::BPF_JSLT => "jslt",
+                    bpf_sys::BPF_JSLE => "jsle",
+                    _ => return write!(f, "unknown {:#04x}", self.code),
+                };
+                write!(f, "{op}{width} r{}, {src}, {:+}", self.dst, self.off)
+            }
+            bpf_sys::BPF_LD => match self.code & 0xe0 {
+                bpf_sys::BPF_ABS => write!(f, "ldabs{size} {:#x}", self.imm),
+                bpf_sys::BPF_IND => write!(f, "ldind{size} r{}, {:#x}", self.src, self.imm),
+                bpf_sys::BPF_IMM => write!(f, "ld{size} r{}, {:#x}", self.dst, self.imm),
+                _ => write!(f, "unknown {:#04x}", self.code),
+            },
+            bpf_sys::BPF_LDX => write!(
+                f,
+                "ldx{size} r{}, [r{}{}]",
+                self.dst,
+                self.src,
+                fmt_off(self.off)
+            ),
+            bpf_sys::BPF_ST => write!(
+                f,
+                "st{size} [r{}{}], {:#x}",
+                self.dst,
+                fmt_off(self.off),
+                self.imm
+            ),
+            bpf_sys::BPF_STX => write!(
+                f,
+                "stx{size} [r{}{}], r{}",
+                self.dst,
+                fmt_off(self.off),
+                self.src
+            ),
+            _ => write!(f, "unknown {:#04x}", self.code),
+        }
+    }
+}
@@ -6,9 +6,14 @@
 //! program using libpcap, the resulting output gets then converted to
 //! ebpf and returned for being consumed.
 
-use std::mem;
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+    sync::Mutex,
+};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use once_cell::sync::Lazy;
 use pcap::{Capture, Linktype};
 
 use super::ebpfinsn::{eBpfInsn, MovInfo};
@@ -21,35 +26,105 @@ use crate::{
     },
 };
 
+/// Layer at which packets are matched by a filter, i.e. the header the
+/// packets given to the filter start with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum FilterLayer {
+    /// Packets start with their Ethernet header.
+    L2,
+    /// Packets start with their network (IPv4 or IPv6) header.
+    L3,
+}
+
+impl FilterLayer {
+    /// Link type used to compile filters at this layer.
+    fn linktype(self) -> Linktype {
+        match self {
+            FilterLayer::L2 => Linktype::ETHERNET,
+            FilterLayer::L3 => Linktype(12), // DLT_RAW
+        }
+    }
+
+    /// Filter type the probes use to identify filters at this layer.
+    pub(crate) fn filter_type(self) -> packet_filter_uapi::filter_type {
+        match self {
+            FilterLayer::L2 => packet_filter_uapi::FILTER_L2,
+            FilterLayer::L3 => packet_filter_uapi::FILTER_L3,
+        }
+    }
+}
+
+/// Compile a pcap-filter expression matching packets at the given layer to
+/// an eBPF program.
+fn compile(expr: &str, layer: FilterLayer) -> Result<eBpfProg> {
+    let bpf_capture = Capture::dead(layer.linktype())?;
+    let program = bpf_capture
+        .compile(expr, true)
+        .with_context(|| format!("Could not compile the filter '{expr}'"))?;
+    let insns = program.get_instructions();
+    let filter =
+        BpfProg::try_from(unsafe { mem::transmute::<&[pcap::BpfInstruction], &[u8]>(insns) })?;
+
+    let ebpf_filter = eBpfProg::try_from(filter)?;
+    if ebpf_filter.len() > packet_filter_uapi::FILTER_MAX_INSNS as usize {
+        bail!("Filter exceeds the maximum allowed size.");
+    }
+
+    Ok(ebpf_filter)
+}
+
+/// Maximum number of compiled filters kept in the cache.
+const FILTER_CACHE_SIZE: usize = 64;
+
+/// Cache of compiled filters, keyed by expression and layer. Once full, the
+/// oldest entry is evicted. Failed compilations are not cached.
+#[derive(Default)]
+struct FilterCache {
+    filters: HashMap<(String, FilterLayer), eBpfProg>,
+    /// Cached keys, oldest first.
+    order: VecDeque<(String, FilterLayer)>,
+    /// Number of filters retrieved from the cache.
+    hits: u64,
+}
+
+impl FilterCache {
+    fn get_or_compile(&mut self, expr: &str, layer: FilterLayer) -> Result<eBpfProg> {
+        let key = (expr.to_string(), layer);
+        if let Some(prog) = self.filters.get(&key) {
+            self.hits += 1;
+            return Ok(prog.clone());
+        }
+
+        let prog = compile(expr, layer)?;
+        if self.order.len() >= FILTER_CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.filters.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.clone());
+        self.filters.insert(key, prog.clone());
+
+        Ok(prog)
+    }
+}
+
+static FILTER_CACHE: Lazy<Mutex<FilterCache>> = Lazy::new(|| Mutex::new(FilterCache::default()));
+
 #[derive(Clone)]
 pub(crate) struct FilterPacket(eBpfProg);
 
 impl FilterPacket {
-    pub(crate) fn from_string_opt(
-        fstring: String,
-        layer_type: packet_filter_uapi::filter_type,
-    ) -> Result<Self> {
-        let link_type = match layer_type {
-            packet_filter_uapi::FILTER_L3 => Linktype(12), // DLT_RAW
-            packet_filter_uapi::FILTER_L2 => Linktype::ETHERNET,
-            _ => bail!("Unsupported filter type"),
-        };
-
-        let bpf_capture = Capture::dead(link_type)?;
-        let program = match bpf_capture.compile(fstring.as_str(), true) {
-            Ok(program) => program,
-            Err(e) => bail!("Could not compile the filter: {e}"),
-        };
-        let insns = program.get_instructions();
-        let filter =
-            BpfProg::try_from(unsafe { mem::transmute::<&[pcap::BpfInstruction], &[u8]>(insns) })?;
-
-        let ebpf_filter = eBpfProg::try_from(filter)?;
-        if ebpf_filter.len() > packet_filter_uapi::FILTER_MAX_INSNS as usize {
-            bail!("Filter exceeds the maximum allowed size.");
-        }
-
-        Ok(FilterPacket(ebpf_filter))
+    /// Compile a pcap-filter expression matching packets at the given layer.
+    /// Compilation errors reported by libpcap are kept as the error source.
+    /// Compiled filters are cached, so building the same filter again is
+    /// cheap.
+    pub(crate) fn from_string(expr: &str, layer: FilterLayer) -> Result<Self> {
+        Ok(FilterPacket(
+            FILTER_CACHE
+                .lock()
+                .map_err(|e| anyhow!("Could not access the filter cache: {e}"))?
+                .get_or_compile(expr, layer)?,
+        ))
     }
 
     // Generate an empty eBPF filter containing only a single nop
@@ -65,7 +140,93 @@ impl FilterPacket {
         FilterPacket(ebpf_filter)
     }
 
+    /// Disassemble the compiled filter, e.g. to check what a pcap-filter
+    /// expression was compiled to.
+    pub(crate) fn disasm(&self) -> String {
+        self.0.disasm()
+    }
+
     pub(crate) fn to_bytes(&self) -> Result<Vec<u8>> {
         Ok(self.0.to_bytes())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_from_string() {
+        let l3 = FilterPacket::from_string("ip", FilterLayer::L3).unwrap();
+        let l2 = FilterPacket::from_string("tcp port 80", FilterLayer::L2).unwrap();
+        assert!(!l3.to_bytes().unwrap().is_empty());
+        assert!(l2.to_bytes().unwrap().len() > l3.to_bytes().unwrap().len());
+
+        // Link-layer expressions can't be compiled at L3.
+        assert!(
+            FilterPacket::from_string("ether host 00:11:22:33:44:55", FilterLayer::L3).is_err()
+        );
+
+        // The libpcap error is preserved.
+        let err = FilterPacket::from_string("tcp port", FilterLayer::L2).unwrap_err();
+        assert!(err.to_string().contains("tcp port"));
+        assert!(err.downcast_ref::<pcap::Error>().is_some());
+    }
+
+    #[test]
+    fn filter_disasm() {
+        let filter = FilterPacket::from_string("ip", FilterLayer::L2).unwrap();
+        let disasm = filter.disasm();
+
+        // One line per instruction.
+        assert_eq!(disasm.lines().count() * 8, filter.to_bytes().unwrap().len());
+        // The ethertype is compared to ETH_P_IP.
+        assert!(disasm.lines().any(|l| l.contains("0x800")));
+        assert!(disasm.contains("jeq") || disasm.contains("jne"));
+        assert!(disasm.lines().last().unwrap().ends_with("exit"));
+
+        assert_eq!(
+            FilterPacket::reject_filter().disasm(),
+            "   0: mov32 r0, 0x0\n"
+        );
+    }
+
+    #[test]
+    fn filter_cache() {
+        let mut cache = FilterCache::default();
+
+        let first = cache
+            .get_or_compile("tcp port 80", FilterLayer::L2)
+            .unwrap();
+        assert_eq!(cache.hits, 0);
+        let second = cache
+            .get_or_compile("tcp port 80", FilterLayer::L2)
+            .unwrap();
+        assert_eq!(cache.hits, 1);
+        assert_eq!(first.to_bytes(), second.to_bytes());
+
+        // The layer is part of the key.
+        cache
+            .get_or_compile("tcp port 80", FilterLayer::L3)
+            .unwrap();
+        assert_eq!(cache.hits, 1);
+
+        // Errors are not cached.
+        assert!(cache.get_or_compile("tcp port", FilterLayer::L2).is_err());
+        assert!(cache.get_or_compile("tcp port", FilterLayer::L2).is_err());
+        assert_eq!(cache.hits, 1);
+
+        // The cache is bounded, the oldest entries are evicted first.
+        (0..FILTER_CACHE_SIZE).for_each(|i| {
+            cache
+                .get_or_compile(&format!("udp port {i}"), FilterLayer::L2)
+                .unwrap();
+        });
+        assert_eq!(cache.filters.len(), FILTER_CACHE_SIZE);
+        assert!(!cache
+            .filters
+            .contains_key(&("tcp port 80".to_string(), FilterLayer::L2)));
+        cache.get_or_compile("udp port 0", FilterLayer::L2).unwrap();
+        assert_eq!(cache.hits, 2);
+    }
+}
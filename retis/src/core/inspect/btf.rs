@@ -1,43 +1,214 @@
-use std::fs;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use anyhow::{anyhow, bail, Result};
 use btf_rs::{Btf, Type};
+use log::{debug, warn};
+use once_cell::sync::OnceCell;
 
-use super::BASE_TEST_DIR;
+use super::{
+    btf_cache::{BtfCache, EnumValues, Prototype},
+    BASE_TEST_DIR,
+};
 use crate::core::kernel::Symbol;
 
+/// Kind of a function parameter, as needed to decode its raw value.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ParamKind {
+    /// Integer of the given size, in bytes.
+    Int { size: usize, signed: bool },
+    /// Enum, with the names of its values.
+    Enum {
+        signed: bool,
+        values: HashMap<u64, String>,
+    },
+    /// Pointer or any other type we don't decode, reported as a raw value.
+    Raw,
+}
+
 /// Btf provides multi-module Btf lookups.
+///
+/// The BTF files are only parsed when first needed, so that runs getting
+/// everything they need from the cache don't pay for it.
 pub(crate) struct BtfInfo {
+    /// Path of the main Btf file (vmlinux).
+    vmlinux: PathBuf,
+    /// Paths of the extra Btf files (modules), alongside their module name.
+    modules: Vec<(String, PathBuf)>,
+    /// Parsed Btf objects.
+    parsed: OnceCell<ParsedBtf>,
+    /// Cache of the data derived from the above, if enabled.
+    cache: Option<BtfCache>,
+}
+
+/// Parsed Btf objects.
+struct ParsedBtf {
     /// Main Btf object (vmlinux).
     vmlinux: Btf,
-    /// Extra Btf objects (modules).
-    modules: Vec<Btf>,
+    /// Extra Btf objects (modules), alongside their module name.
+    modules: Vec<(String, Btf)>,
 }
 
 impl BtfInfo {
-    /// Parse kernel BTF files and create a Btf object.
+    /// Find the kernel BTF files and create a Btf object.
     pub(super) fn new() -> Result<BtfInfo> {
-        let vmlinux = match cfg!(test) || cfg!(feature = "benchmark") {
-            false => "/sys/kernel/btf/vmlinux".to_owned(),
-            true => BASE_TEST_DIR.to_owned() + "/test_data/vmlinux",
-        };
+        let (vmlinux, modules) = Self::files()?;
 
-        let vmlinux = Btf::from_file(vmlinux.clone())
-            .map_err(|e| anyhow!("Could not open {vmlinux}: {e}"))?;
-
-        // Load module btf files if possible.
-        let modules = match cfg!(test) || cfg!(feature = "benchmark") {
-            false => fs::read_dir("/sys/kernel/btf")?
-                .filter(|f| f.is_ok() && f.as_ref().unwrap().file_name().ne("vmlinux"))
-                .map(|f| Btf::from_split_file(f.as_ref().unwrap().path(), &vmlinux))
-                .collect::<Result<Vec<Btf>>>()?,
-            true => vec![Btf::from_split_file(
-                BASE_TEST_DIR.to_owned() + "/test_data/openvswitch",
-                &vmlinux,
-            )?],
-        };
+        if !vmlinux.exists() {
+            bail!("Could not open {}", vmlinux.display());
+        }
+
+        let modules = modules
+            .into_iter()
+            .filter_map(|path| Some((path.file_name()?.to_string_lossy().to_string(), path)))
+            .collect();
+
+        Ok(BtfInfo {
+            vmlinux,
+            modules,
+            parsed: OnceCell::new(),
+            cache: None,
+        })
+    }
+
+    /// Get the parsed Btf objects, parsing the BTF files on first use.
+    fn btf(&self) -> Result<&ParsedBtf> {
+        self.parsed.get_or_try_init(|| {
+            let start = Instant::now();
+
+            let vmlinux = Btf::from_file(&self.vmlinux)
+                .map_err(|e| anyhow!("Could not open {}: {e}", self.vmlinux.display()))?;
+
+            // Load module btf files if possible. Modules whose BTF can't be
+            // parsed are skipped, their types won't be resolvable.
+            let modules = self
+                .modules
+                .iter()
+                .filter_map(|(name, path)| match Btf::from_split_file(path, &vmlinux) {
+                    Ok(btf) => Some((name.clone(), btf)),
+                    Err(e) => {
+                        warn!("Could not load BTF for module {name}: {e}");
+                        None
+                    }
+                })
+                .collect();
+
+            debug!("Parsed BTF in {}ms", start.elapsed().as_millis());
+            Ok(ParsedBtf { vmlinux, modules })
+        })
+    }
+
+    /// Paths of the BTF files to load: vmlinux and the modules ones.
+    fn files() -> Result<(PathBuf, Vec<PathBuf>)> {
+        Ok(match cfg!(test) || cfg!(feature = "benchmark") {
+            false => (
+                PathBuf::from("/sys/kernel/btf/vmlinux"),
+                fs::read_dir("/sys/kernel/btf")?
+                    .filter_map(|f| f.ok().map(|f| f.path()))
+                    .filter(|path| !path.ends_with("vmlinux"))
+                    .collect(),
+            ),
+            true => (
+                PathBuf::from(BASE_TEST_DIR.to_owned() + "/test_data/vmlinux"),
+                vec![PathBuf::from(
+                    BASE_TEST_DIR.to_owned() + "/test_data/openvswitch",
+                )],
+            ),
+        })
+    }
+
+    /// Use an on-disk cache, stored in `dir`, for the data derived from the
+    /// BTF of the given kernel release.
+    pub(super) fn set_cache(&mut self, dir: &Path, release: &str) -> Result<()> {
+        let files = std::iter::once(self.vmlinux.clone())
+            .chain(self.modules.iter().map(|(_, path)| path.clone()))
+            .collect::<Vec<_>>();
+
+        let cache = BtfCache::new(dir, release, &files)?;
+        debug!("Using the BTF cache ({} entries)", cache.len());
+        self.cache = Some(cache);
+        Ok(())
+    }
 
-        Ok(BtfInfo { vmlinux, modules })
+    /// Write the cache to disk, if enabled.
+    pub(super) fn save_cache(&self) -> Result<()> {
+        match &self.cache {
+            Some(cache) => cache.save(),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the parameter types of a function or event, from the cache if
+    /// possible.
+    fn prototype(&self, symbol: &Symbol) -> Result<Prototype> {
+        let name = symbol.typedef_name();
+        if let Some(proto) = self.cache.as_ref().and_then(|c| c.prototype(&name)) {
+            return proto.ok_or_else(|| anyhow!("Failed to resolve prototype for {symbol}"));
+        }
+
+        // Do not cache parsing errors as missing prototypes.
+        self.btf()?;
+
+        let proto = self.find_prototype_btf(symbol).and_then(|(btf, proto)| {
+            proto
+                .parameters
+                .iter()
+                .map(|param| Self::param_type_name(btf, param))
+                .collect::<Result<Prototype>>()
+        });
+
+        if let Some(cache) = &self.cache {
+            cache.insert_prototype(name, proto.as_ref().ok().cloned());
+        }
+        proto
+    }
+
+    /// Get the values of an enum, alongside their names, from the cache if
+    /// possible. Returns `None` if the enum is not found.
+    pub(crate) fn enum_values(&self, name: &str) -> Result<Option<EnumValues>> {
+        if let Some(values) = self.cache.as_ref().and_then(|c| c.r#enum(name)) {
+            return Ok(values);
+        }
+
+        // Do not cache parsing errors as missing enums.
+        self.btf()?;
+
+        let mut values = None;
+        if let Ok(types) = self.resolve_types_by_name(name) {
+            if let Some((btf, Type::Enum(r#enum))) =
+                types.iter().find(|(_, t)| matches!(t, Type::Enum(_)))
+            {
+                let mut members = Vec::new();
+                for member in r#enum.members.iter() {
+                    members.push((member.val(), btf.resolve_name(member)?));
+                }
+                values = Some(members);
+            }
+        }
+
+        if let Some(cache) = &self.cache {
+            cache.insert_enum(name.to_string(), values.clone());
+        }
+        Ok(values)
+    }
+
+    /// Names of the modules having BTF.
+    pub(crate) fn modules(&self) -> Vec<&str> {
+        self.modules.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Check if a given module has BTF.
+    pub(crate) fn has_module(&self, module: &str) -> bool {
+        self.modules.iter().any(|(name, _)| name == module)
+    }
+
+    /// Check if a function or event has a prototype in BTF.
+    pub(crate) fn has_prototype(&self, symbol: &Symbol) -> bool {
+        self.prototype(symbol).is_ok()
     }
 
     /// Get a function's number of arguments.
@@ -50,9 +221,7 @@ impl BtfInfo {
             _ => 0,
         };
 
-        let (_, proto) = self.find_prototype_btf(symbol)?;
-
-        Ok((proto.parameters.len() - fix) as u32)
+        Ok((self.prototype(symbol)?.len() - fix) as u32)
     }
 
     /// Get a parameter offset given a kernel function, if any. Can be used to
@@ -71,9 +240,8 @@ impl BtfInfo {
             _ => 0,
         };
 
-        let (btf, proto) = self.find_prototype_btf(symbol)?;
-        for (offset, param) in proto.parameters.iter().enumerate() {
-            if BtfInfo::is_param_type(btf, param, parameter_type)? {
+        for (offset, param) in self.prototype(symbol)?.iter().enumerate() {
+            if param.as_deref() == Some(parameter_type) {
                 if offset < fix {
                     continue;
                 }
@@ -83,6 +251,88 @@ impl BtfInfo {
         Ok(None)
     }
 
+    /// Get a parameter offset and kind given a function and the parameter
+    /// name. Unknown parameters are reported as errors.
+    pub(super) fn parameter_by_name(
+        &self,
+        symbol: &Symbol,
+        name: &str,
+    ) -> Result<(u32, ParamKind)> {
+        // Parameter names are not part of the events typedef prototype, use
+        // the one of the function the events are attached through instead
+        // (see __bpf_trace_##call in include/trace/bpf_probe.h). It has the
+        // same extra first argument.
+        let (btf, proto, fix) = match symbol {
+            Symbol::Func(_) => {
+                let (btf, proto) = self.find_prototype_btf(symbol)?;
+                (btf, proto, 0)
+            }
+            Symbol::Event(_) => {
+                let func = format!("__bpf_trace_{}", symbol.attach_name());
+                self.resolve_types_by_name(&func)?
+                    .into_iter()
+                    .find_map(|(btf, t)| {
+                        Self::get_function_prototype(btf, &t)
+                            .ok()
+                            .map(|proto| (btf, proto, 1))
+                    })
+                    .ok_or_else(|| anyhow!("Failed to resolve prototype for {symbol}"))?
+            }
+        };
+
+        let mut names = Vec::new();
+        for (offset, param) in proto.parameters.iter().enumerate().skip(fix) {
+            let param_name = btf.resolve_name(param)?;
+            if param_name == name {
+                return Ok(((offset - fix) as u32, Self::param_kind(btf, param)?));
+            }
+            names.push(param_name);
+        }
+
+        bail!(
+            "{symbol} has no parameter named {name} (valid ones: {})",
+            names.join(", ")
+        );
+    }
+
+    /// Get the kind of a parameter.
+    fn param_kind(btf: &Btf, param: &btf_rs::Parameter) -> Result<ParamKind> {
+        let mut resolved = btf.resolve_chained_type(param)?;
+
+        loop {
+            resolved = match resolved {
+                Type::Typedef(t) => btf.resolve_chained_type(&t)?,
+                Type::Volatile(t) => btf.resolve_chained_type(&t)?,
+                Type::Const(t) => btf.resolve_chained_type(&t)?,
+                _ => break,
+            }
+        }
+
+        Ok(match resolved {
+            Type::Int(i) => ParamKind::Int {
+                size: i.size(),
+                signed: i.is_signed(),
+            },
+            Type::Enum(e) => {
+                let mut values = HashMap::new();
+                for member in e.members.iter() {
+                    values.insert(member.val() as u64, btf.resolve_name(member)?);
+                }
+                ParamKind::Enum {
+                    signed: e.is_signed(),
+                    values,
+                }
+            }
+            // Enum64 values are not resolved, but they can still be decoded
+            // as integers.
+            Type::Enum64(e) => ParamKind::Int {
+                size: 8,
+                signed: e.is_signed(),
+            },
+            _ => ParamKind::Raw,
+        })
+    }
+
     /// Look for a type based on its name and return both a Vec of Type objects as well as
     /// the Btf object where it was found.
     /// Subsequent lookups based on this type (such as nested types by id) must be done on
@@ -90,11 +340,15 @@ impl BtfInfo {
     ///
     /// vmlinux is given priority in the lookups.
     pub(crate) fn resolve_types_by_name(&self, name: &str) -> Result<Vec<(&Btf, Type)>> {
+        let parsed = self.btf()?;
         let mut types = Vec::new();
 
-        let mut base_types = self.vmlinux.resolve_types_by_name(name).unwrap_or_default();
+        let mut base_types = parsed
+            .vmlinux
+            .resolve_types_by_name(name)
+            .unwrap_or_default();
 
-        for module in self.modules.iter() {
+        for (_, module) in parsed.modules.iter() {
             if let Ok(mut res) = module.resolve_types_by_name(name) {
                 // FIXME: We can't filter base types so they'll be reported more
                 // than once (we need some changes in btf-rs that are not
@@ -107,7 +361,7 @@ impl BtfInfo {
         // Now add types found in the base BTF.
         base_types
             .drain(..)
-            .for_each(|t| types.push((&self.vmlinux, t)));
+            .for_each(|t| types.push((&parsed.vmlinux, t)));
 
         if types.is_empty() {
             bail!("No type linked to name {name}");
@@ -116,6 +370,70 @@ impl BtfInfo {
         Ok(types)
     }
 
+    /// Look for a type based on its name, optionally qualified with the BTF
+    /// source defining it ("vmlinux" or a module name), and only considering
+    /// types accepted by the given filter. Types of the base BTF are only
+    /// reported for vmlinux. If the type is found in more than one source and
+    /// none was given, the lookup fails and lists the candidates.
+    pub(crate) fn resolve_unique_type<F>(
+        &self,
+        name: &str,
+        source: Option<&str>,
+        accept: F,
+    ) -> Result<(&Btf, Type)>
+    where
+        F: Fn(&Type) -> bool,
+    {
+        let parsed = self.btf()?;
+        let base_ids = parsed.vmlinux.resolve_ids_by_name(name).unwrap_or_default();
+
+        let mut candidates = Vec::new();
+        for (src, btf) in std::iter::once(("vmlinux", &parsed.vmlinux))
+            .chain(parsed.modules.iter().map(|(m, btf)| (m.as_str(), btf)))
+        {
+            if source.is_some_and(|s| s != src) {
+                continue;
+            }
+
+            let ids = match src {
+                "vmlinux" => base_ids.clone(),
+                _ => btf.resolve_ids_by_name(name).unwrap_or_default(),
+            };
+            for id in ids {
+                // Split BTF also reports the types of the base BTF.
+                if src != "vmlinux" && base_ids.contains(&id) {
+                    continue;
+                }
+
+                let r#type = btf.resolve_type_by_id(id)?;
+                if accept(&r#type) {
+                    candidates.push((src, btf, r#type));
+                    // Only one type is taken per source.
+                    break;
+                }
+            }
+        }
+
+        match candidates.len() {
+            0 => match source {
+                Some(source) => bail!("Could not resolve {name} in {source}"),
+                None => bail!("Could not resolve {name}"),
+            },
+            1 => {
+                let (_, btf, r#type) = candidates.remove(0);
+                Ok((btf, r#type))
+            }
+            _ => bail!(
+                "{name} is defined in more than one place, use one of: {}",
+                candidates
+                    .iter()
+                    .map(|(src, _, _)| format!("{name}@{src}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
     /// Look for a function symbol and return a Vec of matching Type objects as well as
     /// the Btf object where it was found.
     ///
@@ -141,8 +459,9 @@ impl BtfInfo {
         bail!("Failed to resolve prototype for {symbol}");
     }
 
-    /// Determine if a parameter is from a specific type.
-    fn is_param_type(btf: &Btf, param: &btf_rs::Parameter, r#type: &str) -> Result<bool> {
+    /// Get the type name of a parameter, C style (e.g. "struct sk_buff *"),
+    /// if supported.
+    fn param_type_name(btf: &Btf, param: &btf_rs::Parameter) -> Result<Option<String>> {
         let mut resolved = btf.resolve_chained_type(param)?;
         let mut full_name = String::new();
 
@@ -160,7 +479,7 @@ impl BtfInfo {
                 Type::Volatile(t) => btf.resolve_chained_type(&t)?,
                 Type::Const(t) => btf.resolve_chained_type(&t)?,
                 // FIXME: arrays are not supported at the moment.
-                Type::Array(_) => return Ok(false),
+                Type::Array(_) => return Ok(None),
                 _ => break,
             }
         }
@@ -174,7 +493,7 @@ impl BtfInfo {
             Type::Typedef(t) => btf.resolve_name(&t)?,
             Type::Float(t) => btf.resolve_name(&t)?,
             Type::Enum64(t) => format!("enum {}", btf.resolve_name(&t)?),
-            _ => return Ok(false),
+            _ => return Ok(None),
         };
         full_name.push_str(type_name.as_str());
 
@@ -186,7 +505,7 @@ impl BtfInfo {
         // We do not get the symbol name; useless and not always there (e.g.
         // raw tracepoints).
 
-        Ok(r#type == full_name)
+        Ok(Some(full_name))
     }
 
     fn get_function_prototype(btf: &Btf, func: &Type) -> Result<btf_rs::FuncProto> {
@@ -226,6 +545,8 @@ impl BtfInfo {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
+
     use super::*;
 
     #[test]
@@ -250,6 +571,111 @@ mod tests {
         );
     }
 
+    #[test]
+    fn btf_cache() {
+        let dir = std::env::temp_dir().join(format!("retis-btf-cache-{}", std::process::id()));
+        let symbols = fs::read_to_string("test_data/available_filter_functions")
+            .unwrap()
+            .lines()
+            .filter_map(|l| l.split(' ').next())
+            .map(String::from)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .take(5000)
+            .map(Symbol::Func)
+            .collect::<Vec<_>>();
+
+        let query = |btf: &BtfInfo| {
+            let offsets = symbols
+                .iter()
+                .map(|s| btf.parameter_offset(s, "struct sk_buff *").ok().flatten())
+                .collect::<Vec<_>>();
+            let reasons = btf.enum_values("skb_drop_reason").unwrap();
+            (offsets, reasons)
+        };
+        let stats = |btf: &BtfInfo| btf.cache.as_ref().unwrap().stats();
+
+        // Cold cache: data is resolved from BTF and cached.
+        let mut cold = BtfInfo::new().unwrap();
+        cold.set_cache(&dir, "6.3.0").unwrap();
+        assert_eq!(cold.cache.as_ref().unwrap().len(), 0);
+        let (cold_offsets, cold_reasons) = query(&cold);
+        assert_eq!(stats(&cold), (0, symbols.len() as u64 + 1));
+        assert!(cold.parsed.get().is_some());
+        cold.save_cache().unwrap();
+
+        // Warm cache: data is loaded from disk and BTF is not parsed.
+        let mut warm = BtfInfo::new().unwrap();
+        warm.set_cache(&dir, "6.3.0").unwrap();
+        assert_eq!(warm.cache.as_ref().unwrap().len(), symbols.len() + 1);
+        let (warm_offsets, warm_reasons) = query(&warm);
+        assert_eq!(stats(&warm), (symbols.len() as u64 + 1, 0));
+        assert!(warm.parsed.get().is_none());
+
+        assert_eq!(cold_offsets, warm_offsets);
+        assert!(cold_offsets.iter().any(|o| *o == Some(0)));
+        assert_eq!(cold_reasons, warm_reasons);
+        assert!(cold_reasons
+            .unwrap()
+            .iter()
+            .any(|(_, name)| name == "SKB_DROP_REASON_NOT_SPECIFIED"));
+
+        // The cache is invalidated when the kernel changes.
+        let mut other = BtfInfo::new().unwrap();
+        other.set_cache(&dir, "6.4.0").unwrap();
+        assert_eq!(other.cache.as_ref().unwrap().len(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn modules() {
+        let btf = BtfInfo::new().unwrap();
+        assert_eq!(btf.modules(), vec!["openvswitch"]);
+        assert!(btf.has_module("openvswitch"));
+        assert!(!btf.has_module("zram"));
+
+        // Module types are resolved in the module BTF.
+        let types = btf.resolve_types_by_name("datapath").unwrap();
+        assert!(types.iter().any(|(b, t)| matches!(t, Type::Struct(_))
+            && std::ptr::eq(*b, &btf.btf().unwrap().modules[0].1)));
+    }
+
+    #[test]
+    fn resolve_unique_type() {
+        let btf = BtfInfo::new().unwrap();
+        let is_struct = |t: &Type| matches!(t, Type::Struct(_));
+
+        // sk_buff is only defined in vmlinux.
+        let (b, _) = btf.resolve_unique_type("sk_buff", None, is_struct).unwrap();
+        assert!(std::ptr::eq(b, &btf.btf().unwrap().vmlinux));
+        assert!(btf
+            .resolve_unique_type("sk_buff", Some("openvswitch"), is_struct)
+            .is_err());
+
+        // struct module is defined in both vmlinux and openvswitch.
+        let err = btf
+            .resolve_unique_type("module", None, is_struct)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "module is defined in more than one place, use one of: module@vmlinux, module@openvswitch"
+        );
+
+        let (b, _) = btf
+            .resolve_unique_type("module", Some("vmlinux"), is_struct)
+            .unwrap();
+        assert!(std::ptr::eq(b, &btf.btf().unwrap().vmlinux));
+        let (b, _) = btf
+            .resolve_unique_type("module", Some("openvswitch"), is_struct)
+            .unwrap();
+        assert!(std::ptr::eq(b, &btf.btf().unwrap().modules[0].1));
+
+        assert!(btf
+            .resolve_unique_type("module", Some("zram"), is_struct)
+            .is_err());
+    }
+
     #[test]
     fn parameter_offset() {
         let btf = BtfInfo::new().unwrap();
@@ -319,4 +745,55 @@ mod tests {
             .unwrap()
             .is_none());
     }
+
+    #[test]
+    fn parameter_by_name() {
+        let btf = BtfInfo::new().unwrap();
+        let kfree_skb = Symbol::Event("skb:kfree_skb".to_string());
+
+        assert_eq!(
+            btf.parameter_by_name(&kfree_skb, "skb").unwrap(),
+            (0, ParamKind::Raw)
+        );
+
+        let (offset, kind) = btf.parameter_by_name(&kfree_skb, "reason").unwrap();
+        assert_eq!(offset, 2);
+        match kind {
+            ParamKind::Enum { values, .. } => {
+                assert_eq!(values.get(&2).unwrap(), "SKB_DROP_REASON_NOT_SPECIFIED")
+            }
+            _ => panic!("reason is not an enum"),
+        }
+
+        // The events extra first argument is not reported.
+        assert!(btf.parameter_by_name(&kfree_skb, "__data").is_err());
+        assert!(btf.parameter_by_name(&kfree_skb, "foo").is_err());
+
+        assert_eq!(
+            btf.parameter_by_name(&Symbol::Func("kfree_skb_reason".to_string()), "skb")
+                .unwrap(),
+            (0, ParamKind::Raw)
+        );
+    }
+}
+
+#[cfg(feature = "benchmark")]
+pub(crate) mod benchmark {
+    use std::path::Path;
+
+    use anyhow::Result;
+
+    use super::BtfInfo;
+    use crate::core::kernel::Symbol;
+
+    /// Resolve the prototypes of the given symbols as done at startup, using
+    /// an on-disk cache stored in `dir`.
+    pub(crate) fn resolve_prototypes(dir: &Path, symbols: &[Symbol]) -> Result<()> {
+        let mut btf = BtfInfo::new()?;
+        btf.set_cache(dir, "benchmark")?;
+        for symbol in symbols.iter() {
+            let _ = btf.parameter_offset(symbol, "struct sk_buff *");
+        }
+        btf.save_cache()
+    }
 }
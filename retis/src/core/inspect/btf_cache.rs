@@ -0,0 +1,182 @@
+//! # BTF cache
+//!
+//! On-disk cache of the data derived from the kernel BTF at startup, i.e. the
+//! parameter types of the functions and events being probed and the enums
+//! used to decode events. Parsing the BTF and resolving those is costly,
+//! especially when probing many targets (e.g. using wildcards), and gives the
+//! same results as long as the kernel does not change. When everything needed
+//! is found in the cache, the BTF files are not parsed at all.
+//!
+//! The cache is bound to the kernel release and to the size and modification
+//! time of the BTF files it was computed from. If any of those changed, its
+//! content is discarded and it is rebuilt.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        RwLock,
+    },
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{anyhow, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+/// Default directory the cache is stored in.
+pub(crate) const BTF_CACHE_DIR: &str = "/var/cache/retis";
+/// Name of the cache file.
+const BTF_CACHE_FILE: &str = "btf.json";
+
+/// Parameter types of a function or event prototype, as C-style names (e.g.
+/// "struct sk_buff *"). Parameters whose type can't be named are `None`.
+pub(super) type Prototype = Vec<Option<String>>;
+/// Values of an enum, alongside their names.
+pub(crate) type EnumValues = Vec<(u32, String)>;
+
+#[derive(Default, Deserialize, Serialize)]
+struct BtfCacheData {
+    /// Kernel release and BTF files the data was computed from.
+    key: String,
+    /// Prototypes, by symbol typedef name. Symbols without a prototype are
+    /// stored as `None`.
+    prototypes: HashMap<String, Option<Prototype>>,
+    /// Enum values, by enum name. Enums not found are stored as `None`.
+    #[serde(default)]
+    enums: HashMap<String, Option<EnumValues>>,
+}
+
+pub(super) struct BtfCache {
+    /// Path of the cache file.
+    path: PathBuf,
+    data: RwLock<BtfCacheData>,
+    /// Were entries added since the cache was loaded?
+    dirty: AtomicBool,
+    /// Number of lookups found in the cache.
+    hits: AtomicU64,
+    /// Number of lookups not found in the cache.
+    misses: AtomicU64,
+}
+
+impl BtfCache {
+    /// Open the cache stored in `dir`. Its content is only used if it was
+    /// computed for the same kernel release and BTF files, otherwise the cache
+    /// starts empty.
+    pub(super) fn new(dir: &Path, release: &str, files: &[PathBuf]) -> Result<Self> {
+        let mut key = release.to_string();
+        for file in files.iter() {
+            let meta = fs::metadata(file)
+                .map_err(|e| anyhow!("Could not stat {}: {e}", file.display()))?;
+            let mtime = meta.modified()?.duration_since(UNIX_EPOCH)?.as_nanos();
+            key.push_str(&format!(";{}:{}:{mtime}", file.display(), meta.len()));
+        }
+
+        let path = dir.join(BTF_CACHE_FILE);
+        let data = match fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice::<BtfCacheData>(&data).ok())
+        {
+            Some(data) if data.key == key => data,
+            _ => {
+                debug!("No valid BTF cache found in {}", path.display());
+                BtfCacheData {
+                    key,
+                    ..Default::default()
+                }
+            }
+        };
+
+        Ok(Self {
+            path,
+            data: RwLock::new(data),
+            dirty: AtomicBool::new(false),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    /// Number of cached entries.
+    pub(super) fn len(&self) -> usize {
+        self.data
+            .read()
+            .map(|d| d.prototypes.len() + d.enums.len())
+            .unwrap_or(0)
+    }
+
+    /// Number of lookups found and not found in the cache, in this order.
+    pub(super) fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Account for a lookup.
+    fn lookup<T>(&self, entry: Option<T>) -> Option<T> {
+        match entry {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        entry
+    }
+
+    /// Get the cached prototype of a symbol. Returns `None` if the symbol is
+    /// not in the cache and `Some(None)` if it is known not to have one.
+    pub(super) fn prototype(&self, name: &str) -> Option<Option<Prototype>> {
+        self.lookup(self.data.read().ok()?.prototypes.get(name).cloned())
+    }
+
+    /// Add the prototype of a symbol to the cache.
+    pub(super) fn insert_prototype(&self, name: String, proto: Option<Prototype>) {
+        if let Ok(mut data) = self.data.write() {
+            data.prototypes.insert(name, proto);
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Get the cached values of an enum. Returns `None` if the enum is not in
+    /// the cache and `Some(None)` if it is known not to exist.
+    pub(super) fn r#enum(&self, name: &str) -> Option<Option<EnumValues>> {
+        self.lookup(self.data.read().ok()?.enums.get(name).cloned())
+    }
+
+    /// Add the values of an enum to the cache.
+    pub(super) fn insert_enum(&self, name: String, values: Option<EnumValues>) {
+        if let Ok(mut data) = self.data.write() {
+            data.enums.insert(name, values);
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Write the cache to disk, if it changed.
+    pub(super) fn save(&self) -> Result<()> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let data = serde_json::to_vec(
+            &*self
+                .data
+                .read()
+                .map_err(|e| anyhow!("Could not access the BTF cache: {e}"))?,
+        )?;
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // Write a temporary file first so concurrent runs never read a
+        // partial cache.
+        let tmp = self
+            .path
+            .with_extension(format!("json.{}", std::process::id()));
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)?;
+
+        debug!("BTF cache written to {}", self.path.display());
+        Ok(())
+    }
+}
@@ -1,21 +1,22 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Result};
+use log::warn;
 use once_cell::sync::OnceCell;
 
-use super::kernel::KernelInspector;
+use super::{btf_cache::BTF_CACHE_DIR, kernel::KernelInspector};
 
 static INSPECTOR: OnceCell<Inspector> = OnceCell::new();
 
 /// Gets a reference on the inspector.
 pub(crate) fn inspector() -> Result<&'static Inspector> {
-    INSPECTOR.get_or_try_init(|| Inspector::from(None))
+    INSPECTOR.get_or_try_init(|| Inspector::from(None, true))
 }
 
 /// Initialize the inspector with custom parameters, fail is already
 /// initialized.
-pub(crate) fn init_inspector(kconf: &PathBuf) -> Result<()> {
-    let inspector = Inspector::from(Some(kconf))?;
+pub(crate) fn init_inspector(kconf: Option<&PathBuf>, btf_cache: bool) -> Result<()> {
+    let inspector = Inspector::from(kconf, btf_cache)?;
     if INSPECTOR.set(inspector).is_err() {
         bail!("Could not init inspector: was already initialized.");
     }
@@ -30,10 +31,17 @@ pub(crate) struct Inspector {
 }
 
 impl Inspector {
-    fn from(kconf: Option<&PathBuf>) -> Result<Inspector> {
-        Ok(Inspector {
-            kernel: KernelInspector::from(kconf)?,
-        })
+    fn from(kconf: Option<&PathBuf>, btf_cache: bool) -> Result<Inspector> {
+        let mut kernel = KernelInspector::from(kconf)?;
+
+        // Tests and benchmarks use their own BTF files, do not cache them.
+        if btf_cache && !(cfg!(test) || cfg!(feature = "benchmark")) {
+            if let Err(e) = kernel.enable_btf_cache(Path::new(BTF_CACHE_DIR)) {
+                warn!("Could not use the BTF cache: {e}");
+            }
+        }
+
+        Ok(Inspector { kernel })
     }
 }
 
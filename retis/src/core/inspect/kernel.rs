@@ -4,27 +4,95 @@ use std::{
     collections::{HashMap, HashSet},
     fmt::Display,
     fs,
-    io::Read,
+    io::{Read, Write},
     ops::Bound::{Included, Unbounded},
     path::{Path, PathBuf},
     str,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock, RwLockReadGuard,
+    },
 };
 
 use anyhow::{anyhow, bail, Result};
 use flate2::bufread::GzDecoder;
-use log::warn;
+use log::{debug, warn};
 use regex::Regex;
 
-use super::{btf::BtfInfo, kernel_version::KernelVersion, BASE_TEST_DIR};
-use crate::core::kernel::Symbol;
+use super::{
+    btf::{BtfInfo, ParamKind},
+    kernel_version::KernelVersion,
+    BASE_TEST_DIR,
+};
+use crate::core::kernel::{modules::ModuleRanges, Symbol};
 use crate::helpers::bimap::BiBTreeMap;
 
+/// Symbols information, as found in kallsyms.
+struct Kallsyms {
+    /// Symbols bi-directional map (addr<>name).
+    symbols: BiBTreeMap<u64, String>,
+    /// Map of module symbols to the module they belong to.
+    modules: HashMap<String, String>,
+}
+
+impl Kallsyms {
+    fn parse(file: &str) -> Result<Kallsyms> {
+        let mut symbols = BiBTreeMap::new();
+        let mut modules = HashMap::new();
+
+        // Lines have to be processed backward in order to overwrite
+        // duplicate addresses and keep the first (which is the last
+        // inserted in the common case involving module init
+        // functions) instead of the last one.
+        for line in fs::read_to_string(file)?.lines().rev() {
+            let data: Vec<&str> = line.split(' ').collect();
+            if data.len() < 3 {
+                bail!("Invalid kallsyms line: {}", line);
+            }
+
+            let mut info = data[2].split('\t');
+            let symbol: &str = info
+                .next()
+                .ok_or_else(|| anyhow!("Couldn't get symbol name for {}", data[0]))?;
+
+            // Module symbols are formatted as "symbol\t[module]".
+            if let Some(module) = info
+                .next()
+                .and_then(|m| m.strip_prefix('['))
+                .and_then(|m| m.strip_suffix(']'))
+            {
+                modules.insert(String::from(symbol), String::from(module));
+            }
+
+            symbols.insert(u64::from_str_radix(data[0], 16)?, String::from(symbol));
+        }
+
+        // If all symbols have a 0-address, only the last one will be left in
+        // the map after the above.
+        if symbols.len() == 1 {
+            bail!("Retis likely does not have the rights to read the symbol addresses from /proc/kallsyms.");
+        }
+
+        Ok(Kallsyms { symbols, modules })
+    }
+}
+
 /// Provides helpers to inspect probe related information in the kernel.
 pub(crate) struct KernelInspector {
     /// Btf information.
     pub(crate) btf: BtfInfo,
-    /// Symbols bi-directional map (addr<>name).
-    symbols: BiBTreeMap<u64, String>,
+    /// Symbols information. Can be reloaded, e.g. when a module is reloaded.
+    kallsyms: RwLock<Kallsyms>,
+    /// Path to the kallsyms file.
+    symbols_file: String,
+    /// Number of times the symbols were reloaded. Allows users caching
+    /// symbols information to know when to invalidate it.
+    symbols_generation: AtomicU64,
+    /// Address ranges of the kernel modules. Can be reloaded alongside the
+    /// symbols.
+    module_ranges: RwLock<ModuleRanges>,
+    /// Path to the modules file.
+    modules_file: String,
     /// Set of traceable events (e.g. tracepoints).
     traceable_events: Option<HashSet<String>>,
     /// Set of traceable functions (e.g. kprobes).
@@ -62,37 +130,17 @@ impl KernelInspector {
         let btf = BtfInfo::new()?;
 
         // First parse the symbol file.
-        let mut symbols = BiBTreeMap::new();
-        // Lines have to be processed backward in order to overwrite
-        // duplicate addresses and keep the first (which is the last
-        // inserted in the common case involving module init
-        // functions) instead of the last one.
-        for line in fs::read_to_string(symbols_file)?.lines().rev() {
-            let data: Vec<&str> = line.split(' ').collect();
-            if data.len() < 3 {
-                bail!("Invalid kallsyms line: {}", line);
-            }
-
-            let symbol: &str = data[2]
-                .split('\t')
-                .next()
-                .ok_or_else(|| anyhow!("Couldn't get symbol name for {}", data[0]))?;
-
-            symbols.insert(u64::from_str_radix(data[0], 16)?, String::from(symbol));
-        }
-
-        // If all symbols have a 0-address, only the last one will be left in
-        // the map after the above.
-        if symbols.len() == 1 {
-            bail!("Retis likely does not have the rights to read the symbol addresses from /proc/kallsyms.");
-        }
+        let kallsyms = RwLock::new(Kallsyms::parse(&symbols_file)?);
 
         let version = KernelVersion::new()?;
         let config = Self::parse_kernel_config(&version.full, kconf)?;
 
         let inspector = KernelInspector {
             btf,
-            symbols,
+            kallsyms,
+            symbols_file,
+            symbols_generation: AtomicU64::new(0),
+            module_ranges: RwLock::new(ModuleRanges::from_file(&modules_file).unwrap_or_default()),
             // Not all events we'll get from BTF/kallsyms are traceable. Use the
             // following, when available, to narrow down our checks.
             traceable_events: Self::file_to_hashset(events_file),
@@ -101,7 +149,8 @@ impl KernelInspector {
             traceable_funcs: Self::file_to_hashset(funcs_file),
             version,
             config,
-            modules: Self::file_to_hashset(modules_file),
+            modules: Self::file_to_hashset(&modules_file),
+            modules_file,
         };
 
         if inspector.traceable_funcs.is_none() || inspector.traceable_events.is_none() {
@@ -110,6 +159,28 @@ impl KernelInspector {
             );
         }
 
+        debug!(
+            "Found BTF for modules: {}",
+            inspector.btf.modules().join(", ")
+        );
+
+        // Types and functions of modules without BTF can't be resolved (e.g.
+        // out-of-tree modules built without it).
+        if let Some(modules) = inspector.modules.as_ref() {
+            let mut missing = modules
+                .iter()
+                .filter(|m| !inspector.btf.has_module(m))
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                missing.sort();
+                warn!(
+                    "No BTF found for modules {}: their types and functions can't be inspected",
+                    missing.join(", ")
+                );
+            }
+        }
+
         Ok(inspector)
     }
 
@@ -215,6 +286,16 @@ impl KernelInspector {
         }
     }
 
+    /// Cache the data derived from BTF on disk, in the given directory.
+    pub(crate) fn enable_btf_cache(&mut self, dir: &Path) -> Result<()> {
+        self.btf.set_cache(dir, &self.version.full)
+    }
+
+    /// Write the BTF cache to disk, if enabled and it changed.
+    pub(crate) fn save_btf_cache(&self) -> Result<()> {
+        self.btf.save_cache()
+    }
+
     /// Return the running kernel version.
     pub(crate) fn version(&self) -> &KernelVersion {
         &self.version
@@ -244,9 +325,72 @@ impl KernelInspector {
             .map(|modules| modules.contains(&module.to_string()))
     }
 
+    fn kallsyms(&self) -> Result<RwLockReadGuard<'_, Kallsyms>> {
+        self.kallsyms
+            .read()
+            .map_err(|e| anyhow!("Could not access the symbols: {e}"))
+    }
+
+    /// Reload the symbols from kallsyms. This is required for symbols
+    /// addresses to be valid after a module was (re)loaded.
+    pub(crate) fn reload_symbols(&self) -> Result<()> {
+        let kallsyms = Kallsyms::parse(&self.symbols_file)?;
+        *self
+            .kallsyms
+            .write()
+            .map_err(|e| anyhow!("Could not update the symbols: {e}"))? = kallsyms;
+
+        *self
+            .module_ranges
+            .write()
+            .map_err(|e| anyhow!("Could not update the module ranges: {e}"))? =
+            ModuleRanges::from_file(&self.modules_file).unwrap_or_default();
+
+        self.symbols_generation.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Get the symbols generation, which changes each time the symbols are
+    /// reloaded.
+    pub(crate) fn symbols_generation(&self) -> u64 {
+        self.symbols_generation.load(Ordering::Relaxed)
+    }
+
+    /// Write the current symbols snapshot, in a kallsyms-like format
+    /// ("address type symbol\t[module]") which can be parsed back. Symbol
+    /// types are not kept and are reported as '?'. This allows raw addresses
+    /// to be symbolized later on, even after the running kernel changed.
+    pub(crate) fn export_symbols<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let kallsyms = self.kallsyms()?;
+        for (addr, symbol) in kallsyms.symbols.range_by_left(&..) {
+            match kallsyms.modules.get(symbol) {
+                Some(module) => writeln!(writer, "{addr:016x} ? {symbol}\t[{module}]")?,
+                None => writeln!(writer, "{addr:016x} ? {symbol}")?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the module a symbol belongs to, if any.
+    pub(crate) fn get_symbol_module(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.kallsyms()?.modules.get(name).cloned())
+    }
+
+    /// Return the module owning an address, if any, using the modules address
+    /// ranges.
+    pub(crate) fn get_addr_module(&self, addr: u64) -> Result<Option<String>> {
+        Ok(self
+            .module_ranges
+            .read()
+            .map_err(|e| anyhow!("Could not access the module ranges: {e}"))?
+            .get(addr)
+            .map(String::from))
+    }
+
     /// Return a symbol name given its address, if a relationship is found.
     pub(crate) fn get_symbol_name(&self, addr: u64) -> Result<String> {
         Ok(self
+            .kallsyms()?
             .symbols
             .get_by_left(&addr)
             .ok_or_else(|| anyhow!("Can't get symbol name for {}", addr))?
@@ -256,6 +400,7 @@ impl KernelInspector {
     /// Return a symbol address given its name, if a relationship is found.
     pub(crate) fn get_symbol_addr(&self, name: &str) -> Result<u64> {
         Ok(*self
+            .kallsyms()?
             .symbols
             .get_by_right(&name.to_string())
             .ok_or_else(|| anyhow!("Can't get symbol address for {}", name))?)
@@ -264,7 +409,8 @@ impl KernelInspector {
     /// Given an address, try to find the nearest symbol, if any.
     pub(crate) fn find_nearest_symbol(&self, target: u64) -> Result<u64> {
         let bounding = (Unbounded, Included(target));
-        let nearest = self.symbols.range_by_left(&bounding).next_back();
+        let kallsyms = self.kallsyms()?;
+        let nearest = kallsyms.symbols.range_by_left(&bounding).next_back();
 
         match nearest {
             Some(symbol) => Ok(*symbol.0),
@@ -311,6 +457,16 @@ impl KernelInspector {
         self.btf.parameter_offset(symbol, parameter_type)
     }
 
+    /// Get a parameter offset and kind given a kernel function and the
+    /// parameter name.
+    pub(crate) fn parameter_by_name(
+        &self,
+        symbol: &Symbol,
+        name: &str,
+    ) -> Result<(u32, ParamKind)> {
+        self.btf.parameter_by_name(symbol, name)
+    }
+
     /// Get a function's number of arguments.
     pub(crate) fn function_nargs(&self, symbol: &Symbol) -> Result<u32> {
         self.btf.function_nargs(symbol)
@@ -326,14 +482,178 @@ impl KernelInspector {
         ))
     }
 
+    /// Batched version of `get_name_offt_from_addr_near`: given a set of
+    /// addresses, gets the name and the offset of their nearest symbol, if
+    /// any. Results are returned in the order of the addresses. The symbols
+    /// table is only locked once and addresses are resolved in ascending
+    /// order, each distinct one being looked up once.
+    pub(crate) fn get_names_offt_from_addrs_near(
+        &self,
+        addrs: &[u64],
+    ) -> Result<Vec<Option<(String, u64)>>> {
+        let kallsyms = self.kallsyms()?;
+
+        let mut order = (0..addrs.len()).collect::<Vec<_>>();
+        order.sort_unstable_by_key(|i| addrs[*i]);
+
+        let mut resolved = vec![None; addrs.len()];
+        let mut last: Option<(u64, Option<(String, u64)>)> = None;
+        for i in order {
+            let addr = addrs[i];
+            if let Some((last_addr, sym)) = last.as_ref() {
+                if *last_addr == addr {
+                    resolved[i] = sym.clone();
+                    continue;
+                }
+            }
+
+            let sym = kallsyms
+                .symbols
+                .range_by_left(&(Unbounded, Included(addr)))
+                .next_back()
+                .map(|(sym_addr, name)| (name.clone(), addr - sym_addr));
+            resolved[i] = sym.clone();
+            last = Some((addr, sym));
+        }
+
+        Ok(resolved)
+    }
+
     fn match_in_set(set: &HashSet<String>, target: &str) -> Result<Vec<String>> {
-        let target = format!("^{}$", target.replace('*', ".*"));
+        // Only wildcards are supported, other characters are matched
+        // literally (e.g. '.' in "ip_rcv.cold").
+        let target = format!("^{}$", regex::escape(target).replace(r"\*", ".*"));
         let re = Regex::new(&target)?;
 
         // Unwrap as we checked above we have a set of valid events.
         Ok(set.iter().filter(|f| re.is_match(f)).cloned().collect())
     }
 
+    /// List the traceable events (under the group:name form, as accepted by
+    /// `Symbol::from_name`), sorted. Only events of the given group are
+    /// returned if one is set.
+    pub(crate) fn list_events(&self, group: Option<&str>) -> Result<Vec<String>> {
+        Ok(Self::list_in_set(
+            self
+                .traceable_events
+                .as_ref()
+                .ok_or_else(|| anyhow!("Could not list events as Retis can't access files in /sys/kernel/debug/tracing"))?,
+            group,
+        ))
+    }
+
+    fn list_in_set(set: &HashSet<String>, group: Option<&str>) -> Vec<String> {
+        let mut events = set
+            .iter()
+            .filter(|e| match group {
+                Some(group) => e.split_once(':').is_some_and(|(g, _)| g == group),
+                None => true,
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        events.sort();
+        events
+    }
+
+    /// List the functions that can be kprobed, sorted and deduplicated. The
+    /// traceable functions are used when available, kallsyms text symbols
+    /// otherwise. Functions in the kprobe blacklist are removed, on a
+    /// best-effort basis: the blacklist might not be readable and does not
+    /// cover all the functions the kernel would refuse to probe.
+    ///
+    /// An optional filter can be given: a pattern with wildcards (*), e.g.
+    /// "tcp_v6_*", or a substring otherwise.
+    pub(crate) fn list_functions(&self, filter: Option<&str>) -> Result<Vec<String>> {
+        let blacklist_file = match cfg!(test) || cfg!(feature = "benchmark") {
+            false => "/sys/kernel/debug/kprobes/blacklist".to_owned(),
+            true => BASE_TEST_DIR.to_owned() + "/test_data/kprobes_blacklist",
+        };
+        let blacklist = fs::read_to_string(blacklist_file)
+            .map(|content| Self::parse_kprobe_blacklist(&content))
+            .unwrap_or_default();
+
+        let re = match filter {
+            Some(filter) => {
+                let re = regex::escape(filter).replace(r"\*", ".*");
+                Some(Regex::new(&match filter.contains('*') {
+                    true => format!("^{re}$"),
+                    false => re,
+                })?)
+            }
+            None => None,
+        };
+
+        let mut funcs = match self.traceable_funcs.as_ref() {
+            Some(funcs) => funcs.iter().cloned().collect::<Vec<_>>(),
+            None => Self::kallsyms_text_symbols(&self.symbols_file)?,
+        }
+        .into_iter()
+        .filter(|f| !blacklist.contains(f))
+        .filter(|f| re.as_ref().map_or(true, |re| re.is_match(f)))
+        .collect::<Vec<_>>();
+
+        funcs.sort();
+        funcs.dedup();
+        Ok(funcs)
+    }
+
+    /// Parse the kprobe blacklist, formatted as "start-end\tsymbol [module]".
+    fn parse_kprobe_blacklist(content: &str) -> HashSet<String> {
+        content
+            .lines()
+            .filter_map(|line| line.split('\t').nth(1)?.split(' ').next())
+            .map(String::from)
+            .collect()
+    }
+
+    /// Retrieve the text symbols from a kallsyms formatted file.
+    fn kallsyms_text_symbols(file: &str) -> Result<Vec<String>> {
+        Ok(fs::read_to_string(file)?
+            .lines()
+            .filter_map(|line| {
+                let mut data = line.split(' ');
+                match data.nth(1)? {
+                    "t" | "T" => data.next()?.split('\t').next().map(String::from),
+                    _ => None,
+                }
+            })
+            .collect())
+    }
+
+    /// Find the traceable symbols closest to a given name, to help users
+    /// fixing typos. Events are looked up if the name contains a ':',
+    /// functions otherwise. At most `max` suggestions are returned, closest
+    /// first; none are if the traceable symbols can't be retrieved.
+    pub(crate) fn similar_symbols(&self, name: &str, max: usize) -> Vec<String> {
+        let set = match name.contains(':') {
+            true => self.traceable_events.as_ref(),
+            false => self.traceable_funcs.as_ref(),
+        };
+        let set = match set {
+            Some(set) => set,
+            None => return Vec::new(),
+        };
+
+        // Do not suggest names too different from the original one.
+        let threshold = usize::max(1, name.len() / 3);
+
+        let mut candidates = set
+            .iter()
+            .filter(|s| s.len().abs_diff(name.len()) <= threshold)
+            .filter_map(|s| match edit_distance(name, s) {
+                dist if dist <= threshold => Some((dist, s)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        candidates.sort();
+
+        candidates
+            .into_iter()
+            .take(max)
+            .map(|(_, s)| s.clone())
+            .collect()
+    }
+
     /// Find events matching a given pattern. So far only wildcards (*) are
     /// supported, e.g. "skb:*", "*:kfree_skb" or "*skb*".
     pub(crate) fn matching_events(&self, target: &str) -> Result<Vec<String>> {
@@ -359,9 +679,27 @@ impl KernelInspector {
     }
 }
 
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut cur = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let subst = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = subst.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+
+    prev[b.len()]
+}
+
 #[cfg(test)]
 mod tests {
-    use super::KernelInspector;
+    use super::{edit_distance, KernelInspector};
     use std::path::PathBuf;
 
     fn inspector() -> KernelInspector {
@@ -385,6 +723,53 @@ mod tests {
         assert!(inspector().get_symbol_addr("consume_skb").unwrap() == 0xffffffff99d1da80);
     }
 
+    #[test]
+    fn export_symbols() {
+        let mut buf = Vec::new();
+        inspector().export_symbols(&mut buf).unwrap();
+        let table = String::from_utf8(buf).unwrap();
+
+        assert!(table.lines().any(|l| l == "ffffffff99d1da80 ? consume_skb"));
+        assert!(table
+            .lines()
+            .any(|l| l.ends_with(" ? ovs_dst_get_mtu\t[openvswitch]")));
+
+        // The exported table can be parsed back.
+        let path = std::env::temp_dir().join(format!("retis-kallsyms-{}", std::process::id()));
+        std::fs::write(&path, &table).unwrap();
+        let kallsyms = super::Kallsyms::parse(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            kallsyms.symbols.get_by_right(&"consume_skb".to_string()),
+            Some(&0xffffffff99d1da80)
+        );
+    }
+
+    #[test]
+    fn symbol_module() {
+        let inspector = inspector();
+        assert_eq!(
+            inspector.get_symbol_module("ovs_dst_get_mtu").unwrap(),
+            Some("openvswitch".to_string())
+        );
+        assert_eq!(inspector.get_symbol_module("consume_skb").unwrap(), None);
+
+        assert_eq!(inspector.symbols_generation(), 0);
+        assert!(inspector.reload_symbols().is_ok());
+        assert!(inspector.get_symbol_addr("consume_skb").unwrap() == 0xffffffff99d1da80);
+        assert_eq!(inspector.symbols_generation(), 1);
+    }
+
+    #[test]
+    fn addr_module() {
+        let inspector = inspector();
+        assert_eq!(
+            inspector.get_addr_module(0xffffffffc0807100).unwrap(),
+            Some("zram".to_string())
+        );
+        assert_eq!(inspector.get_addr_module(0xffffffff99d1da80).unwrap(), None);
+    }
+
     #[test]
     fn test_bijection() {
         let symbol = "consume_skb";
@@ -424,6 +809,124 @@ mod tests {
         assert_eq!(sym_info.1, 0x0_u64);
     }
 
+    #[test]
+    fn names_from_addrs_near() {
+        let inspector = inspector();
+        let virtnet_stats = inspector.get_symbol_addr("virtnet_stats").unwrap();
+        let addrs = [
+            0xffffffff99d1da82,
+            virtnet_stats + 0x10,
+            0xffffffff99d1da80,
+            0xffffffff99d1da82,
+            0xffffffff99d1da80 - 1,
+        ];
+
+        let resolved = inspector.get_names_offt_from_addrs_near(&addrs).unwrap();
+        assert_eq!(resolved.len(), addrs.len());
+        assert_eq!(resolved[0], Some(("consume_skb".to_string(), 0x2)));
+        assert_eq!(resolved[1], Some(("virtnet_stats".to_string(), 0x10)));
+        assert_eq!(resolved[2], Some(("consume_skb".to_string(), 0x0)));
+        assert_eq!(resolved[3], resolved[0]);
+
+        // Results match the per-address resolution.
+        addrs.iter().zip(resolved.iter()).for_each(|(addr, sym)| {
+            assert_eq!(
+                inspector.get_name_offt_from_addr_near(*addr).ok().as_ref(),
+                sym.as_ref()
+            )
+        });
+    }
+
+    #[test]
+    fn matching_functions() {
+        let inspector = inspector();
+
+        assert_eq!(
+            inspector
+                .matching_functions("tdx_panic.constprop.0")
+                .unwrap(),
+            vec!["tdx_panic.constprop.0"]
+        );
+        assert_eq!(
+            inspector.matching_functions("tdx_panic.*").unwrap(),
+            vec!["tdx_panic.constprop.0"]
+        );
+        // '.' is not a wildcard.
+        assert!(inspector
+            .matching_functions("tdx.panic*")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn list_events() {
+        let inspector = inspector();
+
+        let events = inspector.list_events(None).unwrap();
+        assert_eq!(events.len(), 2574);
+        assert!(events.windows(2).all(|w| w[0] <= w[1]));
+
+        let skb = inspector.list_events(Some("skb")).unwrap();
+        assert!(skb.contains(&"skb:kfree_skb".to_string()));
+        assert!(skb.iter().all(|e| e.starts_with("skb:")));
+        assert!(inspector.list_events(Some("sk")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn list_functions() {
+        let inspector = inspector();
+
+        let funcs = inspector.list_functions(None).unwrap();
+        assert!(funcs.windows(2).all(|w| w[0] < w[1]));
+        assert!(funcs.contains(&"tcp_v4_rcv".to_string()));
+        // Blacklisted functions are removed.
+        assert!(!funcs.contains(&"kprobe_int3_handler".to_string()));
+        assert!(!funcs.contains(&"do_int3".to_string()));
+
+        let tcp = inspector.list_functions(Some("tcp_v4_rcv")).unwrap();
+        assert!(tcp.contains(&"tcp_v4_rcv".to_string()));
+        assert!(tcp.iter().all(|f| f.contains("tcp_v4_rcv")));
+        assert!(inspector
+            .list_functions(Some("tcp_v6_*"))
+            .unwrap()
+            .iter()
+            .all(|f| f.starts_with("tcp_v6_")));
+
+        let text = KernelInspector::kallsyms_text_symbols("test_data/kallsyms").unwrap();
+        assert!(text.contains(&"consume_skb".to_string()));
+        assert!(!text.contains(&"_sdata".to_string()));
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "test_cap_bpf"), ignore)]
+    fn list_live_events() {
+        let events = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"]
+            .iter()
+            .find_map(|dir| KernelInspector::file_to_hashset(format!("{dir}/available_events")))
+            .unwrap();
+        assert!(!KernelInspector::list_in_set(&events, None).is_empty());
+        assert!(!KernelInspector::list_in_set(&events, Some("skb")).is_empty());
+    }
+
+    #[test]
+    fn similar_symbols() {
+        let inspector = inspector();
+
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "skb"), 3);
+        assert_eq!(edit_distance("skb", "skb"), 0);
+
+        assert_eq!(
+            inspector.similar_symbols("tcp_v4_rvc", 3).first(),
+            Some(&"tcp_v4_rcv".to_string())
+        );
+        assert!(inspector.similar_symbols("skb:kfree_skbb", 3)[0] == "skb:kfree_skb");
+        assert!(inspector.similar_symbols("tcp_v4_rvc", 1).len() == 1);
+        assert!(inspector
+            .similar_symbols("not_a_kernel_function_at_all", 3)
+            .is_empty());
+    }
+
     #[test]
     fn kernel_config() {
         assert_eq!(
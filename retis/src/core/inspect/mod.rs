@@ -15,6 +15,10 @@ pub(crate) static BASE_TEST_DIR: &str = match (cfg!(test), cfg!(feature = "bench
 };
 
 mod btf;
+mod btf_cache;
+#[cfg(feature = "benchmark")]
+pub(crate) use btf::benchmark as btf_benchmark;
+pub(crate) use btf::ParamKind;
 pub(crate) mod check;
 mod kernel;
 pub(crate) mod kernel_version;
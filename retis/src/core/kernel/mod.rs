@@ -1,5 +1,7 @@
 //! # Kernel helpers
 
+pub(crate) mod modules;
+
 // Re-export symbol::Symbol.
 pub(crate) mod symbol;
 pub(crate) use symbol::Symbol;
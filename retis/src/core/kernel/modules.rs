@@ -0,0 +1,245 @@
+//! # Kernel modules
+//!
+//! Helpers to track kernel modules being loaded and unloaded at runtime and to
+//! retrieve information about the kernel and module images.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    ops::Bound::{Included, Unbounded},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use log::info;
+
+/// Watches the live kernel modules by polling a /proc/modules formatted file.
+pub(crate) struct ModuleWatcher {
+    /// Path to the modules file.
+    file: PathBuf,
+    /// Modules seen as live during the last poll.
+    live: HashSet<String>,
+}
+
+impl ModuleWatcher {
+    /// Create a new watcher on the running kernel modules.
+    pub(crate) fn new() -> Result<Self> {
+        Self::from_file("/proc/modules")
+    }
+
+    /// Create a new watcher on a /proc/modules formatted file.
+    pub(crate) fn from_file<P>(file: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = file.as_ref().to_path_buf();
+        let live = Self::read_live(&file)?;
+
+        Ok(Self { file, live })
+    }
+
+    /// Retrieve the modules in the live state. Modules being loaded or
+    /// unloaded are not reported, as their symbols can't be probed.
+    fn read_live(file: &Path) -> Result<HashSet<String>> {
+        Ok(fs::read_to_string(file)
+            .map_err(|e| anyhow!("Could not read {}: {e}", file.display()))?
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split(' ').collect();
+                match fields.get(4) {
+                    Some(&"Live") => Some(fields[0].to_string()),
+                    _ => None,
+                }
+            })
+            .collect())
+    }
+
+    /// Poll the modules and return the ones which (re)appeared since the last
+    /// poll.
+    pub(crate) fn poll(&mut self) -> Result<Vec<String>> {
+        let live = Self::read_live(&self.file)?;
+
+        self.live
+            .difference(&live)
+            .for_each(|module| info!("Module {module} was unloaded"));
+
+        let mut loaded = live.difference(&self.live).cloned().collect::<Vec<_>>();
+        loaded.sort();
+
+        self.live = live;
+        Ok(loaded)
+    }
+}
+
+/// Address ranges of the kernel modules, as found in a /proc/modules formatted
+/// file.
+#[derive(Default)]
+pub(crate) struct ModuleRanges {
+    /// Map of module start address -> (size, module name).
+    ranges: BTreeMap<u64, (u64, String)>,
+}
+
+impl ModuleRanges {
+    /// Parse the module ranges of a /proc/modules formatted file. Modules
+    /// without a valid address (e.g. when not having the rights to read them)
+    /// are ignored.
+    pub(crate) fn from_file<P>(file: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = file.as_ref();
+        let ranges = fs::read_to_string(file)
+            .map_err(|e| anyhow!("Could not read {}: {e}", file.display()))?
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split(' ').collect();
+                let size = fields.get(1)?.parse::<u64>().ok()?;
+                let addr = u64::from_str_radix(fields.get(5)?.strip_prefix("0x")?, 16).ok()?;
+
+                match addr {
+                    0 => None,
+                    _ => Some((addr, (size, fields[0].to_string()))),
+                }
+            })
+            .collect();
+
+        Ok(Self { ranges })
+    }
+
+    /// Return the module owning an address, if any.
+    pub(crate) fn get(&self, addr: u64) -> Option<&str> {
+        match self.ranges.range((Unbounded, Included(addr))).next_back() {
+            Some((start, (size, module))) if addr - start < *size => Some(module),
+            _ => None,
+        }
+    }
+}
+
+/// ELF note type of GNU build ids.
+const NT_GNU_BUILD_ID: u32 = 3;
+
+/// Retrieve the build id of the kernel image (`None`) or of a module, as an
+/// hex string. Returns `None` if the build id can't be found.
+pub(crate) fn build_id(module: Option<&str>) -> Option<String> {
+    let file = match module {
+        Some(module) => format!("/sys/module/{module}/notes/.note.gnu.build-id"),
+        None => "/sys/kernel/notes".to_string(),
+    };
+
+    parse_build_id(&fs::read(file).ok()?)
+}
+
+/// Parse the GNU build id out of a list of ELF notes.
+fn parse_build_id(mut notes: &[u8]) -> Option<String> {
+    let align = |len: usize| (len + 3) & !3;
+    let read_u32 = |data: &[u8], pos: usize| -> Option<u32> {
+        Some(u32::from_ne_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+    };
+
+    while notes.len() >= 12 {
+        let namesz = read_u32(notes, 0)? as usize;
+        let descsz = read_u32(notes, 4)? as usize;
+        let r#type = read_u32(notes, 8)?;
+
+        let name = notes.get(12..12 + namesz)?;
+        let desc_start = 12 + align(namesz);
+        let desc = notes.get(desc_start..desc_start + descsz)?;
+
+        if r#type == NT_GNU_BUILD_ID && name == b"GNU\0" {
+            return Some(desc.iter().map(|b| format!("{b:02x}")).collect());
+        }
+
+        notes = notes.get(desc_start + align(descsz)..)?;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_reload() {
+        let file = std::env::temp_dir().join(format!("retis-modules-{}", std::process::id()));
+        let live = "openvswitch 217088 2 - Live 0xffffffffc09f3000\n";
+
+        fs::write(
+            &file,
+            format!("{live}zram 57344 2 - Live 0xffffffffc0c35000\n"),
+        )
+        .unwrap();
+        let mut watcher = ModuleWatcher::from_file(&file).unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+
+        // Module disappears.
+        fs::write(&file, live).unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+
+        // Module is being loaded, it can't be probed yet.
+        fs::write(
+            &file,
+            format!("{live}zram 57344 0 - Loading 0xffffffffc0d21000\n"),
+        )
+        .unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+
+        // Module reappears.
+        fs::write(
+            &file,
+            format!("{live}zram 57344 2 - Live 0xffffffffc0d21000\n"),
+        )
+        .unwrap();
+        assert_eq!(watcher.poll().unwrap(), vec!["zram"]);
+        assert!(watcher.poll().unwrap().is_empty());
+
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn module_ranges() {
+        let file = std::env::temp_dir().join(format!("retis-ranges-{}", std::process::id()));
+        fs::write(
+            &file,
+            "openvswitch 217088 2 - Live 0xffffffffc09f3000\n\
+             zram 57344 2 - Live 0xffffffffc0c35000\n\
+             veth 45056 0 - Live 0x0000000000000000\n",
+        )
+        .unwrap();
+        let ranges = ModuleRanges::from_file(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+
+        assert_eq!(ranges.ranges.len(), 2);
+        assert_eq!(ranges.get(0xffffffffc09f3000), Some("openvswitch"));
+        assert_eq!(ranges.get(0xffffffffc0a27fff), Some("openvswitch"));
+        assert_eq!(ranges.get(0xffffffffc0a28000), None);
+        assert_eq!(ranges.get(0xffffffffc0c35042), Some("zram"));
+        assert_eq!(ranges.get(0xffffffff99d1da80), None);
+    }
+
+    fn note(name: &[u8], r#type: u32, desc: &[u8]) -> Vec<u8> {
+        let mut note = Vec::new();
+        note.extend_from_slice(&(name.len() as u32).to_ne_bytes());
+        note.extend_from_slice(&(desc.len() as u32).to_ne_bytes());
+        note.extend_from_slice(&r#type.to_ne_bytes());
+        note.extend_from_slice(name);
+        note.resize((note.len() + 3) & !3, 0);
+        note.extend_from_slice(desc);
+        note.resize((note.len() + 3) & !3, 0);
+        note
+    }
+
+    #[test]
+    fn build_id() {
+        let id = [0xde, 0xad, 0xbe, 0xef, 0x42];
+
+        let mut notes = note(b"Xen\0", 1, &[0; 8]);
+        notes.extend(note(b"GNU\0", 1, &[0; 3]));
+        notes.extend(note(b"GNU\0", NT_GNU_BUILD_ID, &id));
+        assert_eq!(parse_build_id(&notes).as_deref(), Some("deadbeef42"));
+
+        assert!(parse_build_id(&note(b"Xen\0", NT_GNU_BUILD_ID, &id)).is_none());
+        assert!(parse_build_id(&notes[..notes.len() - 8]).is_none());
+        assert!(parse_build_id(&[]).is_none());
+    }
+}
@@ -1,8 +1,8 @@
 use std::fmt;
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 
-use crate::core::inspect::inspector;
+use crate::core::inspect::{inspector, ParamKind};
 
 /// Kernel symbol representation. Only supports traceable symbols: events and
 /// functions.
@@ -22,7 +22,7 @@ impl Symbol {
             );
         }
 
-        if inspector()?.kernel.btf.find_prototype_btf(&self).is_err() {
+        if !inspector()?.kernel.btf.has_prototype(&self) {
             bail!(
                 "Symbol {} is not supported (no corresponding BTF definition)",
                 self
@@ -57,7 +57,7 @@ impl Symbol {
         // We had access to debugfs for inducing the symbol type and we didn't
         // find anything. The symbol isn't traceable.
         if debugfs {
-            bail!("Symbol {} does not exist or isn't traceable", name);
+            return Err(not_found(name));
         }
 
         // We couldn't induce the type with certainty, fallback to a
@@ -73,7 +73,7 @@ impl Symbol {
             })
             .is_err()
         {
-            bail!("Symbol {} does not exist or isn't traceable", name);
+            return Err(not_found(name));
         }
 
         Self::from_name_no_inspect(name).check()
@@ -196,6 +196,12 @@ impl Symbol {
     pub(crate) fn parameter_offset(&self, parameter_type: &str) -> Result<Option<u32>> {
         inspector()?.kernel.parameter_offset(self, parameter_type)
     }
+
+    /// Get a parameter offset and kind given its name. Unknown parameters are
+    /// reported as errors.
+    pub(crate) fn parameter_by_name(&self, name: &str) -> Result<(u32, ParamKind)> {
+        inspector()?.kernel.parameter_by_name(self, name)
+    }
 }
 
 /// Allow nice formatting when using a symbol in a log message.
@@ -209,10 +215,44 @@ impl fmt::Display for Symbol {
     }
 }
 
+/// Maximum number of symbols a pattern can be expanded to. Patterns matching
+/// more symbols are likely too broad, and attaching to that many probes would
+/// be slow and costly.
+const MAX_MATCHING_SYMBOLS: usize = 1024;
+
+/// Check the number of symbols a pattern expanded to is sane.
+fn check_matching(target: &str, kind: &str, matching: &[String]) -> Result<()> {
+    if matching.len() > MAX_MATCHING_SYMBOLS {
+        bail!(
+            "'{target}' matches {} {kind}, more than the limit of {MAX_MATCHING_SYMBOLS}: use a more specific pattern",
+            matching.len()
+        );
+    }
+    Ok(())
+}
+
+/// Build the error reported when a symbol can't be found, suggesting similar
+/// traceable symbols if any. This should only be called on the error path, as
+/// looking for suggestions is costly.
+fn not_found(name: &str) -> anyhow::Error {
+    let suggestions = inspector()
+        .map(|i| i.kernel.similar_symbols(name, 3))
+        .unwrap_or_default();
+
+    match suggestions.is_empty() {
+        true => anyhow!("Symbol {name} does not exist or isn't traceable"),
+        false => anyhow!(
+            "Symbol {name} does not exist or isn't traceable (did you mean {}?)",
+            suggestions.join(", ")
+        ),
+    }
+}
+
 pub(crate) fn matching_events_to_symbols(target: &str) -> Result<Vec<Symbol>> {
-    let symbols = inspector()?
-        .kernel
-        .matching_events(target)?
+    let matching = inspector()?.kernel.matching_events(target)?;
+    check_matching(target, "tracepoints", &matching)?;
+
+    let symbols = matching
         .iter()
         .filter_map(|t| Symbol::from_name(t).ok())
         .collect::<Vec<Symbol>>();
@@ -225,10 +265,10 @@ pub(crate) fn matching_events_to_symbols(target: &str) -> Result<Vec<Symbol>> {
 }
 
 pub(crate) fn matching_functions_to_symbols(target: &str) -> Result<Vec<Symbol>> {
-    let inspector = inspector()?;
-    let symbols = inspector
-        .kernel
-        .matching_functions(target)?
+    let matching = inspector()?.kernel.matching_functions(target)?;
+    check_matching(target, "functions", &matching)?;
+
+    let symbols = matching
         .iter()
         .filter_map(|t| Symbol::from_name(t).ok())
         .collect::<Vec<Symbol>>();
@@ -271,6 +311,30 @@ mod tests {
             .parameter_offset("struct net_device *")
             .unwrap()
             .is_none());
+
+        // Unknown symbols suggest similar ones.
+        let err = Symbol::from_name("tcp_v4_rvc").unwrap_err().to_string();
+        assert!(err.contains("did you mean tcp_v4_rcv"));
+        assert!(Symbol::from_name("skb:kfree_skbb")
+            .unwrap_err()
+            .to_string()
+            .contains("skb:kfree_skb"));
+    }
+
+    #[test]
+    fn matching_symbols() {
+        let symbols = matching_functions_to_symbols("tcp_v6_*").unwrap();
+        assert!(!symbols.is_empty());
+        assert!(symbols.iter().all(|s| s.name().starts_with("tcp_v6_")));
+
+        assert!(matching_events_to_symbols("skb:*").is_ok());
+
+        // Too broad patterns.
+        assert!(matching_functions_to_symbols("*").is_err());
+        assert!(matching_events_to_symbols("*").is_err());
+
+        // No match.
+        assert!(matching_functions_to_symbols("foobar_*").is_err());
     }
 
     #[test]
@@ -4,10 +4,15 @@
 //!
 use std::{
     ffi::OsStr,
+    io,
     os::fd::{BorrowedFd, RawFd},
+    thread,
+    time::Duration,
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
+use log::debug;
+use nix::errno::Errno;
 
 use crate::core::{filters::Filter, probe::*};
 
@@ -30,9 +35,102 @@ pub(super) trait ProbeBuilder {
     ) -> Result<()>;
     /// Attach a probe to a given target (function, tracepoint, etc).
     fn attach(&mut self, probe: &Probe) -> Result<()>;
+    /// Attach multiple probes at once. Builders can override this to share
+    /// resources between the probes (e.g. a loaded object).
+    fn attach_many(&mut self, probes: &[Probe]) -> Result<()> {
+        probes.iter().try_for_each(|p| self.attach(p))
+    }
     /// Detach all probes installed by the builder (function,
     /// tracepoint, etc).
     fn detach(&mut self) -> Result<()>;
+    /// Detach a single probe installed by the builder, e.g. because its
+    /// target went away. Builders not supporting it fail.
+    fn detach_probe(&mut self, probe: &Probe) -> Result<()> {
+        bail!("Detaching {probe} alone is not supported")
+    }
+    /// Set the policy used to retry attaching probes on transient failures.
+    /// Builders not supporting it ignore the policy.
+    fn set_retry(&mut self, _retry: RetryPolicy) {}
+}
+
+/// Policy used to retry an operation (e.g. attaching a probe) failing
+/// transiently, with an exponential backoff. No retry is made by default.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RetryPolicy {
+    /// Maximum number of retries.
+    pub(crate) retries: u32,
+    /// Delay before the first retry, doubled after each one.
+    pub(crate) backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            backoff: Duration::from_millis(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Run `op` until it succeeds, fails with a non-transient error or the
+    /// retries are exhausted. The last error is returned.
+    pub(crate) fn run<T, F>(&self, what: &str, mut op: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        let mut delay = self.backoff;
+        let mut attempt = 0;
+
+        loop {
+            match op() {
+                Ok(val) => return Ok(val),
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    attempt += 1;
+                    debug!(
+                        "Could not {what} ({e}), retrying in {delay:?} ({attempt}/{})",
+                        self.retries
+                    );
+                    thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                }
+                Err(e) if attempt > 0 => {
+                    return Err(
+                        e.context(format!("Could not {what} after {} attempts", attempt + 1))
+                    )
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Check if an error is transient, that is if retrying the operation might
+/// succeed (e.g. the kernel was temporarily short of resources).
+fn is_transient(e: &anyhow::Error) -> bool {
+    let transient = |errno: i32| {
+        matches!(
+            Errno::from_raw(errno),
+            Errno::EAGAIN | Errno::EBUSY | Errno::EINTR | Errno::ENOMEM
+        )
+    };
+
+    e.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<libbpf_rs::Error>() {
+            return matches!(
+                e.kind(),
+                libbpf_rs::ErrorKind::WouldBlock
+                    | libbpf_rs::ErrorKind::Interrupted
+                    | libbpf_rs::ErrorKind::OutOfMemory
+            );
+        }
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            return e.raw_os_error().is_some_and(transient);
+        }
+        cause
+            .downcast_ref::<Errno>()
+            .is_some_and(|e| transient(*e as i32))
+    })
 }
 
 pub(super) fn reuse_map_fds(
@@ -56,6 +154,22 @@ pub(super) fn reuse_map_fds(
     Ok(())
 }
 
+/// Check the hooks do not read more arguments than the probe has, as this
+/// would lead to garbage reads.
+pub(super) fn check_hooks_nargs(hooks: &[Hook], probe: &Probe, nargs: u32) -> Result<()> {
+    for (i, hook) in hooks.iter().enumerate() {
+        if let Some(expected) = hook.nargs {
+            if expected > nargs {
+                bail!(
+                    "Hook {} reads {expected} arguments but {probe} only has {nargs}",
+                    hook.name.clone().unwrap_or_else(|| format!("hook{i}"))
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
 pub(super) fn replace_hooks(fd: RawFd, hooks: &[Hook]) -> Result<Vec<libbpf_rs::Link>> {
     let mut links = Vec::new();
 
@@ -87,3 +201,71 @@ pub(super) fn replace_hooks(fd: RawFd, hooks: &[Hook]) -> Result<Vec<libbpf_rs::
     }
     Ok(links)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn retry() {
+        let retry = RetryPolicy {
+            retries: 3,
+            backoff: Duration::from_millis(1),
+        };
+        let attempts = Cell::new(0);
+
+        // Failing transiently then succeeding.
+        let res = retry.run("attach", || {
+            attempts.set(attempts.get() + 1);
+            match attempts.get() {
+                1 => Err(
+                    anyhow::Error::new(io::Error::from_raw_os_error(Errno::EAGAIN as i32))
+                        .context("Could not attach"),
+                ),
+                2 => Err(Errno::ENOMEM.into()),
+                _ => Ok(42),
+            }
+        });
+        assert_eq!(res.unwrap(), 42);
+        assert_eq!(attempts.get(), 3);
+
+        // Failing transiently, until the retries are exhausted.
+        attempts.set(0);
+        let res = retry.run("attach", || -> Result<()> {
+            attempts.set(attempts.get() + 1);
+            Err(Errno::EBUSY.into())
+        });
+        assert_eq!(attempts.get(), 4);
+        let err = res.unwrap_err();
+        assert_eq!(err.to_string(), "Could not attach after 4 attempts");
+        assert_eq!(err.root_cause().to_string(), Errno::EBUSY.to_string());
+
+        // Non-transient failures are not retried.
+        attempts.set(0);
+        let res = retry.run("attach", || -> Result<()> {
+            attempts.set(attempts.get() + 1);
+            Err(Errno::ENOENT.into())
+        });
+        assert!(res.is_err());
+        assert_eq!(attempts.get(), 1);
+
+        attempts.set(0);
+        let res = retry.run("attach", || -> Result<()> {
+            attempts.set(attempts.get() + 1);
+            bail!("Invalid program")
+        });
+        assert_eq!(res.unwrap_err().to_string(), "Invalid program");
+        assert_eq!(attempts.get(), 1);
+
+        // No retry by default.
+        attempts.set(0);
+        let res = RetryPolicy::default().run("attach", || -> Result<()> {
+            attempts.set(attempts.get() + 1);
+            Err(Errno::EAGAIN.into())
+        });
+        assert!(res.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -0,0 +1,99 @@
+#include <vmlinux.h>
+#include <bpf/bpf_helpers.h>
+
+#include <common.h>
+
+/* It is safe to have these values per-object as the loaded object won't be
+ * shared between attached programs for fentry/fexit: their attach target is
+ * set at load time.
+ */
+const volatile u64 ksym = 0;
+const volatile u32 nargs = 0;
+
+/* We unroll the loop bellow as the verifier disallow arithmetic operations on
+ * context pointer. The loop unrolling pragma doesn't work here, do it manually,
+ * keeping the "dynamic" fashion.
+ */
+static __always_inline void get_regs(struct retis_regs *regs, u64 *ctx)
+{
+#define arg_case(x)	\
+	case x:		\
+		regs->reg[x] = ctx[x];
+
+	if (!nargs)
+		return;
+
+	switch (nargs - 1) {
+	arg_case(11)
+	arg_case(10)
+	arg_case(9)
+	arg_case(8)
+	arg_case(7)
+	arg_case(6)
+	arg_case(5)
+	arg_case(4)
+	arg_case(3)
+	arg_case(2)
+	arg_case(1)
+	arg_case(0)
+	}
+
+	regs->num = nargs;
+}
+
+/* In fexit programs the return value directly follows the arguments. */
+static __always_inline u64 get_ret(u64 *ctx)
+{
+#define ret_case(x)	\
+	case x:		\
+		return ctx[x];
+
+	switch (nargs) {
+	ret_case(0)
+	ret_case(1)
+	ret_case(2)
+	ret_case(3)
+	ret_case(4)
+	ret_case(5)
+	ret_case(6)
+	ret_case(7)
+	ret_case(8)
+	ret_case(9)
+	ret_case(10)
+	ret_case(11)
+	ret_case(12)
+	}
+
+	return 0;
+}
+
+SEC("fentry/probe")
+int probe_fentry(u64 *ctx)
+{
+	struct retis_context context = {};
+
+	context.timestamp = bpf_ktime_get_ns();
+	context.ksym = ksym;
+	context.probe_type = KERNEL_PROBE_FENTRY;
+	context.orig_ctx = ctx;
+	get_regs(&context.regs, ctx);
+
+	return chain(&context);
+}
+
+SEC("fexit/probe")
+int probe_fexit(u64 *ctx)
+{
+	struct retis_context context = {};
+
+	context.timestamp = bpf_ktime_get_ns();
+	context.ksym = ksym;
+	context.probe_type = KERNEL_PROBE_FEXIT;
+	context.orig_ctx = ctx;
+	get_regs(&context.regs, ctx);
+	context.regs.ret = get_ret(ctx);
+
+	return chain(&context);
+}
+
+char __license[] SEC("license") = "GPL";
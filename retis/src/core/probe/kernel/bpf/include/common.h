@@ -13,18 +13,63 @@
 #include <meta_filter.h>
 #include <skb_tracking.h>
 
+/* Kernel raw event sections. */
+enum kernel_sections {
+	KERNEL_SECTION_CORE = 0,
+	KERNEL_SECTION_ARGS,
+	KERNEL_SECTION_ERROR,
+} __binding;
+
 /* Kernel section of the event data. */
 struct kernel_event {
 	u64 symbol;
 	long stack_id;
+	/* return value, only valid for kretprobes and fexit probes. */
+	long ret;
 	/* values from enum kernel_probe_type */
 	u8 type;
+	/* time elapsed since the function entry, in ns. Only valid for
+	 * kretprobes, 0 if the entry wasn't seen.
+	 */
+	u64 latency;
+} __binding;
+
+/* Kernel section holding the raw value of the probe arguments requested in
+ * retis_probe_config.args; others are zeroed.
+ */
+struct kernel_args_event {
+	u64 args[REG_MAX];
+	/* number of arguments of the probe. */
+	u8 nargs;
 } __binding;
 
+/* Kernel section reporting a hook failing while processing the event. Only
+ * the first failure is reported.
+ */
+struct kernel_error_event {
+	/* error code returned by the hook. */
+	s32 code;
+	/* index of the hook in the probe hook list. */
+	u8 hook;
+} __binding;
+
+/* Cpu filter; keep in sync with its Rust counterpart in
+ * crate::core::probe::kernel::config.
+ */
+#define CPU_FILTER_MAX 256
+
 /* Per-probe configuration. */
 struct retis_probe_config {
 	struct retis_probe_offsets offsets;
 	u8 stack_trace;
+	/* only report events from the tasks in pid_filter_map. */
+	u8 pid_filter;
+	/* bitmask of the arguments to report (bit N for argument N). */
+	u16 args;
+	/* only report events from the cpus set in cpus. */
+	u8 cpu_filter;
+	/* bitmask of the cpus to report events from (bit N for cpu N). */
+	u64 cpus[CPU_FILTER_MAX / 64];
 } __binding;
 
 /* Probe configuration; the key is the target symbol address */
@@ -35,6 +80,21 @@ struct {
 	__type(value, struct retis_probe_config);
 } config_map SEC(".maps");
 
+/* Pid filter; keep in sync with its Rust counterpart in
+ * crate::core::probe::kernel::config.
+ */
+#define PID_FILTER_MAX 1024
+
+/* Pids (tgid or tid) events are reported for, on probes having the pid filter
+ * enabled. The map is shared by all probes.
+ */
+struct {
+	__uint(type, BPF_MAP_TYPE_HASH);
+	__uint(max_entries, PID_FILTER_MAX);
+	__type(key, u32);
+	__type(value, u8);
+} pid_filter_map SEC(".maps");
+
 /* Probe stack trace map. */
 struct {
 	__uint(type, BPF_MAP_TYPE_STACK_TRACE);
@@ -192,10 +252,42 @@ static __always_inline void filter(struct retis_context *ctx)
 	 * we have to rely on the value returned into the context.
 	 */
 filter_outcome:
-	ctx->filters_ret |= (!!fctx.ret) << RETIS_F_PACKET_PASS_SH;
+	/* Packet and meta filters are combined (packet AND meta) and evaluated
+	 * in sequence: the packet filter first, as it is the cheapest, then the
+	 * meta one only if the packet matched. Hooks requiring both filters to
+	 * match (RETIS_ALL_FILTERS) are skipped as soon as one of them fails.
+	 */
+	if (!fctx.ret)
+		return;
+	ctx->filters_ret |= RETIS_F_PACKET_PASS;
 	ctx->filters_ret |= (!!meta_filter(skb)) << RETIS_F_META_PASS_SH;
 }
 
+/* Check if the current task is part of the pid filter, using either its
+ * process (tgid) or thread (tid) id. This is best effort: in some contexts
+ * (e.g. softirqs) the current task is whatever task was interrupted and is not
+ * related to the event.
+ */
+static __always_inline bool pid_filter(void)
+{
+	u64 pid_tgid = bpf_get_current_pid_tgid();
+	u32 tgid = pid_tgid >> 32, tid = (u32)pid_tgid;
+
+	return bpf_map_lookup_elem(&pid_filter_map, &tgid) ||
+	       bpf_map_lookup_elem(&pid_filter_map, &tid);
+}
+
+/* Check if the current cpu is part of the probe cpu filter. */
+static __always_inline bool cpu_filter(struct retis_probe_config *cfg)
+{
+	u32 cpu = bpf_get_smp_processor_id();
+
+	if (cpu >= CPU_FILTER_MAX)
+		return false;
+
+	return cfg->cpus[cpu / 64] & (1ULL << (cpu % 64));
+}
+
 /* The chaining function, which contains all our core probe logic. This is
  * called from each probe specific part after filling the common context and
  * just before returning.
@@ -212,6 +304,8 @@ static __always_inline int chain(struct retis_context *ctx)
 	volatile u16 pass_threshold;
 	struct common_event *e;
 	struct kernel_event *k;
+	u8 err_hook = 0;
+	int err = 0;
 
 	/* Check if the collection is enabled, otherwise bail out. Once we have
 	 * a positive result, cache it.
@@ -226,6 +320,14 @@ static __always_inline int chain(struct retis_context *ctx)
 	if (!cfg)
 		return 0;
 
+	/* Drop events from non-matching tasks early, before doing any
+	 * expensive work.
+	 */
+	if (cfg->pid_filter && !pid_filter())
+		return 0;
+	if (cfg->cpu_filter && !cpu_filter(cfg))
+		return 0;
+
 	ctx->offsets = cfg->offsets;
 
 	filter(ctx);
@@ -266,17 +368,37 @@ static __always_inline int chain(struct retis_context *ctx)
 	ti->pid = bpf_get_current_pid_tgid();
 	bpf_get_current_comm(ti->comm, sizeof(ti->comm));
 
-	k = get_event_section(event, KERNEL, 0, sizeof(*k));
+	k = get_event_section(event, KERNEL, KERNEL_SECTION_CORE, sizeof(*k));
 	if (!k)
 		goto discard_event;
 
 	k->symbol = ctx->ksym;
 	k->type = ctx->probe_type;
+	k->ret = ctx->probe_type == KERNEL_PROBE_KRETPROBE ||
+		 ctx->probe_type == KERNEL_PROBE_FEXIT ? ctx->regs.ret : 0;
+	k->latency = ctx->entry_timestamp &&
+		     ctx->timestamp > ctx->entry_timestamp ?
+		     ctx->timestamp - ctx->entry_timestamp : 0;
 	if (cfg->stack_trace)
 		k->stack_id = bpf_get_stackid(ctx->orig_ctx, &stack_map, BPF_F_FAST_STACK_CMP);
 	else
 		k->stack_id = -1;
 
+	if (cfg->args) {
+		struct kernel_args_event *a;
+		int i;
+
+		a = get_event_zsection(event, KERNEL, KERNEL_SECTION_ARGS, sizeof(*a));
+		if (!a)
+			goto discard_event;
+
+		for (i = 0; i < REG_MAX; i++) {
+			if (cfg->args & (1 << i) && i < ctx->regs.num)
+				a->args[i] = ctx->regs.reg[i];
+		}
+		a->nargs = ctx->regs.num;
+	}
+
 	pass_threshold = get_event_size(event);
 	barrier_var(pass_threshold);
 
@@ -287,6 +409,9 @@ static __always_inline int chain(struct retis_context *ctx)
  * found. The use case is to let hooks do some filtering otherwise we can end up
  * being flooded with events in some cases as w/o this hooks can only filter
  * themselves.
+ *
+ * Other errors do not stop the chain, but the first one is recorded so it can
+ * be reported to userspace.
  */
 #define ENOMSG	42
 #define CALL_HOOK(x)				\
@@ -294,6 +419,10 @@ static __always_inline int chain(struct retis_context *ctx)
 		int ret = hook##x(ctx, event);	\
 		if (ret == -ENOMSG)		\
 			goto discard_event;	\
+		if (ret < 0 && !err) {		\
+			err = ret;		\
+			err_hook = x;		\
+		}				\
 	}
 	CALL_HOOK(0)
 	CALL_HOOK(1)
@@ -306,6 +435,20 @@ static __always_inline int chain(struct retis_context *ctx)
 	CALL_HOOK(8)
 	CALL_HOOK(9)
 
+	/* Hook failures are always reported, even if no data was added to the
+	 * event, so they do not go unnoticed.
+	 */
+	if (unlikely(err)) {
+		struct kernel_error_event *kerr;
+
+		kerr = get_event_section(event, KERNEL, KERNEL_SECTION_ERROR,
+					 sizeof(*kerr));
+		if (kerr) {
+			kerr->code = err;
+			kerr->hook = err_hook;
+		}
+	}
+
 	if (get_event_size(event) > pass_threshold)
 		send_event(event);
 	else
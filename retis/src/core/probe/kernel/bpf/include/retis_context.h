@@ -10,6 +10,8 @@ enum kernel_probe_type {
 	KERNEL_PROBE_KPROBE = 0,
 	KERNEL_PROBE_KRETPROBE = 1,
 	KERNEL_PROBE_TRACEPOINT = 2,
+	KERNEL_PROBE_FENTRY = 3,
+	KERNEL_PROBE_FEXIT = 4,
 };
 
 /**
@@ -50,6 +52,8 @@ struct retis_regs {
  * timestamp: Timestamp of when the probe wall called, should be filled as early
  *	    as possible in the probe specific part. Then it should be left
  *	    untouched.
+ * entry_timestamp: Timestamp of the matching function entry, for return
+ *	    probes. 0 if unknown.
  * ksym:      Symbol address of the where the probe was hooked. Should also be
  *	    filled in the probe specific part. It is quite handy as it is the
  *	    only common way of understanding where a probe/hook is running.
@@ -61,6 +65,7 @@ struct retis_regs {
 struct retis_context {
 	enum kernel_probe_type probe_type;
 	u64 timestamp;
+	u64 entry_timestamp;
 	u64 ksym;
 	struct retis_probe_offsets offsets;
 	struct retis_regs regs;
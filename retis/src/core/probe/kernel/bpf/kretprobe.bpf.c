@@ -53,6 +53,7 @@ int probe_kretprobe_kretprobe(struct pt_regs *ctx)
 	bpf_map_delete_elem(&kretprobe_context, &tid);
 
 	context.timestamp = bpf_ktime_get_ns();
+	context.entry_timestamp = kprobe_ctx->timestamp;
 	context.ksym = kprobe_ctx->ksym;
 	context.probe_type = KERNEL_PROBE_KRETPROBE;
 	context.orig_ctx = ctx;
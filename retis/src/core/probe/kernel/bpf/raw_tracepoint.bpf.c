@@ -4,9 +4,13 @@
 #include <common.h>
 
 /* It is safe to have these values per-object as the loaded object won't be
- * shared between attached programs for raw tracepoints.
+ * shared between attached programs for raw tracepoints, unless ksym_cookie is
+ * set. In that case the object is shared between raw tracepoints having the
+ * same number of arguments and the symbol address is retrieved from the attach
+ * cookie (requires Linux >= 6.10).
  */
 const volatile u64 ksym = 0;
+const volatile u8 ksym_cookie = 0;
 const volatile u32 nargs = 0;
 
 /* We unroll the loop bellow as the verifier disallow arithmetic operations on
@@ -47,7 +51,11 @@ int probe_raw_tracepoint(struct bpf_raw_tracepoint_args *ctx)
 	struct retis_context context = {};
 
 	context.timestamp = bpf_ktime_get_ns();
-	context.ksym = ksym;
+	/* Constant at load time, the unused branch is pruned by the verifier. */
+	if (ksym_cookie)
+		context.ksym = bpf_get_attach_cookie(ctx);
+	else
+		context.ksym = ksym;
 	context.probe_type = KERNEL_PROBE_TRACEPOINT;
 	context.orig_ctx = ctx;
 	get_regs(&context.regs, ctx);
@@ -0,0 +1,85 @@
+#include <vmlinux.h>
+#include <bpf/bpf_helpers.h>
+
+#include <common.h>
+
+/* It is safe to have these values per-object as the loaded object won't be
+ * shared between attached programs for tracepoints.
+ *
+ * Classic tracepoints do not give access to their arguments but to a record
+ * whose layout is described in the tracefs format file. The fields to use as
+ * probe arguments are described by their offset in the record and their size
+ * (in bytes). Fields are zero-extended, or sign-extended if signed is set.
+ */
+const volatile u64 ksym = 0;
+const volatile u32 nargs = 0;
+const volatile u16 field_offset[REG_MAX] = { 0 };
+const volatile u8 field_size[REG_MAX] = { 0 };
+const volatile u8 field_signed[REG_MAX] = { 0 };
+
+static __always_inline u64 get_field(void *ctx, u32 x)
+{
+	u8 size = field_size[x];
+	u64 val = 0;
+
+	if (size == 0 || size > sizeof(val))
+		return 0;
+
+	/* The verifier disallows accessing the context at a non-constant
+	 * offset, read the field using a helper.
+	 */
+	if (bpf_probe_read_kernel(&val, size, ctx + field_offset[x]))
+		return 0;
+
+	/* Little endian: the value is already zero-extended. */
+	if (field_signed[x] && size < sizeof(val)) {
+		u8 shift = (sizeof(val) - size) * 8;
+		val = (u64)((s64)(val << shift) >> shift);
+	}
+
+	return val;
+}
+
+/* Unrolled manually for the same reason as in raw_tracepoint.bpf.c. */
+static __always_inline void get_regs(struct retis_regs *regs, void *ctx)
+{
+#define arg_case(x)	\
+	case x:		\
+		regs->reg[x] = get_field(ctx, x);
+
+	if (!nargs)
+		return;
+
+	switch (nargs - 1) {
+	arg_case(11)
+	arg_case(10)
+	arg_case(9)
+	arg_case(8)
+	arg_case(7)
+	arg_case(6)
+	arg_case(5)
+	arg_case(4)
+	arg_case(3)
+	arg_case(2)
+	arg_case(1)
+	arg_case(0)
+	}
+
+	regs->num = nargs;
+}
+
+SEC("tracepoint/probe")
+int probe_tracepoint(void *ctx)
+{
+	struct retis_context context = {};
+
+	context.timestamp = bpf_ktime_get_ns();
+	context.ksym = ksym;
+	context.probe_type = KERNEL_PROBE_TRACEPOINT;
+	context.orig_ctx = ctx;
+	get_regs(&context.regs, ctx);
+
+	return chain(&context);
+}
+
+char __license[] SEC("license") = "GPL";
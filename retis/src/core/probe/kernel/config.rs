@@ -22,6 +22,33 @@ pub(crate) fn init_config_map() -> Result<libbpf_rs::MapHandle> {
     )?)
 }
 
+/// Maximum number of pids in the pid filter. Please keep in sync with its BPF
+/// counterpart in core/probe/kernel/bpf/include/common.h
+pub(crate) const PID_FILTER_MAX: usize = 1024;
+
+/// Maximum number of cpus (ids from 0 to CPU_FILTER_MAX - 1) the cpu filter
+/// can hold. Please keep in sync with its BPF counterpart in
+/// core/probe/kernel/bpf/include/common.h
+pub(crate) const CPU_FILTER_MAX: u32 = 256;
+
+// When testing this isn't used as the pid filter map is hidden.
+#[cfg_attr(test, allow(dead_code))]
+pub(crate) fn init_pid_filter_map() -> Result<libbpf_rs::MapHandle> {
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: mem::size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+
+    Ok(libbpf_rs::MapHandle::create(
+        libbpf_rs::MapType::Hash,
+        Some("pid_filter_map"),
+        mem::size_of::<u32>() as u32,
+        mem::size_of::<u8>() as u32,
+        PID_FILTER_MAX as u32,
+        &opts,
+    )?)
+}
+
 #[cfg(not(test))]
 pub(crate) fn init_stack_map() -> Result<libbpf_rs::MapHandle> {
     const MAX_STACKTRACE_ENTRIES: u32 = 256;
@@ -0,0 +1,183 @@
+//! # Fentry
+//!
+//! Module to handle attaching programs to kernel functions using BPF
+//! trampolines (fentry/fexit). Those have a lower overhead than kprobes and
+//! give access to all the function arguments. The module is split in two
+//! parts, the Rust code (here) and the eBPF one (bpf/fentry.bpf.c and its
+//! auto-generated part in bpf/.out/).
+//!
+//! The attach target of fentry/fexit programs is set at load time, so a new
+//! object is loaded for each probe.
+
+use std::{
+    collections::HashMap,
+    mem::MaybeUninit,
+    os::fd::{AsFd, AsRawFd, RawFd},
+};
+
+use anyhow::{anyhow, bail, Result};
+use libbpf_rs::{
+    skel::{OpenSkel, Skel, SkelBuilder},
+    ProgramAttachType,
+};
+
+use crate::core::{
+    filters::Filter,
+    inspect::{inspector, kernel_version::KernelVersionReq},
+    probe::builder::*,
+    probe::*,
+};
+
+mod fentry_bpf {
+    include!("bpf/.out/fentry.skel.rs");
+}
+use fentry_bpf::FentrySkelBuilder;
+
+#[derive(Default)]
+pub(crate) struct FentryBuilder {
+    hooks: Vec<Hook>,
+    filters: Vec<Filter>,
+    /// Links of the probes, by probe key.
+    links: HashMap<String, Vec<libbpf_rs::Link>>,
+    map_fds: Vec<(String, RawFd)>,
+}
+
+impl FentryBuilder {
+    /// Check the running kernel supports BPF trampolines.
+    fn check_support() -> Result<()> {
+        let kver = inspector()?.kernel.version();
+        if !KernelVersionReq::parse(">= 5.5")?.matches(kver) {
+            bail!(
+                "Fentry/fexit probes are not supported on kernel {} (requires >= 5.5)",
+                kver.full
+            );
+        }
+        Ok(())
+    }
+}
+
+impl ProbeBuilder for FentryBuilder {
+    fn new() -> FentryBuilder {
+        FentryBuilder::default()
+    }
+
+    fn init(
+        &mut self,
+        map_fds: Vec<(String, RawFd)>,
+        hooks: Vec<Hook>,
+        filters: Vec<Filter>,
+    ) -> Result<()> {
+        self.map_fds = map_fds;
+        self.hooks = hooks;
+        self.filters = filters;
+
+        Ok(())
+    }
+
+    fn attach(&mut self, probe: &Probe) -> Result<()> {
+        let (kp, prog_name, attach_type) = match probe.r#type() {
+            ProbeType::Fentry(kp) => (kp, "probe_fentry", ProgramAttachType::TraceFentry),
+            ProbeType::Fexit(kp) => (kp, "probe_fexit", ProgramAttachType::TraceFexit),
+            _ => bail!("Wrong probe type {}", probe),
+        };
+
+        Self::check_support()?;
+
+        // Fail early if hooks read more arguments than the function has.
+        let nargs = kp.symbol.nargs().map_err(|e| {
+            anyhow!(
+                "Could not get BTF information for {}, required by fentry/fexit probes: {e}",
+                kp.symbol
+            )
+        })?;
+        check_hooks_nargs(&self.hooks, probe, nargs)?;
+
+        let mut open_object = MaybeUninit::uninit();
+        let mut skel = FentrySkelBuilder::default().open(&mut open_object)?;
+
+        skel.maps.rodata_data.ksym = kp.symbol.addr()?;
+        skel.maps.rodata_data.nargs = nargs;
+        skel.maps.rodata_data.nhooks = self.hooks.len() as u32;
+        skel.maps.rodata_data.log_level = log::max_level() as u8;
+
+        self.filters.iter().for_each(|f| {
+            if let Filter::Meta(m) = f {
+                skel.maps.rodata_data.nmeta = m.0.len() as u32
+            }
+        });
+
+        // Only load the program matching the probe type and set its target.
+        for mut prog in skel.open_object_mut().progs_mut() {
+            if prog.name() != prog_name {
+                prog.set_autoload(false);
+                continue;
+            }
+
+            prog.set_attach_type(attach_type);
+            prog.set_attach_target(0, Some(kp.symbol.attach_name()))?;
+        }
+
+        reuse_map_fds(skel.open_object_mut(), &self.map_fds)?;
+
+        let skel = skel.load()?;
+        let prog = skel
+            .object()
+            .progs_mut()
+            .find(|p| p.name() == prog_name)
+            .ok_or_else(|| anyhow!("Couldn't get program"))?;
+
+        let mut links = replace_hooks(prog.as_fd().as_raw_fd(), &self.hooks)?;
+        links.push(prog.attach_trace()?);
+
+        self.links.insert(probe.key(), links);
+        Ok(())
+    }
+
+    fn detach(&mut self) -> Result<()> {
+        self.links.clear();
+        Ok(())
+    }
+
+    fn detach_probe(&mut self, probe: &Probe) -> Result<()> {
+        self.links.remove(&probe.key());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use super::*;
+
+    use crate::core::{
+        filters::{fixup_filter_load_fn, register_filter_handler},
+        kernel::Symbol,
+    };
+
+    #[test]
+    #[serial(libbpf)]
+    #[cfg_attr(not(feature = "test_cap_bpf"), ignore)]
+    fn init_and_attach() {
+        let _ = register_filter_handler(
+            "fentry/probe",
+            libbpf_rs::ProgramType::Tracing,
+            Some(fixup_filter_load_fn),
+        );
+        let _ = register_filter_handler(
+            "fexit/probe",
+            libbpf_rs::ProgramType::Tracing,
+            Some(fixup_filter_load_fn),
+        );
+
+        let mut builder = FentryBuilder::new();
+
+        assert!(builder.init(Vec::new(), Vec::new(), Vec::new()).is_ok());
+        assert!(builder
+            .attach(&Probe::fentry(Symbol::from_name("kfree_skb_reason").unwrap()).unwrap())
+            .is_ok());
+        assert!(builder
+            .attach(&Probe::fexit(Symbol::from_name("consume_skb").unwrap()).unwrap())
+            .is_ok());
+    }
+}
@@ -2,19 +2,23 @@
 
 use std::{collections::HashMap, fmt};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 #[cfg(not(test))]
 use libbpf_rs::MapCore;
 
-use super::inspect::inspect_symbol;
+use super::{config::CPU_FILTER_MAX, inspect::inspect_symbol};
 use crate::{
-    bindings::common_uapi::{kernel_event, retis_probe_config},
+    bindings::common_uapi::{
+        kernel_args_event, kernel_error_event, kernel_event, kernel_sections, retis_probe_config,
+        KERNEL_SECTION_ARGS, KERNEL_SECTION_CORE, KERNEL_SECTION_ERROR,
+    },
     core::{
         events::{
-            parse_single_raw_section, BpfRawSection, EventSectionFactory, FactoryId,
+            parse_raw_section, BpfRawSection, EventSectionFactory, FactoryId,
             RawEventSectionFactory,
         },
-        kernel::Symbol,
+        inspect::{inspector, ParamKind},
+        kernel::{modules::build_id, Symbol},
         probe::{
             common::{Counters, CountersKey},
             ProbeOption,
@@ -26,30 +30,61 @@ use crate::{
 
 // Split to exclude from tests.
 #[cfg(not(test))]
-use crate::{core::inspect::inspector, events::kernel::StackTrace};
+use crate::events::kernel::StackTrace;
+
+/// Maximum number of entries in the stack frames cache.
+const FRAMES_CACHE_MAX: usize = 8192;
 
 /// Kernel encapsulates all the information about a kernel probe (kprobe or tracepoint) needed to attach to it.
 #[derive(Clone)]
 pub(crate) struct KernelProbe {
     pub(crate) symbol: Symbol,
+    /// Maximum number of concurrent instances of kretprobes. Uses the kernel
+    /// default if not set.
+    pub(crate) maxactive: Option<u32>,
+    /// Record fields loaded as the probe arguments, in order, for classic
+    /// tracepoints.
+    pub(crate) fields: Vec<String>,
 }
 
 impl KernelProbe {
     pub(crate) fn new(symbol: Symbol) -> Result<Self> {
-        Ok(KernelProbe { symbol })
+        Ok(KernelProbe {
+            symbol,
+            maxactive: None,
+            fields: Vec::new(),
+        })
     }
 
     /// Generate the probe BPF configuration from a list of options.
     pub(crate) fn gen_config(&self, options: &[ProbeOption]) -> Result<retis_probe_config> {
         let mut config = inspect_symbol(&self.symbol)?;
 
-        #[allow(clippy::single_match)]
-        options.iter().for_each(|o| match o {
-            ProbeOption::StackTrace => {
-                config.stack_trace = 1;
+        options.iter().try_for_each(|o| -> Result<()> {
+            match o {
+                ProbeOption::StackTrace => {
+                    config.stack_trace = 1;
+                }
+                ProbeOption::PidFilter(_) => {
+                    config.pid_filter = 1;
+                }
+                ProbeOption::CpuFilter(cpus) => {
+                    config.cpu_filter = 1;
+                    cpus.iter().try_for_each(|cpu| -> Result<()> {
+                        if *cpu >= CPU_FILTER_MAX {
+                            bail!("Cpu {cpu} is out of the filter range (max {CPU_FILTER_MAX})");
+                        }
+                        config.cpus[(cpu / 64) as usize] |= 1 << (cpu % 64);
+                        Ok(())
+                    })?;
+                }
+                ProbeOption::Args(args) => {
+                    config.args = *args;
+                }
+                _ => (),
             }
-            _ => (),
-        });
+            Ok(())
+        })?;
 
         Ok(config)
     }
@@ -72,18 +107,234 @@ impl fmt::Display for KernelProbe {
     }
 }
 
+/// Probe argument to report in events, decoded using its BTF type.
+#[derive(Clone, Debug)]
+pub(crate) struct ProbeArg {
+    pub(crate) name: String,
+    /// Index of the argument in the probe context.
+    pub(crate) index: u32,
+    kind: ParamKind,
+}
+
+impl ProbeArg {
+    /// Maximum number of arguments which can be reported, see REG_MAX in
+    /// retis_context.h.
+    pub(crate) const MAX: u32 = 12;
+
+    /// Resolve a probe argument given the probed symbol and the argument name.
+    pub(crate) fn from_name(symbol: &Symbol, name: &str) -> Result<Self> {
+        let (index, kind) = symbol.parameter_by_name(name)?;
+        if index >= Self::MAX {
+            bail!("Argument {name} of {symbol} can't be reported (index {index} is too large)");
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            index,
+            kind,
+        })
+    }
+
+    /// Decode the raw value of the argument.
+    fn decode(&self, raw: u64) -> KernelArg {
+        // Truncate a raw value to the given size, in bytes, sign extending it
+        // if needed.
+        let int = |size: usize, signed: bool| -> KernelArg {
+            let shift = 64 - 8 * size.clamp(1, 8) as u32;
+            match signed {
+                true => KernelArg::Signed(((raw << shift) as i64) >> shift),
+                false => KernelArg::Unsigned((raw << shift) >> shift),
+            }
+        };
+
+        match &self.kind {
+            ParamKind::Int { size, signed } => int(*size, *signed),
+            ParamKind::Enum { signed, values } => match values.get(&(raw & 0xffffffff)) {
+                Some(name) => KernelArg::String(name.clone()),
+                None => int(4, *signed),
+            },
+            ParamKind::Raw => KernelArg::String(format!("{raw:#x}")),
+        }
+    }
+}
+
 #[event_section_factory(FactoryId::Kernel)]
 #[derive(Default)]
 pub(crate) struct KernelEventFactory {
     #[cfg(not(test))]
     pub(crate) stack_map: Option<libbpf_rs::MapHandle>,
+    /// Also report the raw address of symbolized stack frames.
+    pub(crate) raw_stack: bool,
+    /// Also report stack frames in a structured form.
+    pub(crate) stack_frames: bool,
+    /// Collapse consecutive identical frames of symbolized stacks.
+    pub(crate) dedup_stack: bool,
+    /// Maximum number of stack frames to report, 0 means unlimited.
+    pub(crate) stack_depth: usize,
+    /// Arguments to report, by probed symbol address.
+    pub(crate) args: HashMap<u64, Vec<ProbeArg>>,
+    /// Number of arguments to report the raw value of, for all probes.
+    pub(crate) args_raw: usize,
     // Cache of symbol addr -> name
     symbols_cache: HashMap<u64, String>,
+    // Cache of module (None for the kernel image) -> build id
+    build_ids_cache: HashMap<Option<String>, Option<String>>,
+    // Cache of stack frame addr -> nearest symbol & offset
+    frames_cache: HashMap<u64, Option<(String, u64)>>,
+    // Symbols generation the frames cache was filled with
+    frames_cache_gen: u64,
 }
 
 impl KernelEventFactory {
+    /// Get the frames of a stack, stopping at the first empty one or after
+    /// `max_depth` frames (0 means unlimited).
+    fn frames(sstack: &[u64], max_depth: usize) -> impl Iterator<Item = &u64> {
+        sstack
+            .iter()
+            .take_while(|sym| **sym != 0x00_u64)
+            .take(match max_depth {
+                0 => usize::MAX,
+                _ => max_depth,
+            })
+    }
+
+    /// Resolve the nearest symbol and offset of stack frames. Frames not
+    /// found in the cache are resolved in a single batch.
+    fn resolve_frames<'a, I>(&mut self, frames: I) -> Result<Vec<(u64, Option<(String, u64)>)>>
+    where
+        I: Iterator<Item = &'a u64>,
+    {
+        let kernel = &inspector()?.kernel;
+
+        // Keep the cache bounded; hot addresses will quickly make it back. It
+        // is also invalidated when the symbols are reloaded.
+        let gen = kernel.symbols_generation();
+        if self.frames_cache.len() >= FRAMES_CACHE_MAX || self.frames_cache_gen != gen {
+            self.frames_cache.clear();
+            self.frames_cache_gen = gen;
+        }
+
+        let frames = frames.copied().collect::<Vec<_>>();
+        let missing = frames
+            .iter()
+            .filter(|addr| !self.frames_cache.contains_key(addr))
+            .copied()
+            .collect::<Vec<_>>();
+
+        if !missing.is_empty() {
+            let resolved = kernel.get_names_offt_from_addrs_near(&missing)?;
+            self.frames_cache.extend(missing.into_iter().zip(resolved));
+        }
+
+        Ok(frames
+            .into_iter()
+            .map(|addr| (addr, self.frames_cache.get(&addr).cloned().flatten()))
+            .collect())
+    }
+
+    /// Symbolize a single stack frame, given its nearest symbol and offset.
+    /// Frames in modules are prefixed with the module name, `[module]
+    /// symbol+offset`. Frames which can't be symbolized are reported as raw
+    /// addresses.
+    fn symbolize_frame(&self, addr: u64, sym: Option<(String, u64)>) -> Result<String> {
+        let kernel = &inspector()?.kernel;
+        // Module owning the address, according to the module ranges.
+        let module = kernel.get_addr_module(addr)?;
+
+        let (symbol, offset) = match sym {
+            Some(sym) => sym,
+            None => {
+                return Ok(match module {
+                    Some(module) => format!("[{module}] {addr:#x}"),
+                    None => format!("{addr:#x}"),
+                })
+            }
+        };
+
+        let frame = match (module, kernel.get_symbol_module(&symbol)?) {
+            // The nearest symbol does not belong to the module owning the
+            // address (e.g. the module symbols are not all exposed in
+            // kallsyms), it can't be trusted.
+            (Some(module), sym_module) if sym_module.as_ref() != Some(&module) => {
+                return Ok(format!("[{module}] {addr:#x}"));
+            }
+            (_, Some(module)) => format!("[{module}] {symbol}+{offset:#x}"),
+            (_, None) => format!("{symbol}+{offset:#x}"),
+        };
+
+        Ok(match self.raw_stack {
+            true => format!("{frame} ({addr:#x})"),
+            false => frame,
+        })
+    }
+
+    /// Symbolize the frames of a stack, stopping at the first empty one or
+    /// after `max_depth` frames (0 means unlimited).
+    fn symbolize_stack(&mut self, sstack: &[u64], max_depth: usize) -> Result<Vec<String>> {
+        let mut stack_trace: Vec<String> = Vec::new();
+
+        for (addr, sym) in self.resolve_frames(Self::frames(sstack, max_depth))? {
+            stack_trace.push(self.symbolize_frame(addr, sym)?);
+        }
+
+        Ok(match self.dedup_stack {
+            true => Self::dedup_stack(stack_trace),
+            false => stack_trace,
+        })
+    }
+
+    /// Collapse consecutive identical frames of a symbolized stack into a
+    /// single one, suffixed with the number of repetitions: `frame (xN)`.
+    fn dedup_stack(stack_trace: Vec<String>) -> Vec<String> {
+        let mut frames: Vec<(String, usize)> = Vec::new();
+
+        for frame in stack_trace.into_iter() {
+            match frames.last_mut() {
+                Some((last, count)) if *last == frame => *count += 1,
+                _ => frames.push((frame, 1)),
+            }
+        }
+
+        frames
+            .into_iter()
+            .map(|(frame, count)| match count {
+                1 => frame,
+                _ => format!("{frame} (x{count})"),
+            })
+            .collect()
+    }
+
+    /// Convert the frames of a stack to their structured form, stopping at
+    /// the first empty one or after `max_depth` frames (0 means unlimited).
+    fn stack_frames(&mut self, sstack: &[u64], max_depth: usize) -> Result<Vec<StackFrame>> {
+        let inspector = inspector()?;
+        let mut frames = Vec::new();
+
+        for (addr, sym) in self.resolve_frames(Self::frames(sstack, max_depth))? {
+            let mut frame = StackFrame {
+                addr,
+                ..Default::default()
+            };
+
+            if let Some((symbol, offset)) = sym {
+                let module = inspector.kernel.get_symbol_module(&symbol)?;
+                frame.build_id = self
+                    .build_ids_cache
+                    .entry(module)
+                    .or_insert_with_key(|module| build_id(module.as_deref()))
+                    .clone();
+                frame.symbol = Some(symbol);
+                frame.offset = Some(offset);
+            }
+
+            frames.push(frame);
+        }
+
+        Ok(frames)
+    }
+
     #[cfg(not(test))]
-    fn unmarshal_stackid(&self, event: &mut KernelEvent, stackid: i32) -> Result<()> {
+    fn unmarshal_stackid(&mut self, event: &mut KernelEvent, stackid: i32) -> Result<()> {
         if stackid >= 0 {
             let mut stack_trace: Vec<String> = Vec::new();
             // Only stack_map.lookup() gets intentionally performed. This means that at some point
@@ -101,15 +352,9 @@ impl KernelEventFactory {
                     )
                 };
 
-                for sym in sstack {
-                    if *sym == 0x00_u64 {
-                        break;
-                    }
-
-                    match inspector()?.kernel.get_name_offt_from_addr_near(*sym) {
-                        Ok((symbol, offset)) => stack_trace.push(format!("{symbol}+{offset:#x}")),
-                        Err(_) => stack_trace.push(format!("{sym:#x}")),
-                    }
+                stack_trace = self.symbolize_stack(sstack, self.stack_depth)?;
+                if self.stack_frames {
+                    event.stack_frames = Some(self.stack_frames(sstack, self.stack_depth)?);
                 }
             }
 
@@ -121,7 +366,24 @@ impl KernelEventFactory {
 
 impl RawEventSectionFactory for KernelEventFactory {
     fn create(&mut self, raw_sections: Vec<BpfRawSection>) -> Result<Box<dyn EventSection>> {
-        let raw = parse_single_raw_section::<kernel_event>(&raw_sections)?;
+        let mut raw = None;
+        let mut raw_args = None;
+        let mut raw_error = None;
+
+        for section in raw_sections.iter() {
+            match section.header.data_type as kernel_sections {
+                KERNEL_SECTION_CORE => raw = Some(parse_raw_section::<kernel_event>(section)?),
+                KERNEL_SECTION_ARGS => {
+                    raw_args = Some(parse_raw_section::<kernel_args_event>(section)?)
+                }
+                KERNEL_SECTION_ERROR => {
+                    raw_error = Some(parse_raw_section::<kernel_error_event>(section)?)
+                }
+                x => bail!("Unknown data type ({x})"),
+            }
+        }
+
+        let raw = raw.ok_or_else(|| anyhow!("No kernel section found"))?;
         let mut event = KernelEvent::default();
 
         let symbol_addr = raw.symbol;
@@ -135,12 +397,47 @@ impl RawEventSectionFactory for KernelEventFactory {
         };
 
         event.probe_type = match raw.type_ {
-            0 => "kprobe",
-            1 => "kretprobe",
-            2 => "raw_tracepoint",
+            0 => ProbeType::Kprobe,
+            1 => {
+                event.ret = Some(raw.ret as i64);
+                if raw.latency > 0 {
+                    event.latency_ns = Some(raw.latency);
+                }
+                ProbeType::Kretprobe
+            }
+            2 => ProbeType::RawTracepoint,
+            3 => ProbeType::Fentry,
+            4 => {
+                event.ret = Some(raw.ret as i64);
+                ProbeType::Fexit
+            }
             x => bail!("Unknown probe type {x}"),
+        };
+
+        if let Some(raw_args) = raw_args.filter(|_| self.args_raw > 0) {
+            let nargs = self.args_raw.min(raw_args.nargs as usize);
+            event.args_raw = Some(raw_args.args[..nargs].to_vec());
+        }
+
+        if let (Some(raw_args), Some(args)) = (raw_args, self.args.get(&symbol_addr)) {
+            event.args = Some(
+                args.iter()
+                    .map(|arg| {
+                        (
+                            arg.name.clone(),
+                            arg.decode(raw_args.args[arg.index as usize]),
+                        )
+                    })
+                    .collect(),
+            );
+        }
+
+        if let Some(raw_error) = raw_error {
+            event.error = Some(KernelHookError {
+                code: raw_error.code,
+                hook: raw_error.hook,
+            });
         }
-        .to_string();
 
         #[cfg(not(test))]
         self.unmarshal_stackid(&mut event, raw.stack_id as i32)?;
@@ -149,6 +446,424 @@ impl RawEventSectionFactory for KernelEventFactory {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::events::BpfRawSectionHeader;
+
+    fn as_bytes<T>(raw: &T) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(raw as *const T as *const u8, std::mem::size_of::<T>())
+        }
+    }
+
+    fn create_with(
+        factory: &mut KernelEventFactory,
+        raw: kernel_event,
+        args: Option<kernel_args_event>,
+    ) -> Result<KernelEvent> {
+        let mut sections = vec![BpfRawSection {
+            header: BpfRawSectionHeader::default(),
+            data: as_bytes(&raw),
+        }];
+        if let Some(args) = args.as_ref() {
+            sections.push(BpfRawSection {
+                header: BpfRawSectionHeader {
+                    data_type: KERNEL_SECTION_ARGS as u8,
+                    ..Default::default()
+                },
+                data: as_bytes(args),
+            });
+        }
+
+        let section = factory.create(sections)?;
+        Ok(section
+            .as_any()
+            .downcast_ref::<KernelEvent>()
+            .unwrap()
+            .clone())
+    }
+
+    fn create_raw(raw: kernel_event) -> Result<KernelEvent> {
+        create_with(&mut KernelEventFactory::default(), raw, None)
+    }
+
+    fn create(type_: u8, ret: i64) -> KernelEvent {
+        create_raw(kernel_event {
+            // consume_skb
+            symbol: 0xffffffff99d1da80,
+            stack_id: -1,
+            ret,
+            type_,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn hook_error() {
+        let raw = kernel_event {
+            // consume_skb
+            symbol: 0xffffffff99d1da80,
+            stack_id: -1,
+            ..Default::default()
+        };
+        let raw_error = kernel_error_event { code: -2, hook: 1 };
+        let sections = vec![
+            BpfRawSection {
+                header: BpfRawSectionHeader::default(),
+                data: as_bytes(&raw),
+            },
+            BpfRawSection {
+                header: BpfRawSectionHeader {
+                    data_type: KERNEL_SECTION_ERROR as u8,
+                    ..Default::default()
+                },
+                data: as_bytes(&raw_error),
+            },
+        ];
+
+        let section = KernelEventFactory::default().create(sections).unwrap();
+        let event = section.as_any().downcast_ref::<KernelEvent>().unwrap();
+        assert_eq!(event.symbol, "consume_skb");
+        assert_eq!(event.error, Some(KernelHookError { code: -2, hook: 1 }));
+        assert!(format!(
+            "{}",
+            event.display(&DisplayFormat::new(), &FormatterConf::new())
+        )
+        .ends_with(" error -2 (hook 1)"));
+
+        // No error is reported by default.
+        assert!(create(0, 0).error.is_none());
+    }
+
+    #[test]
+    fn pid_filter() {
+        let probe = KernelProbe::new(Symbol::from_name("consume_skb").unwrap()).unwrap();
+        assert_eq!(probe.gen_config(&[]).unwrap().pid_filter, 0);
+        assert_eq!(
+            probe
+                .gen_config(&[ProbeOption::PidFilter(vec![42])])
+                .unwrap()
+                .pid_filter,
+            1
+        );
+    }
+
+    #[test]
+    fn cpu_filter() {
+        let probe = KernelProbe::new(Symbol::from_name("consume_skb").unwrap()).unwrap();
+        let config = probe.gen_config(&[]).unwrap();
+        assert_eq!(config.cpu_filter, 0);
+        assert_eq!(config.cpus, [0; 4]);
+
+        let config = probe
+            .gen_config(&[ProbeOption::CpuFilter(vec![0, 3, 64, 255])])
+            .unwrap();
+        assert_eq!(config.cpu_filter, 1);
+        assert_eq!(config.cpus, [0x9, 0x1, 0, 1 << 63]);
+
+        assert!(probe
+            .gen_config(&[ProbeOption::CpuFilter(vec![CPU_FILTER_MAX])])
+            .is_err());
+    }
+
+    #[test]
+    fn stack_depth() {
+        // consume_skb
+        let sstack = [
+            0xffffffff99d1da80,
+            0xffffffff99d1da81,
+            0xffffffff99d1da82,
+            0,
+        ];
+        let mut factory = KernelEventFactory::default();
+        assert_eq!(
+            factory.symbolize_stack(&sstack, 0).unwrap(),
+            vec!["consume_skb+0x0", "consume_skb+0x1", "consume_skb+0x2"]
+        );
+        assert_eq!(
+            factory.symbolize_stack(&sstack, 2).unwrap(),
+            vec!["consume_skb+0x0", "consume_skb+0x1"]
+        );
+        // Frames are cached.
+        assert_eq!(factory.frames_cache.len(), 3);
+    }
+
+    #[test]
+    fn module_stack() {
+        let mut factory = KernelEventFactory::default();
+        let kernel = &inspector().unwrap().kernel;
+        let virtnet_stats = kernel.get_symbol_addr("virtnet_stats").unwrap();
+        let init_xfs_fs = kernel.get_symbol_addr("init_xfs_fs").unwrap();
+
+        // consume_skb, virtnet_stats (virtio_net module, from kallsyms only),
+        // init_xfs_fs (xfs module, from kallsyms and the module ranges) and an
+        // address in the zram module (from the module ranges) whose nearest
+        // symbol belongs to another module.
+        let sstack = [
+            0xffffffff99d1da82,
+            virtnet_stats + 0x10,
+            init_xfs_fs + 0x20,
+            0xffffffffc0807100,
+            0,
+        ];
+        assert_eq!(
+            factory.symbolize_stack(&sstack, 0).unwrap(),
+            vec![
+                "consume_skb+0x2",
+                "[virtio_net] virtnet_stats+0x10",
+                "[xfs] init_xfs_fs+0x20",
+                "[zram] 0xffffffffc0807100",
+            ]
+        );
+    }
+
+    #[test]
+    fn raw_stack() {
+        let mut factory = KernelEventFactory {
+            raw_stack: true,
+            ..Default::default()
+        };
+
+        // consume_skb
+        let sstack = [0xffffffff99d1da80, 0xffffffff99d1da82, 0];
+        assert_eq!(
+            factory.symbolize_stack(&sstack, 0).unwrap(),
+            vec![
+                "consume_skb+0x0 (0xffffffff99d1da80)",
+                "consume_skb+0x2 (0xffffffff99d1da82)",
+            ]
+        );
+    }
+
+    #[test]
+    fn dedup_stack() {
+        let mut factory = KernelEventFactory {
+            dedup_stack: true,
+            ..Default::default()
+        };
+
+        // consume_skb, with repeated frames.
+        let sstack = [
+            0xffffffff99d1da80,
+            0xffffffff99d1da82,
+            0xffffffff99d1da82,
+            0xffffffff99d1da82,
+            0xffffffff99d1da80,
+            0xffffffff99d1da82,
+            0xffffffff99d1da82,
+            0,
+        ];
+        assert_eq!(
+            factory.symbolize_stack(&sstack, 0).unwrap(),
+            vec![
+                "consume_skb+0x0",
+                "consume_skb+0x2 (x3)",
+                "consume_skb+0x0",
+                "consume_skb+0x2 (x2)",
+            ]
+        );
+
+        // The depth limit applies to the raw stack.
+        assert_eq!(
+            factory.symbolize_stack(&sstack, 3).unwrap(),
+            vec!["consume_skb+0x0", "consume_skb+0x2 (x2)"]
+        );
+
+        // Raw traces are kept by default.
+        assert_eq!(
+            KernelEventFactory::default()
+                .symbolize_stack(&sstack, 4)
+                .unwrap()
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn stack_frames() {
+        let mut factory = KernelEventFactory::default();
+
+        // consume_skb, ovs_dp_upcall (openvswitch module).
+        let ovs_dp_upcall = inspector()
+            .unwrap()
+            .kernel
+            .get_symbol_addr("ovs_dp_upcall")
+            .unwrap();
+        let sstack = [0xffffffff99d1da82, ovs_dp_upcall + 0x10, 0];
+
+        let frames = factory.stack_frames(&sstack, 0).unwrap();
+        assert_eq!(frames.len(), 2);
+
+        assert_eq!(frames[0].addr, 0xffffffff99d1da82);
+        assert_eq!(frames[0].symbol.as_deref(), Some("consume_skb"));
+        assert_eq!(frames[0].offset, Some(0x2));
+        assert_eq!(frames[0].build_id, build_id(None));
+
+        assert_eq!(frames[1].addr, ovs_dp_upcall + 0x10);
+        assert_eq!(frames[1].symbol.as_deref(), Some("ovs_dp_upcall"));
+        assert_eq!(frames[1].offset, Some(0x10));
+        assert_eq!(frames[1].build_id, build_id(Some("openvswitch")));
+
+        assert_eq!(factory.stack_frames(&sstack, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn kretprobe_ret() {
+        let event = create(1, -22);
+        assert_eq!(event.symbol, "consume_skb");
+        assert_eq!(event.probe_type, ProbeType::Kretprobe);
+        assert_eq!(event.ret, Some(-22));
+
+        // The return value is only reported for kretprobes and fexit probes.
+        let event = create(0, -22);
+        assert_eq!(event.probe_type, ProbeType::Kprobe);
+        assert_eq!(event.ret, None);
+
+        let event = create(3, -22);
+        assert_eq!(event.probe_type, ProbeType::Fentry);
+        assert_eq!(event.ret, None);
+
+        let event = create(4, -22);
+        assert_eq!(event.probe_type, ProbeType::Fexit);
+        assert_eq!(event.ret, Some(-22));
+    }
+
+    #[test]
+    fn probe_type() {
+        assert_eq!(create(2, 0).probe_type, ProbeType::RawTracepoint);
+        // The serialized form is kept as a plain string.
+        assert_eq!(
+            serde_json::to_value(create(2, 0)).unwrap()["probe_type"],
+            "raw_tracepoint"
+        );
+
+        // Unknown probe types are reported as errors.
+        assert!(create_raw(kernel_event {
+            symbol: 0xffffffff99d1da80,
+            stack_id: -1,
+            type_: 42,
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn kretprobe_latency() {
+        let raw = kernel_event {
+            symbol: 0xffffffff99d1da80,
+            stack_id: -1,
+            type_: 1,
+            latency: 1500,
+            ..Default::default()
+        };
+        assert_eq!(create_raw(raw).unwrap().latency_ns, Some(1500));
+
+        // The function entry wasn't seen.
+        assert_eq!(create(1, 0).latency_ns, None);
+        // Only kretprobes report a latency.
+        assert_eq!(
+            create_raw(kernel_event { type_: 0, ..raw })
+                .unwrap()
+                .latency_ns,
+            None
+        );
+    }
+
+    #[test]
+    fn probe_args() {
+        let symbol = Symbol::from_name("skb:kfree_skb").unwrap();
+        let probe = KernelProbe::new(symbol.clone()).unwrap();
+        assert_eq!(probe.gen_config(&[]).unwrap().args, 0);
+        assert_eq!(
+            probe.gen_config(&[ProbeOption::Args(0b101)]).unwrap().args,
+            0b101
+        );
+
+        // Arguments are validated against the tracepoint prototype.
+        assert!(ProbeArg::from_name(&symbol, "foo").is_err());
+
+        let mut factory = KernelEventFactory::default();
+        factory.args.insert(
+            symbol.addr().unwrap(),
+            vec![
+                ProbeArg::from_name(&symbol, "skb").unwrap(),
+                ProbeArg::from_name(&symbol, "reason").unwrap(),
+            ],
+        );
+
+        let raw = kernel_event {
+            symbol: symbol.addr().unwrap(),
+            stack_id: -1,
+            type_: 2,
+            ..Default::default()
+        };
+        let mut raw_args = kernel_args_event::default();
+        raw_args.args[0] = 0xffff888104a5c700;
+        raw_args.args[2] = 2;
+
+        let event = create_with(&mut factory, raw, Some(raw_args)).unwrap();
+        let args = event.args.unwrap();
+        assert_eq!(args.len(), 2);
+        assert_eq!(
+            args["skb"],
+            KernelArg::String("0xffff888104a5c700".to_string())
+        );
+        assert_eq!(
+            args["reason"],
+            KernelArg::String("SKB_DROP_REASON_NOT_SPECIFIED".to_string())
+        );
+
+        // Unknown enum values are reported as numbers.
+        raw_args.args[2] = 0xffff;
+        let event = create_with(&mut factory, raw, Some(raw_args)).unwrap();
+        assert_eq!(event.args.unwrap()["reason"], KernelArg::Unsigned(0xffff));
+
+        // No argument is reported if the section is missing.
+        assert!(create_with(&mut factory, raw, None).unwrap().args.is_none());
+
+        // Raw values are not reported unless asked for.
+        raw_args.nargs = 2;
+        raw_args.args[1] = 0xffff8881032a4000;
+        assert!(create_with(&mut factory, raw, Some(raw_args))
+            .unwrap()
+            .args_raw
+            .is_none());
+
+        // Only the arguments the probe has are reported.
+        factory.args_raw = 3;
+        assert_eq!(
+            create_with(&mut factory, raw, Some(raw_args))
+                .unwrap()
+                .args_raw,
+            Some(vec![0xffff888104a5c700, 0xffff8881032a4000])
+        );
+        factory.args_raw = 1;
+        assert_eq!(
+            create_with(&mut factory, raw, Some(raw_args))
+                .unwrap()
+                .args_raw,
+            Some(vec![0xffff888104a5c700])
+        );
+        factory.args_raw = 0;
+
+        // Integers are truncated to their size and sign extended.
+        let arg = |size, signed| ProbeArg {
+            name: "foo".to_string(),
+            index: 0,
+            kind: ParamKind::Int { size, signed },
+        };
+        assert_eq!(arg(4, true).decode(0xffffffff), KernelArg::Signed(-1));
+        assert_eq!(
+            arg(4, false).decode(0x1_ffffffff),
+            KernelArg::Unsigned(0xffffffff)
+        );
+        assert_eq!(arg(8, true).decode(u64::MAX), KernelArg::Signed(-1));
+        assert_eq!(arg(1, false).decode(0x1ff), KernelArg::Unsigned(0xff));
+    }
+}
+
 #[cfg(feature = "benchmark")]
 pub(crate) mod benchmark {
     use anyhow::Result;
@@ -165,6 +880,8 @@ pub(crate) mod benchmark {
                 symbol: Symbol::from_name("openvswitch:ovs_do_execute_action")?.addr()?,
                 type_: 2, // Raw tracepoint.
                 stack_id: -1,
+                ret: 0,
+                latency: 0,
             };
             build_raw_section(out, FactoryId::Kernel as u8, 0, &mut as_u8_vec(&data));
             Ok(())
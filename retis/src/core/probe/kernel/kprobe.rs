@@ -4,7 +4,10 @@
 //! in two parts, the Rust code (here) and the eBPF one (bpf/kprobe.bpf.c and
 //! its auto-generated part in bpf/.out/).
 
-use std::os::fd::{AsFd, AsRawFd, RawFd};
+use std::{
+    collections::HashMap,
+    os::fd::{AsFd, AsRawFd, RawFd},
+};
 
 use anyhow::{anyhow, bail, Result};
 use libbpf_rs::skel::{OpenSkel, Skel};
@@ -19,6 +22,8 @@ use kprobe_bpf::*;
 #[derive(Default)]
 pub(crate) struct KprobeBuilder<'a> {
     links: Vec<libbpf_rs::Link>,
+    /// Links of the probes, by probe key.
+    probe_links: HashMap<String, libbpf_rs::Link>,
     skel: Option<SkelStorage<KprobeSkel<'a>>>,
 }
 
@@ -70,12 +75,14 @@ impl<'a> ProbeBuilder for KprobeBuilder<'a> {
             Some(skel) => skel.object(),
             _ => bail!("Kprobe builder is uninitialized"),
         };
+        let key = probe.key();
         let probe = match probe.r#type() {
             ProbeType::Kprobe(probe) => probe,
             _ => bail!("Wrong probe type {}", probe),
         };
 
-        self.links.push(
+        self.probe_links.insert(
+            key,
             obj.progs_mut()
                 .find(|p| p.name() == "probe_kprobe")
                 .ok_or_else(|| anyhow!("Couldn't get program"))?
@@ -85,22 +92,59 @@ impl<'a> ProbeBuilder for KprobeBuilder<'a> {
     }
 
     fn detach(&mut self) -> Result<()> {
+        // Links must be removed before the skeleton they're attached to.
+        self.probe_links.clear();
         self.links.drain(..);
+        self.skel = None;
+        Ok(())
+    }
+
+    fn detach_probe(&mut self, probe: &Probe) -> Result<()> {
+        self.probe_links.remove(&probe.key());
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        io::Write,
+        net::{TcpListener, TcpStream, UdpSocket},
+        thread,
+        time::Duration,
+    };
+
+    use libbpf_rs::{MapCore, MapFlags};
     use serial_test::serial;
 
     use super::*;
 
     use crate::core::{
-        filters::{fixup_filter_load_fn, register_filter_handler},
+        filters::{
+            fixup_filter_load_fn, meta::filter::init_meta_map, register_filter,
+            register_filter_handler, CombinedFilter,
+        },
         kernel::Symbol,
+        probe::{
+            common::{init_global_config_map, GlobalConfig},
+            kernel::config::{init_config_map, CPU_FILTER_MAX},
+        },
+        tracking::skb_tracking::tracking_map,
     };
 
+    fn set_mark(fd: RawFd, mark: u32) {
+        let ret = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_MARK,
+                &mark as *const u32 as *const libc::c_void,
+                std::mem::size_of::<u32>() as libc::socklen_t,
+            )
+        };
+        assert_eq!(ret, 0);
+    }
+
     #[test]
     #[serial(libbpf)]
     #[cfg_attr(not(feature = "test_cap_bpf"), ignore)]
@@ -121,4 +165,182 @@ mod tests {
             .attach(&Probe::kprobe(Symbol::from_name("consume_skb").unwrap()).unwrap())
             .is_ok());
     }
+
+    #[test]
+    #[serial(libbpf)]
+    #[cfg_attr(not(feature = "test_cap_bpf"), ignore)]
+    fn detach_and_reattach() {
+        let _ = register_filter_handler(
+            "kprobe/probe",
+            libbpf_rs::ProgramType::Kprobe,
+            Some(fixup_filter_load_fn),
+        );
+
+        let probe = Probe::kprobe(Symbol::from_name("consume_skb").unwrap()).unwrap();
+        let mut builder = KprobeBuilder::new();
+
+        for _ in 0..2 {
+            assert!(builder.init(Vec::new(), Vec::new(), Vec::new()).is_ok());
+            assert!(builder.attach(&probe).is_ok());
+
+            assert!(builder.detach().is_ok());
+            assert!(builder.links.is_empty() && builder.probe_links.is_empty());
+            assert!(builder.skel.is_none());
+            assert!(builder.attach(&probe).is_err());
+        }
+    }
+
+    #[test]
+    #[serial(libbpf)]
+    #[cfg_attr(not(feature = "test_cap_bpf"), ignore)]
+    fn attach_on_cpus() {
+        let _ = register_filter_handler(
+            "kprobe/probe",
+            libbpf_rs::ProgramType::Kprobe,
+            Some(fixup_filter_load_fn),
+        );
+
+        let mut builder = KprobeBuilder::new();
+        assert!(builder.init(Vec::new(), Vec::new(), Vec::new()).is_ok());
+
+        let mut probe = Probe::kprobe(Symbol::from_name("consume_skb").unwrap()).unwrap();
+        assert!(probe.on_cpus(&[]).is_err());
+        assert!(probe.on_cpus(&[CPU_FILTER_MAX]).is_err());
+        assert!(probe.on_cpus(&[1, 0]).is_ok());
+        assert!(probe.on_cpus(&[1]).is_ok());
+        assert!(probe
+            .options()
+            .contains(&ProbeOption::CpuFilter(vec![0, 1])));
+
+        assert!(builder.attach(&probe).is_ok());
+    }
+
+    #[test]
+    #[serial(libbpf)]
+    #[cfg_attr(not(feature = "test_cap_bpf"), ignore)]
+    fn attach_with_combined_filter() {
+        let _ = register_filter_handler(
+            "kprobe/probe",
+            libbpf_rs::ProgramType::Kprobe,
+            Some(fixup_filter_load_fn),
+        );
+
+        // Both the packet programs and the meta ops are loaded along with
+        // the probe, and accepted by the verifier.
+        let filters = CombinedFilter::new(Some("tcp"), Some("sk_buff.mark == 1"))
+            .unwrap()
+            .filters()
+            .unwrap();
+        filters.iter().for_each(|f| {
+            if let Filter::Packet(magic, _) = f {
+                let _ = register_filter(*magic, f);
+            }
+        });
+
+        let mut builder = KprobeBuilder::new();
+        assert!(builder.init(Vec::new(), Vec::new(), filters).is_ok());
+        assert!(builder
+            .attach(&Probe::kprobe(Symbol::from_name("kfree_skb_reason").unwrap()).unwrap())
+            .is_ok());
+    }
+
+    #[test]
+    #[serial(libbpf)]
+    #[cfg_attr(not(feature = "test_cap_bpf"), ignore)]
+    fn combined_filter_short_circuit() {
+        let _ = register_filter_handler(
+            "kprobe/probe",
+            libbpf_rs::ProgramType::Kprobe,
+            Some(fixup_filter_load_fn),
+        );
+
+        // Packet filter (tcp) AND meta filter (mark == 1).
+        let meta_map = init_meta_map().unwrap();
+        let filters = CombinedFilter::new(Some("tcp"), Some("sk_buff.mark == 1"))
+            .unwrap()
+            .filters()
+            .unwrap();
+        filters.iter().for_each(|f| match f {
+            Filter::Packet(magic, _) => {
+                let _ = register_filter(*magic, f);
+            }
+            Filter::Meta(ops) => ops.0.iter().enumerate().for_each(|(pos, op)| {
+                meta_map
+                    .update(
+                        &(pos as u32).to_ne_bytes(),
+                        unsafe { plain::as_bytes(op) },
+                        MapFlags::ANY,
+                    )
+                    .unwrap()
+            }),
+        });
+
+        // The outcome of the filters is observed through skb tracking, which
+        // only starts for skbs whose filters_ret has RETIS_ALL_FILTERS set.
+        let config_map = init_config_map().unwrap();
+        let global_config_map = init_global_config_map().unwrap();
+        let tracking_map = tracking_map().unwrap();
+
+        let probe = Probe::kprobe(Symbol::from_name("ip_output").unwrap()).unwrap();
+        let kp = match probe.r#type() {
+            ProbeType::Kprobe(kp) => kp,
+            _ => panic!("not a kprobe"),
+        };
+        config_map
+            .update(
+                &kp.symbol.addr().unwrap().to_ne_bytes(),
+                unsafe { plain::as_bytes(&kp.gen_config(&[]).unwrap()) },
+                MapFlags::ANY,
+            )
+            .unwrap();
+
+        let mut builder = KprobeBuilder::new();
+        let map_fds = [
+            ("config_map", config_map.as_fd()),
+            ("global_config_map", global_config_map.as_fd()),
+            ("filter_meta_map", meta_map.as_fd()),
+            ("tracking_map", tracking_map.as_fd()),
+        ]
+        .iter()
+        .map(|(name, fd)| (name.to_string(), fd.as_raw_fd()))
+        .collect();
+        assert!(builder.init(map_fds, Vec::new(), filters).is_ok());
+        assert!(builder.attach(&probe).is_ok());
+
+        let config = GlobalConfig { enabled: 1 };
+        global_config_map
+            .update(&[0], unsafe { plain::as_bytes(&config) }, MapFlags::ANY)
+            .unwrap();
+
+        let tracked = |send: &mut dyn FnMut()| {
+            send();
+            thread::sleep(Duration::from_millis(100));
+            tracking_map.keys().count()
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut tcp = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let _peer = listener.accept().unwrap();
+
+        // (a) tcp skb with mark != 1: the packet filter matches, the meta one
+        // does not.
+        set_mark(tcp.as_raw_fd(), 2);
+        assert_eq!(tracked(&mut || tcp.write_all(b"retis").unwrap()), 0);
+
+        // (b) udp skb with mark == 1: the packet filter does not match and the
+        // meta one is not evaluated.
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        set_mark(udp.as_raw_fd(), 1);
+        let dst = UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert_eq!(
+            tracked(&mut || {
+                udp.send_to(b"retis", dst.local_addr().unwrap()).unwrap();
+            }),
+            0
+        );
+
+        // tcp skb with mark == 1: both filters match.
+        set_mark(tcp.as_raw_fd(), 1);
+        assert!(tracked(&mut || tcp.write_all(b"retis").unwrap()) > 0);
+    }
 }
@@ -0,0 +1,163 @@
+//! # Kprobe events
+//!
+//! Helpers to create kprobes using the tracefs kprobe_events interface. This
+//! is only used for features the perf-based kprobes used by libbpf do not
+//! support, e.g. setting the maximum number of kretprobe instances
+//! (maxactive).
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{Error, Write},
+    mem,
+    os::fd::{FromRawFd, OwnedFd},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Result};
+use log::warn;
+
+/// Group of the kprobe events created by Retis.
+const GROUP: &str = "retis";
+/// Maximum value of maxactive accepted by the kernel.
+pub(crate) const KRETPROBE_MAXACTIVE_MAX: u32 = 4096;
+
+/// perf_event_attr type for tracepoints (including kprobe events).
+const PERF_TYPE_TRACEPOINT: u32 = 2;
+/// perf_event_open flag to set close-on-exec on the returned fd.
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 8;
+
+/// First published version of `struct perf_event_attr` (PERF_ATTR_SIZE_VER0),
+/// which is all we need to open a tracepoint perf event.
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    r#type: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+/// Get the tracefs mount point.
+fn tracefs() -> Result<&'static str> {
+    ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"]
+        .into_iter()
+        .find(|path| Path::new(path).join("kprobe_events").exists())
+        .ok_or_else(|| anyhow!("Could not find the tracefs kprobe_events file"))
+}
+
+/// Write a command to the kprobe_events file.
+fn kprobe_events_write(cmd: &str) -> Result<()> {
+    let file = format!("{}/kprobe_events", tracefs()?);
+    OpenOptions::new()
+        .append(true)
+        .open(&file)
+        .and_then(|mut f| f.write_all(cmd.as_bytes()))
+        .map_err(|e| anyhow!("Could not write '{cmd}' to {file}: {e}"))
+}
+
+/// Kprobe event created using the kprobe_events interface. The event is
+/// removed when the object is dropped, which must happen after all the perf
+/// events opened on it were closed.
+pub(crate) struct KprobeEvent {
+    name: String,
+}
+
+impl KprobeEvent {
+    /// Build the name of the event probing `func`. Event names can only
+    /// contain alphanumeric characters and underscores.
+    fn event_name(func: &str) -> String {
+        let func: String = func
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c,
+                false => '_',
+            })
+            .collect();
+
+        format!("kretprobe_{}_{func}", std::process::id())
+    }
+
+    /// Create a kretprobe event on `func`, allowing up to `maxactive`
+    /// instances of the function to be probed concurrently (see
+    /// `KRETPROBE_MAXACTIVE_MAX`).
+    pub(crate) fn kretprobe(func: &str, maxactive: u32) -> Result<Self> {
+        let name = Self::event_name(func);
+        kprobe_events_write(&format!("r{maxactive}:{GROUP}/{name} {func}\n"))?;
+
+        Ok(Self { name })
+    }
+
+    /// Open a perf event on the kprobe event; BPF programs can then be attached
+    /// to it. The perf event is only opened on a single cpu as this is enough
+    /// for BPF programs to be run on all of them.
+    pub(crate) fn perf_event_open(&self) -> Result<OwnedFd> {
+        let file = format!("{}/events/{GROUP}/{}/id", tracefs()?, self.name);
+        let id = fs::read_to_string(&file)
+            .map_err(|e| anyhow!("Could not read {file}: {e}"))?
+            .trim()
+            .parse::<u64>()
+            .map_err(|e| anyhow!("Invalid event id in {file}: {e}"))?;
+
+        let attr = PerfEventAttr {
+            r#type: PERF_TYPE_TRACEPOINT,
+            size: mem::size_of::<PerfEventAttr>() as u32,
+            config: id,
+            sample_period: 1,
+            wakeup_events: 1,
+            ..Default::default()
+        };
+
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                &attr as *const PerfEventAttr,
+                -1 as libc::pid_t,
+                0 as libc::c_int,
+                -1 as libc::c_int,
+                PERF_FLAG_FD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            bail!(
+                "Could not open perf event for {}: {}",
+                self.name,
+                Error::last_os_error()
+            );
+        }
+
+        Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+    }
+}
+
+impl Drop for KprobeEvent {
+    fn drop(&mut self) {
+        if let Err(e) = kprobe_events_write(&format!("-:{GROUP}/{}\n", self.name)) {
+            warn!("Could not remove kprobe event {}: {e}", self.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_name() {
+        let pid = std::process::id();
+
+        assert_eq!(
+            KprobeEvent::event_name("tcp_sendmsg"),
+            format!("kretprobe_{pid}_tcp_sendmsg")
+        );
+        assert_eq!(
+            KprobeEvent::event_name("ip_rcv.cold"),
+            format!("kretprobe_{pid}_ip_rcv_cold")
+        );
+    }
+}
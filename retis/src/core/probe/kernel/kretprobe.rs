@@ -7,12 +7,19 @@
 //! Since function arguments are not available in kretprobes, we attach a small
 //! program into the associated kprobe that safes the context into a map which is
 //! then retrieved by the kretprobe program..
+//!
+//! Kretprobes with a custom maxactive are attached using a kprobe event, see
+//! `kprobe_events`.
 
-use std::os::fd::{AsFd, AsRawFd, RawFd};
+use std::{
+    collections::HashMap,
+    os::fd::{AsFd, AsRawFd, IntoRawFd, RawFd},
+};
 
 use anyhow::{anyhow, bail, Result};
 use libbpf_rs::skel::{OpenSkel, Skel};
 
+use super::kprobe_events::KprobeEvent;
 use crate::core::{filters::Filter, probe::builder::*, probe::*, workaround::*};
 
 mod kretprobe_bpf {
@@ -23,6 +30,10 @@ use kretprobe_bpf::*;
 #[derive(Default)]
 pub(crate) struct KretprobeBuilder<'a> {
     links: Vec<libbpf_rs::Link>,
+    /// Links of the probes, by probe key.
+    probe_links: HashMap<String, Vec<libbpf_rs::Link>>,
+    // Must be dropped after the links attached to them.
+    events: HashMap<String, KprobeEvent>,
     skel: Option<SkelStorage<KretprobeSkel<'a>>>,
 }
 
@@ -75,31 +86,56 @@ impl<'a> ProbeBuilder for KretprobeBuilder<'a> {
             _ => bail!("Kretprobe builder is uninitialized"),
         };
 
+        let key = probe.key();
         let probe = match probe.r#type() {
             ProbeType::Kretprobe(probe) => probe,
             _ => bail!("Wrong probe type {}", probe),
         };
 
         // Attach the kretprobe
-        self.links.push(
-            obj.progs_mut()
-                .find(|p| p.name() == "probe_kretprobe_kretprobe")
-                .ok_or_else(|| anyhow!("Couldn't get kretprobe program"))?
-                .attach_kprobe(true, probe.symbol.attach_name())?,
-        );
+        let prog = obj
+            .progs_mut()
+            .find(|p| p.name() == "probe_kretprobe_kretprobe")
+            .ok_or_else(|| anyhow!("Couldn't get kretprobe program"))?;
+        let kretprobe = match probe.maxactive {
+            Some(maxactive) => {
+                let event = KprobeEvent::kretprobe(&probe.symbol.attach_name(), maxactive)?;
+                let pfd = event.perf_event_open()?;
+                let link = prog.attach_perf_event(pfd.as_raw_fd())?;
+                // The perf event fd is now owned (and closed) by the link.
+                let _ = pfd.into_raw_fd();
+
+                self.events.insert(key.clone(), event);
+                link
+            }
+            None => prog.attach_kprobe(true, probe.symbol.attach_name())?,
+        };
 
         // Attach the kprobe
-        self.links.push(
-            obj.progs_mut()
-                .find(|p| p.name() == "probe_kretprobe_kprobe")
-                .ok_or_else(|| anyhow!("Couldn't get kprobe program"))?
-                .attach_kprobe(false, probe.symbol.attach_name())?,
-        );
+        let kprobe = obj
+            .progs_mut()
+            .find(|p| p.name() == "probe_kretprobe_kprobe")
+            .ok_or_else(|| anyhow!("Couldn't get kprobe program"))?
+            .attach_kprobe(false, probe.symbol.attach_name())?;
+
+        self.probe_links.insert(key, vec![kretprobe, kprobe]);
         Ok(())
     }
 
     fn detach(&mut self) -> Result<()> {
+        // Links must be removed before the events and the skeleton they're
+        // attached to.
+        self.probe_links.clear();
         self.links.drain(..);
+        self.events.clear();
+        self.skel = None;
+        Ok(())
+    }
+
+    fn detach_probe(&mut self, probe: &Probe) -> Result<()> {
+        // Links must be removed before the event they're attached to.
+        self.probe_links.remove(&probe.key());
+        self.events.remove(&probe.key());
         Ok(())
     }
 }
@@ -142,4 +178,60 @@ mod tests {
             )
             .is_ok());
     }
+
+    #[test]
+    fn maxactive() {
+        let symbol = || Symbol::from_name("tcp_sendmsg").unwrap();
+
+        let probe = Probe::kretprobe_with_maxactive(symbol(), 128).unwrap();
+        match probe.r#type() {
+            ProbeType::Kretprobe(kp) => assert_eq!(kp.maxactive, Some(128)),
+            _ => panic!("not a kretprobe"),
+        }
+        match Probe::kretprobe(symbol()).unwrap().r#type() {
+            ProbeType::Kretprobe(kp) => assert_eq!(kp.maxactive, None),
+            _ => panic!("not a kretprobe"),
+        }
+
+        assert!(Probe::kretprobe_with_maxactive(symbol(), 0).is_err());
+        assert!(Probe::kretprobe_with_maxactive(symbol(), 4097).is_err());
+        assert!(
+            Probe::kretprobe_with_maxactive(Symbol::from_name("skb:kfree_skb").unwrap(), 1)
+                .is_err()
+        );
+    }
+
+    #[test]
+    #[serial(libbpf)]
+    #[cfg_attr(not(feature = "test_cap_bpf"), ignore)]
+    fn attach_maxactive() {
+        let _ = register_filter_handler(
+            "kretprobe/probe",
+            libbpf_rs::ProgramType::Kprobe,
+            Some(fixup_filter_load_fn),
+        );
+
+        let probe = Probe::kretprobe_with_maxactive(
+            Symbol::from_name("tcp_sendmsg").expect("symbol should exist"),
+            512,
+        )
+        .expect("kreprobe creation should succeed");
+
+        let mut builder = KretprobeBuilder::new();
+        assert!(builder.init(Vec::new(), Vec::new(), Vec::new()).is_ok());
+        assert!(builder.attach(&probe).is_ok());
+        assert_eq!(builder.events.len(), 1);
+
+        // A single probe can be detached, along with its event.
+        assert!(builder.detach_probe(&probe).is_ok());
+        assert!(builder.probe_links.is_empty() && builder.events.is_empty());
+
+        assert!(builder.attach(&probe).is_ok());
+        assert!(builder.detach().is_ok());
+        assert!(builder.events.is_empty());
+        assert!(builder.skel.is_none());
+
+        // The builder can be initialized again.
+        assert!(builder.init(Vec::new(), Vec::new(), Vec::new()).is_ok());
+    }
 }
@@ -1,10 +1,10 @@
 //! # Kernel probes
 //!
 //! Module providing an API to attach probes in the Linux kernel, e.g. using
-//! kprobes and raw tracepoints. The need to attach a probe in the kernel can
-//! come from various sources (different collectors, the user, etc) and as such
-//! some kind of synchronization and common logic is required; which is provided
-//! here.
+//! kprobes, raw and classic tracepoints and fentry/fexit. The need to attach a probe in the
+//! kernel can come from various sources (different collectors, the user, etc)
+//! and as such some kind of synchronization and common logic is required; which
+//! is provided here.
 //!
 //! Additional BPF function (defined outside this module) can be registered and
 //! dynamically attached to the probes. These are refered as hooks. We support
@@ -27,7 +27,10 @@ pub(crate) mod probe_stack;
 
 mod inspect;
 
+pub(in crate::core::probe) mod fentry;
 pub(in crate::core::probe) mod kprobe;
+pub(in crate::core::probe) mod kprobe_events;
 pub(in crate::core::probe) mod kretprobe;
 pub(in crate::core::probe) mod raw_tracepoint;
+pub(in crate::core::probe) mod tracepoint;
 pub(crate) mod utils;
@@ -6,13 +6,18 @@
 //! (bpf/raw_tracepoint.bpf.c and its auto-generated part in bpf/.out/).
 
 use std::{
-    mem::MaybeUninit,
-    os::fd::{AsFd, AsRawFd, RawFd},
+    collections::{BTreeMap, HashMap},
+    ffi::CString,
+    io,
+    mem::{self, MaybeUninit},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
 };
 
 use anyhow::{anyhow, bail, Result};
 use libbpf_rs::skel::{OpenSkel, Skel, SkelBuilder};
+use log::debug;
 
+use super::kernel::KernelProbe;
 use crate::core::{filters::Filter, probe::builder::*, probe::*};
 
 mod raw_tracepoint_bpf {
@@ -24,39 +29,39 @@ use raw_tracepoint_bpf::RawTracepointSkelBuilder;
 pub(crate) struct RawTracepointBuilder {
     hooks: Vec<Hook>,
     filters: Vec<Filter>,
+    /// Links of the hooks of shared loaded objects.
     links: Vec<libbpf_rs::Link>,
+    /// Links of the raw tracepoints having their own loaded object, by probe
+    /// key.
+    probe_links: HashMap<String, Vec<libbpf_rs::Link>>,
+    /// Links of the raw tracepoints sharing a loaded object, identified by
+    /// their attach cookie, by probe key. Closing them detaches the program.
+    cookie_links: HashMap<String, OwnedFd>,
     map_fds: Vec<(String, RawFd)>,
+    /// Sharing a loaded object between raw tracepoints failed once (e.g.
+    /// attach cookies aren't supported), do not try again.
+    no_sharing: bool,
+    /// Policy used to retry loading and attaching on transient failures.
+    retry: RetryPolicy,
 }
 
-impl ProbeBuilder for RawTracepointBuilder {
-    fn new() -> RawTracepointBuilder {
-        RawTracepointBuilder::default()
-    }
-
-    fn init(
-        &mut self,
-        map_fds: Vec<(String, RawFd)>,
-        hooks: Vec<Hook>,
-        filters: Vec<Filter>,
-    ) -> Result<()> {
-        self.map_fds = map_fds;
-        self.hooks = hooks;
-        self.filters = filters;
-
-        Ok(())
-    }
+impl RawTracepointBuilder {
+    /// Load an object for raw tracepoints having `nargs` arguments and attach
+    /// it to all of them, given alongside their probe key. If more than one
+    /// raw tracepoint is given the object is shared and attach cookies are
+    /// used. Nothing is kept on error.
+    fn load_and_attach(&mut self, nargs: u32, tps: &[(String, &KernelProbe)]) -> Result<()> {
+        let shared = tps.len() > 1;
 
-    fn attach(&mut self, probe: &Probe) -> Result<()> {
         let mut open_object = MaybeUninit::uninit();
         let mut skel = RawTracepointSkelBuilder::default().open(&mut open_object)?;
 
-        let probe = match probe.r#type() {
-            ProbeType::RawTracepoint(probe) => probe,
-            _ => bail!("Wrong probe type {}", probe),
+        skel.maps.rodata_data.ksym = match shared {
+            true => 0,
+            false => tps[0].1.symbol.addr()?,
         };
-
-        skel.maps.rodata_data.ksym = probe.symbol.addr()?;
-        skel.maps.rodata_data.nargs = probe.symbol.nargs()?;
+        skel.maps.rodata_data.ksym_cookie = shared as u8;
+        skel.maps.rodata_data.nargs = nargs;
         skel.maps.rodata_data.nhooks = self.hooks.len() as u32;
         skel.maps.rodata_data.log_level = log::max_level() as u8;
 
@@ -76,17 +81,137 @@ impl ProbeBuilder for RawTracepointBuilder {
             .ok_or_else(|| anyhow!("Couldn't get program"))?;
 
         let mut links = replace_hooks(prog.as_fd().as_raw_fd(), &self.hooks)?;
-        self.links.append(&mut links);
 
-        self.links
-            .push(prog.attach_raw_tracepoint(probe.symbol.attach_name())?);
+        if shared {
+            let cookie_links = tps
+                .iter()
+                .map(|(key, tp)| {
+                    let fd = attach_with_cookie(
+                        prog.as_fd(),
+                        &tp.symbol.attach_name(),
+                        tp.symbol.addr()?,
+                    )?;
+                    Ok((key.clone(), fd))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            self.cookie_links.extend(cookie_links);
+            self.links.append(&mut links);
+        } else {
+            links.push(prog.attach_raw_tracepoint(tps[0].1.symbol.attach_name())?);
+            self.probe_links.insert(tps[0].0.clone(), links);
+        }
+
+        Ok(())
+    }
+}
+
+/// Attach a program to a raw tracepoint, setting the given attach cookie.
+fn attach_with_cookie(prog: BorrowedFd, name: &str, cookie: u64) -> Result<OwnedFd> {
+    let tp_name = CString::new(name)?;
+
+    let mut opts: libbpf_sys::bpf_raw_tp_opts = unsafe { mem::zeroed() };
+    opts.sz = mem::size_of::<libbpf_sys::bpf_raw_tp_opts>() as libbpf_sys::size_t;
+    opts.tp_name = tp_name.as_ptr();
+    opts.cookie = cookie;
+
+    let fd = unsafe { libbpf_sys::bpf_raw_tracepoint_open_opts(prog.as_raw_fd(), &mut opts) };
+    if fd < 0 {
+        return Err(anyhow::Error::new(io::Error::from_raw_os_error(-fd))
+            .context(format!("Could not attach to raw tracepoint {name}")));
+    }
+
+    // The fd was just returned by the kernel and isn't owned by anything else.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+impl ProbeBuilder for RawTracepointBuilder {
+    fn new() -> RawTracepointBuilder {
+        RawTracepointBuilder::default()
+    }
+
+    fn init(
+        &mut self,
+        map_fds: Vec<(String, RawFd)>,
+        hooks: Vec<Hook>,
+        filters: Vec<Filter>,
+    ) -> Result<()> {
+        self.map_fds = map_fds;
+        self.hooks = hooks;
+        self.filters = filters;
+
+        Ok(())
+    }
+
+    fn attach(&mut self, probe: &Probe) -> Result<()> {
+        self.attach_many(std::slice::from_ref(probe))
+    }
+
+    /// Attach multiple raw tracepoints at once. Raw tracepoints having the
+    /// same number of arguments share a single loaded object, their symbol
+    /// address being retrieved at runtime from the attach cookie. If this
+    /// isn't supported (Linux < 6.10), an object is loaded per raw tracepoint.
+    fn attach_many(&mut self, probes: &[Probe]) -> Result<()> {
+        let mut groups: BTreeMap<u32, Vec<(String, &KernelProbe)>> = BTreeMap::new();
+
+        for probe in probes.iter() {
+            let tp = match probe.r#type() {
+                ProbeType::RawTracepoint(tp) => tp,
+                _ => bail!("Wrong probe type {}", probe),
+            };
+
+            // Fail early if hooks read more arguments than the tracepoint has.
+            let nargs = tp.symbol.nargs()?;
+            check_hooks_nargs(&self.hooks, probe, nargs)?;
+
+            groups.entry(nargs).or_default().push((probe.key(), tp));
+        }
+
+        let retry = self.retry;
+        for (nargs, tps) in groups.iter() {
+            if tps.len() > 1 && !self.no_sharing {
+                match retry.run("attach raw tracepoints", || {
+                    self.load_and_attach(*nargs, tps)
+                }) {
+                    Ok(()) => continue,
+                    Err(e) => {
+                        debug!("Can't share raw tracepoint objects, loading one per probe: {e}");
+                        self.no_sharing = true;
+                    }
+                }
+            }
+
+            tps.iter().try_for_each(|(key, tp)| {
+                retry.run(&format!("attach raw tracepoint {}", tp.symbol), || {
+                    self.load_and_attach(*nargs, &[(key.clone(), *tp)])
+                })
+            })?;
+        }
         Ok(())
     }
 
     fn detach(&mut self) -> Result<()> {
+        self.probe_links.clear();
+        self.cookie_links.clear();
         self.links.drain(..);
+
+        // Loaded objects are not kept around, only release the state given
+        // at init time so the builder can be initialized again.
+        self.hooks.clear();
+        self.filters.clear();
+        self.map_fds.clear();
+        self.no_sharing = false;
         Ok(())
     }
+
+    fn detach_probe(&mut self, probe: &Probe) -> Result<()> {
+        self.probe_links.remove(&probe.key());
+        self.cookie_links.remove(&probe.key());
+        Ok(())
+    }
+
+    fn set_retry(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
 }
 
 #[cfg(test)]
@@ -121,4 +246,95 @@ mod tests {
             .attach(&Probe::raw_tracepoint(Symbol::from_name("skb:consume_skb").unwrap()).unwrap())
             .is_ok());
     }
+
+    #[test]
+    #[serial(libbpf)]
+    #[cfg_attr(not(feature = "test_cap_bpf"), ignore)]
+    fn attach_many() {
+        let _ = register_filter_handler(
+            "raw_tracepoint/probe",
+            libbpf_rs::ProgramType::RawTracepoint,
+            Some(fixup_filter_load_fn),
+        );
+
+        let mut builder = RawTracepointBuilder::new();
+        assert!(builder.init(Vec::new(), Vec::new(), Vec::new()).is_ok());
+
+        // Both raw tracepoints have a single argument and can share an object.
+        let probes = ["net:netif_receive_skb", "net:net_dev_queue"]
+            .iter()
+            .map(|tp| Probe::raw_tracepoint(Symbol::from_name(tp).unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        assert!(builder.attach_many(&probes).is_ok());
+        assert_eq!(builder.probe_links.len() + builder.cookie_links.len(), 2);
+
+        // A single raw tracepoint can be detached.
+        assert!(builder.detach_probe(&probes[0]).is_ok());
+        assert_eq!(builder.probe_links.len() + builder.cookie_links.len(), 1);
+
+        // Other probe types are rejected.
+        assert!(builder
+            .attach_many(&[Probe::kprobe(Symbol::from_name("consume_skb").unwrap()).unwrap()])
+            .is_err());
+
+        assert!(builder.detach().is_ok());
+        assert!(builder.probe_links.is_empty() && builder.cookie_links.is_empty());
+    }
+
+    #[test]
+    #[serial(libbpf)]
+    #[cfg_attr(not(feature = "test_cap_bpf"), ignore)]
+    fn detach_and_reattach() {
+        let _ = register_filter_handler(
+            "raw_tracepoint/probe",
+            libbpf_rs::ProgramType::RawTracepoint,
+            Some(fixup_filter_load_fn),
+        );
+
+        let probe = Probe::raw_tracepoint(Symbol::from_name("skb:kfree_skb").unwrap()).unwrap();
+        let mut builder = RawTracepointBuilder::new();
+
+        for _ in 0..2 {
+            // Maps not found in the object are ignored.
+            let map_fds = vec![("unknown_map".to_string(), 0)];
+            assert!(builder.init(map_fds, Vec::new(), Vec::new()).is_ok());
+            assert!(builder.attach(&probe).is_ok());
+            assert!(!builder.probe_links.is_empty());
+
+            assert!(builder.detach().is_ok());
+            assert!(builder.probe_links.is_empty() && builder.cookie_links.is_empty());
+            assert!(builder.map_fds.is_empty());
+        }
+    }
+
+    #[test]
+    fn hooks_nargs() {
+        let probe = Probe::raw_tracepoint(Symbol::from_name("skb:kfree_skb").unwrap()).unwrap();
+
+        // skb:kfree_skb has 3 arguments.
+        let mut hook = Hook::from(&[0]);
+        hook.name("foo").nargs(3);
+        assert!(check_hooks_nargs(&[hook], &probe, 3).is_ok());
+
+        let mut hook = Hook::from(&[0]);
+        hook.name("foo").nargs(4);
+        let mut builder = RawTracepointBuilder::new();
+        builder
+            .init(Vec::new(), vec![Hook::from(&[0]), hook], Vec::new())
+            .unwrap();
+        assert_eq!(
+            builder.attach(&probe).unwrap_err().to_string(),
+            "Hook foo reads 4 arguments but tp:skb:kfree_skb only has 3"
+        );
+
+        // Unnamed hooks are reported using their position.
+        let mut hook = Hook::from(&[0]);
+        hook.nargs(4);
+        assert_eq!(
+            check_hooks_nargs(&[Hook::from(&[0]), hook], &probe, 3)
+                .unwrap_err()
+                .to_string(),
+            "Hook hook1 reads 4 arguments but tp:skb:kfree_skb only has 3"
+        );
+    }
 }
@@ -0,0 +1,312 @@
+//! # Tracepoint
+//!
+//! Module to handle attaching programs to kernel tracepoints using the classic
+//! (non-raw) interface. Unlike raw tracepoints, classic tracepoints do not
+//! give access to their arguments but to a record whose fields are described
+//! in the tracefs format file of the event. Fields are selected by name and
+//! loaded as the probe arguments, in order. The module is split in two parts,
+//! the Rust code (here) and the eBPF one (bpf/tracepoint.bpf.c and its
+//! auto-generated part in bpf/.out/).
+
+use std::{
+    fs,
+    mem::MaybeUninit,
+    os::fd::{AsFd, AsRawFd, RawFd},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Result};
+use libbpf_rs::skel::{OpenSkel, Skel, SkelBuilder};
+
+use super::kernel::ProbeArg;
+use crate::core::{filters::Filter, probe::builder::*, probe::*};
+
+mod tracepoint_bpf {
+    include!("bpf/.out/tracepoint.skel.rs");
+}
+use tracepoint_bpf::TracepointSkelBuilder;
+
+/// Field of a tracepoint record, as described in its format file.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TracepointField {
+    pub(crate) name: String,
+    /// Offset of the field in the record, in bytes.
+    pub(crate) offset: u16,
+    /// Size of the field, in bytes.
+    pub(crate) size: u16,
+    pub(crate) signed: bool,
+}
+
+/// Layout of a tracepoint record, as described in its format file.
+#[derive(Debug)]
+pub(crate) struct TracepointFormat {
+    fields: Vec<TracepointField>,
+}
+
+impl TracepointFormat {
+    /// Read the format of a tracepoint (e.g. `skb:kfree_skb`) from tracefs.
+    pub(crate) fn from_tracefs(name: &str) -> Result<Self> {
+        let (group, event) = name
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid tracepoint name {name}"))?;
+
+        let file = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"]
+            .into_iter()
+            .map(|path| Path::new(path).join(format!("events/{group}/{event}/format")))
+            .find(|path| path.exists())
+            .ok_or_else(|| anyhow!("Could not find the tracefs format file of {name}"))?;
+
+        Self::parse(&fs::read_to_string(&file)?)
+            .map_err(|e| anyhow!("Could not parse {}: {e}", file.display()))
+    }
+
+    /// Parse the content of a tracepoint format file. Only the field
+    /// descriptions are used, e.g.
+    /// `field:void * skbaddr;	offset:8;	size:8;	signed:0;`.
+    pub(crate) fn parse(format: &str) -> Result<Self> {
+        let fields = format
+            .lines()
+            .map(str::trim)
+            .filter(|l| l.starts_with("field:"))
+            .map(|l| {
+                let mut field = TracepointField {
+                    name: String::new(),
+                    offset: 0,
+                    size: 0,
+                    signed: false,
+                };
+
+                for attr in l.split(';').map(str::trim).filter(|a| !a.is_empty()) {
+                    let (key, val) = attr
+                        .split_once(':')
+                        .ok_or_else(|| anyhow!("Invalid field attribute '{attr}'"))?;
+                    match key {
+                        // The name is the last word of the declaration, minus
+                        // the array size if any (e.g. `char name[16]`).
+                        "field" => {
+                            field.name = val
+                                .split_whitespace()
+                                .last()
+                                .and_then(|n| n.split('[').next())
+                                .filter(|n| !n.is_empty())
+                                .ok_or_else(|| anyhow!("Invalid field declaration '{val}'"))?
+                                .to_string();
+                        }
+                        "offset" => field.offset = val.parse()?,
+                        "size" => field.size = val.parse()?,
+                        "signed" => field.signed = val == "1",
+                        _ => (),
+                    }
+                }
+
+                if field.name.is_empty() {
+                    bail!("Field without a name in '{l}'");
+                }
+                Ok(field)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if fields.is_empty() {
+            bail!("No field found");
+        }
+        Ok(Self { fields })
+    }
+
+    /// Get a field given its name.
+    pub(crate) fn field(&self, name: &str) -> Result<&TracepointField> {
+        self.fields
+            .iter()
+            .find(|f| f.name == name)
+            .ok_or_else(|| anyhow!("Unknown tracepoint field {name}"))
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct TracepointBuilder {
+    hooks: Vec<Hook>,
+    filters: Vec<Filter>,
+    links: Vec<libbpf_rs::Link>,
+    map_fds: Vec<(String, RawFd)>,
+}
+
+impl ProbeBuilder for TracepointBuilder {
+    fn new() -> TracepointBuilder {
+        TracepointBuilder::default()
+    }
+
+    fn init(
+        &mut self,
+        map_fds: Vec<(String, RawFd)>,
+        hooks: Vec<Hook>,
+        filters: Vec<Filter>,
+    ) -> Result<()> {
+        self.map_fds = map_fds;
+        self.hooks = hooks;
+        self.filters = filters;
+
+        Ok(())
+    }
+
+    fn attach(&mut self, probe: &Probe) -> Result<()> {
+        let tp = match probe.r#type() {
+            ProbeType::Tracepoint(tp) => tp,
+            _ => bail!("Wrong probe type {}", probe),
+        };
+
+        // Resolve the fields to load as arguments.
+        let format = TracepointFormat::from_tracefs(&tp.symbol.name())?;
+        let fields = tp
+            .fields
+            .iter()
+            .map(|name| {
+                let field = format.field(name)?;
+                if field.size == 0 || field.size > 8 {
+                    bail!(
+                        "Tracepoint field {name} cannot be used as an argument (size {})",
+                        field.size
+                    );
+                }
+                Ok(field)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if fields.len() > ProbeArg::MAX as usize {
+            bail!(
+                "Too many fields for {probe} ({} > {})",
+                fields.len(),
+                ProbeArg::MAX
+            );
+        }
+
+        // Fail early if hooks read more arguments than fields are loaded.
+        let nargs = fields.len() as u32;
+        check_hooks_nargs(&self.hooks, probe, nargs)?;
+
+        let mut open_object = MaybeUninit::uninit();
+        let mut skel = TracepointSkelBuilder::default().open(&mut open_object)?;
+
+        skel.maps.rodata_data.ksym = tp.symbol.addr()?;
+        skel.maps.rodata_data.nargs = nargs;
+        fields.iter().enumerate().for_each(|(i, f)| {
+            skel.maps.rodata_data.field_offset[i] = f.offset;
+            skel.maps.rodata_data.field_size[i] = f.size as u8;
+            skel.maps.rodata_data.field_signed[i] = f.signed as u8;
+        });
+        skel.maps.rodata_data.nhooks = self.hooks.len() as u32;
+        skel.maps.rodata_data.log_level = log::max_level() as u8;
+
+        self.filters.iter().for_each(|f| {
+            if let Filter::Meta(m) = f {
+                skel.maps.rodata_data.nmeta = m.0.len() as u32
+            }
+        });
+
+        reuse_map_fds(skel.open_object_mut(), &self.map_fds)?;
+
+        let skel = skel.load()?;
+        let prog = skel
+            .object()
+            .progs_mut()
+            .find(|p| p.name() == "probe_tracepoint")
+            .ok_or_else(|| anyhow!("Couldn't get program"))?;
+
+        let mut links = replace_hooks(prog.as_fd().as_raw_fd(), &self.hooks)?;
+        self.links.append(&mut links);
+
+        let name = tp.symbol.name();
+        let (group, event) = name
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid tracepoint name {name}"))?;
+        self.links.push(prog.attach_tracepoint(group, event)?);
+        Ok(())
+    }
+
+    fn detach(&mut self) -> Result<()> {
+        self.links.drain(..);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use super::*;
+
+    use crate::core::{
+        filters::{fixup_filter_load_fn, register_filter_handler},
+        kernel::Symbol,
+    };
+
+    const KFREE_SKB_FORMAT: &str = r#"name: kfree_skb
+ID: 1500
+format:
+	field:unsigned short common_type;	offset:0;	size:2;	signed:0;
+	field:unsigned char common_flags;	offset:2;	size:1;	signed:0;
+	field:unsigned char common_preempt_count;	offset:3;	size:1;	signed:0;
+	field:int common_pid;	offset:4;	size:4;	signed:1;
+
+	field:void * skbaddr;	offset:8;	size:8;	signed:0;
+	field:void * location;	offset:16;	size:8;	signed:0;
+	field:unsigned short protocol;	offset:24;	size:2;	signed:0;
+	field:enum skb_drop_reason reason;	offset:28;	size:4;	signed:0;
+	field:char name[16];	offset:32;	size:16;	signed:0;
+
+print fmt: "skbaddr=%p protocol=%u location=%pS reason: %s", REC->skbaddr
+"#;
+
+    #[test]
+    fn parse_format() {
+        let format = TracepointFormat::parse(KFREE_SKB_FORMAT).unwrap();
+        assert_eq!(format.fields.len(), 9);
+        assert_eq!(
+            format.field("common_pid").unwrap(),
+            &TracepointField {
+                name: "common_pid".to_string(),
+                offset: 4,
+                size: 4,
+                signed: true,
+            }
+        );
+        assert_eq!(format.field("skbaddr").unwrap().offset, 8);
+        assert_eq!(format.field("reason").unwrap().offset, 28);
+        assert_eq!(format.field("name").unwrap().size, 16);
+        assert!(format.field("foo").is_err());
+
+        assert!(TracepointFormat::parse("name: foo\nformat:\n").is_err());
+        assert!(TracepointFormat::parse("\tfield:int foo;\toffset:x;\tsize:4;").is_err());
+    }
+
+    #[test]
+    #[serial(libbpf)]
+    #[cfg_attr(not(feature = "test_cap_bpf"), ignore)]
+    fn init_and_attach() {
+        let _ = register_filter_handler(
+            "tracepoint/probe",
+            libbpf_rs::ProgramType::Tracepoint,
+            Some(fixup_filter_load_fn),
+        );
+
+        // Named fields are read from the tracefs format file.
+        let format = TracepointFormat::from_tracefs("skb:kfree_skb").unwrap();
+        assert_eq!(format.field("skbaddr").unwrap().size, 8);
+
+        let mut builder = TracepointBuilder::new();
+        assert!(builder.init(Vec::new(), Vec::new(), Vec::new()).is_ok());
+        assert!(builder
+            .attach(
+                &Probe::tracepoint(
+                    Symbol::from_name("skb:kfree_skb").unwrap(),
+                    &["skbaddr", "reason"]
+                )
+                .unwrap()
+            )
+            .is_ok());
+
+        // Unknown fields are reported.
+        assert!(builder
+            .attach(
+                &Probe::tracepoint(Symbol::from_name("skb:kfree_skb").unwrap(), &["foo"]).unwrap()
+            )
+            .is_err());
+    }
+}
@@ -1,5 +1,6 @@
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 
+use super::kernel::ProbeArg;
 use crate::core::{
     kernel::symbol::{matching_events_to_symbols, matching_functions_to_symbols, Symbol},
     probe::Probe,
@@ -10,6 +11,8 @@ pub(crate) enum CliProbeType {
     Kprobe,
     Kretprobe,
     RawTracepoint,
+    Fentry,
+    Fexit,
 }
 
 impl CliProbeType {
@@ -19,6 +22,8 @@ impl CliProbeType {
             Kprobe => "kprobe",
             Kretprobe => "kretprobe",
             RawTracepoint => "raw_tracepoint",
+            Fentry => "fentry",
+            Fexit => "fexit",
         }
     }
 }
@@ -33,6 +38,8 @@ pub(crate) fn parse_cli_probe(input: &str) -> Result<(CliProbeType, &str)> {
             "kprobe" | "k" => (Kprobe, target),
             "kretprobe" | "kr" => (Kretprobe, target),
             "raw_tracepoint" | "tp" => (RawTracepoint, target),
+            "fentry" | "fe" => (Fentry, target),
+            "fexit" | "fx" => (Fexit, target),
             // If a single ':' was found in the probe name but we didn't match
             // any known type, defaults to trying using it as a raw tracepoint.
             _ if input.chars().filter(|c| *c == ':').count() == 1 => (RawTracepoint, input),
@@ -56,7 +63,7 @@ where
     // Convert the target to a list of matching ones for probe types
     // supporting it.
     let mut symbols = match r#type {
-        Kprobe | Kretprobe => matching_functions_to_symbols(target)?,
+        Kprobe | Kretprobe | Fentry | Fexit => matching_functions_to_symbols(target)?,
         RawTracepoint => matching_events_to_symbols(target)?,
     };
 
@@ -71,14 +78,55 @@ where
             Kprobe => Probe::kprobe(symbol)?,
             Kretprobe => Probe::kretprobe(symbol)?,
             RawTracepoint => Probe::raw_tracepoint(symbol)?,
+            Fentry => Probe::fentry(symbol)?,
+            Fexit => Probe::fexit(symbol)?,
         })
     }
 
     Ok(probes)
 }
 
+/// Parse the arguments to report for a tracepoint, given as a cli argument
+/// following the TRACEPOINT=ARG[,ARG...] pattern, and resolve them.
+pub(crate) fn probe_args_from_cli(input: &str) -> Result<(Symbol, Vec<ProbeArg>)> {
+    let (target, args) = input.split_once('=').ok_or_else(|| {
+        anyhow!("Invalid probe arguments {input} (must be TRACEPOINT=ARG[,ARG...])")
+    })?;
+
+    let symbol = Symbol::from_name(target)?;
+    if !matches!(symbol, Symbol::Event(_)) {
+        bail!("{target} is not a tracepoint");
+    }
+
+    let args = args
+        .split(',')
+        .map(|name| ProbeArg::from_name(&symbol, name.trim()))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((symbol, args))
+}
+
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn probe_args_from_cli() {
+        let (symbol, args) = super::probe_args_from_cli("skb:kfree_skb=skb, reason").unwrap();
+        assert_eq!(symbol.name(), "skb:kfree_skb");
+        assert_eq!(
+            args.iter()
+                .map(|a| (a.name.as_str(), a.index))
+                .collect::<Vec<_>>(),
+            vec![("skb", 0), ("reason", 2)]
+        );
+
+        // Invalid arguments.
+        assert!(super::probe_args_from_cli("skb:kfree_skb").is_err());
+        assert!(super::probe_args_from_cli("skb:kfree_skb=").is_err());
+        assert!(super::probe_args_from_cli("skb:kfree_skb=skb,foo").is_err());
+        assert!(super::probe_args_from_cli("skb:foo=skb").is_err());
+        assert!(super::probe_args_from_cli("kfree_skb_reason=skb").is_err());
+    }
+
     #[test]
     fn probe_from_cli() {
         let filter = |_: &_| true;
@@ -96,6 +144,10 @@ mod tests {
             .is_empty());
         assert!(super::probe_from_cli("kretprobe:tcp_*", filter).is_ok());
         assert!(super::probe_from_cli("kr:tcp_*", filter).is_ok());
+        assert!(super::probe_from_cli("fentry:kfree_skb_reason", filter).is_ok());
+        assert!(super::probe_from_cli("fe:tcp_*", filter).is_ok());
+        assert!(super::probe_from_cli("fexit:kfree_skb_reason", filter).is_ok());
+        assert!(super::probe_from_cli("fx:tcp_*", filter).is_ok());
         assert!(super::probe_from_cli("tp:skb:kfree_*", filter).is_ok());
         assert!(super::probe_from_cli("tp:*skb*", filter).is_ok());
 
@@ -108,6 +160,7 @@ mod tests {
 
         // Invalid probe: wrong TYPE.
         assert!(super::probe_from_cli("kprobe:skb:kfree_skb", filter).is_err());
+        assert!(super::probe_from_cli("fentry:skb:kfree_skb", filter).is_err());
         assert!(super::probe_from_cli("foo:kfree_skb", filter).is_err());
 
         // Invalid probe: empty parts.
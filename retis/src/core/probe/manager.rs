@@ -4,6 +4,7 @@ use std::{
     cmp,
     collections::{HashMap, HashSet},
     os::fd::{AsFd, AsRawFd, RawFd},
+    path::Path,
 };
 
 use anyhow::{anyhow, bail, Result};
@@ -14,14 +15,18 @@ use plain::Plain;
 use super::common::{Counters, CountersKey};
 use super::*;
 use super::{
-    builder::ProbeBuilder,
-    kernel::{kprobe, kretprobe, raw_tracepoint},
+    builder::{ProbeBuilder, RetryPolicy},
+    kernel::{fentry, kprobe, kretprobe, raw_tracepoint, tracepoint},
     user::usdt,
 };
 
-use super::{common::*, kernel::config::init_config_map};
+use super::{
+    common::*,
+    kernel::config::{init_config_map, init_pid_filter_map},
+};
 use crate::core::{
     filters::{self, fixup_filter_load_fn, register_filter_handler, Filter},
+    inspect::inspector,
     kernel::Symbol,
     probe::user::UsdtProbe,
     user::proc::Process,
@@ -156,6 +161,21 @@ impl ProbeManager {
             libbpf_rs::ProgramType::RawTracepoint,
             Some(fixup_filter_load_fn),
         )?;
+        register_filter_handler(
+            "tracepoint/probe",
+            libbpf_rs::ProgramType::Tracepoint,
+            Some(fixup_filter_load_fn),
+        )?;
+        register_filter_handler(
+            "fentry/probe",
+            libbpf_rs::ProgramType::Tracing,
+            Some(fixup_filter_load_fn),
+        )?;
+        register_filter_handler(
+            "fexit/probe",
+            libbpf_rs::ProgramType::Tracing,
+            Some(fixup_filter_load_fn),
+        )?;
 
         // Initiliaze the manager runtime.
         #[cfg_attr(test, allow(unused_mut))]
@@ -164,23 +184,37 @@ impl ProbeManager {
             config_map: builder.config_map,
             #[cfg(not(test))]
             counters_map: builder.counters_map,
+            #[cfg(not(test))]
+            pid_filter_map: builder.pid_filter_map,
             map_fds: builder.maps.into_iter().collect(),
             hooks: builder.generic_hooks.into_iter().collect(),
             generic_builders: HashMap::new(),
-            targeted_builders: Vec::new(),
+            targeted_builders: HashMap::new(),
             probes: HashSet::new(),
+            kernel_probes: builder
+                .probes
+                .values()
+                .filter(|p| !matches!(p.r#type(), ProbeType::Usdt(_)))
+                .cloned()
+                .collect(),
             filters: builder.filters,
+            retry: builder.retry,
         };
 
-        // Install probes.
+        // Install probes. Generic probes are attached in batches, allowing
+        // builders to share resources between them.
         #[cfg(not(test))]
-        builder
-            .probes
-            .values_mut()
-            .try_for_each(|p| match p.is_generic() {
-                true => runtime.attach_generic_probe(p),
-                false => runtime.attach_targeted_probe(p),
+        {
+            let mut generic = Vec::new();
+            builder.probes.values_mut().try_for_each(|p| {
+                if p.is_generic() {
+                    generic.push(p);
+                    return Ok(());
+                }
+                runtime.attach_targeted_probe(p)
             })?;
+            runtime.attach_generic_probes(&mut generic)?;
+        }
 
         // All probes loaded, issue an info log.
         info!("{} probe(s) loaded", builder.probes.len());
@@ -213,6 +247,10 @@ pub(crate) struct ProbeBuilderManager {
     global_probes_options: Vec<ProbeOption>,
     /// HashMap of map names and file descriptors, to be reused in all hooks.
     maps: HashMap<String, RawFd>,
+    /// Pinned maps reused in all hooks, kept open until the probes are loaded.
+    pinned_maps: Vec<libbpf_rs::MapHandle>,
+    /// Policy used to retry attaching probes on transient failures.
+    retry: RetryPolicy,
     /// Common configuration for all probes.
     #[cfg(not(test))]
     global_config_map: libbpf_rs::MapHandle,
@@ -225,6 +263,10 @@ pub(crate) struct ProbeBuilderManager {
     /// Global per-probe map used to report counters.
     #[cfg(not(test))]
     counters_map: libbpf_rs::MapHandle,
+    /// Global map holding the pids to report events for, when the pid filter
+    /// is used.
+    #[cfg(not(test))]
+    pid_filter_map: libbpf_rs::MapHandle,
 }
 
 impl ProbeBuilderManager {
@@ -238,6 +280,8 @@ impl ProbeBuilderManager {
             filters: Vec::new(),
             global_probes_options: Vec::new(),
             maps: HashMap::new(),
+            pinned_maps: Vec::new(),
+            retry: RetryPolicy::default(),
             #[cfg(not(test))]
             global_config_map: init_global_config_map()?,
             #[cfg(not(test))]
@@ -246,6 +290,8 @@ impl ProbeBuilderManager {
             meta_map: filters::meta::filter::init_meta_map()?,
             #[cfg(not(test))]
             counters_map: init_counters_map()?,
+            #[cfg(not(test))]
+            pid_filter_map: init_pid_filter_map()?,
         };
 
         #[cfg(not(test))]
@@ -270,6 +316,12 @@ impl ProbeBuilderManager {
             mgr.counters_map.as_fd().as_raw_fd(),
         );
 
+        #[cfg(not(test))]
+        mgr.maps.insert(
+            "pid_filter_map".to_string(),
+            mgr.pid_filter_map.as_fd().as_raw_fd(),
+        );
+
         Ok(mgr)
     }
 
@@ -289,6 +341,12 @@ impl ProbeBuilderManager {
         Ok(())
     }
 
+    /// Set the policy used to retry attaching probes on transient failures
+    /// (e.g. the kernel being temporarily short of resources).
+    pub(crate) fn set_attach_retry(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
+
     /// Request to attach a dynamic probe to `Probe`.
     ///
     /// ```
@@ -323,6 +381,12 @@ impl ProbeBuilderManager {
         Ok(())
     }
 
+    /// Get the keys of the registered probes.
+    #[cfg(test)]
+    pub(crate) fn registered_probes(&self) -> Vec<String> {
+        self.probes.keys().cloned().collect()
+    }
+
     /// Request to reuse a map fd. Useful for sharing maps across probes, for
     /// configuration, event reporting, or other use cases.
     ///
@@ -340,6 +404,21 @@ impl ProbeBuilderManager {
         Ok(())
     }
 
+    /// Request to reuse a map pinned in the BPF filesystem, e.g. by a
+    /// privileged helper, see `reuse_map`.
+    ///
+    /// ```
+    /// mgr.reuse_pinned_map("config", Path::new("/sys/fs/bpf/retis/config")).unwrap();
+    /// ```
+    pub(crate) fn reuse_pinned_map(&mut self, name: &str, path: &Path) -> Result<()> {
+        let map = libbpf_rs::MapHandle::from_pinned_path(path)
+            .map_err(|e| anyhow!("Failed to open pinned map {}: {e}", path.display()))?;
+
+        self.reuse_map(name, map.as_fd().as_raw_fd())?;
+        self.pinned_maps.push(map);
+        Ok(())
+    }
+
     /// Request a filter to be attached to all probes.
     ///
     /// ```
@@ -407,35 +486,64 @@ pub(crate) struct ProbeRuntimeManager {
     /// Global per-probe map used to report counters.
     #[cfg(not(test))]
     counters_map: libbpf_rs::MapHandle,
+    /// Global map holding the pids to report events for, when the pid filter
+    /// is used.
+    #[cfg(not(test))]
+    pid_filter_map: libbpf_rs::MapHandle,
     generic_builders: HashMap<usize, Box<dyn ProbeBuilder>>,
-    targeted_builders: Vec<Box<dyn ProbeBuilder>>,
+    /// Builders of the targeted probes, by probe key.
+    targeted_builders: HashMap<String, Box<dyn ProbeBuilder>>,
     map_fds: Vec<(String, RawFd)>,
     hooks: Vec<Hook>,
     probes: HashSet<String>,
+    /// Kernel probes registered at build time, kept for re-attaching them.
+    kernel_probes: Vec<Probe>,
     filters: Vec<Filter>,
+    retry: RetryPolicy,
 }
 
 impl ProbeRuntimeManager {
-    /// Internal function installing a probe using a type-specific builder.
+    /// Internal function setting up the configuration and counters of a probe,
+    /// before attaching it.
     #[cfg(not(test))]
-    fn attach_probe(
-        builder: &mut Box<dyn ProbeBuilder>,
-        config_map: &mut libbpf_rs::MapHandle,
-        counters_map: &mut libbpf_rs::MapHandle,
-        probe: &mut Probe,
-    ) -> Result<()> {
+    fn setup_probe(&mut self, probe: &mut Probe) -> Result<()> {
         let (counters_key, counters);
         // First load the probe configuration.
         let options = probe.options();
 
+        // The pid filter map is shared by all probes, add the pids the probe
+        // is interested in.
+        for opt in options.iter() {
+            if let ProbeOption::PidFilter(pids) = opt {
+                pids.iter().try_for_each(|pid| {
+                    self.pid_filter_map
+                        .update(&pid.to_ne_bytes(), &[1], libbpf_rs::MapFlags::ANY)
+                })?;
+            }
+        }
+
         match probe.type_mut() {
             ProbeType::Kprobe(ref mut kp)
             | ProbeType::Kretprobe(ref mut kp)
-            | ProbeType::RawTracepoint(ref mut kp) => {
+            | ProbeType::RawTracepoint(ref mut kp)
+            | ProbeType::Fentry(ref mut kp)
+            | ProbeType::Fexit(ref mut kp) => {
                 let addr = kp.symbol.addr()?.to_ne_bytes();
                 let config = kp.gen_config(&options)?;
                 let config = unsafe { plain::as_bytes(&config) };
-                config_map.update(&addr, config, libbpf_rs::MapFlags::ANY)?;
+                self.config_map
+                    .update(&addr, config, libbpf_rs::MapFlags::ANY)?;
+                (counters_key, counters) = kp.gen_counters()?;
+            }
+            ProbeType::Tracepoint(ref mut kp) => {
+                let addr = kp.symbol.addr()?.to_ne_bytes();
+                let mut config = kp.gen_config(&options)?;
+                // The probe arguments are record fields, not the event
+                // parameters the offsets were resolved from.
+                config.offsets = Default::default();
+                let config = unsafe { plain::as_bytes(&config) };
+                self.config_map
+                    .update(&addr, config, libbpf_rs::MapFlags::ANY)?;
                 (counters_key, counters) = kp.gen_counters()?;
             }
             ProbeType::Usdt(ref mut up) => {
@@ -443,27 +551,37 @@ impl ProbeRuntimeManager {
             }
         }
 
-        counters_map.update(
+        self.counters_map.update(
             unsafe { plain::as_bytes(&counters_key) },
             unsafe { plain::as_bytes(&counters) },
             libbpf_rs::MapFlags::ANY,
         )?;
+        Ok(())
+    }
 
-        // Finally attach a probe to the target.
-        debug!("Attaching probe to {}", probe);
-        builder.attach(probe)
+    #[cfg(test)]
+    fn setup_probe(&mut self, _: &mut Probe) -> Result<()> {
+        Ok(())
     }
 
     /// Generate a new builder for the given probe.
+    #[cfg(not(test))]
     fn gen_builder(probe: &Probe) -> Box<dyn ProbeBuilder> {
         match probe.r#type() {
             ProbeType::Kprobe(_) => Box::new(kprobe::KprobeBuilder::new()),
             ProbeType::Kretprobe(_) => Box::new(kretprobe::KretprobeBuilder::new()),
             ProbeType::RawTracepoint(_) => Box::new(raw_tracepoint::RawTracepointBuilder::new()),
+            ProbeType::Tracepoint(_) => Box::new(tracepoint::TracepointBuilder::new()),
             ProbeType::Usdt(_) => Box::new(usdt::UsdtBuilder::new()),
+            ProbeType::Fentry(_) | ProbeType::Fexit(_) => Box::new(fentry::FentryBuilder::new()),
         }
     }
 
+    #[cfg(test)]
+    fn gen_builder(_: &Probe) -> Box<dyn ProbeBuilder> {
+        Box::new(tests::TestBuilder::new())
+    }
+
     /// Populates generic builders.
     fn gen_generic_builders(&mut self) -> Result<()> {
         // Already initialized? Bail out early.
@@ -476,6 +594,8 @@ impl ProbeRuntimeManager {
             Probe::kretprobe(Symbol::from_name_no_inspect("dummy"))?,
             Probe::raw_tracepoint(Symbol::from_name_no_inspect("dummy:dummy"))?,
             Probe::usdt(UsdtProbe::dummy())?,
+            Probe::fentry(Symbol::from_name_no_inspect("dummy"))?,
+            Probe::fexit(Symbol::from_name_no_inspect("dummy"))?,
         ];
 
         let mut builders = HashMap::new();
@@ -491,6 +611,7 @@ impl ProbeRuntimeManager {
                 },
                 self.filters.clone(),
             )?;
+            builder.set_retry(self.retry);
 
             builders.insert(p.type_key(), builder);
             Ok(())
@@ -501,7 +622,6 @@ impl ProbeRuntimeManager {
     }
 
     /// Attach a new targeted probe.
-    #[cfg(not(test))]
     fn attach_targeted_probe(&mut self, probe: &mut Probe) -> Result<()> {
         if !self.probes.insert(probe.key()) {
             bail!("A probe on {probe} is already attached");
@@ -515,28 +635,102 @@ impl ProbeRuntimeManager {
         }
 
         builder.init(self.map_fds.clone(), hooks, self.filters.clone())?;
+        builder.set_retry(self.retry);
 
-        Self::attach_probe(
-            &mut builder,
-            &mut self.config_map,
-            &mut self.counters_map,
-            probe,
-        )?;
-        self.targeted_builders.push(builder);
+        self.setup_probe(probe)?;
+
+        debug!("Attaching probe to {}", probe);
+        builder.attach(probe)?;
+
+        self.targeted_builders.insert(probe.key(), builder);
         Ok(())
     }
 
     /// Attach a new generic probe.
-    #[cfg(not(test))]
     pub(crate) fn attach_generic_probe(&mut self, probe: &mut Probe) -> Result<()> {
-        if !self.probes.insert(probe.key()) {
-            bail!("A probe on {probe} is already attached");
-        }
+        self.attach_generic_probes(&mut [probe])
+    }
 
+    /// Attach new generic probes. Probes are attached in batches, one per
+    /// probe type, so builders can share resources between them.
+    fn attach_generic_probes(&mut self, probes: &mut [&mut Probe]) -> Result<()> {
         self.gen_generic_builders()?;
 
-        let builder = self.generic_builders.get_mut(&probe.r#type_key()).unwrap();
-        Self::attach_probe(builder, &mut self.config_map, &mut self.counters_map, probe)
+        let mut batches: HashMap<usize, Vec<Probe>> = HashMap::new();
+        for probe in probes.iter_mut() {
+            if !self.probes.insert(probe.key()) {
+                bail!("A probe on {probe} is already attached");
+            }
+
+            self.setup_probe(probe)?;
+
+            debug!("Attaching probe to {}", probe);
+            batches
+                .entry(probe.r#type_key())
+                .or_default()
+                .push((**probe).clone());
+        }
+
+        batches.iter().try_for_each(|(key, probes)| {
+            self.generic_builders
+                .get_mut(key)
+                .unwrap()
+                .attach_many(probes)
+        })
+    }
+
+    /// Get the kernel probes targeting symbols of a given module.
+    fn module_probes(&self, module: &str) -> Result<Vec<Probe>> {
+        let inspector = inspector()?;
+        let mut probes = Vec::new();
+
+        for probe in self.kernel_probes.iter() {
+            let symbol = match probe.r#type() {
+                ProbeType::Kprobe(kp)
+                | ProbeType::Kretprobe(kp)
+                | ProbeType::RawTracepoint(kp)
+                | ProbeType::Fentry(kp)
+                | ProbeType::Fexit(kp)
+                | ProbeType::Tracepoint(kp) => &kp.symbol,
+                ProbeType::Usdt(_) => continue,
+            };
+
+            let symbol_module = inspector.kernel.get_symbol_module(&symbol.addr_name())?;
+            if symbol_module.as_deref() == Some(module) {
+                probes.push(probe.clone());
+            }
+        }
+
+        Ok(probes)
+    }
+
+    /// Re-attach the probes targeting symbols of a given module. This is used
+    /// when a module was reloaded, as the probes installed on its previous
+    /// instance are gone. Kernel symbols must be reloaded first for their
+    /// addresses to be valid. Returns the number of re-attached probes.
+    pub(crate) fn reattach_module_probes(&mut self, module: &str) -> Result<usize> {
+        let mut probes = self.module_probes(module)?;
+
+        for probe in probes.iter_mut() {
+            // Release the links to the previous instance of the module.
+            let key = probe.key();
+            match self.targeted_builders.remove(&key) {
+                Some(mut builder) => builder.detach()?,
+                None => {
+                    if let Some(builder) = self.generic_builders.get_mut(&probe.type_key()) {
+                        builder.detach_probe(probe)?;
+                    }
+                }
+            }
+            self.probes.remove(&key);
+
+            match probe.is_generic() {
+                true => self.attach_generic_probe(probe)?,
+                false => self.attach_targeted_probe(probe)?,
+            }
+        }
+
+        Ok(probes.len())
     }
 
     /// Get the list of all currently attached probes.
@@ -550,7 +744,7 @@ impl ProbeRuntimeManager {
             .values_mut()
             .try_for_each(|builder| builder.detach())?;
         self.targeted_builders
-            .iter_mut()
+            .values_mut()
             .try_for_each(|builder| builder.detach())
     }
 
@@ -559,6 +753,29 @@ impl ProbeRuntimeManager {
         Ok(())
     }
 
+    #[cfg(test)]
+    pub(crate) fn dropped_events(&self) -> Result<u64> {
+        Ok(0)
+    }
+
+    /// Total number of events lost so far, for all probes.
+    #[cfg(not(test))]
+    pub(crate) fn dropped_events(&self) -> Result<u64> {
+        let mut counters = Counters::default();
+        let mut total: u64 = 0;
+
+        for k in self.counters_map.keys() {
+            if let Some(counters_val) = self.counters_map.lookup(&k, libbpf_rs::MapFlags::ANY)? {
+                counters
+                    .copy_from_bytes(&counters_val)
+                    .or_else(|_| bail!("Cannot retrieve the counters map value"))?;
+                total = total.saturating_add(counters.dropped_events);
+            }
+        }
+
+        Ok(total)
+    }
+
     #[cfg(not(test))]
     pub(crate) fn report_counters(&self) -> Result<()> {
         let mut counters_key = CountersKey::default();
@@ -613,9 +830,54 @@ impl ProbeRuntimeManager {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::RefCell;
+
     use super::*;
 
-    use crate::core::kernel::Symbol;
+    use crate::core::kernel::{modules::ModuleWatcher, Symbol};
+
+    thread_local! {
+        /// Probes attached and detached by the test builders.
+        static JOURNAL: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Builder recording the probes attached and detached through it.
+    pub(super) struct TestBuilder {
+        probes: Vec<String>,
+    }
+
+    impl TestBuilder {
+        fn log(op: &str, probe: &str) {
+            JOURNAL.with(|j| j.borrow_mut().push(format!("{op} {probe}")));
+        }
+    }
+
+    impl ProbeBuilder for TestBuilder {
+        fn new() -> Self {
+            Self { probes: Vec::new() }
+        }
+
+        fn init(&mut self, _: Vec<(String, RawFd)>, _: Vec<Hook>, _: Vec<Filter>) -> Result<()> {
+            Ok(())
+        }
+
+        fn attach(&mut self, probe: &Probe) -> Result<()> {
+            Self::log("attach", &probe.key());
+            self.probes.push(probe.key());
+            Ok(())
+        }
+
+        fn detach(&mut self) -> Result<()> {
+            self.probes.drain(..).for_each(|p| Self::log("detach", &p));
+            Ok(())
+        }
+
+        fn detach_probe(&mut self, probe: &Probe) -> Result<()> {
+            Self::log("detach", &probe.key());
+            self.probes.retain(|p| *p != probe.key());
+            Ok(())
+        }
+    }
 
     // Dummy hook.
     const HOOK: &[u8] = &[0];
@@ -684,6 +946,87 @@ mod tests {
         assert!(mgr.register_probe(probe).is_err());
     }
 
+    #[test]
+    fn module_probes() {
+        let mut mgr = ProbeBuilderManager::new().unwrap();
+
+        assert!(mgr.register_probe(kprobe!("consume_skb")).is_ok());
+        assert!(mgr.register_probe(kprobe!("ovs_dp_upcall")).is_ok());
+        assert!(mgr.register_probe(raw_tp!("skb:kfree_skb")).is_ok());
+
+        let mgr = ProbeManager::Builder(mgr).into_runtime().unwrap();
+        let runtime = mgr.runtime().unwrap();
+
+        let probes = runtime.module_probes("openvswitch").unwrap();
+        assert_eq!(probes.len(), 1);
+        assert_eq!(probes[0].key(), "kprobe:ovs_dp_upcall");
+        assert!(runtime.module_probes("zram").unwrap().is_empty());
+    }
+
+    #[test]
+    fn reattach_module_probes() {
+        let mut mgr = ProbeBuilderManager::new().unwrap();
+
+        assert!(mgr.register_probe(kprobe!("consume_skb")).is_ok());
+        assert!(mgr.register_probe(kprobe!("ovs_dp_upcall")).is_ok());
+        let mut probe = kprobe!("ovs_execute_actions");
+        probe.add_hook(Hook::from(HOOK)).unwrap();
+        assert!(mgr.register_probe(probe).is_ok());
+
+        let mut mgr = ProbeManager::Builder(mgr).into_runtime().unwrap();
+        let runtime = mgr.runtime_mut().unwrap();
+
+        // Probes are not installed in tests, do it by hand.
+        let mut probes = runtime.kernel_probes.clone();
+        for probe in probes.iter_mut() {
+            match probe.is_generic() {
+                true => runtime.attach_generic_probe(probe).unwrap(),
+                false => runtime.attach_targeted_probe(probe).unwrap(),
+            }
+        }
+        JOURNAL.with(|j| j.borrow_mut().clear());
+
+        let file = std::env::temp_dir().join(format!("retis-reattach-{}", std::process::id()));
+        let ovs = "openvswitch 217088 2 - Live 0xffffffffc09f3000\n";
+        std::fs::write(&file, ovs).unwrap();
+        let mut watcher = ModuleWatcher::from_file(&file).unwrap();
+
+        // The openvswitch module is unloaded and loaded again.
+        std::fs::write(&file, "").unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+        std::fs::write(&file, ovs).unwrap();
+        let loaded = watcher.poll().unwrap();
+        assert_eq!(loaded, vec!["openvswitch"]);
+        std::fs::remove_file(&file).unwrap();
+
+        for module in loaded.iter() {
+            assert_eq!(runtime.reattach_module_probes(module).unwrap(), 2);
+        }
+
+        // Probes to the previous module instance were detached before
+        // attaching the new ones; others were left untouched.
+        let journal = JOURNAL.with(|j| j.take());
+        assert_eq!(journal.len(), 4);
+        for probe in ["kprobe:ovs_dp_upcall", "kprobe:ovs_execute_actions"] {
+            let pos = |op| journal.iter().position(|e| *e == format!("{op} {probe}"));
+            assert!(pos("detach").unwrap() < pos("attach").unwrap());
+        }
+        assert!(!journal.iter().any(|e| e.contains("consume_skb")));
+
+        // The builder of the targeted probe was replaced, not leaked.
+        assert_eq!(runtime.targeted_builders.len(), 1);
+        let mut attached = runtime.attached_probes();
+        attached.sort();
+        assert_eq!(
+            attached,
+            vec![
+                "kprobe:consume_skb",
+                "kprobe:ovs_dp_upcall",
+                "kprobe:ovs_execute_actions"
+            ]
+        );
+    }
+
     #[test]
     fn reuse_map() {
         let mut mgr = ProbeBuilderManager::new().unwrap();
@@ -692,4 +1035,46 @@ mod tests {
         assert!(mgr.reuse_map("event", 0).is_ok());
         assert!(mgr.reuse_map("event", 0).is_err());
     }
+
+    #[test]
+    #[cfg_attr(not(feature = "test_cap_bpf"), ignore)]
+    fn reuse_pinned_map() {
+        let opts = libbpf_sys::bpf_map_create_opts {
+            sz: std::mem::size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+            ..Default::default()
+        };
+        let map =
+            libbpf_rs::MapHandle::create(libbpf_rs::MapType::Array, Some("pinned"), 4, 4, 1, &opts)
+                .unwrap();
+        map.update(&[0; 4], &[42, 0, 0, 0], libbpf_rs::MapFlags::ANY)
+            .unwrap();
+
+        let path = format!("/sys/fs/bpf/retis-test-{}", std::process::id());
+        let cpath = std::ffi::CString::new(path.clone()).unwrap();
+        assert_eq!(
+            unsafe { libbpf_sys::bpf_obj_pin(map.as_fd().as_raw_fd(), cpath.as_ptr()) },
+            0
+        );
+
+        let mut mgr = ProbeBuilderManager::new().unwrap();
+        let res = mgr.reuse_pinned_map("pinned", Path::new(&path));
+        std::fs::remove_file(&path).unwrap();
+        res.unwrap();
+
+        // The reused map is the pinned one.
+        let fd = *mgr.maps.get("pinned").unwrap();
+        let reused = &mgr.pinned_maps[0];
+        assert_eq!(reused.as_fd().as_raw_fd(), fd);
+        assert_eq!(
+            reused
+                .lookup(&[0; 4], libbpf_rs::MapFlags::ANY)
+                .unwrap()
+                .unwrap(),
+            vec![42, 0, 0, 0]
+        );
+
+        assert!(mgr
+            .reuse_pinned_map("pinned", Path::new("/sys/fs/bpf/retis-none"))
+            .is_err());
+    }
 }
@@ -3,6 +3,7 @@
 //! Module providing a public API to attach to various types of probes.
 
 mod builder;
+pub(crate) use builder::RetryPolicy;
 
 pub(crate) mod common;
 pub(crate) mod kernel;
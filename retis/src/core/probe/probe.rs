@@ -6,7 +6,7 @@ use std::{
 
 use anyhow::{bail, Result};
 
-use super::kernel::KernelProbe;
+use super::kernel::{config::CPU_FILTER_MAX, kprobe_events::KRETPROBE_MAXACTIVE_MAX, KernelProbe};
 use super::user::UsdtProbe;
 use crate::core::kernel;
 
@@ -21,6 +21,10 @@ pub(crate) enum ProbeType {
     RawTracepoint(KernelProbe),
     #[allow(dead_code)]
     Usdt(UsdtProbe),
+    Fentry(KernelProbe),
+    Fexit(KernelProbe),
+    #[allow(dead_code)]
+    Tracepoint(KernelProbe),
 }
 
 /// Probe options, to toggle opt-in/out features.
@@ -28,6 +32,17 @@ pub(crate) enum ProbeType {
 pub(crate) enum ProbeOption {
     StackTrace,
     NoGenericHook,
+    /// Only report events from the given pids (process or thread ids). This
+    /// is best effort as the current task isn't always related to the event,
+    /// e.g. in softirq context. The pids are shared by all probes using the
+    /// option.
+    PidFilter(Vec<u32>),
+    /// Report the raw value of the probe arguments, as a bitmask (bit N for
+    /// argument N).
+    Args(u16),
+    /// Only report events happening on the given cpus. Unlike the pid
+    /// filter, the cpus are specific to each probe.
+    CpuFilter(Vec<u32>),
 }
 
 /// Represents a probe we can install in a target (kernel, user space program,
@@ -67,6 +82,27 @@ impl Probe {
         Ok(Probe::from(r#type))
     }
 
+    /// Create a new kretprobe allowing up to `maxactive` instances of the
+    /// function to be probed concurrently. The kernel default can lead to
+    /// missed returns when the function is called a lot in parallel.
+    #[allow(dead_code)]
+    pub(crate) fn kretprobe_with_maxactive(
+        symbol: kernel::Symbol,
+        maxactive: u32,
+    ) -> Result<Probe> {
+        if maxactive == 0 || maxactive > KRETPROBE_MAXACTIVE_MAX {
+            bail!(
+                "Invalid kretprobe maxactive {maxactive} (must be in 1..={KRETPROBE_MAXACTIVE_MAX})"
+            );
+        }
+
+        let mut probe = Probe::kretprobe(symbol)?;
+        if let ProbeType::Kretprobe(ref mut kp) = probe.r#type {
+            kp.maxactive = Some(maxactive);
+        }
+        Ok(probe)
+    }
+
     /// Create a new raw tracepoint.
     pub(crate) fn raw_tracepoint(symbol: kernel::Symbol) -> Result<Probe> {
         let r#type = match symbol {
@@ -76,12 +112,60 @@ impl Probe {
         Ok(Probe::from(r#type))
     }
 
+    /// Create a new classic tracepoint, loading the given record fields (as
+    /// named in the tracefs format file of the event) as the probe arguments.
+    #[allow(dead_code)]
+    pub(crate) fn tracepoint(symbol: kernel::Symbol, fields: &[&str]) -> Result<Probe> {
+        let mut kp = match symbol {
+            kernel::Symbol::Event(_) => KernelProbe::new(symbol)?,
+            kernel::Symbol::Func(_) => bail!("Symbol cannot be probed with a tracepoint"),
+        };
+        kp.fields = fields.iter().map(|f| f.to_string()).collect();
+        Ok(Probe::from(ProbeType::Tracepoint(kp)))
+    }
+
+    /// Create a new fentry probe.
+    pub(crate) fn fentry(symbol: kernel::Symbol) -> Result<Probe> {
+        let r#type = match symbol {
+            kernel::Symbol::Func(_) => ProbeType::Fentry(KernelProbe::new(symbol)?),
+            kernel::Symbol::Event(_) => bail!("Symbol cannot be probed with a fentry probe"),
+        };
+        Ok(Probe::from(r#type))
+    }
+
+    /// Create a new fexit probe.
+    pub(crate) fn fexit(symbol: kernel::Symbol) -> Result<Probe> {
+        let r#type = match symbol {
+            kernel::Symbol::Func(_) => ProbeType::Fexit(KernelProbe::new(symbol)?),
+            kernel::Symbol::Event(_) => bail!("Symbol cannot be probed with a fexit probe"),
+        };
+        Ok(Probe::from(r#type))
+    }
+
     /// Create a new usdt probe.
     pub(crate) fn usdt(usdt_probe: UsdtProbe) -> Result<Probe> {
         let r#type = ProbeType::Usdt(usdt_probe);
         Ok(Probe::from(r#type))
     }
 
+    /// Only run the probe on the given cpus: events happening on other cpus
+    /// are dropped in the kernel, before doing any work. Cpus are accumulated
+    /// if called multiple times.
+    #[allow(dead_code)]
+    pub(crate) fn on_cpus(&mut self, cpus: &[u32]) -> Result<()> {
+        if cpus.is_empty() {
+            bail!("No cpu given to restrict {self} to");
+        }
+        if let Some(cpu) = cpus.iter().find(|cpu| **cpu >= CPU_FILTER_MAX) {
+            bail!(
+                "Cannot restrict {self} to cpu {cpu} (max {})",
+                CPU_FILTER_MAX - 1
+            );
+        }
+
+        self.set_option(ProbeOption::CpuFilter(cpus.to_vec()))
+    }
+
     /// Retrieve a reference to the underlying ProbeType.
     #[allow(dead_code)]
     pub(crate) fn r#type(&self) -> &ProbeType {
@@ -107,6 +191,9 @@ impl Probe {
             ProbeType::Kretprobe(_) => 1,
             ProbeType::RawTracepoint(_) => 2,
             ProbeType::Usdt(_) => 3,
+            ProbeType::Fentry(_) => 4,
+            ProbeType::Fexit(_) => 5,
+            ProbeType::Tracepoint(_) => 6,
         }
     }
 
@@ -141,10 +228,68 @@ impl Probe {
 
     /// Set a probe option.
     pub(crate) fn set_option(&mut self, option: ProbeOption) -> Result<()> {
+        // Only a single pid filter can be set, pids are accumulated.
+        let option = match option {
+            ProbeOption::PidFilter(mut pids) => {
+                if let Some(prev) = self.take_pid_filter() {
+                    pids.extend(prev);
+                }
+                pids.sort_unstable();
+                pids.dedup();
+                ProbeOption::PidFilter(pids)
+            }
+            // Only a single cpu filter can be set, cpus are accumulated.
+            ProbeOption::CpuFilter(mut cpus) => {
+                if let Some(prev) = self.take_cpu_filter() {
+                    cpus.extend(prev);
+                }
+                cpus.sort_unstable();
+                cpus.dedup();
+                ProbeOption::CpuFilter(cpus)
+            }
+            // Only a single set of arguments can be set, they are accumulated.
+            ProbeOption::Args(mut args) => {
+                if let Some(prev) = self.args() {
+                    self.options.remove(&ProbeOption::Args(prev));
+                    args |= prev;
+                }
+                ProbeOption::Args(args)
+            }
+            option => option,
+        };
+
         self.options.insert(option);
         Ok(())
     }
 
+    /// Get the probe's arguments to report, if any.
+    fn args(&self) -> Option<u16> {
+        self.options.iter().find_map(|o| match o {
+            ProbeOption::Args(args) => Some(*args),
+            _ => None,
+        })
+    }
+
+    /// Remove the probe's pid filter, if any, and return its pids.
+    fn take_pid_filter(&mut self) -> Option<Vec<u32>> {
+        let pids = self.options.iter().find_map(|o| match o {
+            ProbeOption::PidFilter(pids) => Some(pids.clone()),
+            _ => None,
+        })?;
+        self.options.remove(&ProbeOption::PidFilter(pids.clone()));
+        Some(pids)
+    }
+
+    /// Remove the probe's cpu filter, if any, and return its cpus.
+    fn take_cpu_filter(&mut self) -> Option<Vec<u32>> {
+        let cpus = self.options.iter().find_map(|o| match o {
+            ProbeOption::CpuFilter(cpus) => Some(cpus.clone()),
+            _ => None,
+        })?;
+        self.options.remove(&ProbeOption::CpuFilter(cpus.clone()));
+        Some(cpus)
+    }
+
     /// Get all probe's options.
     #[cfg_attr(test, allow(dead_code))]
     pub(crate) fn options(&self) -> Vec<ProbeOption> {
@@ -170,12 +315,29 @@ impl Probe {
         //   set in the resulting probe.
         // - ProbeOption::NoGenericHook: has to be set in both probes to be set in the
         //   resulting probe.
+        // - ProbeOption::PidFilter: pids are merged, and no filter is set if one
+        //   of the probes does not have it.
+        // - ProbeOption::Args: arguments reported by any of the probes are
+        //   reported by the resulting probe.
+        // - ProbeOption::CpuFilter: cpus are merged, and no filter is set if one
+        //   of the probes does not have it.
         if let Some(opt) = other.options.take(&ProbeOption::StackTrace) {
             self.options.insert(opt);
         }
         if !other.options.contains(&ProbeOption::NoGenericHook) {
             self.options.remove(&ProbeOption::NoGenericHook);
         }
+        if let (Some(pids), Some(other)) = (self.take_pid_filter(), other.take_pid_filter()) {
+            self.set_option(ProbeOption::PidFilter(pids))?;
+            self.set_option(ProbeOption::PidFilter(other))?;
+        }
+        if let Some(args) = other.args() {
+            self.set_option(ProbeOption::Args(args))?;
+        }
+        if let (Some(cpus), Some(other)) = (self.take_cpu_filter(), other.take_cpu_filter()) {
+            self.set_option(ProbeOption::CpuFilter(cpus))?;
+            self.set_option(ProbeOption::CpuFilter(other))?;
+        }
 
         // Merge hooks.
         self.hooks.append(&mut other.hooks);
@@ -191,6 +353,9 @@ impl fmt::Display for Probe {
             ProbeType::Kretprobe(symbol) => write!(f, "kretprobe:{symbol}"),
             ProbeType::RawTracepoint(symbol) => write!(f, "tp:{symbol}"),
             ProbeType::Usdt(symbol) => write!(f, "usdt {symbol}"),
+            ProbeType::Fentry(symbol) => write!(f, "fentry:{symbol}"),
+            ProbeType::Fexit(symbol) => write!(f, "fexit:{symbol}"),
+            ProbeType::Tracepoint(symbol) => write!(f, "tracepoint:{symbol}"),
         }
     }
 }
@@ -202,6 +367,10 @@ pub(crate) struct Hook {
     pub(super) bpf_prog: &'static [u8],
     /// HashMap of maps names and their fd, for reuse by the hook.
     pub(super) maps: HashMap<String, RawFd>,
+    /// Hook name, used in error messages.
+    pub(super) name: Option<String>,
+    /// Number of probe arguments the hook reads, if known.
+    pub(super) nargs: Option<u32>,
 }
 
 impl Hook {
@@ -210,9 +379,25 @@ impl Hook {
         Hook {
             bpf_prog,
             maps: HashMap::new(),
+            name: None,
+            nargs: None,
         }
     }
 
+    /// Set the hook name, used in error messages.
+    pub(crate) fn name(&mut self, name: &str) -> &mut Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Set the number of probe arguments the hook reads (e.g. accessing
+    /// `ctx->regs.reg[3]` means reading 4 arguments). This is checked against
+    /// the number of arguments of the probes before attaching the hook.
+    pub(crate) fn nargs(&mut self, nargs: u32) -> &mut Self {
+        self.nargs = Some(nargs);
+        self
+    }
+
     /// Request to reuse a map specifically in the hook. For maps being globally
     /// reused please use Kernel::reuse_map() instead.
     pub(crate) fn reuse_map(&mut self, name: &str, fd: RawFd) -> Result<&mut Self> {
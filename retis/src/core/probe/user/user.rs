@@ -82,7 +82,7 @@ impl fmt::Display for UsdtProbe {
 #[event_section_factory(FactoryId::Userspace)]
 #[derive(Default)]
 pub(crate) struct UserEventFactory {
-    cache: HashMap<String, Box<dyn Any>>,
+    cache: HashMap<String, Box<dyn Any + Send>>,
 }
 
 impl RawEventSectionFactory for UserEventFactory {
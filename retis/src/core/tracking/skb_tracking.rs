@@ -128,7 +128,7 @@ fn config_map() -> Result<libbpf_rs::MapHandle> {
     .or_else(|e| bail!("Could not create the tracking config map: {}", e))
 }
 
-fn tracking_map() -> Result<libbpf_rs::MapHandle> {
+pub(crate) fn tracking_map() -> Result<libbpf_rs::MapHandle> {
     let opts = libbpf_sys::bpf_map_create_opts {
         sz: mem::size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
         ..Default::default()
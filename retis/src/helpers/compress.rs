@@ -0,0 +1,159 @@
+//! Compressed output
+//!
+//! Writer compressing the data written to an underlying writer (e.g. a file),
+//! using gzip or zstd. The compressed stream is finalized when the writer is
+//! dropped, so the output isn't truncated.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+use log::warn;
+
+/// Compression algorithm.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub(crate) enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Guess the compression algorithm from a file extension (.gz or .zst).
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip),
+            "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+enum Encoder<W: Write> {
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+/// Compresses the data written to an underlying writer.
+pub(crate) struct CompressedWriter<W: Write> {
+    encoder: Encoder<W>,
+}
+
+impl<W: Write> CompressedWriter<W> {
+    pub(crate) fn new(writer: W, compression: Compression) -> Result<Self> {
+        Ok(Self {
+            encoder: match compression {
+                Compression::Gzip => {
+                    Encoder::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+                }
+                Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(writer, 0)?),
+            },
+        })
+    }
+
+    /// Finalize the compressed stream, writing its trailer. Nothing can be
+    /// written afterwards.
+    pub(crate) fn finish(&mut self) -> io::Result<()> {
+        match &mut self.encoder {
+            Encoder::Gzip(e) => e.try_finish(),
+            Encoder::Zstd(e) => e.do_finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.encoder {
+            Encoder::Gzip(e) => e.write(buf),
+            Encoder::Zstd(e) => e.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.encoder {
+            Encoder::Gzip(e) => e.flush(),
+            Encoder::Zstd(e) => e.flush(),
+        }
+    }
+}
+
+impl<W: Write> Drop for CompressedWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            warn!("Could not finalize the compressed output: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufRead, BufReader, BufWriter, Read},
+    };
+
+    use super::*;
+    use crate::{
+        events::{file::FileEventsFactory, Event},
+        process::display::{PrintEvent, PrintEventFormat},
+    };
+
+    #[test]
+    fn from_path() {
+        assert_eq!(
+            Compression::from_path(Path::new("events.json.gz")),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_path(Path::new("/tmp/events.json.zst")),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::from_path(Path::new("events.json")), None);
+        assert_eq!(Compression::from_path(Path::new("gz")), None);
+    }
+
+    #[test]
+    fn compressed_ndjson() -> Result<()> {
+        let mut factory = FileEventsFactory::new("test_data/test_events_bench.json")?;
+        let mut events = Vec::new();
+        while let Some(event) = factory.next_event()? {
+            events.push(event);
+        }
+
+        let dir = std::env::temp_dir().join(format!("retis-compress-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let path = dir.join("events.json");
+            let writer = CompressedWriter::new(File::create(&path)?, compression)?;
+
+            // Dropping the printer finalizes the compressed stream.
+            let mut printer =
+                PrintEvent::new(Box::new(BufWriter::new(writer)), PrintEventFormat::Json);
+            events.iter().try_for_each(|e| printer.process_one(e))?;
+            printer.flush()?;
+            drop(printer);
+
+            let file = File::open(&path)?;
+            let reader: Box<dyn Read> = match compression {
+                Compression::Gzip => Box::new(flate2::read::GzDecoder::new(file)),
+                Compression::Zstd => Box::new(zstd::Decoder::new(file)?),
+            };
+
+            // One valid json event per line.
+            let lines = BufReader::new(reader)
+                .lines()
+                .collect::<io::Result<Vec<_>>>()?;
+            assert_eq!(lines.len(), events.len());
+            lines
+                .into_iter()
+                .try_for_each(|l| Event::from_json(l).map(|_| ()))?;
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
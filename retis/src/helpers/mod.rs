@@ -1,4 +1,5 @@
 pub(crate) mod bimap;
+pub(crate) mod compress;
 pub(crate) mod logger;
 pub(crate) mod net;
 pub(crate) mod pager;
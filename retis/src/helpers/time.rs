@@ -1,4 +1,6 @@
-use anyhow::{bail, Result};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
 use nix::time::{clock_gettime, ClockId};
 
 use crate::events::TimeSpec;
@@ -23,3 +25,52 @@ pub(crate) fn monotonic_clock_offset() -> Result<TimeSpec> {
 
     Ok(TimeSpec::new(offset.tv_sec(), offset.tv_nsec()))
 }
+
+/// Parses a human-friendly duration, e.g. "100ms", "30s", "5m" or "1h". A
+/// value without unit is in seconds.
+pub(crate) fn parse_duration(duration: &str) -> Result<Duration> {
+    let (val, unit) = match duration.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => duration.split_at(pos),
+        None => (duration, "s"),
+    };
+
+    let val = val
+        .parse::<u64>()
+        .map_err(|_| anyhow!("Invalid duration ({duration})"))?;
+    let ms = match unit {
+        "ms" => Some(val),
+        "s" => val.checked_mul(1000),
+        "m" => val.checked_mul(60 * 1000),
+        "h" => val.checked_mul(3600 * 1000),
+        _ => bail!("Invalid duration unit in {duration} (must be one of ms, s, m or h)"),
+    }
+    .ok_or_else(|| anyhow!("Duration is too large ({duration})"))?;
+
+    if ms == 0 {
+        bail!("Duration cannot be 0");
+    }
+    Ok(Duration::from_millis(ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration() {
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("0ms").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("10d").is_err());
+        assert!(parse_duration("1.5h").is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX)).is_err());
+    }
+}
@@ -5,7 +5,7 @@ use clap::{arg, Parser};
 
 use crate::{
     cli::*,
-    core::{kernel::Symbol, probe::kernel::utils::probe_from_cli},
+    core::{inspect::inspector, kernel::Symbol, probe::kernel::utils::probe_from_cli},
     module::Modules,
 };
 
@@ -25,6 +25,25 @@ a compatibility check is performed for each one.
 Eg. '-p tp:*'. See `retis collect --help` for more details on the probe format."
     )]
     pub(crate) probe: Option<String>,
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "",
+        help = "List all tracepoints (under the category:name form), or only the ones of the given
+category. Unlike --probe, no compatibility check is performed.
+Eg. '--tracepoints skb'."
+    )]
+    pub(crate) tracepoints: Option<String>,
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "",
+        help = "List all functions that can be kprobed, or only the ones matching a pattern (with
+wildcards) or containing a substring. Functions in the kprobe blacklist are excluded on a
+best-effort basis. Unlike --probe, no compatibility check is performed.
+Eg. '--functions tcp_v6_*'."
+    )]
+    pub(crate) functions: Option<String>,
 }
 
 impl SubCommandParserRunner for Inspect {
@@ -40,6 +59,28 @@ impl SubCommandParserRunner for Inspect {
             }
         }
 
+        if let Some(category) = &self.tracepoints {
+            inspector()?
+                .kernel
+                .list_events(match category.is_empty() {
+                    true => None,
+                    false => Some(category.as_str()),
+                })?
+                .iter()
+                .for_each(|tp| println!("{tp}"));
+        }
+
+        if let Some(filter) = &self.functions {
+            inspector()?
+                .kernel
+                .list_functions(match filter.is_empty() {
+                    true => None,
+                    false => Some(filter.as_str()),
+                })?
+                .iter()
+                .for_each(|func| println!("{func}"));
+        }
+
         Ok(())
     }
 }
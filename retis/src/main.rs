@@ -42,6 +42,7 @@ fn main() -> Result<()> {
     // Save the --kconf option value before using the cli object to dispatch the
     // command.
     let kconf_opt = cli.main_config.kconf.clone();
+    let btf_cache = !cli.main_config.no_btf_cache;
 
     // Step 3: get the modules.
     let modules = get_modules()?;
@@ -51,12 +52,13 @@ fn main() -> Result<()> {
 
     // Per-command early fixups.
     match command.name().as_str() {
-        // If the user provided a custom kernel config location, use it early to
-        // initialize the inspector. As the inspector is only used by the
-        // collect command, only initialize it there for now.
+        // If the user provided a custom kernel config location or disabled
+        // the BTF cache, use it early to initialize the inspector. As the
+        // inspector is only used by the collect command, only initialize it
+        // there for now.
         "collect" => {
-            if let Some(kconf) = &kconf_opt {
-                init_inspector(kconf)?;
+            if kconf_opt.is_some() || !btf_cache {
+                init_inspector(kconf_opt.as_ref(), btf_cache)?;
             }
         }
         // Try setting up the pager for a selected subset of commands.
@@ -3,7 +3,6 @@
 use std::collections::HashMap;
 
 use anyhow::{anyhow, bail, Result};
-use btf_rs::Type;
 use std::net::Ipv6Addr;
 
 use crate::{
@@ -97,26 +96,19 @@ impl CtEventFactory {
     }
 
     fn parse_tcp_states(&mut self) -> Result<()> {
-        if let Ok(types) = inspector()?
+        for (val, name) in inspector()?
             .kernel
             .btf
-            .resolve_types_by_name("tcp_conntrack")
+            .enum_values("tcp_conntrack")?
+            .unwrap_or_default()
         {
-            if let Some((btf, Type::Enum(r#enum))) =
-                types.iter().find(|(_, t)| matches!(t, Type::Enum(_)))
-            {
-                for member in r#enum.members.iter() {
-                    if (member.val() as i32) < 0 {
-                        continue;
-                    }
-                    self.tcp_states.insert(
-                        member.val() as i32,
-                        btf.resolve_name(member)?
-                            .trim_start_matches("TCP_CONNTRACK_")
-                            .to_string(),
-                    );
-                }
+            if (val as i32) < 0 {
+                continue;
             }
+            self.tcp_states.insert(
+                val as i32,
+                name.trim_start_matches("TCP_CONNTRACK_").to_string(),
+            );
         }
 
         Ok(())
@@ -204,6 +204,8 @@ pub(super) fn unmarshall_ct(raw_section: &BpfRawSection, event: &mut OvsEvent) -
         flags: raw.flags,
         zone_id: raw.zone_id,
         nat,
+        mark: (raw.flags & R_OVS_CT_MARK != 0).then_some(raw.mark),
+        labels: (raw.flags & R_OVS_CT_LABELS != 0).then_some(raw.labels),
     };
     update_action_event(event, OvsAction::Ct { ct })
 }
@@ -211,6 +213,20 @@ pub(super) fn unmarshall_ct(raw_section: &BpfRawSection, event: &mut OvsEvent) -
 pub(super) fn unmarshall_recv(raw_section: &BpfRawSection) -> Result<OvsEvent> {
     let raw = parse_raw_section::<recv_upcall_event>(raw_section)?;
 
+    let key_len = (raw.key_len as usize).min(raw.key.len());
+    let (key, flow_key) = match key_len {
+        0 => (None, None),
+        _ => {
+            let key = &raw.key[..key_len];
+            (
+                Some(key.iter().map(|b| format!("{b:02x}")).collect::<String>()),
+                // A malformed key shouldn't prevent the event from being
+                // reported, the raw key is still available.
+                OvsFlowKey::from_bytes(key).ok(),
+            )
+        }
+    };
+
     Ok(OvsEvent::RecvUpcall {
         recv_upcall: RecvUpcallEvent {
             key_size: raw.key_size,
@@ -219,6 +235,8 @@ pub(super) fn unmarshall_recv(raw_section: &BpfRawSection) -> Result<OvsEvent> {
             queue_id: raw.queue_id,
             r#type: raw.type_,
             batch_idx: raw.batch_idx,
+            key,
+            flow_key,
         },
     })
 }
@@ -259,6 +277,7 @@ pub(super) fn unmarshall_upcall_return(raw_section: &BpfRawSection) -> Result<Ov
             upcall_ts: raw.upcall_ts,
             upcall_cpu: raw.upcall_cpu,
             ret: raw.ret,
+            upcall_latency_ns: None,
         },
     })
 }
@@ -320,8 +339,16 @@ impl OvsEventFactory {
                     Some("PUSH_NSH") => Some(OvsAction::PushNsh(OvsDummyAction)),
                     Some("POP_NSH") => Some(OvsAction::PopNsh(OvsDummyAction)),
                     Some("METER") => Some(OvsAction::Meter(OvsDummyAction)),
-                    Some("CLONE") => Some(OvsAction::Clone(OvsDummyAction)),
-                    Some("CHECK_PKT_LEN") => Some(OvsAction::CheckPktLen(OvsDummyAction)),
+                    // Nested actions are not captured yet, only report the
+                    // action itself.
+                    Some("CLONE") => Some(OvsAction::Clone {
+                        clone: OvsActionClone::default(),
+                    }),
+                    // The packet length and nested actions are not captured
+                    // yet, only report the action itself.
+                    Some("CHECK_PKT_LEN") => Some(OvsAction::CheckPktLen {
+                        check_pkt_len: OvsActionCheckPktLen::default(),
+                    }),
                     Some("ADD_MPLS") => Some(OvsAction::AddMpls(OvsDummyAction)),
                     Some("DEC_TTL") => Some(OvsAction::DecTtl(OvsDummyAction)),
                     Some("DROP") => Some(OvsAction::Drop { reason: 0 }),
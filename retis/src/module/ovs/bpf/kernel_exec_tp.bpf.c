@@ -40,6 +40,8 @@ struct exec_drop {
 #define R_OVS_CT_NAT_RANGE_PROTO_RANDOM		(1 << 9)
 #define R_OVS_CT_NAT_RANGE_PERSISTENT		(1 << 10)
 #define R_OVS_CT_NAT_RANGE_PROTO_RANDOM_FULLY	(1 << 11)
+#define R_OVS_CT_MARK				(1 << 12)
+#define R_OVS_CT_LABELS				(1 << 13)
 
 union exec_ip {
 	u32 addr4;
@@ -53,6 +55,8 @@ struct exec_ct {
 	u16 zone_id;
 	u16 min_port;
 	u16 max_port;
+	u32 mark;
+	u8 labels[16];
 } __binding;
 
 static __always_inline void fill_nat(struct ovs_conntrack_info *info,
@@ -189,6 +193,21 @@ DEFINE_HOOK_RAW(
 			ct->flags |= R_OVS_CT_NAT;
 			fill_nat(&info, ct);
 		}
+
+		/* Mark and labels are only set by the action if their mask
+		 * is non-zero. */
+		if (info.mark.mask) {
+			ct->flags |= R_OVS_CT_MARK;
+			ct->mark = info.mark.value;
+		}
+		if (info.labels.mask.ct_labels_32[0] |
+		    info.labels.mask.ct_labels_32[1] |
+		    info.labels.mask.ct_labels_32[2] |
+		    info.labels.mask.ct_labels_32[3]) {
+			ct->flags |= R_OVS_CT_LABELS;
+			__builtin_memcpy(ct->labels, info.labels.value.ct_labels,
+					 sizeof(ct->labels));
+		}
 	} else if (bpf_core_enum_value_exists(enum ovs_action_attr,
 					      OVS_ACTION_ATTR_DROP) &&
 		   exec->action == bpf_core_enum_value(enum ovs_action_attr,
@@ -5,6 +5,9 @@
 #include <ovs_common.h>
 #include <ovs_operation.h>
 
+/* Maximum number of flow key bytes reported. */
+BINDING_DEF(RECV_UPCALL_KEY_MAX, 128)
+
 struct recv_upcall_event {
 	u64 key_size;
 	u64 batch_ts;
@@ -12,6 +15,9 @@ struct recv_upcall_event {
 	u32 queue_id;
 	u32 type;
 	u8 batch_idx;
+	/* Number of valid bytes in key. */
+	u8 key_len;
+	u8 key[RECV_UPCALL_KEY_MAX];
 } __binding;
 
 static __always_inline u32 queue_id_gen_data(void *data, u32 len)
@@ -32,6 +38,7 @@ DEFINE_USDT_HOOK (
 	struct upcall_batch *batch;
 	struct recv_upcall_event *recv_event;
 	u32 size = (u32) ctx->args[3];
+	u32 key_len;
 	u32 queue_id = queue_id_gen_data((void *) ctx->args[2], size);
 	bool skip_event = false;
 
@@ -59,6 +66,11 @@ DEFINE_USDT_HOOK (
 	recv_event->key_size = (u64) ctx->args[5];
 	recv_event->queue_id = queue_id;
 
+	key_len = MIN(recv_event->key_size, RECV_UPCALL_KEY_MAX);
+	if (!bpf_probe_read_user(recv_event->key, key_len,
+				 (void *) ctx->args[4]))
+		recv_event->key_len = key_len;
+
 	recv_event->batch_idx = batch->current_upcall;
 	recv_event->batch_ts = batch->leader_ts;
 	return 0;
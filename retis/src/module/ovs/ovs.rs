@@ -287,6 +287,7 @@ impl OvsModule {
 
         // Upcall probe.
         let mut kernel_upcall_tp_hook = Hook::from(hooks::kernel_upcall_tp::DATA);
+        kernel_upcall_tp_hook.name("kernel_upcall_tp").nargs(4);
         kernel_upcall_tp_hook.reuse_map("inflight_upcalls", inflight_upcalls_map)?;
         let mut probe = Probe::raw_tracepoint(Symbol::from_name("openvswitch:ovs_dp_upcall")?)?;
         probe.add_hook(kernel_upcall_tp_hook)?;
@@ -338,6 +339,7 @@ impl OvsModule {
 
         // ovs_do_execute_action tracepoint
         let mut exec_action_hook = Hook::from(hooks::kernel_exec_tp::DATA);
+        exec_action_hook.name("kernel_exec_tp").nargs(4);
         exec_action_hook.reuse_map("inflight_exec", inflight_exec_map.as_fd().as_raw_fd())?;
         let mut probe =
             Probe::raw_tracepoint(Symbol::from_name("openvswitch:ovs_do_execute_action")?)?;
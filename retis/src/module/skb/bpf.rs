@@ -208,7 +208,18 @@ pub(super) fn unmarshal_packet(
     });
 
     // Then start parsing the raw packet to generate other sections.
-    let eth = EthernetPacket::new(&raw.packet[..(raw.capture_len as usize)]).ok_or_else(|| {
+    decode_packet(
+        event,
+        &raw.packet[..(raw.capture_len as usize)],
+        report_eth && raw.fake_eth == 0,
+    )
+}
+
+/// Decode the protocol headers (Ethernet, ARP, IPv4/6, TCP, UDP, ICMP/v6) of a
+/// captured packet into their own sub-sections. The Ethernet header is only
+/// reported if `report_eth` is set, or if no upper layer could be decoded.
+pub(super) fn decode_packet(event: &mut SkbEvent, packet: &[u8], report_eth: bool) -> Result<()> {
+    let eth = EthernetPacket::new(packet).ok_or_else(|| {
         anyhow!("Could not parse Ethernet packet (buffer size less than minimal)")
     })?;
 
@@ -219,7 +230,7 @@ pub(super) fn unmarshal_packet(
         return Ok(());
     }
 
-    if report_eth && raw.fake_eth == 0 {
+    if report_eth {
         event.eth = Some(unmarshal_eth(&eth)?);
     }
 
@@ -313,6 +324,63 @@ impl RawEventSectionFactory for SkbEventFactory {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ethernet / IPv4 / TCP (FIN, ACK) packet, 1.1.1.1:443 > 10.0.42.2:40204.
+    const TCP_PACKET: [u8; 66] = [
+        46, 137, 59, 254, 34, 122, 42, 186, 90, 193, 129, 79, 8, 0, 69, 0, 0, 52, 32, 32, 64, 0,
+        55, 6, 237, 160, 1, 1, 1, 1, 10, 0, 42, 2, 1, 187, 157, 12, 31, 149, 22, 86, 145, 251, 180,
+        241, 128, 17, 0, 8, 17, 72, 0, 0, 1, 1, 8, 10, 28, 109, 231, 120, 127, 134, 144, 92,
+    ];
+
+    #[test]
+    fn decode_tcp_packet() {
+        let mut event = SkbEvent::default();
+        decode_packet(&mut event, &TCP_PACKET, true).unwrap();
+
+        let eth = event.eth.unwrap();
+        assert_eq!(eth.etype, 0x0800);
+        assert_eq!(eth.src, "2a:ba:5a:c1:81:4f");
+        assert_eq!(eth.dst, "2e:89:3b:fe:22:7a");
+
+        let ip = event.ip.unwrap();
+        assert_eq!(ip.saddr, "1.1.1.1");
+        assert_eq!(ip.daddr, "10.0.42.2");
+        assert_eq!(ip.protocol, 6);
+        assert_eq!(ip.len, 52);
+        assert_eq!(ip.ttl, 55);
+        match ip.version {
+            SkbIpVersion::V4 { v4 } => assert_eq!(v4.id, 0x2020),
+            _ => panic!("Expected an IPv4 header"),
+        }
+
+        let tcp = event.tcp.unwrap();
+        assert_eq!(tcp.sport, 443);
+        assert_eq!(tcp.dport, 40204);
+        assert_eq!(tcp.seq, 0x1f951656);
+        assert_eq!(tcp.ack_seq, 0x91fbb4f1);
+        assert_eq!(tcp.window, 8);
+        assert_eq!(tcp.doff, 8);
+        assert_eq!(tcp.flags, 0x11);
+
+        assert!(event.udp.is_none());
+        assert!(event.icmp.is_none());
+
+        // The Ethernet header is not reported when not asked to, as upper
+        // layers could be decoded.
+        let mut event = SkbEvent::default();
+        decode_packet(&mut event, &TCP_PACKET, false).unwrap();
+        assert!(event.eth.is_none());
+        assert!(event.tcp.is_some());
+
+        // Truncated packets are rejected.
+        let mut event = SkbEvent::default();
+        assert!(decode_packet(&mut event, &TCP_PACKET[..10], true).is_err());
+    }
+}
+
 #[cfg(feature = "benchmark")]
 pub(crate) mod benchmark {
     use anyhow::Result;
@@ -0,0 +1,270 @@
+//! Checksum filter
+//!
+//! Userspace filter verifying the checksums of the packets captured in the
+//! events (IPv4 header, TCP and UDP), to only emit events holding a corrupted
+//! packet. Checksums can only be verified when the relevant part of the packet
+//! was captured; events for which nothing could be verified are discarded.
+
+use pnet_packet::{
+    ethernet::{EtherTypes, EthernetPacket},
+    ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
+    ipv4::{self, Ipv4Flags, Ipv4Packet},
+    ipv6::Ipv6Packet,
+    tcp::{self, TcpPacket},
+    udp::{self, UdpPacket},
+    Packet,
+};
+
+use crate::events::*;
+
+/// Value of ip_summed when the L4 checksum is to be computed by the hardware
+/// (or later in the stack); it is not valid yet in the packet.
+const CHECKSUM_PARTIAL: u8 = 3;
+
+/// Verify the checksums of a packet, starting at its Ethernet header. The L4
+/// checksum is only verified if `l4` is set. Returns `None` if no checksum
+/// could be verified (unsupported protocol, truncated packet, etc).
+pub(crate) fn checksums_valid(packet: &[u8], l4: bool) -> Option<bool> {
+    let eth = EthernetPacket::new(packet)?;
+
+    match eth.get_ethertype() {
+        EtherTypes::Ipv4 => {
+            let data = eth.payload();
+            let ip = Ipv4Packet::new(data)?;
+
+            let hlen = ip.get_header_length() as usize * 4;
+            if hlen < Ipv4Packet::minimum_packet_size() || hlen > data.len() {
+                return None;
+            }
+            if ipv4::checksum(&ip) != ip.get_checksum() {
+                return Some(false);
+            }
+
+            // Fragments do not hold the full L4 payload.
+            let total = ip.get_total_length() as usize;
+            if !l4
+                || total < hlen
+                || total > data.len()
+                || ip.get_flags() & Ipv4Flags::MoreFragments != 0
+                || ip.get_fragment_offset() != 0
+            {
+                return Some(true);
+            }
+
+            let (src, dst) = (ip.get_source(), ip.get_destination());
+            let valid = l4_checksum_valid(
+                ip.get_next_level_protocol(),
+                &data[hlen..total],
+                |p| tcp::ipv4_checksum(p, &src, &dst),
+                |p| udp::ipv4_checksum(p, &src, &dst),
+            );
+            Some(valid.unwrap_or(true))
+        }
+        // IPv6 has no header checksum. Extension headers are not supported.
+        EtherTypes::Ipv6 if l4 => {
+            let data = eth.payload();
+            let ip = Ipv6Packet::new(data)?;
+
+            let hlen = Ipv6Packet::minimum_packet_size();
+            let total = hlen + ip.get_payload_length() as usize;
+            if total == hlen || total > data.len() {
+                return None;
+            }
+
+            let (src, dst) = (ip.get_source(), ip.get_destination());
+            l4_checksum_valid(
+                ip.get_next_header(),
+                &data[hlen..total],
+                |p| tcp::ipv6_checksum(p, &src, &dst),
+                |p| udp::ipv6_checksum(p, &src, &dst),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Verify the checksum of a full L4 segment, given functions computing it.
+fn l4_checksum_valid<T, U>(
+    protocol: IpNextHeaderProtocol,
+    payload: &[u8],
+    tcp_checksum: T,
+    udp_checksum: U,
+) -> Option<bool>
+where
+    T: Fn(&TcpPacket) -> u16,
+    U: Fn(&UdpPacket) -> u16,
+{
+    match protocol {
+        IpNextHeaderProtocols::Tcp => {
+            let tcp = TcpPacket::new(payload)?;
+            Some(tcp_checksum(&tcp) == tcp.get_checksum())
+        }
+        IpNextHeaderProtocols::Udp => {
+            let udp = UdpPacket::new(payload)?;
+            match udp.get_checksum() {
+                // No checksum was computed by the sender.
+                0 => None,
+                // A computed checksum of 0 is transmitted as 0xffff.
+                csum => Some(match udp_checksum(&udp) {
+                    0 => csum == 0xffff,
+                    x => x == csum,
+                }),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Only emits events holding a packet with an invalid checksum. The startup
+/// event is always emitted as it is needed to post-process the events.
+pub(crate) struct ChecksumFilter;
+
+impl ChecksumFilter {
+    pub(crate) fn new() -> Self {
+        Self
+    }
+
+    /// Process an event and return whether it should be emitted.
+    pub(crate) fn process_one(&self, event: &Event) -> bool {
+        if event.sections().any(|id| id == SectionId::Startup) {
+            return true;
+        }
+
+        let skb = match event.get_section::<SkbEvent>(SectionId::Skb) {
+            Some(skb) => skb,
+            None => return false,
+        };
+        let packet = match skb.packet.as_ref() {
+            Some(packet) => packet,
+            None => return false,
+        };
+
+        let l4 = skb
+            .meta
+            .as_ref()
+            .map(|meta| meta.ip_summed != CHECKSUM_PARTIAL)
+            .unwrap_or(true);
+
+        checksums_valid(&packet.packet.0, l4) == Some(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pnet_packet::{
+        ethernet::MutableEthernetPacket, ipv4::MutableIpv4Packet, tcp::MutableTcpPacket,
+        udp::MutableUdpPacket,
+    };
+
+    use super::*;
+    use crate::{events::helpers::RawPacket, process::testing::event};
+
+    const ETH_LEN: usize = 14;
+    const IP_LEN: usize = 20;
+
+    // Build an Ethernet/IPv4 packet holding a TCP or UDP segment, with valid
+    // checksums.
+    fn packet(protocol: IpNextHeaderProtocol) -> Vec<u8> {
+        let l4_len = match protocol {
+            IpNextHeaderProtocols::Tcp => 20,
+            _ => 8,
+        } + 4;
+        let mut buf = vec![0u8; ETH_LEN + IP_LEN + l4_len];
+
+        let mut eth = MutableEthernetPacket::new(&mut buf).unwrap();
+        eth.set_ethertype(EtherTypes::Ipv4);
+
+        let mut ip = MutableIpv4Packet::new(&mut buf[ETH_LEN..]).unwrap();
+        ip.set_version(4);
+        ip.set_header_length(5);
+        ip.set_total_length((IP_LEN + l4_len) as u16);
+        ip.set_ttl(64);
+        ip.set_next_level_protocol(protocol);
+        ip.set_source([10, 0, 0, 1].into());
+        ip.set_destination([10, 0, 0, 2].into());
+        let (src, dst) = (ip.get_source(), ip.get_destination());
+        ip.set_checksum(ipv4::checksum(&ip.to_immutable()));
+
+        let l4 = &mut buf[(ETH_LEN + IP_LEN)..];
+        l4[(l4_len - 4)..].copy_from_slice(b"data");
+        match protocol {
+            IpNextHeaderProtocols::Tcp => {
+                let mut tcp = MutableTcpPacket::new(l4).unwrap();
+                tcp.set_source(42000);
+                tcp.set_destination(80);
+                tcp.set_data_offset(5);
+                tcp.set_checksum(tcp::ipv4_checksum(&tcp.to_immutable(), &src, &dst));
+            }
+            _ => {
+                let mut udp = MutableUdpPacket::new(l4).unwrap();
+                udp.set_source(42000);
+                udp.set_destination(53);
+                udp.set_length(l4_len as u16);
+                udp.set_checksum(udp::ipv4_checksum(&udp.to_immutable(), &src, &dst));
+            }
+        }
+
+        buf
+    }
+
+    #[test]
+    fn checksums() {
+        for protocol in [IpNextHeaderProtocols::Tcp, IpNextHeaderProtocols::Udp] {
+            let good = packet(protocol);
+            assert_eq!(checksums_valid(&good, true), Some(true));
+
+            // Corrupt the payload, only the L4 checksum is invalid.
+            let mut bad_l4 = good.clone();
+            *bad_l4.last_mut().unwrap() ^= 0xff;
+            assert_eq!(checksums_valid(&bad_l4, true), Some(false));
+            assert_eq!(checksums_valid(&bad_l4, false), Some(true));
+
+            // Corrupt the IP header.
+            let mut bad_ip = good.clone();
+            bad_ip[ETH_LEN + 8] ^= 0xff;
+            assert_eq!(checksums_valid(&bad_ip, false), Some(false));
+
+            // Truncated captures can't be fully verified.
+            assert_eq!(
+                checksums_valid(&bad_l4[..(ETH_LEN + IP_LEN)], true),
+                Some(true)
+            );
+            assert_eq!(checksums_valid(&good[..ETH_LEN], true), None);
+
+            let filter = ChecksumFilter::new();
+            let out = [&good, &bad_l4]
+                .iter()
+                .map(|p| {
+                    event()
+                        .skb(SkbEvent {
+                            packet: Some(SkbPacketEvent {
+                                len: p.len() as u32,
+                                capture_len: p.len() as u32,
+                                packet: RawPacket(p.to_vec()),
+                            }),
+                            ..Default::default()
+                        })
+                        .build()
+                })
+                .filter(|e| filter.process_one(e))
+                .collect::<Vec<_>>();
+
+            // Only the corrupted packet is kept.
+            assert_eq!(out.len(), 1);
+            assert_eq!(
+                out[0]
+                    .get_section::<SkbEvent>(SectionId::Skb)
+                    .unwrap()
+                    .packet
+                    .as_ref()
+                    .unwrap()
+                    .packet
+                    .0,
+                bad_l4
+            );
+        }
+
+        // Events without a packet are not kept.
+        assert!(!ChecksumFilter::new().process_one(&Event::new()));
+    }
+}
@@ -0,0 +1,185 @@
+//! CIDR filter
+//!
+//! Userspace filter matching IP address fields of events against a list of
+//! CIDRs loaded from a file. This only applies to address fields captured in
+//! the events (e.g. the IP addresses of the skb section).
+
+use std::{fs, net::IpAddr, path::Path, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use ipnet::IpNet;
+use serde_json::Value;
+
+use super::field::FieldPath;
+
+/// CIDR filter definition, under the form `section.field=/path/to/cidrs`.
+#[derive(Clone, Debug)]
+pub(crate) struct CidrSpec {
+    /// Path to the address field.
+    path: FieldPath,
+    /// CIDRs the address must be part of (any of them).
+    cidrs: Vec<IpNet>,
+}
+
+impl FromStr for CidrSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let (field, file) = spec.split_once('=').ok_or_else(|| {
+            anyhow!("Invalid CIDR filter {spec} (must be section.field=/path/to/cidrs)")
+        })?;
+
+        Ok(Self {
+            path: field.parse()?,
+            cidrs: Self::load(file)?,
+        })
+    }
+}
+
+impl CidrSpec {
+    /// Load a list of CIDRs from a file, one per line. Single addresses are
+    /// also accepted. Empty lines and lines starting with '#' are ignored.
+    fn load<P>(file: P) -> Result<Vec<IpNet>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = file.as_ref();
+
+        Self::parse(
+            &fs::read_to_string(file)
+                .map_err(|e| anyhow!("Could not read {}: {e}", file.display()))?,
+        )
+    }
+
+    /// Parse a list of CIDRs, see `CidrSpec::load`.
+    fn parse(content: &str) -> Result<Vec<IpNet>> {
+        let cidrs = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| match line.parse::<IpNet>() {
+                Ok(net) => Ok(net),
+                Err(_) => line
+                    .parse::<IpAddr>()
+                    .map(IpNet::from)
+                    .map_err(|_| anyhow!("Invalid CIDR {line}")),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if cidrs.is_empty() {
+            bail!("No CIDR found");
+        }
+
+        Ok(cidrs)
+    }
+
+    /// Check if an event address field is part of any of the CIDRs. Events not
+    /// having the field, or having a non-address one, do not match.
+    fn matches(&self, json: &Value) -> bool {
+        match self
+            .path
+            .get(json)
+            .and_then(Value::as_str)
+            .and_then(|addr| addr.parse::<IpAddr>().ok())
+        {
+            Some(addr) => self.cidrs.iter().any(|net| net.contains(&addr)),
+            None => false,
+        }
+    }
+}
+
+/// Filters events using a set of CIDR filters; only events matching all of
+/// them are emitted.
+pub(crate) struct CidrFilter {
+    specs: Vec<CidrSpec>,
+}
+
+impl CidrFilter {
+    pub(crate) fn new(specs: Vec<CidrSpec>) -> Self {
+        Self { specs }
+    }
+
+    /// Process an event, given its json representation, and return whether
+    /// it should be emitted.
+    pub(crate) fn process_one(&self, json: &Value) -> bool {
+        self.specs.iter().all(|spec| spec.matches(json))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{events::*, process::testing::event};
+
+    fn json(saddr: &str) -> Value {
+        event()
+            .skb(SkbEvent {
+                ip: Some(SkbIpEvent {
+                    saddr: saddr.to_string(),
+                    daddr: "10.0.0.1".to_string(),
+                    version: SkbIpVersion::V4 {
+                        v4: SkbIpv4Event {
+                            tos: 0,
+                            id: 0,
+                            flags: 0,
+                            offset: 0,
+                        },
+                    },
+                    protocol: 6,
+                    len: 40,
+                    ttl: 64,
+                    ecn: 0,
+                }),
+                ..Default::default()
+            })
+            .build()
+            .to_json()
+    }
+
+    fn spec(cidrs: &str) -> CidrSpec {
+        CidrSpec {
+            path: "skb.ip.saddr".parse().unwrap(),
+            cidrs: CidrSpec::parse(cidrs).unwrap(),
+        }
+    }
+
+    #[test]
+    fn cidr_spec() {
+        let cidrs =
+            CidrSpec::parse("# Private ranges\n10.0.0.0/8\n\n  fd00::/8 \n192.0.2.1\n").unwrap();
+        assert_eq!(cidrs.len(), 3);
+        assert_eq!(cidrs[2], "192.0.2.1/32".parse::<IpNet>().unwrap());
+
+        assert!(CidrSpec::parse("10.0.0.0/33").is_err());
+        assert!(CidrSpec::parse("# Nothing\n").is_err());
+
+        assert!(CidrSpec::from_str("skb.ip.saddr").is_err());
+        assert!(CidrSpec::from_str("saddr=/dev/null").is_err());
+        assert!(CidrSpec::from_str("skb.ip.saddr=/non/existent").is_err());
+
+        let file = std::env::temp_dir().join(format!("retis-cidrs-{}", std::process::id()));
+        fs::write(&file, "10.0.0.0/8\n").unwrap();
+        let spec = CidrSpec::from_str(&format!("skb.ip.saddr={}", file.display()));
+        fs::remove_file(&file).unwrap();
+
+        let spec = spec.unwrap();
+        assert_eq!(spec.path.to_string(), "skb.ip.saddr");
+        assert_eq!(spec.cidrs, vec!["10.0.0.0/8".parse::<IpNet>().unwrap()]);
+    }
+
+    #[test]
+    fn cidr_filter() {
+        let filter = CidrFilter::new(vec![spec("10.0.0.0/8\n2001:db8::/32")]);
+
+        assert!(filter.process_one(&json("10.42.0.1")));
+        assert!(filter.process_one(&json("2001:db8::1")));
+        assert!(!filter.process_one(&json("192.168.0.1")));
+        assert!(!filter.process_one(&json("2001:db9::1")));
+        assert!(!filter.process_one(&event().build().to_json()));
+
+        // All filters must match.
+        let filter = CidrFilter::new(vec![spec("10.0.0.0/8"), spec("10.1.0.0/16")]);
+        assert!(filter.process_one(&json("10.1.0.1")));
+        assert!(!filter.process_one(&json("10.2.0.1")));
+    }
+}
@@ -13,5 +13,8 @@ pub(crate) mod python;
 #[cfg(feature = "python")]
 pub(crate) use python::*;
 
+pub(crate) mod replay;
+pub(crate) use replay::*;
+
 pub(crate) mod sort;
 pub(crate) use sort::*;
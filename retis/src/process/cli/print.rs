@@ -5,13 +5,14 @@
 
 use std::{io::stdout, path::PathBuf};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::Parser;
 
 use crate::{
     cli::*,
     events::{
         file::{FileEventsFactory, FileType},
+        segment::SegmentEventsFactory,
         *,
     },
     helpers::signals::Running,
@@ -23,7 +24,7 @@ use crate::{
 #[derive(Parser, Debug, Default)]
 #[command(name = "print")]
 pub(crate) struct Print {
-    /// File from which to read events.
+    /// File (or segmented log directory) from which to read events.
     #[arg(default_value = "retis.data")]
     pub(super) input: PathBuf,
     #[arg(long, help = "Format used when printing an event.")]
@@ -31,6 +32,19 @@ pub(crate) struct Print {
     pub(super) format: CliDisplayFormat,
     #[arg(long, help = "Print the time as UTC")]
     pub(super) utc: bool,
+    #[arg(
+        long,
+        help = "Colorize the events: sections are colorized by type and drops are highlighted.
+'auto' only does so when writing to a terminal."
+    )]
+    #[clap(value_enum, default_value_t=CliColor::Auto)]
+    pub(super) color: CliColor,
+    #[arg(
+        long,
+        help = "Only print events whose timestamp is greater or equal to the given one. When
+reading a segmented log (see 'collect --segment-size') older segments are skipped entirely."
+    )]
+    pub(super) seek: Option<u64>,
 }
 
 impl SubCommandParserRunner for Print {
@@ -39,18 +53,39 @@ impl SubCommandParserRunner for Print {
         let run = Running::new();
         run.register_term_signals()?;
 
-        // Create event factory.
-        let mut factory = FileEventsFactory::new(self.input.as_path())?;
-
         // Format.
         let format = DisplayFormat::new()
             .multiline(self.format == CliDisplayFormat::MultiLine)
+            .color(self.color.stdout())
             .time_format(if self.utc {
                 TimeFormat::UtcDate
             } else {
                 TimeFormat::MonotonicTimestamp
             });
 
+        // Segmented logs are directories and only hold events.
+        if self.input.is_dir() {
+            let mut factory = SegmentEventsFactory::new(self.input.as_path())?;
+            if let Some(ts) = self.seek {
+                factory.seek(ts);
+            }
+
+            let mut event_output =
+                PrintEvent::new(Box::new(stdout()), PrintEventFormat::Text(format));
+
+            while run.running() {
+                match factory.next_event()? {
+                    Some(event) => event_output.process_one(&event)?,
+                    None => break,
+                }
+            }
+
+            return Ok(());
+        }
+
+        // Create event factory.
+        let mut factory = FileEventsFactory::new(self.input.as_path())?;
+
         match factory.file_type() {
             FileType::Event => {
                 // Formatter & printer for events.
@@ -59,12 +94,25 @@ impl SubCommandParserRunner for Print {
 
                 while run.running() {
                     match factory.next_event()? {
-                        Some(event) => event_output.process_one(&event)?,
+                        Some(event) => {
+                            if self.seek.is_some_and(|ts| {
+                                event
+                                    .get_section::<CommonEvent>(SectionId::Common)
+                                    .is_some_and(|common| common.timestamp < ts)
+                            }) {
+                                continue;
+                            }
+                            event_output.process_one(&event)?
+                        }
                         None => break,
                     }
                 }
             }
             FileType::Series => {
+                if self.seek.is_some() {
+                    bail!("--seek is not supported on sorted files");
+                }
+
                 // Formatter & printer for series.
                 let mut series_output =
                     PrintSeries::new(Box::new(stdout()), PrintEventFormat::Text(format));
@@ -0,0 +1,238 @@
+//! # Replay
+//!
+//! Replay reads stored events back and either renders them using the normal
+//! formatters or converts them to another output format.
+
+use std::{
+    fs::OpenOptions,
+    io::{stdout, BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use pcap_file::pcapng::PcapNgWriter;
+
+#[cfg(feature = "parquet")]
+use crate::process::parquet::ParquetWriter;
+use crate::{
+    cli::*,
+    collect::cli::OutFormat,
+    events::{
+        file::{FileEventsFactory, FileType},
+        msgpack::MsgpackWriter,
+        *,
+    },
+    helpers::signals::Running,
+    module::Modules,
+    process::{display::*, pcap::EventParser},
+};
+
+/// Replay stored events.
+///
+/// Reads events from the INPUT file (json or MessagePack) and renders them back to stdout, or
+/// converts them to another output format when --out is used.
+#[derive(Parser, Debug, Default)]
+#[command(name = "replay")]
+pub(crate) struct Replay {
+    /// File from which to read events.
+    #[arg(default_value = "retis.data")]
+    pub(super) input: PathBuf,
+
+    /// Write events to a file rather than to stdout.
+    #[arg(short, long)]
+    pub(super) out: Option<PathBuf>,
+
+    /// Format of the events written with --out.
+    #[arg(long, requires = "out")]
+    #[clap(value_enum, default_value_t=OutFormat::Json)]
+    pub(super) out_format: OutFormat,
+
+    /// Format used when printing an event.
+    #[arg(long)]
+    #[clap(value_enum, default_value_t=CliDisplayFormat::MultiLine)]
+    pub(super) format: CliDisplayFormat,
+
+    /// Print the time as UTC.
+    #[arg(long)]
+    pub(super) utc: bool,
+}
+
+impl Replay {
+    /// Replay all the events of the input file, until the end of it or until
+    /// asked to stop.
+    fn replay(&self, run: &Running) -> Result<()> {
+        let mut factory = FileEventsFactory::new(self.input.as_path())?;
+        if matches!(factory.file_type(), FileType::Series) {
+            bail!("Sorted files can't be replayed, use 'print' instead");
+        }
+
+        let mut printer = None;
+        let mut msgpack = None;
+        #[cfg(feature = "parquet")]
+        let mut parquet = None;
+        let mut pcap_writer = None;
+        match self.out.as_ref() {
+            Some(out) => {
+                // Writing to the input file would truncate it before it is read.
+                if out
+                    .canonicalize()
+                    .is_ok_and(|out| self.input.canonicalize().is_ok_and(|input| out.eq(&input)))
+                {
+                    bail!("Cannot replay a file in-place. Please specify an output file that's different to the input one.");
+                }
+
+                let file = BufWriter::new(
+                    OpenOptions::new()
+                        .create(true)
+                        .write(true)
+                        .truncate(true)
+                        .open(out)
+                        .or_else(|_| bail!("Could not create or open '{}'", out.display()))?,
+                );
+
+                match self.out_format {
+                    OutFormat::Json => {
+                        printer = Some(PrintEvent::new(Box::new(file), PrintEventFormat::Json))
+                    }
+                    OutFormat::Msgpack => msgpack = Some(MsgpackWriter::new(file)?),
+                    #[cfg(feature = "parquet")]
+                    OutFormat::Parquet => parquet = Some(ParquetWriter::new(file)?),
+                    OutFormat::Pcapng => pcap_writer = Some(PcapNgWriter::new(file)?),
+                }
+            }
+            None => {
+                let format = DisplayFormat::new()
+                    .multiline(self.format == CliDisplayFormat::MultiLine)
+                    .time_format(if self.utc {
+                        TimeFormat::UtcDate
+                    } else {
+                        TimeFormat::MonotonicTimestamp
+                    });
+
+                printer = Some(PrintEvent::new(
+                    Box::new(stdout()),
+                    PrintEventFormat::Text(format),
+                ));
+            }
+        }
+
+        // Only events holding a packet can be written as pcapng.
+        let mut pcap = pcap_writer.as_mut().map(EventParser::from);
+
+        while run.running() {
+            let event = match factory.next_event()? {
+                Some(event) => event,
+                None => break,
+            };
+
+            if let Some(printer) = printer.as_mut() {
+                printer.process_one(&event)?;
+            }
+            if let Some(msgpack) = msgpack.as_mut() {
+                msgpack.write_event(&event)?;
+            }
+            #[cfg(feature = "parquet")]
+            if let Some(parquet) = parquet.as_mut() {
+                parquet.write_event(&event)?;
+            }
+            if let Some(pcap) = pcap.as_mut() {
+                if event
+                    .get_section::<KernelEvent>(SectionId::Kernel)
+                    .is_some()
+                {
+                    pcap.parse(&event)?;
+                }
+            }
+        }
+
+        if let Some(mut printer) = printer {
+            printer.flush()?;
+        }
+        if let Some(msgpack) = msgpack {
+            msgpack.into_inner()?;
+        }
+        #[cfg(feature = "parquet")]
+        if let Some(parquet) = parquet {
+            parquet.finish()?.flush()?;
+        }
+        if let Some(pcap) = pcap {
+            pcap.report_stats();
+        }
+        if let Some(writer) = pcap_writer {
+            writer.into_inner().flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SubCommandParserRunner for Replay {
+    fn run(&mut self, _: Modules) -> Result<()> {
+        // Create running instance that will handle signal termination.
+        let run = Running::new();
+        run.register_term_signals()?;
+
+        self.replay(&run)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    fn read_events(path: &Path) -> Result<Vec<serde_json::Value>> {
+        let mut factory = FileEventsFactory::new(path)?;
+        let mut events = Vec::new();
+        while let Some(event) = factory.next_event()? {
+            events.push(event.to_json());
+        }
+        Ok(events)
+    }
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("retis-replay-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let input = PathBuf::from("test_data/test_events_bench.json");
+        let msgpack = dir.join("events.msgpack");
+        let json = dir.join("events.json");
+        let run = Running::new();
+
+        // json -> msgpack -> json.
+        Replay {
+            input: input.clone(),
+            out: Some(msgpack.clone()),
+            out_format: OutFormat::Msgpack,
+            ..Default::default()
+        }
+        .replay(&run)?;
+        Replay {
+            input: msgpack.clone(),
+            out: Some(json.clone()),
+            out_format: OutFormat::Json,
+            ..Default::default()
+        }
+        .replay(&run)?;
+
+        let events = read_events(&input)?;
+        assert!(!events.is_empty());
+        assert_eq!(read_events(&msgpack)?, events);
+        assert_eq!(read_events(&json)?, events);
+
+        // Replaying a file in-place is not allowed.
+        assert!(Replay {
+            input: json.clone(),
+            out: Some(json.clone()),
+            ..Default::default()
+        }
+        .replay(&run)
+        .is_err());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,8 +1,88 @@
 use std::io::{ErrorKind, Write};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 
-use crate::events::*;
+use crate::events::{file::TimestampDelta, *};
+
+/// Formats events into bytes, ready to be written. This is used by
+/// `PrintEvent` and allows to plug custom output formats into it (see
+/// `PrintEventFormat::Custom`). Formatters can keep a state across events.
+pub(crate) trait OutputFormatter {
+    /// Format a single event, including its trailing separator (if any). An
+    /// empty output means the event should not be written.
+    fn format(&mut self, event: &Event) -> Result<Vec<u8>>;
+}
+
+/// Formats events as JSON, one per line. This is the format used to store
+/// events, which can be read back by the post-processing commands.
+#[derive(Default)]
+pub(crate) struct JsonFormatter {
+    /// Delta-encode the timestamps of events, if set.
+    deltas: Option<TimestampDelta>,
+}
+
+impl JsonFormatter {
+    /// Delta-encode the timestamps of events (see `TimestampDelta`).
+    pub(crate) fn delta_timestamps(mut self) -> Self {
+        self.deltas = Some(TimestampDelta::new());
+        self
+    }
+}
+
+impl OutputFormatter for JsonFormatter {
+    fn format(&mut self, event: &Event) -> Result<Vec<u8>> {
+        let mut json = event.to_json();
+        if let Some(deltas) = self.deltas.as_mut() {
+            deltas.encode(&mut json);
+        }
+
+        let mut out = serde_json::to_vec(&json)?;
+        out.push(b'\n');
+        Ok(out)
+    }
+}
+
+/// Formats events as pretty-printed (indented) JSON, for humans.
+#[derive(Default)]
+pub(crate) struct JsonPrettyFormatter;
+
+impl OutputFormatter for JsonPrettyFormatter {
+    fn format(&mut self, event: &Event) -> Result<Vec<u8>> {
+        let mut out = serde_json::to_vec_pretty(&event.to_json())?;
+        out.push(b'\n');
+        Ok(out)
+    }
+}
+
+/// Formats events in their text representation, following the rules
+/// defined in a `DisplayFormat`. The monotonic clock offset is taken from the
+/// startup event, if any, and used for all the following events.
+pub(crate) struct TextFormatter {
+    format: DisplayFormat,
+}
+
+impl TextFormatter {
+    pub(crate) fn new(format: DisplayFormat) -> Self {
+        Self { format }
+    }
+}
+
+impl OutputFormatter for TextFormatter {
+    fn format(&mut self, event: &Event) -> Result<Vec<u8>> {
+        if let Some(startup) = event.get_section::<StartupEvent>(SectionId::Startup) {
+            self.format.monotonic_offset = Some(startup.clock_monotonic_offset);
+        }
+
+        let mut out = format!("{}", event.display(&self.format, &FormatterConf::new()));
+        if !out.is_empty() {
+            out.push('\n');
+            if self.format.multiline {
+                out.push('\n');
+            }
+        }
+        Ok(out.into_bytes())
+    }
+}
 
 /// Select the format to follow when printing events with `PrintEvent`.
 pub(crate) enum PrintEventFormat {
@@ -11,44 +91,37 @@ pub(crate) enum PrintEventFormat {
     Text(DisplayFormat),
     /// Json: display the event as JSON.
     Json,
+    /// Custom(formatter): display the events using the given formatter. Only
+    /// supported by `PrintEvent`.
+    Custom(Box<dyn OutputFormatter>),
 }
 
 /// Handles event individually and write to a `Write`.
 pub(crate) struct PrintEvent {
     writer: Box<dyn Write>,
-    format: PrintEventFormat,
+    formatter: Box<dyn OutputFormatter>,
 }
 
 impl PrintEvent {
     pub(crate) fn new(writer: Box<dyn Write>, format: PrintEventFormat) -> Self {
-        Self { writer, format }
+        Self {
+            writer,
+            formatter: match format {
+                PrintEventFormat::Text(format) => Box::new(TextFormatter::new(format)),
+                PrintEventFormat::Json => Box::<JsonFormatter>::default(),
+                PrintEventFormat::Custom(formatter) => formatter,
+            },
+        }
     }
 
     /// Process events one by one (format & print).
     pub(crate) fn process_one(&mut self, e: &Event) -> Result<()> {
-        match self.format {
-            PrintEventFormat::Text(ref mut format) => {
-                if let Some(common) = e.get_section::<StartupEvent>(SectionId::Startup) {
-                    format.monotonic_offset = Some(common.clock_monotonic_offset);
+        let event = self.formatter.format(e)?;
+        if !event.is_empty() {
+            if let Err(e) = self.writer.write_all(&event) {
+                if e.kind() != ErrorKind::BrokenPipe {
+                    return Err(e.into());
                 }
-
-                let mut event = format!("{}", e.display(format, &FormatterConf::new()));
-                if !event.is_empty() {
-                    event.push('\n');
-                    if format.multiline {
-                        event.push('\n');
-                    }
-                    if let Err(e) = self.writer.write_all(event.as_bytes()) {
-                        if e.kind() != ErrorKind::BrokenPipe {
-                            return Err(e.into());
-                        }
-                    }
-                }
-            }
-            PrintEventFormat::Json => {
-                let mut event = serde_json::to_vec(&e.to_json())?;
-                event.push(b'\n');
-                self.writer.write_all(&event)?;
             }
         }
 
@@ -111,6 +184,7 @@ impl PrintSeries {
                 event.push(b'\n');
                 self.writer.write_all(&event)?;
             }
+            PrintEventFormat::Custom(_) => bail!("Custom formatters can't print series"),
         }
 
         Ok(())
@@ -0,0 +1,69 @@
+//! Field path
+//!
+//! Path to an event field, in the event json representation (e.g.
+//! `skb.dev.name`). This is how userspace filters and annotations reference
+//! the fields they apply to.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+/// Path to an event field, under the form `section.field[.field...]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FieldPath(Vec<String>);
+
+impl FromStr for FieldPath {
+    type Err = anyhow::Error;
+
+    fn from_str(field: &str) -> Result<Self> {
+        let path = field.split('.').map(String::from).collect::<Vec<_>>();
+        if path.len() < 2 || path.iter().any(|p| p.is_empty()) {
+            bail!("Invalid field {field} (must be under the form section.field)");
+        }
+
+        Ok(Self(path))
+    }
+}
+
+impl FieldPath {
+    /// Retrieve the field value from an event json representation, if the
+    /// event has it.
+    pub(crate) fn get<'a>(&self, json: &'a Value) -> Option<&'a Value> {
+        self.0.iter().try_fold(json, |json, key| json.get(key))
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.join("."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn field_path() {
+        let path = FieldPath::from_str("skb.dev.name").unwrap();
+        assert_eq!(path.to_string(), "skb.dev.name");
+
+        assert!(FieldPath::from_str("skb").is_err());
+        assert!(FieldPath::from_str("skb.").is_err());
+        assert!(FieldPath::from_str("skb..name").is_err());
+
+        let json = json!({"skb": {"dev": {"name": "eth0", "ifindex": 2}}});
+        assert_eq!(path.get(&json), Some(&json!("eth0")));
+        assert!(FieldPath::from_str("skb.dev.mtu")
+            .unwrap()
+            .get(&json)
+            .is_none());
+        assert!(FieldPath::from_str("skb.dev.name.len")
+            .unwrap()
+            .get(&json)
+            .is_none());
+    }
+}
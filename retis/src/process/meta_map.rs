@@ -0,0 +1,220 @@
+//! Meta map
+//!
+//! Translates the raw value of event fields to names, using user-provided
+//! tables (e.g. to give a meaning to a custom skb mark). This only applies to
+//! fields captured in the events; names are reported in the common section.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+
+use super::field::FieldPath;
+use crate::events::*;
+
+/// Meta map definition, under the form `section.field=/path/to/table.csv`.
+#[derive(Clone, Debug)]
+pub(crate) struct MetaMapSpec {
+    /// Path to the field.
+    path: FieldPath,
+    /// Names, by normalized value (see `MetaMapSpec::key`).
+    table: HashMap<String, String>,
+}
+
+impl FromStr for MetaMapSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let (field, file) = spec.split_once('=').ok_or_else(|| {
+            anyhow!("Invalid meta map {spec} (must be section.field=/path/to/table.csv)")
+        })?;
+
+        Ok(Self {
+            path: field.parse()?,
+            table: Self::load(file)?,
+        })
+    }
+}
+
+impl MetaMapSpec {
+    /// Load a translation table from a csv file, one `value,name` entry per
+    /// line. Empty lines and lines starting with '#' are ignored.
+    fn load<P>(file: P) -> Result<HashMap<String, String>>
+    where
+        P: AsRef<Path>,
+    {
+        let file = file.as_ref();
+
+        Self::parse(
+            &fs::read_to_string(file)
+                .map_err(|e| anyhow!("Could not read {}: {e}", file.display()))?,
+        )
+    }
+
+    /// Parse a translation table, see `MetaMapSpec::load`.
+    fn parse(content: &str) -> Result<HashMap<String, String>> {
+        let mut table = HashMap::new();
+
+        for line in content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        {
+            let (value, name) = line
+                .split_once(',')
+                .map(|(value, name)| (value.trim(), name.trim()))
+                .filter(|(value, name)| !value.is_empty() && !name.is_empty())
+                .ok_or_else(|| anyhow!("Invalid table entry {line} (must be value,name)"))?;
+
+            if table.insert(Self::key(value), name.to_string()).is_some() {
+                bail!("Value {value} is defined multiple times");
+            }
+        }
+
+        if table.is_empty() {
+            bail!("No table entry found");
+        }
+
+        Ok(table)
+    }
+
+    /// Normalize a table value so it can be compared to event fields: integers
+    /// (decimal or hexadecimal) are stored in their decimal form, other values
+    /// are kept as-is.
+    fn key(value: &str) -> String {
+        let int = match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => value.parse::<u64>().ok(),
+        };
+
+        match int {
+            Some(int) => int.to_string(),
+            None => value.to_string(),
+        }
+    }
+
+    /// Translate an event field, if the event has it and its value is part of
+    /// the table.
+    fn translate(&self, json: &Value) -> Option<&str> {
+        let value = self.path.get(json)?;
+
+        let key = match value {
+            Value::Number(n) => n.to_string(),
+            Value::String(s) => Self::key(s),
+            Value::Bool(b) => b.to_string(),
+            _ => return None,
+        };
+
+        self.table.get(&key).map(String::as_str)
+    }
+}
+
+/// Annotates events with the names of their field values, using a set of
+/// meta maps.
+pub(crate) struct MetaMap {
+    specs: Vec<MetaMapSpec>,
+}
+
+impl MetaMap {
+    pub(crate) fn new(specs: Vec<MetaMapSpec>) -> Self {
+        Self { specs }
+    }
+
+    /// Annotate an event with the names of its field values, given its json
+    /// representation. Events without a common section are left untouched.
+    pub(crate) fn process_one(&self, event: &mut Event, json: &Value) {
+        let names = self
+            .specs
+            .iter()
+            .filter_map(|spec| Some((spec.path.to_string(), spec.translate(json)?.to_string())))
+            .collect::<BTreeMap<_, _>>();
+        if names.is_empty() {
+            return;
+        }
+
+        if let Some(common) = event.get_section_mut::<CommonEvent>(SectionId::Common) {
+            common.names.get_or_insert_with(BTreeMap::new).extend(names);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::testing::event;
+
+    fn skb_event(priority: u32) -> Event {
+        event()
+            .timestamp(0)
+            .skb(SkbEvent {
+                meta: Some(SkbMetaEvent {
+                    len: 40,
+                    data_len: 0,
+                    hash: 0,
+                    ip_summed: 0,
+                    csum: 0,
+                    csum_level: 0,
+                    priority,
+                }),
+                ..Default::default()
+            })
+            .build()
+    }
+
+    #[test]
+    fn meta_map_spec() {
+        let table =
+            MetaMapSpec::parse("# Priorities\n1,gold\n\n 0x10 , silver \nfoo,bar\n").unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table["1"], "gold");
+        assert_eq!(table["16"], "silver");
+        assert_eq!(table["foo"], "bar");
+
+        assert!(MetaMapSpec::parse("1").is_err());
+        assert!(MetaMapSpec::parse("1,").is_err());
+        assert!(MetaMapSpec::parse("1,gold\n0x1,silver").is_err());
+        assert!(MetaMapSpec::parse("# Nothing\n").is_err());
+
+        assert!(MetaMapSpec::from_str("skb.meta.priority").is_err());
+        assert!(MetaMapSpec::from_str("priority=/dev/null").is_err());
+        assert!(MetaMapSpec::from_str("skb.meta.priority=/non/existent").is_err());
+    }
+
+    #[test]
+    fn meta_map() {
+        let file = std::env::temp_dir().join(format!("retis-map-{}.csv", std::process::id()));
+        fs::write(&file, "1,gold\n0x2,silver\n").unwrap();
+        let spec = MetaMapSpec::from_str(&format!("skb.meta.priority={}", file.display()));
+        fs::remove_file(&file).unwrap();
+        let map = MetaMap::new(vec![spec.unwrap()]);
+
+        let mut event = skb_event(2);
+        let json = event.to_json();
+        map.process_one(&mut event, &json);
+        let common = event.get_section::<CommonEvent>(SectionId::Common).unwrap();
+        assert_eq!(
+            common.names,
+            Some(BTreeMap::from([(
+                "skb.meta.priority".to_string(),
+                "silver".to_string()
+            )]))
+        );
+        assert!(format!(
+            "{}",
+            common.display(&DisplayFormat::new(), &FormatterConf::new())
+        )
+        .contains("{skb.meta.priority=silver}"));
+
+        // Values not in the table are not translated.
+        let mut event = skb_event(3);
+        let json = event.to_json();
+        map.process_one(&mut event, &json);
+        let common = event.get_section::<CommonEvent>(SectionId::Common).unwrap();
+        assert!(common.names.is_none());
+    }
+}
@@ -4,6 +4,27 @@
 
 pub(crate) mod cli;
 
+pub(crate) mod checksum;
+pub(crate) mod cidr_filter;
 pub(crate) mod display;
+pub(crate) mod field;
+pub(crate) mod meta_map;
+pub(crate) mod moving_avg;
+pub(crate) mod outlier;
+#[cfg(feature = "parquet")]
+pub(crate) mod parquet;
+pub(crate) mod pcap;
+pub(crate) mod plugin;
+pub(crate) mod regex_filter;
+pub(crate) mod region;
+pub(crate) mod reorder;
+pub(crate) mod sections;
 pub(crate) mod series;
+pub(crate) mod sort;
+pub(crate) mod stacks;
+pub(crate) mod tags;
+#[cfg(test)]
+pub(crate) mod testing;
 pub(crate) mod tracking;
+pub(crate) mod trigger;
+pub(crate) mod upcall;
@@ -0,0 +1,145 @@
+//! Moving average
+//!
+//! Statistical filter only emitting events whose numeric field deviates from
+//! the moving average of its previous values, computed over a sliding window.
+//! This surfaces sudden changes in noisy fields.
+
+use std::{collections::VecDeque, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+
+use super::field::FieldPath;
+
+/// Moving average filter definition, under the form `section.field[:FACTOR]`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MovingAvgSpec {
+    /// Path to the numeric field.
+    path: FieldPath,
+    /// Factor by which a value must deviate from the average to be emitted.
+    factor: f64,
+}
+
+impl FromStr for MovingAvgSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let (field, factor) = match spec.rsplit_once(':') {
+            Some((field, factor)) => (
+                field,
+                factor
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("Invalid factor {factor} (e.g. 2 or 1.5)"))?,
+            ),
+            None => (spec, 2.0),
+        };
+
+        if !(factor > 1.0 && factor.is_finite()) {
+            bail!("Factor must be greater than 1");
+        }
+
+        Ok(Self {
+            path: field.parse()?,
+            factor,
+        })
+    }
+}
+
+/// Filters events by comparing their field value to the average of the
+/// previous `window` values: only values above `average * factor` or below
+/// `average / factor` are emitted. All values are added to the window, which
+/// must be full before any event is emitted. Events not having the field are
+/// not filtered.
+pub(crate) struct MovingAvgFilter {
+    spec: MovingAvgSpec,
+    /// Number of values the average is computed over.
+    window: usize,
+    /// Last values, oldest first.
+    values: VecDeque<f64>,
+    /// Sum of the values in the window.
+    sum: f64,
+}
+
+impl MovingAvgFilter {
+    pub(crate) fn new(spec: MovingAvgSpec, window: usize) -> Result<Self> {
+        if window == 0 {
+            bail!("Moving average window cannot be empty");
+        }
+
+        Ok(Self {
+            spec,
+            window,
+            values: VecDeque::with_capacity(window),
+            sum: 0.0,
+        })
+    }
+
+    /// Check if a value deviates from the current average.
+    fn deviates(&self, value: f64) -> bool {
+        let avg = self.sum / self.values.len() as f64;
+        value > avg * self.spec.factor || value * self.spec.factor < avg
+    }
+
+    /// Process an event, given its json representation, and return whether
+    /// it should be emitted.
+    pub(crate) fn process_one(&mut self, json: &Value) -> bool {
+        let value = match self.spec.path.get(json).and_then(Value::as_f64) {
+            Some(value) => value,
+            None => return true,
+        };
+
+        let emit = self.values.len() == self.window && self.deviates(value);
+
+        if self.values.len() == self.window {
+            self.sum -= self.values.pop_front().unwrap_or_default();
+        }
+        self.values.push_back(value);
+        self.sum += value;
+
+        emit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::testing::event;
+
+    fn json(ts: u64) -> Value {
+        event().timestamp(ts).build().to_json()
+    }
+
+    #[test]
+    fn moving_avg_spec() {
+        let spec = MovingAvgSpec::from_str("kernel.latency_ns:1.5").unwrap();
+        assert_eq!(spec.path, FieldPath::from_str("kernel.latency_ns").unwrap());
+        assert_eq!(spec.factor, 1.5);
+        assert_eq!(MovingAvgSpec::from_str("skb.len").unwrap().factor, 2.0);
+
+        assert!(MovingAvgSpec::from_str("len").is_err());
+        assert!(MovingAvgSpec::from_str("skb.:2").is_err());
+        assert!(MovingAvgSpec::from_str("skb.len:x").is_err());
+        assert!(MovingAvgSpec::from_str("skb.len:1").is_err());
+        assert!(MovingAvgSpec::from_str("skb.len:inf").is_err());
+    }
+
+    #[test]
+    fn moving_avg_filter() {
+        let spec = MovingAvgSpec::from_str("common.timestamp:2").unwrap();
+        let mut filter = MovingAvgFilter::new(spec.clone(), 10).unwrap();
+        assert!(MovingAvgFilter::new(spec, 0).is_err());
+
+        // Steady stream, slightly noisy: never emitted, including while the
+        // window is being filled.
+        assert!((0..50).all(|i| !filter.process_one(&json(100 + i % 3))));
+
+        // Only the spike (and the drop) are emitted.
+        assert!(filter.process_one(&json(1000)));
+        assert!(!filter.process_one(&json(101)));
+        assert!(!filter.process_one(&json(99)));
+        assert!(filter.process_one(&json(10)));
+
+        // Events without the field are not filtered.
+        assert!(filter.process_one(&event().build().to_json()));
+    }
+}
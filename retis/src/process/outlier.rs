@@ -0,0 +1,148 @@
+//! Outlier
+//!
+//! Statistical filter only emitting events whose numeric field exceeds a
+//! baseline. The baseline is a percentile of the field values, learned during
+//! a warm-up window.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+
+use super::field::FieldPath;
+
+/// Outlier filter definition, under the form `section.field[:pNN]`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct OutlierSpec {
+    /// Path to the numeric field.
+    path: FieldPath,
+    /// Percentile used as a baseline.
+    percentile: f64,
+}
+
+impl FromStr for OutlierSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let (field, percentile) = match spec.rsplit_once(':') {
+            Some((field, percentile)) => {
+                let percentile = percentile
+                    .strip_prefix('p')
+                    .ok_or_else(|| anyhow!("Invalid percentile {percentile} (e.g. p99)"))?
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("Invalid percentile {percentile} (e.g. p99)"))?;
+                (field, percentile)
+            }
+            None => (spec, 99.0),
+        };
+
+        if !(percentile > 0.0 && percentile < 100.0) {
+            bail!("Percentile must be in the ]0, 100[ range");
+        }
+
+        Ok(Self {
+            path: field.parse()?,
+            percentile,
+        })
+    }
+}
+
+/// Filters events using a baseline learned during a warm-up window: the first
+/// `warmup` values of the field are used to compute the baseline and are not
+/// emitted. Then only events with a value above the baseline are. Events not
+/// having the field are not filtered.
+pub(crate) struct OutlierFilter {
+    spec: OutlierSpec,
+    /// Number of values used to learn the baseline.
+    warmup: usize,
+    /// Values seen during the warm-up window.
+    samples: Vec<f64>,
+    /// Baseline, once learned.
+    threshold: Option<f64>,
+}
+
+impl OutlierFilter {
+    pub(crate) fn new(spec: OutlierSpec, warmup: usize) -> Result<Self> {
+        if warmup == 0 {
+            bail!("Warm-up window cannot be empty");
+        }
+
+        Ok(Self {
+            spec,
+            warmup,
+            samples: Vec::with_capacity(warmup),
+            threshold: None,
+        })
+    }
+
+    /// Compute the baseline from the warm-up samples, using the nearest-rank
+    /// method.
+    fn learn(&mut self) {
+        self.samples.sort_by(f64::total_cmp);
+
+        let rank = (self.spec.percentile / 100.0 * self.samples.len() as f64).ceil() as usize;
+        self.threshold = Some(self.samples[rank.saturating_sub(1)]);
+        self.samples = Vec::new();
+    }
+
+    /// Process an event, given its json representation, and return whether
+    /// it should be emitted.
+    pub(crate) fn process_one(&mut self, json: &Value) -> bool {
+        let value = match self.spec.path.get(json).and_then(Value::as_f64) {
+            Some(value) => value,
+            None => return true,
+        };
+
+        match self.threshold {
+            Some(threshold) => value > threshold,
+            None => {
+                self.samples.push(value);
+                if self.samples.len() == self.warmup {
+                    self.learn();
+                }
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::testing::event;
+
+    fn json(ts: u64) -> Value {
+        event().timestamp(ts).build().to_json()
+    }
+
+    #[test]
+    fn outlier_spec() {
+        let spec = OutlierSpec::from_str("skb.len:p90").unwrap();
+        assert_eq!(spec.path, FieldPath::from_str("skb.len").unwrap());
+        assert_eq!(spec.percentile, 90.0);
+        assert_eq!(OutlierSpec::from_str("skb.len").unwrap().percentile, 99.0);
+
+        assert!(OutlierSpec::from_str("len").is_err());
+        assert!(OutlierSpec::from_str("skb.:p90").is_err());
+        assert!(OutlierSpec::from_str("skb.len:90").is_err());
+        assert!(OutlierSpec::from_str("skb.len:p100").is_err());
+    }
+
+    #[test]
+    fn outlier_filter() {
+        let spec = OutlierSpec::from_str("common.timestamp:p90").unwrap();
+        let mut filter = OutlierFilter::new(spec, 10).unwrap();
+
+        // Baseline distribution, never emitted.
+        assert!((1..=10).all(|ts| !filter.process_one(&json(ts))));
+        assert_eq!(filter.threshold, Some(9.0));
+
+        assert!(!filter.process_one(&json(5)));
+        assert!(!filter.process_one(&json(9)));
+        assert!(filter.process_one(&json(10)));
+        assert!(filter.process_one(&json(1000)));
+
+        // Events without the field are not filtered.
+        assert!(filter.process_one(&event().build().to_json()));
+    }
+}
@@ -0,0 +1,390 @@
+//! Parquet
+//!
+//! Writes events to a Parquet file, a columnar format suited for analytics
+//! (e.g. using SQL engines or dataframes). Events are batched in memory and
+//! each batch is written as a row group.
+//!
+//! The schema is derived from the event types: the common fields and the main
+//! fields of the most used sections (e.g. `kernel.symbol`) are stored in typed
+//! columns, and each event section in its own (nullable) column holding its
+//! json representation.
+
+use std::{io::Write, sync::Arc};
+
+use anyhow::Result;
+use arrow_array::{
+    builder::{
+        Int32Builder, Int64Builder, StringBuilder, UInt16Builder, UInt32Builder, UInt64Builder,
+        UInt8Builder,
+    },
+    ArrayRef, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+
+use crate::events::*;
+
+/// Number of events written per batch (row group).
+const BATCH_SIZE: usize = 4096;
+
+/// Column builder for a typed section field, along with the function
+/// retrieving the field from an event.
+enum FieldBuilder {
+    UInt8(UInt8Builder, fn(&Event) -> Option<u8>),
+    UInt16(UInt16Builder, fn(&Event) -> Option<u16>),
+    UInt32(UInt32Builder, fn(&Event) -> Option<u32>),
+    UInt64(UInt64Builder, fn(&Event) -> Option<u64>),
+    Int64(Int64Builder, fn(&Event) -> Option<i64>),
+    Utf8(StringBuilder, fn(&Event) -> Option<String>),
+}
+
+impl FieldBuilder {
+    fn data_type(&self) -> DataType {
+        use FieldBuilder::*;
+        match self {
+            UInt8(..) => DataType::UInt8,
+            UInt16(..) => DataType::UInt16,
+            UInt32(..) => DataType::UInt32,
+            UInt64(..) => DataType::UInt64,
+            Int64(..) => DataType::Int64,
+            Utf8(..) => DataType::Utf8,
+        }
+    }
+
+    fn append(&mut self, event: &Event) {
+        use FieldBuilder::*;
+        match self {
+            UInt8(b, get) => b.append_option(get(event)),
+            UInt16(b, get) => b.append_option(get(event)),
+            UInt32(b, get) => b.append_option(get(event)),
+            UInt64(b, get) => b.append_option(get(event)),
+            Int64(b, get) => b.append_option(get(event)),
+            Utf8(b, get) => b.append_option(get(event)),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        use FieldBuilder::*;
+        match self {
+            UInt8(b, _) => Arc::new(b.finish()),
+            UInt16(b, _) => Arc::new(b.finish()),
+            UInt32(b, _) => Arc::new(b.finish()),
+            UInt64(b, _) => Arc::new(b.finish()),
+            Int64(b, _) => Arc::new(b.finish()),
+            Utf8(b, _) => Arc::new(b.finish()),
+        }
+    }
+}
+
+fn kernel(event: &Event) -> Option<&KernelEvent> {
+    event.get_section::<KernelEvent>(SectionId::Kernel)
+}
+
+fn skb(event: &Event) -> Option<&SkbEvent> {
+    event.get_section::<SkbEvent>(SectionId::Skb)
+}
+
+/// Section fields stored in their own typed column, by column name.
+fn typed_fields() -> Vec<(&'static str, FieldBuilder)> {
+    use FieldBuilder::*;
+    vec![
+        (
+            "kernel.symbol",
+            Utf8(StringBuilder::new(), |e| Some(kernel(e)?.symbol.clone())),
+        ),
+        (
+            "kernel.probe_type",
+            Utf8(StringBuilder::new(), |e| {
+                Some(kernel(e)?.probe_type.to_str().to_string())
+            }),
+        ),
+        ("kernel.ret", Int64(Int64Builder::new(), |e| kernel(e)?.ret)),
+        (
+            "kernel.latency_ns",
+            UInt64(UInt64Builder::new(), |e| kernel(e)?.latency_ns),
+        ),
+        (
+            "skb.dev.name",
+            Utf8(StringBuilder::new(), |e| {
+                Some(skb(e)?.dev.as_ref()?.name.clone())
+            }),
+        ),
+        (
+            "skb.dev.ifindex",
+            UInt32(UInt32Builder::new(), |e| {
+                Some(skb(e)?.dev.as_ref()?.ifindex)
+            }),
+        ),
+        (
+            "skb.ns.netns",
+            UInt32(UInt32Builder::new(), |e| Some(skb(e)?.ns.as_ref()?.netns)),
+        ),
+        (
+            "skb.ip.saddr",
+            Utf8(StringBuilder::new(), |e| {
+                Some(skb(e)?.ip.as_ref()?.saddr.clone())
+            }),
+        ),
+        (
+            "skb.ip.daddr",
+            Utf8(StringBuilder::new(), |e| {
+                Some(skb(e)?.ip.as_ref()?.daddr.clone())
+            }),
+        ),
+        (
+            "skb.ip.protocol",
+            UInt8(UInt8Builder::new(), |e| Some(skb(e)?.ip.as_ref()?.protocol)),
+        ),
+        (
+            "skb.ip.len",
+            UInt16(UInt16Builder::new(), |e| Some(skb(e)?.ip.as_ref()?.len)),
+        ),
+        (
+            "skb.tcp.sport",
+            UInt16(UInt16Builder::new(), |e| Some(skb(e)?.tcp.as_ref()?.sport)),
+        ),
+        (
+            "skb.tcp.dport",
+            UInt16(UInt16Builder::new(), |e| Some(skb(e)?.tcp.as_ref()?.dport)),
+        ),
+        (
+            "skb.udp.sport",
+            UInt16(UInt16Builder::new(), |e| Some(skb(e)?.udp.as_ref()?.sport)),
+        ),
+        (
+            "skb.udp.dport",
+            UInt16(UInt16Builder::new(), |e| Some(skb(e)?.udp.as_ref()?.dport)),
+        ),
+    ]
+}
+
+/// Writes events in the Parquet format.
+pub(crate) struct ParquetWriter<W: Write + Send> {
+    writer: ArrowWriter<W>,
+    schema: SchemaRef,
+    /// Sections stored in their own column, in the schema order.
+    sections: Vec<SectionId>,
+    // Column builders for the current batch.
+    timestamp: UInt64Builder,
+    smp_id: UInt32Builder,
+    comm: StringBuilder,
+    pid: Int32Builder,
+    tgid: Int32Builder,
+    fields: Vec<FieldBuilder>,
+    section_data: Vec<StringBuilder>,
+    /// Number of events in the current batch.
+    len: usize,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    pub(crate) fn new(writer: W) -> Result<Self> {
+        let sections = (SectionId::Common as u8..SectionId::_MAX as u8)
+            .map(SectionId::from_u8)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut fields = vec![
+            Field::new("timestamp", DataType::UInt64, true),
+            Field::new("smp_id", DataType::UInt32, true),
+            Field::new("comm", DataType::Utf8, true),
+            Field::new("pid", DataType::Int32, true),
+            Field::new("tgid", DataType::Int32, true),
+        ];
+        let (names, builders): (Vec<_>, Vec<_>) = typed_fields().into_iter().unzip();
+        fields.extend(
+            names
+                .iter()
+                .zip(builders.iter())
+                .map(|(name, builder)| Field::new(*name, builder.data_type(), true)),
+        );
+        fields.extend(
+            sections
+                .iter()
+                .map(|id| Field::new(id.to_str(), DataType::Utf8, true)),
+        );
+        let schema = Arc::new(Schema::new(fields));
+
+        let props = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .set_max_row_group_size(BATCH_SIZE)
+            .build();
+
+        Ok(Self {
+            writer: ArrowWriter::try_new(writer, schema.clone(), Some(props))?,
+            schema,
+            section_data: sections.iter().map(|_| StringBuilder::new()).collect(),
+            sections,
+            timestamp: UInt64Builder::new(),
+            smp_id: UInt32Builder::new(),
+            comm: StringBuilder::new(),
+            pid: Int32Builder::new(),
+            tgid: Int32Builder::new(),
+            fields: builders,
+            len: 0,
+        })
+    }
+
+    /// Write a single event. Events are only written to the underlying writer
+    /// once a batch is complete, or when finishing the file.
+    pub(crate) fn write_event(&mut self, event: &Event) -> Result<()> {
+        let common = event.get_section::<CommonEvent>(SectionId::Common);
+        let task = common.and_then(|c| c.task.as_ref());
+
+        self.timestamp.append_option(common.map(|c| c.timestamp));
+        self.smp_id.append_option(common.and_then(|c| c.smp_id));
+        self.comm.append_option(task.map(|t| &t.comm));
+        self.pid.append_option(task.map(|t| t.pid));
+        self.tgid.append_option(task.map(|t| t.tgid));
+        self.fields.iter_mut().for_each(|f| f.append(event));
+
+        let json = event.to_json();
+        self.sections
+            .iter()
+            .zip(self.section_data.iter_mut())
+            .for_each(|(id, column)| {
+                column.append_option(json.get(id.to_str()).map(|s| s.to_string()))
+            });
+
+        self.len += 1;
+        if self.len >= BATCH_SIZE {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Write the current batch, if not empty.
+    fn write_batch(&mut self) -> Result<()> {
+        if self.len == 0 {
+            return Ok(());
+        }
+
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(self.timestamp.finish()),
+            Arc::new(self.smp_id.finish()),
+            Arc::new(self.comm.finish()),
+            Arc::new(self.pid.finish()),
+            Arc::new(self.tgid.finish()),
+        ];
+        columns.extend(self.fields.iter_mut().map(FieldBuilder::finish));
+        columns.extend(
+            self.section_data
+                .iter_mut()
+                .map(|column| Arc::new(column.finish()) as ArrayRef),
+        );
+
+        self.writer
+            .write(&RecordBatch::try_new(self.schema.clone(), columns)?)?;
+        self.len = 0;
+        Ok(())
+    }
+
+    /// Write the pending events and the file footer, and give the underlying
+    /// writer back. The file is not valid until this is called.
+    pub(crate) fn finish(mut self) -> Result<W> {
+        self.write_batch()?;
+        Ok(self.writer.into_inner()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use arrow_array::{Array, Int64Array, StringArray, UInt16Array, UInt64Array};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use super::*;
+    use crate::process::testing::event;
+
+    #[test]
+    fn parquet() -> Result<()> {
+        let mut factory = file::FileEventsFactory::new("test_data/test_events_bench.json")?;
+        let mut events = Vec::new();
+        while let Some(event) = factory.next_event()? {
+            events.push(event);
+        }
+        events.push(
+            event()
+                .skb(SkbEvent {
+                    tcp: Some(SkbTcpEvent {
+                        sport: 443,
+                        dport: 39308,
+                        seq: 1230199517,
+                        ack_seq: 233042766,
+                        window: 261,
+                        doff: 5,
+                        flags: 16,
+                    }),
+                    ..Default::default()
+                })
+                .build(),
+        );
+
+        let path = std::env::temp_dir().join(format!("retis-parquet-{}", std::process::id()));
+        let mut writer = ParquetWriter::new(File::create(&path)?)?;
+        events.iter().try_for_each(|e| writer.write_event(e))?;
+        writer.finish()?;
+
+        let batches = ParquetRecordBatchReaderBuilder::try_new(File::open(&path)?)?
+            .build()?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).sum::<usize>(),
+            events.len()
+        );
+
+        let batch = &batches[0];
+        let timestamp = batch
+            .column_by_name("timestamp")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(timestamp.value(0), 23868643385999);
+
+        let kernel = batch
+            .column_by_name("kernel")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert!(kernel.value(0).contains("\"symbol\":\"tcp_v4_rcv\""));
+
+        // Typed section fields.
+        let symbol = batch
+            .column_by_name("kernel.symbol")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(symbol.value(0), "tcp_v4_rcv");
+
+        let sport = batch
+            .column_by_name("skb.tcp.sport")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt16Array>()
+            .unwrap();
+        assert_eq!(sport.value(events.len() - 1), 443);
+        // Events without the field are null.
+        assert!(sport.is_null(0));
+
+        let ret = batch
+            .column_by_name("kernel.ret")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert!(ret.is_null(0));
+
+        // Sections not part of an event are null.
+        let ovs = batch.column_by_name("ovs").unwrap();
+        assert!(ovs.is_null(0) && !ovs.is_null(1));
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+//! # Pcap
+//!
+//! Converts events holding raw packets to the pcapng format, one Enhanced
+//! Packet Block per packet and one Interface Description Block per network
+//! interface (netns|ifindex) seen.
+
+use std::{borrow::Cow, collections::HashMap, io::Write, time::Duration};
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use pcap_file::{
+    pcapng::{
+        blocks::{
+            enhanced_packet::{EnhancedPacketBlock, EnhancedPacketOption},
+            interface_description::{InterfaceDescriptionBlock, InterfaceDescriptionOption},
+        },
+        PcapNgBlock, PcapNgWriter,
+    },
+    DataLink,
+};
+
+use crate::events::{CommonEvent, KernelEvent, SkbEvent, *};
+
+/// Statistics of the event parser about events (processed, skipped, etc).
+#[derive(Default)]
+struct EventParserStats {
+    /// Events that were processed by the parser. Aka. all events that were
+    /// matched by the filter.
+    processed: u32,
+    /// Events w/o an skb section (skipped).
+    missing_skb: u32,
+    /// Events w/o a packet section (skipped).
+    missing_packet: u32,
+    /// Events w/o a dev section (fake one was used instead).
+    missing_dev: u32,
+    /// Events w/o a netns section (fake one was used instead).
+    missing_ns: u32,
+}
+
+/// Events parser: handles the logic to convert our events to the PCAP format
+/// that is represented by the internal writer.
+pub(crate) struct EventParser<'a, W: Write> {
+    writer: &'a mut PcapNgWriter<W>,
+    /// Known network interfaces and their PCAP id: netns|ifindex -> pcap id.
+    ifaces: HashMap<u64, u32>,
+    /// Statistics.
+    stats: EventParserStats,
+}
+
+// Unwrap a Some(_) value or return from the function.
+macro_rules! some_or_return {
+    ($section: expr, $stat: expr) => {
+        match $section {
+            Some(val) => val,
+            None => {
+                $stat += 1;
+                return Ok(());
+            }
+        }
+    };
+}
+
+impl<'a, W: Write> EventParser<'a, W> {
+    /// Creates a new EventParser from a PcapNgWriter<W: Write>.
+    pub(crate) fn from(writer: &'a mut PcapNgWriter<W>) -> Self {
+        Self {
+            writer,
+            ifaces: HashMap::new(),
+            stats: EventParserStats::default(),
+        }
+    }
+
+    /// Parse & process a single Retis event.
+    pub(crate) fn parse(&mut self, event: &Event) -> Result<()> {
+        // Having a common & a kernel section is mandatory for now, seeing a
+        // filtered event w/o one of those is bogus.
+        let common = event
+            .get_section::<CommonEvent>(SectionId::Common)
+            .ok_or_else(|| anyhow!("No common section in event"))?;
+        let kernel = event
+            .get_section::<KernelEvent>(SectionId::Kernel)
+            .ok_or_else(|| anyhow!("No skb section in event"))?;
+
+        self.stats.processed += 1;
+
+        // The skb & packet sections are mandatory for us to generate PCAP
+        // events, but they might not be present in some filtered events. Stats
+        // are kept here to inform the user.
+        let skb = some_or_return!(
+            event.get_section::<SkbEvent>(SectionId::Skb),
+            self.stats.missing_skb
+        );
+        let packet = some_or_return!(skb.packet.as_ref(), self.stats.missing_packet);
+
+        // The dev & ns sections are best to have but not mandatory to generate
+        // an event. If not found, fake them.
+        let (ifindex, ifname) = match skb.dev.as_ref() {
+            Some(dev) => (dev.ifindex, dev.name.as_str()),
+            None => {
+                self.stats.missing_dev += 1;
+                (0, "?")
+            }
+        };
+        let netns = match skb.ns.as_ref() {
+            Some(ns) => ns.netns,
+            None => {
+                self.stats.missing_ns += 1;
+                0
+            }
+        };
+
+        // If we see this iface for the first time, add a description block.
+        let key: u64 = (netns as u64) << 32 | ifindex as u64;
+        let id = match self.ifaces.contains_key(&key) {
+            // Unwrap if contains is true.
+            true => *self.ifaces.get(&key).unwrap(),
+            false => {
+                self.writer.write_block(
+                    &InterfaceDescriptionBlock {
+                        linktype: DataLink::ETHERNET,
+                        snaplen: 0xffff,
+                        options: vec![
+                            InterfaceDescriptionOption::IfName(Cow::Owned(format!(
+                                "{} ({})",
+                                ifname, netns
+                            ))),
+                            InterfaceDescriptionOption::IfDescription(Cow::Owned(match ifindex {
+                                0 => "Fake interface".to_string(),
+                                _ => format!("ifindex={}", ifindex),
+                            })),
+                        ],
+                    }
+                    .into_block(),
+                )?;
+
+                let id = self.ifaces.len() as u32;
+                self.ifaces.insert(key, id);
+                id
+            }
+        };
+
+        // Add the packet itself.
+        self.writer.write_block(
+            &EnhancedPacketBlock {
+                interface_id: id,
+                timestamp: Duration::from_nanos(common.timestamp),
+                original_len: packet.len,
+                data: Cow::Borrowed(&packet.packet.0),
+                options: vec![EnhancedPacketOption::Comment(Cow::Owned(format!(
+                    "probe={}:{}",
+                    &kernel.probe_type, &kernel.symbol
+                )))],
+            }
+            .into_block(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Report parser statistics. Should be called after processing was
+    /// completed.
+    pub(crate) fn report_stats(&self) {
+        info!("{} event(s) were processed", self.stats.processed);
+
+        if self.stats.missing_skb != 0 {
+            warn!(
+                "{} event(s) were skipped because of missing skb information",
+                self.stats.missing_skb
+            );
+        }
+        if self.stats.missing_packet != 0 {
+            warn!(
+                "{} event(s) were skipped because of missing raw packet",
+                self.stats.missing_packet
+            );
+        }
+        if self.stats.missing_dev != 0 {
+            warn!(
+                "{} event(s) are using a fake net device (no device information was found)",
+                self.stats.missing_dev
+            );
+        }
+        if self.stats.missing_ns != 0 {
+            warn!(
+                "{} event(s) are using a fake netns (no netns information was found)",
+                self.stats.missing_ns
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pcap_file::pcapng::{Block, PcapNgReader};
+
+    use super::*;
+    use crate::{
+        events::helpers::RawPacket,
+        process::testing::{event, EventBuilder},
+    };
+
+    fn skb(packet: Option<&[u8]>, ifindex: u32) -> EventBuilder {
+        event().timestamp(1_000_000_042).skb(SkbEvent {
+            dev: Some(SkbDevEvent {
+                name: format!("eth{ifindex}"),
+                ifindex,
+                ..Default::default()
+            }),
+            ns: Some(SkbNsEvent { netns: 4026531840 }),
+            packet: packet.map(|p| SkbPacketEvent {
+                len: p.len() as u32 + 10,
+                capture_len: p.len() as u32,
+                packet: RawPacket(p.to_vec()),
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn kfree_skb(packet: Option<&[u8]>, ifindex: u32) -> Event {
+        skb(packet, ifindex)
+            .section(
+                SectionId::Kernel,
+                KernelEvent {
+                    symbol: "kfree_skb_reason".to_string(),
+                    probe_type: ProbeType::RawTracepoint,
+                    ..Default::default()
+                },
+            )
+            .build()
+    }
+
+    #[test]
+    fn pcapng() {
+        let packet = [0x42u8; 60];
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+        let mut parser = EventParser::from(&mut writer);
+
+        parser.parse(&kfree_skb(Some(&packet), 2)).unwrap();
+        parser.parse(&kfree_skb(Some(&packet), 2)).unwrap();
+        parser.parse(&kfree_skb(Some(&packet[..20]), 3)).unwrap();
+        // Events without a packet are skipped.
+        parser.parse(&kfree_skb(None, 4)).unwrap();
+        // Events without a kernel section are invalid.
+        assert!(parser.parse(&skb(Some(&packet), 2).build()).is_err());
+
+        assert_eq!(parser.stats.processed, 4);
+        assert_eq!(parser.stats.missing_packet, 1);
+
+        let data = writer.into_inner();
+        let mut reader = PcapNgReader::new(data.as_slice()).unwrap();
+        let (mut ifaces, mut packets) = (Vec::new(), Vec::new());
+        while let Some(block) = reader.next_block() {
+            match block.unwrap() {
+                Block::InterfaceDescription(idb) => ifaces.push(idb.linktype),
+                Block::EnhancedPacket(epb) => packets.push((
+                    epb.interface_id,
+                    epb.timestamp,
+                    epb.original_len,
+                    epb.data.to_vec(),
+                )),
+                _ => (),
+            }
+        }
+
+        // One interface description per interface seen.
+        assert_eq!(ifaces.len(), 2);
+        assert!(ifaces.iter().all(|l| *l == DataLink::ETHERNET));
+
+        assert_eq!(packets.len(), 3);
+        assert_eq!(
+            packets[0],
+            (0, Duration::from_nanos(1_000_000_042), 70, packet.to_vec())
+        );
+        assert_eq!(packets[1].0, 0);
+        assert_eq!(packets[2].0, 1);
+        assert_eq!(packets[2].3.len(), 20);
+    }
+}
@@ -0,0 +1,222 @@
+//! Event plugin
+//!
+//! Post-processing plugin loaded from a user provided shared library, to
+//! transform, annotate or drop events without having to modify Retis. The
+//! library must expose the following C function, called for each event:
+//!
+//! ```c
+//! /* The event is given in its json representation (not NUL terminated). The
+//!  * return value is:
+//!  * - 0 to keep the event as-is.
+//!  * - A positive value, the size of the modified event written in out, to
+//!  *   replace the event. It can't be larger than out_size.
+//!  * - A negative value to drop the event.
+//!  */
+//! long retis_plugin_process(const char *event, size_t len, char *out,
+//!                           size_t out_size);
+//! ```
+//!
+//! Modified events must be valid Retis json events.
+
+use std::{
+    ffi::{c_char, c_long, c_void, CStr, CString},
+    os::unix::ffi::OsStrExt,
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::events::Event;
+
+/// Name of the function the plugins must expose.
+const PLUGIN_PROCESS_FN: &str = "retis_plugin_process";
+/// Size of the buffer modified events are written to.
+const PLUGIN_OUT_SIZE: usize = 64 * 1024;
+
+type PluginProcessFn = unsafe extern "C" fn(*const c_char, usize, *mut c_char, usize) -> c_long;
+
+/// Get the last dynamic linking error.
+fn dlerror() -> String {
+    let err = unsafe { libc::dlerror() };
+    match err.is_null() {
+        true => "unknown error".to_string(),
+        false => unsafe { CStr::from_ptr(err) }.to_string_lossy().to_string(),
+    }
+}
+
+/// Post-processing plugin, see the module documentation.
+pub(crate) struct Plugin {
+    handle: *mut c_void,
+    process: PluginProcessFn,
+    out: Vec<u8>,
+}
+
+impl Plugin {
+    /// Load a plugin from a shared library.
+    pub(crate) fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file = CString::new(path.as_os_str().as_bytes())?;
+
+        let handle = unsafe { libc::dlopen(file.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            bail!("Could not load plugin {}: {}", path.display(), dlerror());
+        }
+
+        let name = CString::new(PLUGIN_PROCESS_FN)?;
+        let process = unsafe { libc::dlsym(handle, name.as_ptr()) };
+        if process.is_null() {
+            let err = anyhow!(
+                "Plugin {} does not expose {PLUGIN_PROCESS_FN}: {}",
+                path.display(),
+                dlerror()
+            );
+            unsafe { libc::dlclose(handle) };
+            return Err(err);
+        }
+
+        Ok(Self {
+            handle,
+            process: unsafe { std::mem::transmute::<*mut c_void, PluginProcessFn>(process) },
+            out: vec![0; PLUGIN_OUT_SIZE],
+        })
+    }
+
+    /// Process an event and return it (modified or not), or None if it should
+    /// be dropped.
+    pub(crate) fn process_one(&mut self, event: Event) -> Result<Option<Event>> {
+        let json = event.to_json().to_string();
+
+        let ret = unsafe {
+            (self.process)(
+                json.as_ptr() as *const c_char,
+                json.len(),
+                self.out.as_mut_ptr() as *mut c_char,
+                self.out.len(),
+            )
+        };
+
+        Ok(match ret {
+            0 => Some(event),
+            len if len < 0 => None,
+            len if len as usize > self.out.len() => {
+                bail!(
+                    "Plugin returned a too large event ({len} > {})",
+                    self.out.len()
+                )
+            }
+            len => {
+                let json = String::from_utf8(self.out[..len as usize].to_vec())
+                    .map_err(|e| anyhow!("Plugin returned an invalid event: {e}"))?;
+                Some(Event::from_json(json)?)
+            }
+        })
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        unsafe { libc::dlclose(self.handle) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process::Command};
+
+    use super::*;
+    use crate::events::*;
+
+    // Trivial plugin setting the return value of kernel events, dropping
+    // events on consume_skb and keeping the others as-is.
+    const PLUGIN: &str = r#"
+#include <stddef.h>
+#include <string.h>
+
+static const char stamp[] = "\"kernel\":{\"ret\":42,";
+
+long retis_plugin_process(const char *event, size_t len, char *out,
+                          size_t out_size)
+{
+	const char *kernel = "\"kernel\":{";
+	size_t klen = strlen(kernel);
+	size_t i;
+
+	if (memmem(event, len, "consume_skb", strlen("consume_skb")))
+		return -1;
+
+	for (i = 0; i + klen <= len; i++) {
+		if (memcmp(event + i, kernel, klen))
+			continue;
+
+		if (len + sizeof(stamp) - 1 - klen > out_size)
+			return 0;
+
+		memcpy(out, event, i);
+		memcpy(out + i, stamp, sizeof(stamp) - 1);
+		memcpy(out + i + sizeof(stamp) - 1, event + i + klen,
+		       len - i - klen);
+		return len + sizeof(stamp) - 1 - klen;
+	}
+
+	return 0;
+}
+"#;
+
+    fn kernel_event(symbol: &str) -> Event {
+        let mut event = Event::new();
+        event
+            .insert_section(
+                SectionId::Kernel,
+                Box::new(KernelEvent {
+                    symbol: symbol.to_string(),
+                    probe_type: ProbeType::Kprobe,
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        event
+    }
+
+    #[test]
+    fn plugin() {
+        let dir = std::env::temp_dir().join(format!("retis-plugin-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("plugin.c"), PLUGIN).unwrap();
+        let status = Command::new("cc")
+            .args(["-shared", "-fPIC", "-D_GNU_SOURCE", "-o"])
+            .arg(dir.join("plugin.so"))
+            .arg(dir.join("plugin.c"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let plugin = Plugin::load(dir.join("plugin.so"));
+        assert!(Plugin::load(dir.join("plugin.c")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+        let mut plugin = plugin.unwrap();
+
+        // The plugin stamps kernel events.
+        let event = plugin
+            .process_one(kernel_event("kfree_skb_reason"))
+            .unwrap()
+            .unwrap();
+        let kernel = event.get_section::<KernelEvent>(SectionId::Kernel).unwrap();
+        assert_eq!(kernel.symbol, "kfree_skb_reason");
+        assert_eq!(kernel.ret, Some(42));
+
+        // And drops others.
+        assert!(plugin
+            .process_one(kernel_event("consume_skb"))
+            .unwrap()
+            .is_none());
+
+        // Events it doesn't know about are kept as-is.
+        let event = plugin.process_one(Event::new()).unwrap().unwrap();
+        assert!(event
+            .get_section::<KernelEvent>(SectionId::Kernel)
+            .is_none());
+    }
+}
@@ -0,0 +1,118 @@
+//! Regex filter
+//!
+//! Userspace filter matching string fields of events against regular
+//! expressions. As regular expressions can't be evaluated in eBPF, this
+//! only applies to string fields captured in the events (e.g. the net device
+//! name of the skb section).
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde_json::Value;
+
+use super::field::FieldPath;
+
+/// Regex filter definition, under the form `section.field=pattern`.
+#[derive(Clone, Debug)]
+pub(crate) struct RegexSpec {
+    /// Path to the string field.
+    path: FieldPath,
+    /// Regular expression the field must match.
+    regex: Regex,
+}
+
+impl FromStr for RegexSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let (field, pattern) = spec.split_once('=').ok_or_else(|| {
+            anyhow!("Invalid regex filter {spec} (must be section.field=pattern)")
+        })?;
+
+        let path = field.parse()?;
+        let regex = Regex::new(pattern).map_err(|e| anyhow!("Invalid regex {pattern}: {e}"))?;
+
+        Ok(Self { path, regex })
+    }
+}
+
+impl RegexSpec {
+    /// Check if an event field matches the regular expression. Events not
+    /// having the field, or having a non-string one, do not match.
+    fn matches(&self, json: &Value) -> bool {
+        match self.path.get(json).and_then(Value::as_str) {
+            Some(value) => self.regex.is_match(value),
+            None => false,
+        }
+    }
+}
+
+/// Filters events using a set of regex filters; only events matching all of
+/// them are emitted.
+pub(crate) struct RegexFilter {
+    specs: Vec<RegexSpec>,
+}
+
+impl RegexFilter {
+    pub(crate) fn new(specs: Vec<RegexSpec>) -> Self {
+        Self { specs }
+    }
+
+    /// Process an event, given its json representation, and return whether
+    /// it should be emitted.
+    pub(crate) fn process_one(&self, json: &Value) -> bool {
+        self.specs.iter().all(|spec| spec.matches(json))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{events::*, process::testing::event};
+
+    fn json(dev: &str) -> Value {
+        event()
+            .skb(SkbEvent {
+                dev: Some(SkbDevEvent {
+                    name: dev.to_string(),
+                    ifindex: 2,
+                    rx_ifindex: None,
+                }),
+                ..Default::default()
+            })
+            .build()
+            .to_json()
+    }
+
+    #[test]
+    fn regex_spec() {
+        let spec = RegexSpec::from_str(r"skb.dev.name=^eth\d+$").unwrap();
+        assert_eq!(spec.path, FieldPath::from_str("skb.dev.name").unwrap());
+        assert_eq!(spec.regex.as_str(), r"^eth\d+$");
+
+        // Only the first '=' separates the field from the pattern.
+        let spec = RegexSpec::from_str("skb.dev.name=a=b").unwrap();
+        assert_eq!(spec.regex.as_str(), "a=b");
+
+        assert!(RegexSpec::from_str("skb.dev.name").is_err());
+        assert!(RegexSpec::from_str("name=eth0").is_err());
+        assert!(RegexSpec::from_str("skb..name=eth0").is_err());
+        assert!(RegexSpec::from_str("skb.dev.name=eth(").is_err());
+    }
+
+    #[test]
+    fn regex_filter() {
+        let filter = RegexFilter::new(vec![RegexSpec::from_str(r"skb.dev.name=^eth\d+$").unwrap()]);
+
+        assert!(filter.process_one(&json("eth0")));
+        assert!(filter.process_one(&json("eth42")));
+        assert!(!filter.process_one(&json("eth")));
+        assert!(!filter.process_one(&json("veth0")));
+        assert!(!filter.process_one(&event().build().to_json()));
+
+        // Non-string fields never match.
+        let filter = RegexFilter::new(vec![RegexSpec::from_str(r"skb.dev.ifindex=\d").unwrap()]);
+        assert!(!filter.process_one(&json("eth0")));
+    }
+}
@@ -0,0 +1,210 @@
+//! Pointer region classifier
+//!
+//! Annotates the pointer arguments reported in kernel events (see
+//! `--probe-args`) with the kernel memory region they point into, e.g.
+//! `direct-map`, `vmalloc` or `percpu`. Region boundaries are retrieved from
+//! the inspector (kernel image sections) and, for x86_64, from the documented
+//! virtual memory layout.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use log::debug;
+
+use crate::{core::inspect::inspector, events::*};
+
+/// Kernel image sections, as (region name, start symbol, end symbol).
+const IMAGE_SECTIONS: &[(&str, &str, &str)] = &[
+    ("text", "_stext", "_etext"),
+    ("data", "_sdata", "_edata"),
+    ("bss", "__bss_start", "__bss_stop"),
+    ("percpu", "__per_cpu_start", "__per_cpu_end"),
+];
+
+/// x86_64 virtual memory layout with 4-level page tables (see
+/// Documentation/arch/x86/x86_64/mm.rst), as (region name, start, end). The
+/// first three regions are randomized when CONFIG_RANDOMIZE_MEMORY is set.
+const X86_64_LAYOUT: &[(&str, u64, u64)] = &[
+    ("direct-map", 0xffff888000000000, 0xffffc88000000000),
+    ("vmalloc", 0xffffc90000000000, 0xffffe90000000000),
+    ("vmemmap", 0xffffea0000000000, 0xffffeb0000000000),
+    ("cpu-entry-area", 0xfffffe0000000000, 0xfffffe8000000000),
+    ("modules", 0xffffffffa0000000, 0xffffffffff000000),
+];
+/// Number of randomized regions at the start of `X86_64_LAYOUT`.
+const X86_64_RANDOMIZED: usize = 3;
+
+/// Kernel memory region, covering [start, end).
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct KernelRegion {
+    pub(crate) name: String,
+    pub(crate) start: u64,
+    pub(crate) end: u64,
+}
+
+impl KernelRegion {
+    pub(crate) fn new(name: &str, start: u64, end: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            start,
+            end,
+        }
+    }
+
+    fn contains(&self, addr: u64) -> bool {
+        addr >= self.start && addr < self.end
+    }
+}
+
+/// Classifies pointers using a set of kernel memory regions.
+pub(crate) struct RegionClassifier {
+    regions: Vec<KernelRegion>,
+}
+
+impl RegionClassifier {
+    pub(crate) fn new(regions: Vec<KernelRegion>) -> Self {
+        Self { regions }
+    }
+
+    /// Build a classifier using the regions of the running kernel.
+    pub(crate) fn from_inspector() -> Result<Self> {
+        let kernel = &inspector()?.kernel;
+        let mut regions = Vec::new();
+
+        // Kernel image sections. Per-cpu variables are zero-based on some
+        // architectures (e.g. x86_64), in which case the section does not
+        // describe an address range.
+        for (name, start, end) in IMAGE_SECTIONS {
+            match (kernel.get_symbol_addr(start), kernel.get_symbol_addr(end)) {
+                (Ok(start), Ok(end)) if start != 0 && start < end => {
+                    regions.push(KernelRegion::new(name, start, end))
+                }
+                _ => debug!("Could not find the {name} region boundaries"),
+            }
+        }
+
+        if cfg!(target_arch = "x86_64") {
+            let randomized = kernel
+                .get_config_option("CONFIG_RANDOMIZE_MEMORY")
+                .ok()
+                .flatten()
+                == Some("y");
+
+            X86_64_LAYOUT
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !randomized || *i >= X86_64_RANDOMIZED)
+                .for_each(|(_, (name, start, end))| {
+                    regions.push(KernelRegion::new(name, *start, *end))
+                });
+        }
+
+        Ok(Self::new(regions))
+    }
+
+    /// Get the region an address points into, if any. The smallest matching
+    /// region is returned, so kernel image sections take precedence over the
+    /// larger regions containing them.
+    pub(crate) fn classify(&self, addr: u64) -> Option<&str> {
+        self.regions
+            .iter()
+            .filter(|r| r.contains(addr))
+            .min_by_key(|r| r.end - r.start)
+            .map(|r| r.name.as_str())
+    }
+
+    /// Annotate the pointer arguments of an event with their region.
+    pub(crate) fn process_one(&self, event: &mut Event) {
+        let kernel = match event.get_section_mut::<KernelEvent>(SectionId::Kernel) {
+            Some(kernel) => kernel,
+            None => return,
+        };
+
+        let regions = match kernel.args.as_ref() {
+            Some(args) => args
+                .iter()
+                .filter_map(|(name, arg)| match arg {
+                    KernelArg::String(val) => {
+                        let addr = u64::from_str_radix(val.strip_prefix("0x")?, 16).ok()?;
+                        Some((name.clone(), self.classify(addr)?.to_string()))
+                    }
+                    _ => None,
+                })
+                .collect::<BTreeMap<_, _>>(),
+            None => return,
+        };
+
+        if !regions.is_empty() {
+            kernel.arg_regions = Some(regions);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classifier() -> RegionClassifier {
+        RegionClassifier::new(vec![
+            KernelRegion::new("direct-map", 0xffff888000000000, 0xffffc88000000000),
+            KernelRegion::new("vmalloc", 0xffffc90000000000, 0xffffe90000000000),
+            KernelRegion::new("percpu", 0xffff888100000000, 0xffff888100200000),
+        ])
+    }
+
+    #[test]
+    fn classify() {
+        let classifier = classifier();
+
+        assert_eq!(classifier.classify(0xffff888004a1c000), Some("direct-map"));
+        assert_eq!(classifier.classify(0xffffc90000123000), Some("vmalloc"));
+        // The smallest region wins.
+        assert_eq!(classifier.classify(0xffff888100001000), Some("percpu"));
+        // End is excluded.
+        assert_eq!(classifier.classify(0xffff888100200000), Some("direct-map"));
+        assert_eq!(classifier.classify(0xffffffff99d1da80), None);
+        assert_eq!(classifier.classify(0), None);
+    }
+
+    #[test]
+    fn from_inspector() {
+        let classifier = RegionClassifier::from_inspector().unwrap();
+        assert_eq!(classifier.classify(0xffffffff99d1da80), Some("text"));
+        assert_eq!(classifier.classify(0xffffffff9b000010), Some("data"));
+    }
+
+    #[test]
+    fn annotate() {
+        let mut event = Event::new();
+        event
+            .insert_section(
+                SectionId::Kernel,
+                Box::new(KernelEvent {
+                    symbol: "kfree_skb_reason".to_string(),
+                    args: Some(BTreeMap::from([
+                        (
+                            "skb".to_string(),
+                            KernelArg::String("0xffff888004a1c000".to_string()),
+                        ),
+                        (
+                            "dev".to_string(),
+                            KernelArg::String("0xffffffff99d1da80".to_string()),
+                        ),
+                        ("reason".to_string(), KernelArg::Unsigned(2)),
+                    ])),
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+
+        classifier().process_one(&mut event);
+        let kernel = event.get_section::<KernelEvent>(SectionId::Kernel).unwrap();
+        assert_eq!(
+            kernel.arg_regions,
+            Some(BTreeMap::from([(
+                "skb".to_string(),
+                "direct-map".to_string()
+            )]))
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Reorder
+//!
+//! Reorders a stream of events to follow the order of a reference capture.
+//! This makes the output of multiple runs diff-stable, e.g. for golden-file
+//! testing.
+//!
+//! Events are matched to the reference ones using a correlation key only
+//! built from fields expected to be stable across runs (probe, sections
+//! and packet data); timestamps, tasks, etc are not part of it.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    path::Path,
+};
+
+use anyhow::{bail, Result};
+
+use crate::events::{file::FileEventsFactory, *};
+
+/// Build the correlation key of an event.
+fn correlation_key(event: &Event) -> String {
+    let mut sections = event.sections().map(|s| s as u8).collect::<Vec<_>>();
+    sections.sort();
+
+    let mut key = sections
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    if let Some(kernel) = event.get_section::<KernelEvent>(SectionId::Kernel) {
+        key.push_str(&format!("|{}:{}", kernel.probe_type, kernel.symbol));
+    }
+    if let Some(user) = event.get_section::<UserEvent>(SectionId::Userspace) {
+        key.push_str(&format!("|{}:{}", user.probe_type, user.symbol));
+    }
+    if let Some(packet) = event
+        .get_section::<SkbEvent>(SectionId::Skb)
+        .and_then(|skb| skb.packet.as_ref())
+    {
+        key.push('|');
+        packet
+            .packet
+            .0
+            .iter()
+            .for_each(|b| key.push_str(&format!("{b:02x}")));
+    }
+
+    key
+}
+
+/// Maximum number of events buffered at once, for both the events waiting for
+/// the ones preceding them in the reference and the unmatched ones. Once
+/// reached, the missing reference events are considered lost and the oldest
+/// unmatched events are emitted to bound memory usage.
+const REORDER_MAX_EVENTS: usize = 64 * 1024;
+
+/// Reorders events following the order of a reference capture. Events are
+/// buffered until all the events preceding them in the reference were seen.
+pub(crate) struct ReferenceOrder {
+    /// Positions in the reference of the events not matched yet, by
+    /// correlation key.
+    positions: HashMap<String, VecDeque<usize>>,
+    /// Position in the reference of the next event to emit.
+    next: usize,
+    /// Buffered events, by position in the reference.
+    pending: BTreeMap<usize, Event>,
+    /// Events not matching any reference event, in arrival order.
+    unmatched: VecDeque<Event>,
+    /// Maximum number of events in each of the above buffers.
+    max_events: usize,
+}
+
+impl ReferenceOrder {
+    /// Build a reordering helper from a reference capture file.
+    pub(crate) fn from_file<P>(file: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mut factory = FileEventsFactory::new(file)?;
+        let mut reference = Vec::new();
+
+        match factory.file_type() {
+            file::FileType::Event => (),
+            file::FileType::Series => bail!("Reference capture cannot be a sorted file"),
+        }
+
+        while let Some(event) = factory.next_event()? {
+            reference.push(event);
+        }
+
+        Ok(Self::new(reference.iter()))
+    }
+
+    /// Build a reordering helper from a list of reference events.
+    pub(crate) fn new<'a, I>(reference: I) -> Self
+    where
+        I: Iterator<Item = &'a Event>,
+    {
+        let mut positions: HashMap<String, VecDeque<usize>> = HashMap::new();
+        reference.enumerate().for_each(|(pos, event)| {
+            positions
+                .entry(correlation_key(event))
+                .or_default()
+                .push_back(pos)
+        });
+
+        Self {
+            positions,
+            next: 0,
+            pending: BTreeMap::new(),
+            unmatched: VecDeque::new(),
+            max_events: REORDER_MAX_EVENTS,
+        }
+    }
+
+    /// Find the first reference event matching the given key and not
+    /// matched yet, if any.
+    fn claim(&mut self, key: &str) -> Option<usize> {
+        let positions = self.positions.get_mut(key)?;
+        // Positions before the next one to emit were skipped.
+        while let Some(pos) = positions.pop_front() {
+            if pos >= self.next {
+                return Some(pos);
+            }
+        }
+        None
+    }
+
+    /// Process a new event and return the ones which can be emitted, in the
+    /// reference order.
+    pub(crate) fn process_one(&mut self, event: Event) -> Vec<Event> {
+        match self.claim(&correlation_key(&event)) {
+            Some(pos) => {
+                self.pending.insert(pos, event);
+            }
+            None => self.unmatched.push_back(event),
+        }
+
+        let mut ready = Vec::new();
+        while self.unmatched.len() > self.max_events {
+            // Unwrap as we just checked the buffer is not empty.
+            ready.push(self.unmatched.pop_front().unwrap());
+        }
+        if self.pending.len() > self.max_events {
+            // Skip the reference events missing before the first buffered one.
+            // Unwrap as we just checked the buffer is not empty.
+            self.next = *self.pending.first_key_value().unwrap().0;
+        }
+        while let Some(event) = self.pending.remove(&self.next) {
+            ready.push(event);
+            self.next += 1;
+        }
+
+        ready
+    }
+
+    /// Return all the remaining events. Events matching the reference are
+    /// returned first, in the reference order (missing ones are skipped);
+    /// then all the others in their arrival order.
+    pub(crate) fn flush(&mut self) -> Vec<Event> {
+        self.positions.clear();
+
+        let mut ready = std::mem::take(&mut self.pending)
+            .into_values()
+            .collect::<Vec<_>>();
+        ready.extend(self.unmatched.drain(..));
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::testing::event;
+
+    fn symbols(events: &[Event]) -> Vec<String> {
+        events
+            .iter()
+            .map(|e| {
+                e.get_section::<KernelEvent>(SectionId::Kernel)
+                    .unwrap()
+                    .symbol
+                    .clone()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reorder_to_reference() {
+        let reference = ["a", "b", "c", "b"]
+            .iter()
+            .enumerate()
+            .map(|(i, s)| event().timestamp(i as u64).symbol(s).build())
+            .collect::<Vec<_>>();
+        let mut reorder = ReferenceOrder::new(reference.iter());
+
+        // Timestamps differ from the reference ones, they are not part of
+        // the correlation key.
+        let mut out = Vec::new();
+        out.extend(reorder.process_one(event().timestamp(100).symbol("b").build()));
+        out.extend(reorder.process_one(event().timestamp(101).symbol("x").build()));
+        out.extend(reorder.process_one(event().timestamp(102).symbol("c").build()));
+        assert!(out.is_empty());
+
+        out.extend(reorder.process_one(event().timestamp(103).symbol("a").build()));
+        assert_eq!(symbols(&out), vec!["a", "b", "c"]);
+
+        out.extend(reorder.process_one(event().timestamp(104).symbol("b").build()));
+        out.extend(reorder.process_one(event().timestamp(105).symbol("a").build()));
+        out.extend(reorder.flush());
+        assert_eq!(symbols(&out), vec!["a", "b", "c", "b", "x", "a"]);
+    }
+
+    #[test]
+    fn reorder_limit() {
+        let reference = ["a", "b", "c", "d"]
+            .iter()
+            .enumerate()
+            .map(|(i, s)| event().timestamp(i as u64).symbol(s).build())
+            .collect::<Vec<_>>();
+        let mut reorder = ReferenceOrder::new(reference.iter());
+        reorder.max_events = 2;
+
+        // "a" never comes: once too many events wait for it, it is skipped.
+        let mut out = Vec::new();
+        out.extend(reorder.process_one(event().timestamp(100).symbol("c").build()));
+        out.extend(reorder.process_one(event().timestamp(101).symbol("b").build()));
+        assert!(out.is_empty());
+        out.extend(reorder.process_one(event().timestamp(102).symbol("d").build()));
+        assert_eq!(symbols(&out), vec!["b", "c", "d"]);
+
+        // Unmatched events are emitted in arrival order once too many are
+        // buffered.
+        out.extend(reorder.process_one(event().timestamp(103).symbol("x").build()));
+        out.extend(reorder.process_one(event().timestamp(104).symbol("y").build()));
+        out.extend(reorder.process_one(event().timestamp(105).symbol("a").build()));
+        assert_eq!(symbols(&out), vec!["b", "c", "d", "x"]);
+
+        out.extend(reorder.flush());
+        assert_eq!(symbols(&out), vec!["b", "c", "d", "x", "y", "a"]);
+    }
+}
@@ -0,0 +1,76 @@
+//! Section filter
+//!
+//! Userspace filter only emitting events holding given sections (e.g. only
+//! the OvS ones). Events are kept as a whole, including their sections not
+//! being selected, so cross-referenced data (e.g. tracking ids) is preserved.
+
+use std::{collections::HashSet, str::FromStr};
+
+use anyhow::{anyhow, Result};
+
+use crate::events::*;
+
+/// Parse a section name given on the command line.
+pub(crate) fn parse_section(name: &str) -> Result<SectionId> {
+    SectionId::from_str(name).map_err(|_| anyhow!("Unknown section {name}"))
+}
+
+/// Filters events by section; only events holding at least one of the
+/// sections are emitted. The startup event is always emitted as it is needed
+/// to post-process the events.
+pub(crate) struct SectionFilter {
+    sections: HashSet<SectionId>,
+}
+
+impl SectionFilter {
+    pub(crate) fn new(sections: &[SectionId]) -> Self {
+        Self {
+            sections: sections.iter().copied().collect(),
+        }
+    }
+
+    /// Process an event and return whether it should be emitted.
+    pub(crate) fn process_one(&self, event: &Event) -> bool {
+        event
+            .sections()
+            .any(|id| id == SectionId::Startup || self.sections.contains(&id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections() -> Result<()> {
+        assert_eq!(parse_section("ovs")?, SectionId::Ovs);
+        assert_eq!(parse_section("skb-drop")?, SectionId::SkbDrop);
+        assert_eq!(
+            parse_section("foo").unwrap_err().to_string(),
+            "Unknown section foo"
+        );
+
+        let mut factory = file::FileEventsFactory::new("test_data/test_events_bench.json")?;
+        let mut events = Vec::new();
+        while let Some(event) = factory.next_event()? {
+            events.push(event);
+        }
+
+        let filter = SectionFilter::new(&[parse_section("ovs")?]);
+        let out = events
+            .iter()
+            .filter(|e| filter.process_one(e))
+            .collect::<Vec<_>>();
+
+        // Only events holding an OvS section (or the startup one) are kept,
+        // with all their sections.
+        assert!(!out.is_empty() && out.len() < events.len());
+        assert!(out.iter().all(|e| e
+            .sections()
+            .any(|id| id == SectionId::Ovs || id == SectionId::Startup)));
+        assert!(out
+            .iter()
+            .any(|e| e.sections().any(|id| id == SectionId::Kernel)));
+        Ok(())
+    }
+}
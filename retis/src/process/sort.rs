@@ -0,0 +1,131 @@
+//! Sort
+//!
+//! Sorts a stream of events by timestamp. Events are retrieved from multiple
+//! CPUs and the resulting stream is not strictly ordered; buffering them for a
+//! short time window is enough to restore the order in practice.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::events::*;
+
+/// Maximum number of events buffered at once. Once reached, the oldest ones
+/// are emitted regardless of the time window to bound memory usage.
+const SORT_WINDOW_MAX_EVENTS: usize = 64 * 1024;
+
+/// Sorts events by timestamp within a time window. Events are buffered until
+/// one at least `window` newer is seen; they can then be emitted in order.
+pub(crate) struct SortWindow {
+    /// Size of the window, in nanoseconds.
+    window: u64,
+    /// Buffered events, by timestamp and arrival sequence number so that
+    /// events sharing a timestamp keep their arrival order.
+    pending: BTreeMap<(u64, u64), Event>,
+    /// Sequence number of the next incoming event.
+    seq: u64,
+    /// Latest timestamp seen so far.
+    latest: u64,
+}
+
+impl SortWindow {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window: window.as_nanos() as u64,
+            pending: BTreeMap::new(),
+            seq: 0,
+            latest: 0,
+        }
+    }
+
+    /// Process a new event and return the ones which can be emitted, in
+    /// timestamp order. Events without a timestamp are considered as
+    /// happening at the latest timestamp seen.
+    pub(crate) fn process_one(&mut self, event: Event) -> Vec<Event> {
+        let ts = event
+            .get_section::<CommonEvent>(SectionId::Common)
+            .map(|common| common.timestamp)
+            .unwrap_or(self.latest);
+        self.latest = self.latest.max(ts);
+
+        self.pending.insert((ts, self.seq), event);
+        self.seq += 1;
+
+        let mut ready = Vec::new();
+        while self.pending.len() > SORT_WINDOW_MAX_EVENTS {
+            // Unwrap as we just checked the buffer is not empty.
+            ready.push(self.pending.pop_first().unwrap().1);
+        }
+        ready.extend(self.expire(self.latest));
+        ready
+    }
+
+    /// Return the events older than the window, given the current time (a
+    /// monotonic timestamp in nanoseconds), in timestamp order. This allows
+    /// emitting events when no new one is coming.
+    pub(crate) fn expire(&mut self, now: u64) -> Vec<Event> {
+        let limit = now.saturating_sub(self.window);
+
+        let mut ready = Vec::new();
+        while let Some(entry) = self.pending.first_entry() {
+            if entry.key().0 > limit {
+                break;
+            }
+            ready.push(entry.remove());
+        }
+        ready
+    }
+
+    /// Return all the remaining events, in timestamp order.
+    pub(crate) fn flush(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.pending).into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::testing::event;
+
+    fn timestamps(events: &[Event]) -> Vec<u64> {
+        events
+            .iter()
+            .map(|e| {
+                e.get_section::<CommonEvent>(SectionId::Common)
+                    .unwrap()
+                    .timestamp
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sort_window() {
+        let mut sort = SortWindow::new(Duration::from_nanos(100));
+
+        let mut out = Vec::new();
+        [1000, 1050, 980, 1020, 1000]
+            .iter()
+            .for_each(|ts| out.extend(sort.process_one(event().timestamp(*ts).build())));
+        assert!(out.is_empty());
+
+        // Events at least 100ns older than the latest one are emitted, in
+        // order.
+        out.extend(sort.process_one(event().timestamp(1110).build()));
+        assert_eq!(timestamps(&out), vec![980, 1000, 1000]);
+
+        // Events within the window are held.
+        out.extend(sort.process_one(event().timestamp(1030).build()));
+        assert_eq!(timestamps(&out), vec![980, 1000, 1000]);
+
+        // Until enough time passed, even if no new event is seen.
+        out.extend(sort.expire(1125));
+        assert_eq!(timestamps(&out), vec![980, 1000, 1000, 1020]);
+
+        // Remaining events are drained in order.
+        out.extend(sort.process_one(event().timestamp(1060).build()));
+        out.extend(sort.flush());
+        assert_eq!(
+            timestamps(&out),
+            vec![980, 1000, 1000, 1020, 1030, 1050, 1060, 1110]
+        );
+        assert!(sort.flush().is_empty());
+    }
+}
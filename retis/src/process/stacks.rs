@@ -0,0 +1,119 @@
+//! Unique stacks
+//!
+//! Aggregates the symbolized stack traces of kernel events during a
+//! collection, to report each distinct stack once along with its number of
+//! occurrences. This gives a quick overview of the dominant code paths.
+
+use std::{collections::HashMap, io::Write};
+
+use anyhow::Result;
+
+use crate::events::*;
+
+/// Counts the occurrences of each distinct stack trace.
+#[derive(Default)]
+pub(crate) struct UniqueStacks {
+    counts: HashMap<Vec<String>, u64>,
+}
+
+impl UniqueStacks {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Account for the stack trace of an event, if any.
+    pub(crate) fn process_one(&mut self, event: &Event) {
+        let stack = match event
+            .get_section::<KernelEvent>(SectionId::Kernel)
+            .and_then(|k| k.stack_trace.as_ref())
+        {
+            Some(stack) if !stack.raw().is_empty() => stack,
+            _ => return,
+        };
+
+        match self.counts.get_mut(stack.raw()) {
+            Some(count) => *count += 1,
+            None => {
+                self.counts.insert(stack.raw().clone(), 1);
+            }
+        }
+    }
+
+    /// Get the distinct stacks and their number of occurrences, sorted by
+    /// decreasing frequency. Stacks with the same number of occurrences are
+    /// sorted by their frames.
+    pub(crate) fn summary(&self) -> Vec<(&Vec<String>, u64)> {
+        let mut stacks = self
+            .counts
+            .iter()
+            .map(|(stack, count)| (stack, *count))
+            .collect::<Vec<_>>();
+        stacks.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        stacks
+    }
+
+    /// Write the summary, one stack after the other.
+    pub(crate) fn write_summary<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let summary = self.summary();
+        writeln!(writer, "{} unique stack(s)", summary.len())?;
+
+        for (stack, count) in summary {
+            writeln!(writer, "\n{count} occurrence(s):")?;
+            for frame in stack {
+                writeln!(writer, "    {frame}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::testing::event;
+
+    fn kernel(stack: Option<&[&str]>) -> KernelEvent {
+        KernelEvent {
+            symbol: "kfree_skb_reason".to_string(),
+            stack_trace: stack.map(|s| StackTrace(s.iter().map(|f| f.to_string()).collect())),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unique_stacks() {
+        let rx = ["tcp_v4_rcv+0x35a", "ip_local_deliver+0x6e"];
+        let tx = ["ip_finish_output2+0x1b2", "ip_output+0x71"];
+
+        let mut stacks = UniqueStacks::new();
+        let kfree_skb = |stack| event().section(SectionId::Kernel, kernel(stack)).build();
+        stacks.process_one(&kfree_skb(Some(&tx)));
+        stacks.process_one(&kfree_skb(Some(&rx)));
+        stacks.process_one(&kfree_skb(Some(&rx)));
+        // Events without a stack are ignored.
+        stacks.process_one(&kfree_skb(None));
+        stacks.process_one(&Event::new());
+
+        let summary = stacks.summary();
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0], (&rx.map(String::from).to_vec(), 2));
+        assert_eq!(summary[1], (&tx.map(String::from).to_vec(), 1));
+
+        let mut out = Vec::new();
+        stacks.write_summary(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "2 unique stack(s)
+
+2 occurrence(s):
+    tcp_v4_rcv+0x35a
+    ip_local_deliver+0x6e
+
+1 occurrence(s):
+    ip_finish_output2+0x1b2
+    ip_output+0x71
+"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Tags
+//!
+//! Stamps events with static, user-provided tags (e.g. host role,
+//! environment). This helps telling apart events when aggregating captures
+//! from many hosts. Tags are reported in the common section.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::events::*;
+
+/// Stamps events with a set of tags.
+pub(crate) struct EventTags {
+    tags: BTreeMap<String, String>,
+}
+
+impl EventTags {
+    /// Load tags from a file, one `key=value` entry per line. Empty lines and
+    /// lines starting with '#' are ignored.
+    pub(crate) fn from_file<P>(file: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = file.as_ref();
+
+        Self::parse(
+            &fs::read_to_string(file)
+                .map_err(|e| anyhow!("Could not read {}: {e}", file.display()))?,
+        )
+    }
+
+    /// Parse tags, see `EventTags::from_file`.
+    fn parse(content: &str) -> Result<Self> {
+        let mut tags = BTreeMap::new();
+
+        for line in content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        {
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .filter(|(key, _)| !key.is_empty())
+                .ok_or_else(|| anyhow!("Invalid tag {line} (must be key=value)"))?;
+
+            if tags.insert(key.to_string(), value.to_string()).is_some() {
+                bail!("Tag {key} is defined multiple times");
+            }
+        }
+
+        if tags.is_empty() {
+            bail!("No tag found");
+        }
+        Ok(Self { tags })
+    }
+
+    /// Stamp an event with the tags. Events without a common section are
+    /// left untouched.
+    pub(crate) fn process_one(&self, event: &mut Event) {
+        if let Some(common) = event.get_section_mut::<CommonEvent>(SectionId::Common) {
+            common.tags = Some(self.tags.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let tags = EventTags::parse("# Host\nrole = gateway\n\nenv=\n").unwrap();
+        assert_eq!(tags.tags.len(), 2);
+        assert_eq!(tags.tags["role"], "gateway");
+        assert_eq!(tags.tags["env"], "");
+
+        assert!(EventTags::parse("role").is_err());
+        assert!(EventTags::parse("=gateway").is_err());
+        assert!(EventTags::parse("role=a\nrole=b").is_err());
+        assert!(EventTags::parse("# Nothing\n").is_err());
+        assert!(EventTags::from_file("/non/existent").is_err());
+    }
+
+    #[test]
+    fn tags() {
+        let file = std::env::temp_dir().join(format!("retis-tags-{}", std::process::id()));
+        fs::write(&file, "role=gateway\nenv=production\n").unwrap();
+        let tags = EventTags::from_file(&file);
+        fs::remove_file(&file).unwrap();
+        let tags = tags.unwrap();
+
+        let mut event = Event::new();
+        event
+            .insert_section(SectionId::Common, Box::new(CommonEvent::default()))
+            .unwrap();
+        tags.process_one(&mut event);
+
+        let json = event.to_json();
+        assert_eq!(json["common"]["tags"]["role"], "gateway");
+        assert_eq!(json["common"]["tags"]["env"], "production");
+    }
+}
@@ -0,0 +1,50 @@
+//! Helpers shared by the process unit tests.
+
+use crate::events::*;
+
+/// Start building a test event.
+pub(crate) fn event() -> EventBuilder {
+    EventBuilder(Event::new())
+}
+
+/// Builds test events, section by section.
+pub(crate) struct EventBuilder(Event);
+
+impl EventBuilder {
+    /// Add a section to the event.
+    pub(crate) fn section<T: EventSection + 'static>(mut self, id: SectionId, section: T) -> Self {
+        self.0.insert_section(id, Box::new(section)).unwrap();
+        self
+    }
+
+    /// Add a common section with the given timestamp.
+    pub(crate) fn timestamp(self, timestamp: u64) -> Self {
+        self.section(
+            SectionId::Common,
+            CommonEvent {
+                timestamp,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Add a kernel section with the given symbol, reported by a kprobe.
+    pub(crate) fn symbol(self, symbol: &str) -> Self {
+        self.section(
+            SectionId::Kernel,
+            KernelEvent {
+                symbol: symbol.to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Add an skb section.
+    pub(crate) fn skb(self, skb: SkbEvent) -> Self {
+        self.section(SectionId::Skb, skb)
+    }
+
+    pub(crate) fn build(self) -> Event {
+        self.0
+    }
+}
@@ -0,0 +1,71 @@
+//! Trigger
+//!
+//! Stateful gate discarding events until one matches a trigger, then letting
+//! that event and all the following ones pass. This allows to only record
+//! events from an interesting moment on. The trigger is expressed using regex
+//! filters (see `RegexSpec`).
+
+use serde_json::Value;
+
+use super::regex_filter::{RegexFilter, RegexSpec};
+use crate::events::*;
+
+/// Discards events until the trigger is seen.
+pub(crate) struct Trigger {
+    filter: RegexFilter,
+    triggered: bool,
+}
+
+impl Trigger {
+    pub(crate) fn new(specs: Vec<RegexSpec>) -> Self {
+        Self {
+            filter: RegexFilter::new(specs),
+            triggered: false,
+        }
+    }
+
+    /// Process an event, given its json representation, and return whether
+    /// it should be emitted. The startup event is always emitted as it is
+    /// needed to post-process the events.
+    pub(crate) fn process_one(&mut self, json: &Value) -> bool {
+        if !self.triggered {
+            if json.get(SectionId::Startup.to_str()).is_some() {
+                return true;
+            }
+            self.triggered = self.filter.process_one(json);
+        }
+        self.triggered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::testing::event;
+
+    #[test]
+    fn trigger() {
+        let mut trigger = Trigger::new(vec!["kernel.symbol=^kfree_skb$".parse().unwrap()]);
+
+        let out = [
+            "consume_skb",
+            "ip_rcv",
+            "kfree_skb",
+            "consume_skb",
+            "ip_rcv",
+        ]
+        .iter()
+        .map(|s| event().symbol(s).build())
+        .filter(|e| trigger.process_one(&e.to_json()))
+        .map(|e| {
+            e.get_section::<KernelEvent>(SectionId::Kernel)
+                .unwrap()
+                .symbol
+                .clone()
+        })
+        .collect::<Vec<_>>();
+
+        // Only the trigger and the events following it are emitted.
+        assert_eq!(out, vec!["kfree_skb", "consume_skb", "ip_rcv"]);
+    }
+}
@@ -0,0 +1,132 @@
+//! Upcall latency
+//!
+//! Correlates OvS upcall events with their return and reports the time spent
+//! processing the upcall in the return event.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::events::*;
+
+/// Maximum number of upcalls waiting for their return. Upcall returns can be
+/// lost (or not probed), older upcalls are forgotten once the limit is
+/// reached.
+const MAX_PENDING_UPCALLS: usize = 4096;
+
+/// Adds the upcall latency to upcall return events. Upcalls and their return
+/// are paired using the (cpu, timestamp) of the upcall.
+pub(crate) struct UpcallLatency {
+    /// Upcalls waiting for their return.
+    pending: HashSet<(u32, u64)>,
+    /// Upcalls in the order they were seen, to forget the oldest ones.
+    order: VecDeque<(u32, u64)>,
+}
+
+impl UpcallLatency {
+    pub(crate) fn new() -> Self {
+        Self {
+            pending: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Process one event, tracking upcalls and setting the latency of upcall
+    /// returns.
+    pub(crate) fn process_one(&mut self, event: &mut Event) {
+        let ts = match event.get_section::<CommonEvent>(SectionId::Common) {
+            Some(common) => common.timestamp,
+            None => return,
+        };
+
+        match event.get_section_mut::<OvsEvent>(SectionId::Ovs) {
+            Some(OvsEvent::Upcall { upcall }) => {
+                let key = (upcall.cpu, ts);
+                if self.pending.insert(key) {
+                    self.order.push_back(key);
+                }
+
+                if self.order.len() > MAX_PENDING_UPCALLS {
+                    if let Some(old) = self.order.pop_front() {
+                        self.pending.remove(&old);
+                    }
+                }
+            }
+            Some(OvsEvent::UpcallReturn { upcall_return: ret }) => {
+                let key = (ret.upcall_cpu, ret.upcall_ts);
+                if self.pending.remove(&key) {
+                    self.order.retain(|k| k != &key);
+                    ret.upcall_latency_ns = Some(ts.saturating_sub(ret.upcall_ts));
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::testing::event;
+
+    fn upcall(ts: u64, cpu: u32) -> Event {
+        event()
+            .timestamp(ts)
+            .section(
+                SectionId::Ovs,
+                OvsEvent::Upcall {
+                    upcall: UpcallEvent {
+                        cpu,
+                        ..Default::default()
+                    },
+                },
+            )
+            .build()
+    }
+
+    fn upcall_return(ts: u64, upcall_ts: u64, upcall_cpu: u32) -> Event {
+        event()
+            .timestamp(ts)
+            .section(
+                SectionId::Ovs,
+                OvsEvent::UpcallReturn {
+                    upcall_return: UpcallReturnEvent {
+                        upcall_ts,
+                        upcall_cpu,
+                        ..Default::default()
+                    },
+                },
+            )
+            .build()
+    }
+
+    fn latency(event: &Event) -> Option<u64> {
+        match event.get_section::<OvsEvent>(SectionId::Ovs) {
+            Some(OvsEvent::UpcallReturn { upcall_return }) => upcall_return.upcall_latency_ns,
+            _ => panic!("not an upcall return event"),
+        }
+    }
+
+    #[test]
+    fn upcall_latency() {
+        let mut upcalls = UpcallLatency::new();
+
+        let mut up = upcall(1000, 2);
+        upcalls.process_one(&mut up);
+        let mut up = upcall(1000, 3);
+        upcalls.process_one(&mut up);
+
+        // Different cpu, not paired with the first upcall.
+        let mut ret = upcall_return(1500, 1000, 3);
+        upcalls.process_one(&mut ret);
+        assert_eq!(latency(&ret), Some(500));
+
+        let mut ret = upcall_return(3500, 1000, 2);
+        upcalls.process_one(&mut ret);
+        assert_eq!(latency(&ret), Some(2500));
+
+        // Upcall was already paired.
+        let mut ret = upcall_return(4000, 1000, 2);
+        upcalls.process_one(&mut ret);
+        assert_eq!(latency(&ret), None);
+        assert!(upcalls.pending.is_empty() && upcalls.order.is_empty());
+    }
+}
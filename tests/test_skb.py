@@ -1,5 +1,8 @@
+import os
 import time
 
+import pytest
+
 from testlib import Retis, assert_events_present
 
 
@@ -24,6 +27,31 @@ def test_skb_sanity(two_ns_simple):
     assert len(ip_rcv_events) == 2
 
 
+@pytest.mark.skipif(os.cpu_count() < 2, reason="requires at least two cpus")
+def test_skb_cpu_filter(two_ns_simple):
+    ns = two_ns_simple
+    retis = Retis()
+
+    retis.collect("-c", "skb", "-f", "icmp", "-p", "kprobe:ip_rcv", "--cpu", "0")
+    # Packets are received on the cpu they were sent from (veth), only the
+    # ones of the first ping must be reported.
+    print(ns.run("ns0", "taskset", "-c", "0", "ping", "-c", "1", "10.0.42.2"))
+    print(ns.run("ns0", "taskset", "-c", "1", "ping", "-c", "1", "10.0.42.2"))
+    retis.stop()
+
+    events = retis.events()
+    print(events)
+    ip_rcv_events = list(
+        filter(
+            lambda e: e.get("kernel", {}).get("symbol") == "ip_rcv",
+            events,
+        )
+    )
+
+    assert len(ip_rcv_events) == 2
+    assert all(e["common"]["smp_id"] == 0 for e in ip_rcv_events)
+
+
 def test_skb_tcp_cc(two_ns_simple):
     ns = two_ns_simple
     retis = Retis()